@@ -0,0 +1,122 @@
+use crate::contacts::carddav::{AddressBook, CardDavClient};
+use crate::contacts::store::{self, ContactDetail, ContactInput, ContactSuggestion, ImportSummary};
+use crate::contacts::sync::{self, SyncSummary};
+use crate::contacts::vcard::{self, VCard, VCardVersion};
+use crate::credentials::{delete_credentials, retrieve_secret, store_secret, SecretPurpose};
+use crate::db::Database;
+use crate::error::{Context, MailError, MailResult};
+use crate::models::CardDavAccount;
+use std::fs;
+use tauri::command;
+
+#[command]
+pub async fn list_contacts(db: tauri::State<'_, Database>, limit: Option<u32>, offset: Option<u32>) -> MailResult<Vec<ContactDetail>> {
+    store::list(&db.pool, limit.unwrap_or(100) as i64, offset.unwrap_or(0) as i64).await
+}
+
+#[command]
+pub async fn get_contact(db: tauri::State<'_, Database>, contact_id: String) -> MailResult<Option<ContactDetail>> {
+    store::get(&db.pool, &contact_id).await
+}
+
+// Recipient suggestions for the composer, best match first
+#[command]
+pub async fn autocomplete_contacts(db: tauri::State<'_, Database>, prefix: String, limit: Option<u32>) -> MailResult<Vec<ContactSuggestion>> {
+    store::autocomplete(&db.pool, &prefix, limit.unwrap_or(10) as usize).await
+}
+
+#[command]
+pub async fn save_contact(db: tauri::State<'_, Database>, contact: ContactInput) -> MailResult<String> {
+    store::save(&db.pool, &contact).await
+}
+
+#[command]
+pub async fn merge_contacts(db: tauri::State<'_, Database>, target_id: String, source_ids: Vec<String>) -> MailResult<ContactDetail> {
+    store::merge(&db.pool, &target_id, &source_ids).await?;
+    store::get(&db.pool, &target_id)
+        .await?
+        .ok_or_else(|| MailError::NotFound(format!("Contact '{}' not found", target_id)))
+}
+
+#[command]
+pub async fn delete_contact(db: tauri::State<'_, Database>, contact_id: String) -> MailResult<()> {
+    store::delete(&db.pool, &contact_id).await
+}
+
+#[command]
+pub async fn import_vcard_file(db: tauri::State<'_, Database>, path: String) -> MailResult<ImportSummary> {
+    let bytes = fs::read(&path)
+        .context(&format!("Failed to read '{}'", path))?;
+    let cards = vcard::parse(&String::from_utf8_lossy(&bytes)).map_err(MailError::validation)?;
+    store::import_cards(&db.pool, &cards).await
+}
+
+// One-click import of cards found in a message, see ParsedEmail::contact_cards
+#[command]
+pub async fn import_contact_cards(db: tauri::State<'_, Database>, cards: Vec<VCard>) -> MailResult<ImportSummary> {
+    store::import_cards(&db.pool, &cards).await
+}
+
+// Exports the given contacts, or all of them, as a single .vcf document
+#[command]
+pub async fn export_contacts_vcard(db: tauri::State<'_, Database>, contact_ids: Option<Vec<String>>, version: Option<VCardVersion>) -> MailResult<String> {
+    let cards = store::export_cards(&db.pool, contact_ids.as_deref()).await?;
+    Ok(vcard::serialize_all(&cards, version.unwrap_or_default()))
+}
+
+#[command]
+pub async fn export_contacts_vcard_file(db: tauri::State<'_, Database>, path: String, contact_ids: Option<Vec<String>>, version: Option<VCardVersion>) -> MailResult<usize> {
+    let cards = store::export_cards(&db.pool, contact_ids.as_deref()).await?;
+    fs::write(&path, vcard::serialize_all(&cards, version.unwrap_or_default()))
+        .context(&format!("Failed to write '{}'", path))?;
+    Ok(cards.len())
+}
+
+// Lists the address books the user can reach from a server, principal or address book URL
+#[command]
+pub async fn discover_carddav_addressbooks(server_url: String, username: String, password: String) -> MailResult<Vec<AddressBook>> {
+    let client = CardDavClient::new(&username, &password)?;
+    Ok(client.discover(&server_url).await?)
+}
+
+// Links an address book for syncing; without `addressbook_url` the first one found is used
+#[command]
+pub async fn add_carddav_account(
+    db: tauri::State<'_, Database>,
+    app_handle: tauri::AppHandle,
+    server_url: String,
+    username: String,
+    password: String,
+    addressbook_url: Option<String>,
+) -> MailResult<CardDavAccount> {
+    let client = CardDavClient::new(&username, &password)?;
+    // Discovery started at an address book returns just that one
+    let books = client.discover(addressbook_url.as_deref().unwrap_or(&server_url))
+        .await?;
+    let book = &books[0];
+
+    let account_id = uuid::Uuid::new_v4().to_string();
+    store_secret(&app_handle, &account_id, SecretPurpose::CardDav, &password).await?;
+    sync::create_account(&db.pool, &account_id, &server_url, &username, book).await
+}
+
+#[command]
+pub async fn list_carddav_accounts(db: tauri::State<'_, Database>) -> MailResult<Vec<CardDavAccount>> {
+    sync::list_accounts(&db.pool).await
+}
+
+#[command]
+pub async fn remove_carddav_account(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<()> {
+    sync::delete_account(&db.pool, &account_id).await?;
+    delete_credentials(&app_handle, &account_id).await
+}
+
+#[command]
+pub async fn sync_carddav_account(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<SyncSummary> {
+    let account = sync::get_account(&db.pool, &account_id)
+        .await?
+        .ok_or_else(|| MailError::NotFound(format!("CardDAV account '{}' not found", account_id)))?;
+    let password = retrieve_secret(&app_handle, &account_id, SecretPurpose::CardDav).await?;
+    let client = CardDavClient::new(&account.username, &password)?;
+    sync::sync_account(&db.pool, &client, &account).await
+}
@@ -0,0 +1,142 @@
+use crate::db::Database;
+use crate::email::address;
+use crate::email::parser::{DeliveryReport, ReportKind};
+use crate::error::{Context, MailResult};
+use crate::smtp_client::EmailMessage;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Sqlite};
+use tauri::command;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SentMessage {
+    pub message_id: String,
+    pub account_id: String,
+    pub subject: Option<String>,
+    pub recipients: Option<String>,
+    pub dsn_requested: bool,
+    pub read_receipt_requested: bool,
+    pub sent_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecipientReport {
+    pub email_id: String,
+    pub kind: String,
+    pub recipient: String,
+    pub action: Option<String>,
+    pub status: Option<String>,
+    pub diagnostic: Option<String>,
+    pub received_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliveryStatus {
+    pub message: Option<SentMessage>,
+    pub reports: Vec<RecipientReport>,
+}
+
+// Delivery and read reports received so far for one of our sent messages
+#[command]
+pub async fn get_delivery_status(db: tauri::State<'_, Database>, message_id: String) -> MailResult<DeliveryStatus> {
+    let message_id = message_id.trim().trim_start_matches('<').trim_end_matches('>').to_string();
+
+    let message = sqlx::query_as::<_, SentMessage>(
+        r#"
+        SELECT message_id, account_id, subject, recipients, dsn_requested, read_receipt_requested, sent_at
+        FROM sent_messages WHERE message_id = ?
+        "#
+    )
+    .bind(&message_id)
+    .fetch_optional(&db.pool)
+    .await
+    .context("Failed to get sent message")?;
+
+    let reports = sqlx::query_as::<_, RecipientReport>(
+        r#"
+        SELECT email_id, kind, recipient, action, status, diagnostic, received_at
+        FROM delivery_reports WHERE original_message_id = ? ORDER BY received_at
+        "#
+    )
+    .bind(&message_id)
+    .fetch_all(&db.pool)
+    .await
+    .context("Failed to get delivery reports")?;
+
+    Ok(DeliveryStatus { message, reports })
+}
+
+#[command]
+pub async fn list_sent_messages(db: tauri::State<'_, Database>, account_id: String, limit: Option<u32>) -> MailResult<Vec<SentMessage>> {
+    sqlx::query_as::<_, SentMessage>(
+        r#"
+        SELECT message_id, account_id, subject, recipients, dsn_requested, read_receipt_requested, sent_at
+        FROM sent_messages WHERE account_id = ? ORDER BY sent_at DESC LIMIT ?
+        "#
+    )
+    .bind(&account_id)
+    .bind(limit.unwrap_or(50) as i64)
+    .fetch_all(&db.pool)
+    .await
+    .context("Failed to list sent messages")
+}
+
+pub async fn record_sent_message(pool: &Pool<Sqlite>, account_id: &str, message_id: &str, message: &EmailMessage) -> MailResult<()> {
+    let mailboxes: Vec<_> = message.to.iter()
+        .chain(message.cc.iter())
+        .chain(message.bcc.iter())
+        .flat_map(|recipient| address::parse_list(recipient).0)
+        .collect();
+    let recipients = address::format_list(&mailboxes);
+
+    sqlx::query(
+        r#"
+        INSERT OR REPLACE INTO sent_messages (message_id, account_id, subject, recipients, dsn_requested, read_receipt_requested)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(message_id)
+    .bind(account_id)
+    .bind(&message.subject)
+    .bind(&recipients)
+    .bind(message.request_dsn)
+    .bind(message.request_read_receipt)
+    .execute(pool)
+    .await
+    .context("Failed to record sent message")?;
+
+    Ok(())
+}
+
+// Stores a report found in a synced message, one row per reported recipient
+pub async fn store_delivery_report(pool: &Pool<Sqlite>, account_id: &str, email_id: &str, report: &DeliveryReport) -> MailResult<()> {
+    let kind = match report.kind {
+        ReportKind::Delivery => "delivery",
+        ReportKind::Disposition => "disposition",
+    };
+    // We send ENVID = Message-ID, so the envelope id links reports that omit the headers
+    let original_message_id = report.original_message_id.as_ref()
+        .or(report.original_envelope_id.as_ref());
+
+    for recipient in &report.recipients {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO delivery_reports (id, account_id, email_id, original_message_id, kind, recipient, action, status, diagnostic)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(format!("{}-{}", email_id, recipient.recipient))
+        .bind(account_id)
+        .bind(email_id)
+        .bind(original_message_id)
+        .bind(kind)
+        .bind(&recipient.recipient)
+        .bind(&recipient.action)
+        .bind(&recipient.status)
+        .bind(&recipient.diagnostic)
+        .execute(pool)
+        .await
+        .context("Failed to store delivery report")?;
+    }
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use crate::email::parser::{self, ParsedEmail};
+use crate::error::MailResult;
 use tauri::command;
 
 #[command]
-pub fn parse_email_content(content: Vec<u8>) -> Result<ParsedEmail, String> {
+pub fn parse_email_content(content: Vec<u8>) -> MailResult<ParsedEmail> {
     parser::parse_email(&content)
 }
@@ -1,220 +1,218 @@
+use crate::db::repo::{FolderRepo, FolderStats, MessageRepo};
 use crate::db::Database;
-use crate::models::{Email, MailAttachment};
-use crate::imap_client::{ImapClient, ImapConfig};
-use crate::smtp_client::{SmtpClient, SmtpConfig, EmailMessage};
+use crate::email::folder_role::FolderRole;
+use crate::error::{Context, MailError, MailResult};
+use crate::imap_client::ImapClient;
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{Pool, Sqlite};
+use std::collections::BTreeMap;
 use tauri::command;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmailAction {
-    pub email_ids: Vec<String>,
-    pub action: EmailActionType,
-}
+pub use crate::db::repo::MessageLocation;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum EmailActionType {
-    MarkAsRead,
-    MarkAsUnread,
-    Star,
-    Unstar,
-    Delete,
-    Move { target_folder: String },
+// Where a bulk move sends messages. A role names the matching folder in each message's
+// own account, so a selection from the unified inbox can be archived or trashed at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MoveTarget {
+    Role { role: FolderRole },
+    // A folder by name; every message must come from the same account
+    Folder { name: String },
 }
 
-#[command]
-pub async fn mark_emails_as_read(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>) -> Result<(), String> {
-    if email_ids.is_empty() {
-        return Ok(());
+// The messages grouped by account. With `account_id` set every message must belong to that
+// account; ids that aren't in the database are skipped.
+pub async fn locate(pool: &Pool<Sqlite>, email_ids: &[String], account_id: Option<&str>) -> MailResult<BTreeMap<String, Vec<MessageLocation>>> {
+    let mut groups: BTreeMap<String, Vec<MessageLocation>> = BTreeMap::new();
+    for location in MessageRepo::new(pool).locate(email_ids).await? {
+        if let Some(account_id) = account_id {
+            if location.account_id != account_id {
+                return Err(MailError::validation(format!("Email {} does not belong to account {}", location.id, account_id)));
+            }
+        }
+        groups.entry(location.account_id.clone()).or_default().push(location);
     }
+    Ok(groups)
+}
 
-    // Get account with credentials
-    let config = crate::commands::email_secure::get_account_with_credentials(db, app_handle.clone(), account_id.clone()).await?;
-    
-    let mut client = ImapClient::new(config.imap_config);
-    client.connect()
-        .map_err(|e| format!("Failed to connect to IMAP: {}", e))?;
-
-    // Mark emails as read on server
-    for email_id in &email_ids {
-        // Extract folder and UID from email_id (format: "account-folder-uid")
-        let parts: Vec<&str> = email_id.split('-').collect();
-        if parts.len() >= 3 {
-            let folder = parts[1];
-            if let Some(uid_str) = parts.last() {
-                if let Ok(uid) = uid_str.parse::<u32>() {
-                    client.mark_as_read(folder, uid)
-                        .map_err(|e| format!("Failed to mark email {} as read on server: {}", email_id, e))?;
-                }
+// Runs `apply` on every message, with one IMAP connection per account. An account that
+// fails doesn't hold up the others: the messages of the accounts that went through are
+// returned so the caller can update them locally, along with the error of those that didn't.
+async fn on_server<F>(
+    db: &tauri::State<'_, Database>,
+    app_handle: &tauri::AppHandle,
+    groups: &BTreeMap<String, Vec<MessageLocation>>,
+    mut apply: F,
+) -> (BTreeMap<String, Vec<MessageLocation>>, MailResult<()>)
+where
+    F: FnMut(&mut ImapClient, &MessageLocation) -> MailResult<()>,
+{
+    let mut done = BTreeMap::new();
+    let mut failures = Vec::new();
+    for (account_id, locations) in groups {
+        let result = on_account(db, app_handle, account_id, locations, &mut apply).await;
+        match result {
+            Ok(()) => {
+                done.insert(account_id.clone(), locations.clone());
             }
+            Err(error) => failures.push((account_id.clone(), error)),
         }
     }
+    (done, combine_failures(failures))
+}
 
-    client.disconnect()
-        .map_err(|e| format!("Failed to disconnect: {}", e))?;
+async fn on_account<F>(
+    db: &tauri::State<'_, Database>,
+    app_handle: &tauri::AppHandle,
+    account_id: &str,
+    locations: &[MessageLocation],
+    apply: &mut F,
+) -> MailResult<()>
+where
+    F: FnMut(&mut ImapClient, &MessageLocation) -> MailResult<()>,
+{
+    let config = crate::commands::email_secure::get_account_with_credentials(db.clone(), app_handle.clone(), account_id.to_string()).await?;
 
-    // Update emails in database
-    let placeholders = email_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let query = format!("UPDATE emails SET is_read = 1 WHERE id IN ({})", placeholders);
-    
-    let mut query_builder = sqlx::query(&query);
-    for email_id in &email_ids {
-        query_builder = query_builder.bind(email_id);
+    let mut client = ImapClient::new(config.imap_config);
+    client.connect()
+        .context("Failed to connect to IMAP")?;
+    for location in locations {
+        apply(&mut client, location)?;
     }
-    
-    query_builder.execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to update emails in database: {}", e))?;
-
+    client.disconnect()
+        .context("Failed to disconnect")?;
     Ok(())
 }
 
-#[command]
-pub async fn mark_emails_as_unread(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>) -> Result<(), String> {
-    if email_ids.is_empty() {
+// One error naming every account that failed, with the kind of the last failure
+pub fn combine_failures(mut failures: Vec<(String, MailError)>) -> MailResult<()> {
+    let Some((account_id, error)) = failures.pop() else {
         return Ok(());
-    }
+    };
+    let mut earlier: Vec<String> = failures.iter()
+        .map(|(account_id, error)| format!("{}: {}", account_id, error))
+        .collect();
+    earlier.push(account_id);
+    Err(error.context(&earlier.join("; ")))
+}
 
-    // Get account with credentials
-    let config = crate::commands::email_secure::get_account_with_credentials(db, app_handle.clone(), account_id.clone()).await?;
-    
-    let mut client = ImapClient::new(config.imap_config);
-    client.connect()
-        .map_err(|e| format!("Failed to connect to IMAP: {}", e))?;
+fn ids_of(groups: &BTreeMap<String, Vec<MessageLocation>>) -> Vec<String> {
+    groups.values().flatten().map(|location| location.id.clone()).collect()
+}
 
-    // Mark emails as unread on server
-    for email_id in &email_ids {
-        // Extract folder and UID from email_id
-        let parts: Vec<&str> = email_id.split('-').collect();
-        if parts.len() >= 3 {
-            let folder = parts[1];
-            if let Some(uid_str) = parts.last() {
-                if let Ok(uid) = uid_str.parse::<u32>() {
-                    client.mark_as_unread(folder, uid)
-                        .map_err(|e| format!("Failed to mark email {} as unread on server: {}", email_id, e))?;
-                }
-            }
-        }
-    }
+pub async fn set_read(pool: &Pool<Sqlite>, email_ids: &[String], is_read: bool) -> MailResult<()> {
+    MessageRepo::new(pool).set_read(email_ids, is_read).await?;
+    Ok(())
+}
 
-    client.disconnect()
-        .map_err(|e| format!("Failed to disconnect: {}", e))?;
+// Starring is a local operation, so messages from any account can be starred together
+pub async fn set_starred(pool: &Pool<Sqlite>, email_ids: &[String], account_id: Option<&str>, starred: bool) -> MailResult<()> {
+    let ids = ids_of(&locate(pool, email_ids, account_id).await?);
+    MessageRepo::new(pool).set_starred(&ids, starred).await?;
+    Ok(())
+}
 
-    // Update emails in database
-    let placeholders = email_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let query = format!("UPDATE emails SET is_read = 0 WHERE id IN ({})", placeholders);
-    
-    let mut query_builder = sqlx::query(&query);
-    for email_id in &email_ids {
-        query_builder = query_builder.bind(email_id);
+// The id of the folder each account's messages move to
+pub async fn resolve_target(pool: &Pool<Sqlite>, account_ids: &[String], target: &MoveTarget) -> MailResult<BTreeMap<String, (String, String)>> {
+    let mut folders = BTreeMap::new();
+    match target {
+        MoveTarget::Folder { name } => {
+            if account_ids.len() > 1 {
+                return Err(MailError::validation("Emails from several accounts can only be moved to a folder role"));
+            }
+            // Checked before anything is moved on the server, which would otherwise create
+            // the folder or fail halfway through
+            for account_id in account_ids {
+                let folder = FolderRepo::new(pool).find_by_name(account_id, name)
+                    .await?
+                    .ok_or_else(|| MailError::NotFound(format!("Account {} has no folder '{}'", account_id, name)))?;
+                folders.insert(account_id.clone(), (folder.id, folder.name));
+            }
+        }
+        MoveTarget::Role { role } => {
+            for account_id in account_ids {
+                let folder = FolderRepo::new(pool).find_by_role(account_id, *role)
+                    .await?
+                    .ok_or_else(|| MailError::NotFound(format!("Account {} has no {} folder", account_id, role.as_str())))?;
+                folders.insert(account_id.clone(), (folder.id, folder.name));
+            }
+        }
     }
-    
-    query_builder.execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to update emails in database: {}", e))?;
-
-    Ok(())
+    Ok(folders)
 }
 
 #[command]
-pub async fn star_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>) -> Result<(), String> {
-    if email_ids.is_empty() {
-        return Ok(());
-    }
+pub async fn mark_emails_as_read(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: Option<String>, email_ids: Vec<String>) -> MailResult<()> {
+    let groups = locate(&db.pool, &email_ids, account_id.as_deref()).await?;
 
-    // Update emails in database (starring is typically a local operation)
-    let placeholders = email_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let query = format!("UPDATE emails SET is_starred = 1 WHERE id IN ({})", placeholders);
-    
-    let mut query_builder = sqlx::query(&query);
-    for email_id in &email_ids {
-        query_builder = query_builder.bind(email_id);
-    }
-    
-    query_builder.execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to star emails in database: {}", e))?;
+    let (done, result) = on_server(&db, &app_handle, &groups, |client, location| {
+        client.mark_as_read(&location.folder, location.uid)
+            .context(&format!("Failed to mark email {} as read on server", location.id))
+    }).await;
 
-    Ok(())
+    set_read(&db.pool, &ids_of(&done), true).await?;
+    result
 }
 
 #[command]
-pub async fn unstar_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>) -> Result<(), String> {
-    if email_ids.is_empty() {
-        return Ok(());
-    }
+pub async fn mark_emails_as_unread(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: Option<String>, email_ids: Vec<String>) -> MailResult<()> {
+    let groups = locate(&db.pool, &email_ids, account_id.as_deref()).await?;
 
-    // Update emails in database
-    let placeholders = email_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let query = format!("UPDATE emails SET is_starred = 0 WHERE id IN ({})", placeholders);
-    
-    let mut query_builder = sqlx::query(&query);
-    for email_id in &email_ids {
-        query_builder = query_builder.bind(email_id);
-    }
-    
-    query_builder.execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to unstar emails in database: {}", e))?;
+    let (done, result) = on_server(&db, &app_handle, &groups, |client, location| {
+        client.mark_as_unread(&location.folder, location.uid)
+            .context(&format!("Failed to mark email {} as unread on server", location.id))
+    }).await;
 
-    Ok(())
+    set_read(&db.pool, &ids_of(&done), false).await?;
+    result
 }
 
 #[command]
-pub async fn delete_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>) -> Result<(), String> {
-    if email_ids.is_empty() {
-        return Ok(());
-    }
-
-    // Get account with credentials
-    let config = crate::commands::email_secure::get_account_with_credentials(db, app_handle.clone(), account_id.clone()).await?;
-    
-    let mut client = ImapClient::new(config.imap_config);
-    client.connect()
-        .map_err(|e| format!("Failed to connect to IMAP: {}", e))?;
+pub async fn star_emails(db: tauri::State<'_, Database>, account_id: Option<String>, email_ids: Vec<String>) -> MailResult<()> {
+    set_starred(&db.pool, &email_ids, account_id.as_deref(), true).await
+}
 
-    // Delete emails on server
-    for email_id in &email_ids {
-        // Extract folder and UID from email_id
-        let parts: Vec<&str> = email_id.split('-').collect();
-        if parts.len() >= 3 {
-            let folder = parts[1];
-            if let Some(uid_str) = parts.last() {
-                if let Ok(uid) = uid_str.parse::<u32>() {
-                    client.delete_email(folder, uid)
-                        .map_err(|e| format!("Failed to delete email {} on server: {}", email_id, e))?;
-                }
-            }
-        }
-    }
+#[command]
+pub async fn unstar_emails(db: tauri::State<'_, Database>, account_id: Option<String>, email_ids: Vec<String>) -> MailResult<()> {
+    set_starred(&db.pool, &email_ids, account_id.as_deref(), false).await
+}
 
-    client.disconnect()
-        .map_err(|e| format!("Failed to disconnect: {}", e))?;
+#[command]
+pub async fn delete_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: Option<String>, email_ids: Vec<String>) -> MailResult<()> {
+    let groups = locate(&db.pool, &email_ids, account_id.as_deref()).await?;
 
-    // Delete emails from database
-    let placeholders = email_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let query = format!("DELETE FROM emails WHERE id IN ({})", placeholders);
-    
-    let mut query_builder = sqlx::query(&query);
-    for email_id in &email_ids {
-        query_builder = query_builder.bind(email_id);
-    }
-    
-    query_builder.execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to delete emails from database: {}", e))?;
+    let (done, result) = on_server(&db, &app_handle, &groups, |client, location| {
+        client.delete_email(&location.folder, location.uid)
+            .context(&format!("Failed to delete email {} on server", location.id))
+    }).await;
 
-    Ok(())
+    db.messages().delete(&ids_of(&done)).await?;
+    result
 }
 
 #[command]
-pub async fn bulk_move_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, source_folder: String, target_folder: String, email_ids: Vec<String>) -> Result<(), String> {
-    // Use the existing move_emails_to_folder function
-    crate::commands::folder_ops::move_emails_to_folder(db, app_handle, account_id, source_folder, target_folder, email_ids).await
+pub async fn bulk_move_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: Option<String>, email_ids: Vec<String>, target: MoveTarget) -> MailResult<()> {
+    let groups = locate(&db.pool, &email_ids, account_id.as_deref()).await?;
+    let accounts: Vec<String> = groups.keys().cloned().collect();
+    let targets = resolve_target(&db.pool, &accounts, &target).await?;
+
+    let (done, result) = on_server(&db, &app_handle, &groups, |client, location| {
+        let (_, folder_name) = &targets[&location.account_id];
+        client.move_email(&location.folder, location.uid, folder_name)
+            .context(&format!("Failed to move email {} on server", location.id))
+    }).await;
+
+    for (account_id, locations) in &done {
+        let (folder_id, _) = &targets[account_id];
+        let ids: Vec<String> = locations.iter().map(|location| location.id.clone()).collect();
+        db.messages().move_to(&ids, folder_id).await?;
+    }
+
+    result
 }
 
 #[command]
-pub async fn bulk_mark_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>, mark_as_read: bool) -> Result<(), String> {
+pub async fn bulk_mark_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: Option<String>, email_ids: Vec<String>, mark_as_read: bool) -> MailResult<()> {
     if mark_as_read {
         mark_emails_as_read(db, app_handle, account_id, email_ids).await
     } else {
@@ -223,46 +221,15 @@ pub async fn bulk_mark_emails(db: tauri::State<'_, Database>, app_handle: tauri:
 }
 
 #[command]
-pub async fn bulk_star_emails(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, email_ids: Vec<String>, star: bool) -> Result<(), String> {
+pub async fn bulk_star_emails(db: tauri::State<'_, Database>, account_id: Option<String>, email_ids: Vec<String>, star: bool) -> MailResult<()> {
     if star {
-        star_emails(db, app_handle, account_id, email_ids).await
+        star_emails(db, account_id, email_ids).await
     } else {
-        unstar_emails(db, app_handle, account_id, email_ids).await
+        unstar_emails(db, account_id, email_ids).await
     }
 }
 
 #[command]
-pub async fn get_email_actions_summary(db: tauri::State<'_, Database>, account_id: String, folder_name: String) -> Result<EmailActionsSummary, String> {
-    let folder_id = format!("{}-{}", account_id, folder_name);
-    
-    let summary = sqlx::query!(
-        r#"
-        SELECT 
-            COUNT(*) as total_emails,
-            COUNT(CASE WHEN NOT is_read THEN 1 END) as unread_emails,
-            COUNT(CASE WHEN is_starred THEN 1 END) as starred_emails,
-            COUNT(CASE WHEN has_attachments THEN 1 END) as emails_with_attachments
-        FROM emails 
-        WHERE folder_id = ?
-        "#,
-        folder_id
-    )
-    .fetch_one(&db.pool)
-    .await
-    .map_err(|e| format!("Failed to get email actions summary: {}", e))?;
-
-    Ok(EmailActionsSummary {
-        total_emails: summary.total_emails as u32,
-        unread_emails: summary.unread_emails as u32,
-        starred_emails: summary.starred_emails as u32,
-        emails_with_attachments: summary.emails_with_attachments as u32,
-    })
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmailActionsSummary {
-    pub total_emails: u32,
-    pub unread_emails: u32,
-    pub starred_emails: u32,
-    pub emails_with_attachments: u32,
+pub async fn get_email_actions_summary(db: tauri::State<'_, Database>, account_id: String, folder_name: String) -> MailResult<FolderStats> {
+    db.messages().stats(&FolderRepo::id_for(&account_id, &folder_name)).await
 }
@@ -0,0 +1,137 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::email_actions::*;
+    use crate::email::folder_role::FolderRole;
+    use crate::error::MailError;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('work', 'me@work.example'), ('home', 'me@home.example');
+            INSERT INTO folders (id, account_id, name, role) VALUES
+                ('work-INBOX', 'work', 'INBOX', 'inbox'),
+                ('work-Archive', 'work', 'Archive', 'archive'),
+                ('home-INBOX', 'home', 'INBOX', 'inbox'),
+                ('home-Archiv', 'home', 'Archiv', 'archive');
+            INSERT INTO emails (id, account_id, folder_id, uid) VALUES
+                ('w1', 'work', 'work-INBOX', 11),
+                ('w2', 'work', 'work-INBOX', 12),
+                ('h1', 'home', 'home-INBOX', 21);
+            "#
+        )
+        .await
+    }
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_locate_groups_by_account() {
+        let pool = setup_pool().await;
+
+        let groups = locate(&pool, &ids(&["w2", "h1", "w1", "missing"]), None).await.unwrap();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["home", "work"]);
+        let work: Vec<(&str, &str, u32)> = groups["work"].iter()
+            .map(|location| (location.id.as_str(), location.folder.as_str(), location.uid))
+            .collect();
+        assert_eq!(work, vec![("w1", "INBOX", 11), ("w2", "INBOX", 12)]);
+        assert_eq!(groups["home"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_locate_falls_back_to_stored_folder_name() {
+        let pool = setup_pool().await;
+        // Written before foreign keys were enforced, with the folder name as folder_id
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO emails (id, account_id, folder_id, uid) VALUES ('old', 'home', 'INBOX', 22)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let groups = locate(&pool, &ids(&["old"]), None).await.unwrap();
+        assert_eq!(groups["home"][0].folder, "INBOX");
+    }
+
+    #[tokio::test]
+    async fn test_locate_checks_account() {
+        let pool = setup_pool().await;
+
+        assert!(locate(&pool, &ids(&["w1", "w2"]), Some("work")).await.is_ok());
+        let error = locate(&pool, &ids(&["w1", "h1"]), Some("work")).await.unwrap_err();
+        assert!(error.to_string().contains("h1"));
+    }
+
+    #[tokio::test]
+    async fn test_star_across_accounts() {
+        let pool = setup_pool().await;
+
+        set_starred(&pool, &ids(&["w1", "h1"]), None, true).await.unwrap();
+        let starred: Vec<String> = sqlx::query_scalar("SELECT id FROM emails WHERE is_starred = 1 ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(starred, vec!["h1", "w1"]);
+
+        assert!(set_starred(&pool, &ids(&["w1", "h1"]), Some("home"), false).await.is_err());
+        set_starred(&pool, &ids(&["h1"]), Some("home"), false).await.unwrap();
+        let starred: Vec<String> = sqlx::query_scalar("SELECT id FROM emails WHERE is_starred = 1")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(starred, vec!["w1"]);
+    }
+
+    #[tokio::test]
+    async fn test_move_target_by_role_per_account() {
+        let pool = setup_pool().await;
+
+        let accounts = ids(&["home", "work"]);
+        let targets = resolve_target(&pool, &accounts, &MoveTarget::Role { role: FolderRole::Archive }).await.unwrap();
+        assert_eq!(targets["home"], ("home-Archiv".to_string(), "Archiv".to_string()));
+        assert_eq!(targets["work"], ("work-Archive".to_string(), "Archive".to_string()));
+
+        let error = resolve_target(&pool, &accounts, &MoveTarget::Role { role: FolderRole::Trash }).await.unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+        assert!(error.to_string().contains("no trash folder"));
+    }
+
+    #[tokio::test]
+    async fn test_move_target_by_name_needs_one_account() {
+        let pool = setup_pool().await;
+
+        let target = MoveTarget::Folder { name: "Archive".to_string() };
+        let targets = resolve_target(&pool, &ids(&["work"]), &target).await.unwrap();
+        assert_eq!(targets["work"].0, "work-Archive");
+        assert!(resolve_target(&pool, &ids(&["home", "work"]), &target).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_move_target_by_name_must_exist() {
+        let pool = setup_pool().await;
+
+        let target = MoveTarget::Folder { name: "Archive".to_string() };
+        let error = resolve_target(&pool, &ids(&["home"]), &target).await.unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+        assert!(error.to_string().contains("Archive"));
+    }
+
+    #[test]
+    fn test_combine_failures() {
+        assert!(combine_failures(vec![]).is_ok());
+
+        let error = combine_failures(vec![("work".to_string(), MailError::Auth("Login failed".to_string()))]).unwrap_err();
+        assert_eq!(error.to_string(), "work: Login failed");
+        assert_eq!(error.kind(), "auth");
+
+        let error = combine_failures(vec![
+            ("home".to_string(), MailError::Auth("Login failed".to_string())),
+            ("work".to_string(), MailError::Network("Connection reset".to_string())),
+        ])
+        .unwrap_err();
+        assert_eq!(error.to_string(), "home: Login failed; work: Connection reset");
+        assert!(error.retryable());
+    }
+}
@@ -1,10 +1,17 @@
-use crate::credentials::{store_credentials, retrieve_credentials, delete_credentials};
+use crate::credentials::{store_secret, retrieve_secret, delete_credentials, SecretPurpose};
+use crate::commands::identities::{create_default_identity, resolve_identity};
+use crate::commands::signatures::load_rich_signature;
+use crate::commands::delivery_status::{record_sent_message, store_delivery_report};
+use crate::contacts::store as contacts;
+use crate::db::repo::NewAccount;
 use crate::db::Database;
-use crate::models::{Account, Email, Folder};
-use crate::imap_client::{ImapClient, ImapConfig};
-use crate::smtp_client::{SmtpClient, SmtpConfig, EmailMessage};
+use crate::commands::threads;
+use crate::error::{Context, MailError, MailResult};
+use crate::outbox;
+use crate::models::{Account, Email, Folder, MailAccount};
+use crate::imap_client::{ImapClient, ImapConfig, ImapEmail, ImapFolder};
+use crate::smtp_client::{SmtpClient, SmtpConfig, EmailMessage, ComposeMode, Sender};
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
 use tauri::command;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,116 +22,102 @@ pub struct AccountConfig {
     pub smtp_config: SmtpConfig,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendReceipt {
+    // Set when the message was queued; pass it to undo_send to pull it back
+    pub outbox_id: Option<String>,
+    pub send_at: Option<String>,
+}
+
 #[command]
-pub async fn save_account_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, config: AccountConfig) -> Result<String, String> {
+pub async fn save_account_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, config: AccountConfig) -> MailResult<String> {
     let account_id = uuid::Uuid::new_v4().to_string();
-    
-    // Store passwords securely
-    store_credentials(&app_handle, &account_id, &config.imap_config.password).await?;
-    
-    // Save account without passwords
-    sqlx::query(
-        r#"
-        INSERT INTO accounts (id, email, name, provider, imap_host, imap_port, imap_username, 
-                              smtp_host, smtp_port, smtp_username)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#
-    )
-    .bind(&account_id)
-    .bind(&config.email)
-    .bind(&config.name)
-    .bind("custom") // Default provider
-    .bind(&config.imap_config.host)
-    .bind(config.imap_config.port as i64)
-    .bind(&config.imap_config.username)
-    .bind(&config.smtp_config.host)
-    .bind(config.smtp_config.port as i64)
-    .bind(&config.smtp_config.username)
-    .execute(&db.pool)
-    .await
-    .map_err(|e| format!("Failed to save account: {}", e))?;
 
-    Ok(account_id)
-}
+    // Save account without passwords first, so a failed insert leaves no orphaned secrets
+    db.accounts().insert(&new_account(&account_id, &config)).await?;
 
-#[command]
-pub async fn get_accounts_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle) -> Result<Vec<Account>, String> {
-    let accounts = sqlx::query("SELECT id, email, name, provider, imap_host, imap_port, smtp_host, smtp_port FROM accounts")
-        .fetch_all(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to fetch accounts: {}", e))?;
+    // Store passwords securely; an empty SMTP password means the IMAP one is used for both
+    let smtp_password = match config.smtp_config.password.as_str() {
+        "" => &config.imap_config.password,
+        password => password,
+    };
+    let stored: MailResult<()> = async {
+        store_secret(&app_handle, &account_id, SecretPurpose::Imap, &config.imap_config.password).await?;
+        store_secret(&app_handle, &account_id, SecretPurpose::Smtp, smtp_password).await
+    }.await;
+    if let Err(e) = stored {
+        // Don't leave an account behind that can never connect
+        let _ = delete_credentials(&app_handle, &account_id).await;
+        let _ = db.accounts().delete(&account_id).await;
+        return Err(e);
+    }
 
-    Ok(accounts)
-}
+    create_default_identity(&db.pool, &account_id, &config.name, &config.email).await?;
 
-#[command]
-pub async fn get_account_with_credentials(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> Result<AccountConfig, String> {
-    // Get account from database
-    let account = sqlx::query("SELECT * FROM accounts WHERE id = ?")
-        .bind(&account_id)
-        .fetch_one(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to get account: {}", e))?;
+    Ok(account_id)
+}
 
-    // Retrieve password securely
-    let password = retrieve_credentials(&app_handle, &account_id).await?;
+// The account row for a new account; "custom" is the default provider
+pub fn new_account(account_id: &str, config: &AccountConfig) -> NewAccount {
+    NewAccount {
+        id: account_id.to_string(),
+        email: config.email.clone(),
+        name: config.name.clone(),
+        provider: "custom".to_string(),
+        imap_host: config.imap_config.host.clone(),
+        imap_port: config.imap_config.port,
+        imap_username: config.imap_config.username.clone(),
+        smtp_host: config.smtp_config.host.clone(),
+        smtp_port: config.smtp_config.port,
+        smtp_username: config.smtp_config.username.clone(),
+    }
+}
 
+// The connection settings of a stored account, completed with its passwords
+pub fn account_config(account: MailAccount, imap_password: String, smtp_password: String) -> AccountConfig {
     let imap_config = ImapConfig {
-        host: account.get("imap_host"),
-        port: account.get::<i64, _>("imap_port") as u16,
-        username: account.get("imap_username"),
-        password,
+        host: account.imap_host.unwrap_or_default(),
+        port: account.imap_port.unwrap_or_default() as u16,
+        username: account.imap_username.unwrap_or_default(),
+        password: imap_password,
         tls: true,
     };
 
     let smtp_config = SmtpConfig {
-        host: account.get("smtp_host"),
-        port: account.get::<i64, _>("smtp_port") as u16,
-        username: account.get("smtp_username"),
-        password: password.clone(), // Use same password for SMTP
-        from: account.get("email"),
+        host: account.smtp_host.unwrap_or_default(),
+        port: account.smtp_port.unwrap_or_default() as u16,
+        username: account.smtp_username.unwrap_or_default(),
+        password: smtp_password,
+        from: account.email.clone(),
     };
 
-    Ok(AccountConfig {
-        name: account.get("name"),
-        email: account.get("email"),
+    AccountConfig {
+        name: account.name.unwrap_or_default(),
+        email: account.email,
         imap_config,
         smtp_config,
-    })
+    }
+}
+
+#[command]
+pub async fn get_accounts_secure(db: tauri::State<'_, Database>) -> MailResult<Vec<Account>> {
+    db.accounts().list().await
 }
 
 #[command]
-pub async fn delete_account_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> Result<(), String> {
-    // Start transaction for cascading delete
-    let mut tx = db.pool.begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction: {}", e))?;
-
-    // Delete emails for this account
-    sqlx::query("DELETE FROM emails WHERE account_id = ?")
-        .bind(&account_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| format!("Failed to delete emails: {}", e))?;
-
-    // Delete folders for this account
-    sqlx::query("DELETE FROM folders WHERE account_id = ?")
-        .bind(&account_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| format!("Failed to delete folders: {}", e))?;
-
-    // Delete the account
-    sqlx::query("DELETE FROM accounts WHERE id = ?")
-        .bind(&account_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| format!("Failed to delete account: {}", e))?;
-
-    // Commit transaction
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+pub async fn get_account_with_credentials(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<AccountConfig> {
+    let account = db.accounts().get(&account_id).await?;
+
+    // Retrieve passwords securely
+    let imap_password = retrieve_secret(&app_handle, &account_id, SecretPurpose::Imap).await?;
+    let smtp_password = retrieve_secret(&app_handle, &account_id, SecretPurpose::Smtp).await?;
+
+    Ok(account_config(account, imap_password, smtp_password))
+}
+
+#[command]
+pub async fn delete_account_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<()> {
+    db.accounts().delete(&account_id).await?;
 
     // Delete stored credentials
     delete_credentials(&app_handle, &account_id).await?;
@@ -132,157 +125,201 @@ pub async fn delete_account_secure(db: tauri::State<'_, Database>, app_handle: t
     Ok(())
 }
 
+// Replaces one stored secret, e.g. after the user created a new app password for SMTP
+#[command]
+pub async fn update_account_credential(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, purpose: SecretPurpose, secret: String) -> MailResult<()> {
+    if secret.is_empty() {
+        return Err(MailError::validation("The new credential is empty"));
+    }
+    if purpose == SecretPurpose::CardDav {
+        return Err(MailError::validation("CardDAV passwords belong to CardDAV accounts, not mail accounts"));
+    }
+    db.accounts().get(&account_id).await?;
+
+    store_secret(&app_handle, &account_id, purpose, &secret).await
+}
+
 #[command]
-pub async fn sync_folders_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> Result<Vec<Folder>, String> {
+pub async fn sync_folders_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<Vec<Folder>> {
     // Get account with credentials
-    let config = get_account_with_credentials(db, app_handle.clone(), account_id.clone()).await?;
+    let config = get_account_with_credentials(db.clone(), app_handle.clone(), account_id.clone()).await?;
     
     let mut client = ImapClient::new(config.imap_config);
     client.connect()
-        .map_err(|e| format!("Failed to connect to IMAP: {}", e))?;
+        .context("Failed to connect to IMAP")?;
 
     let imap_folders = client.list_folders()
-        .map_err(|e| format!("Failed to list folders: {}", e))?;
+        .context("Failed to list folders")?;
 
     client.disconnect()
-        .map_err(|e| format!("Failed to disconnect: {}", e))?;
-
-    // Save folders to database
-    for folder in &imap_folders {
-        let folder_id = format!("{}-{}", account_id, folder.name);
-        sqlx::query(
-            "INSERT OR REPLACE INTO folders (id, account_id, name, delimiter) VALUES (?, ?, ?, ?)"
-        )
-        .bind(&folder_id)
-        .bind(&account_id)
-        .bind(&folder.name)
-        .bind(&folder.delimiter)
-        .execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to save folder: {}", e))?;
-    }
-
-    let folders = imap_folders.into_iter().map(|f| Folder {
-        id: format!("{}-{}", account_id, f.name),
-        account_id,
-        name: f.name,
-        delimiter: Some(f.delimiter),
-    }).collect();
+        .context("Failed to disconnect")?;
 
-    Ok(folders)
+    store_folders(&db, &account_id, &imap_folders).await
 }
 
 #[command]
-pub async fn fetch_emails_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, folder_name: String, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn fetch_emails_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, folder_name: String, limit: Option<u32>) -> MailResult<Vec<Email>> {
     // Get account with credentials
-    let config = get_account_with_credentials(db, app_handle.clone(), account_id.clone()).await?;
+    let config = get_account_with_credentials(db.clone(), app_handle.clone(), account_id.clone()).await?;
 
     let mut client = ImapClient::new(config.imap_config);
     client.connect()
-        .map_err(|e| format!("Failed to connect to IMAP: {}", e))?;
+        .context("Failed to connect to IMAP")?;
 
     let imap_emails = client.fetch_emails(&folder_name, limit.unwrap_or(50))
-        .map_err(|e| format!("Failed to fetch emails: {}", e))?;
+        .context("Failed to fetch emails")?;
 
     client.disconnect()
-        .map_err(|e| format!("Failed to disconnect: {}", e))?;
-
-    // Save emails to database
-    for email in &imap_emails {
-        let email_id = format!("{}-{}-{}", account_id, folder_name, email.uid);
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO emails (id, account_id, folder_id, uid, message_id, subject, from_addr, to_addr, 
-                                          date, is_read, is_starred, has_attachments, preview)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(&email_id)
-        .bind(&account_id)
-        .bind(&folder_name)
-        .bind(email.uid as i64)
-        .bind(&email.id)
-        .bind(&email.subject)
-        .bind(&email.from)
-        .bind(&email.to.join(","))
-        .bind(&email.date)
-        .bind(email.read)
-        .bind(email.starred)
-        .bind(email.has_attachments)
-        .bind(&email.body.chars().take(100).collect::<String>())
-        .execute(&db.pool)
-        .await
-        .map_err(|e| format!("Failed to save email: {}", e))?;
+        .context("Failed to disconnect")?;
+
+    store_fetched(&db, &account_id, &folder_name, &imap_emails).await
+}
+
+// Saves the folders listed by the server
+pub async fn store_folders(db: &Database, account_id: &str, imap_folders: &[ImapFolder]) -> MailResult<Vec<Folder>> {
+    let mut folders = Vec::new();
+    for folder in imap_folders {
+        folders.push(db.folders().save_synced(account_id, folder).await?);
+    }
+    Ok(folders)
+}
+
+// Saves messages fetched from a folder, with their attachments, delivery reports and the
+// contacts they mention, then threads them
+pub async fn store_fetched(db: &Database, account_id: &str, folder_name: &str, imap_emails: &[ImapEmail]) -> MailResult<Vec<Email>> {
+    // Filed under the folder's row in `folders`
+    let folder_id = db.folders().ensure(account_id, folder_name).await?;
+
+    let mut emails = Vec::new();
+    for email in imap_emails {
+        let (saved, inserted) = db.messages().save_synced(account_id, &folder_id, folder_name, email).await?;
+
+        if let Some(report) = &email.report {
+            store_delivery_report(&db.pool, account_id, &saved.id, report).await?;
+        }
+        db.attachments().save_received(&saved.id, &email.attachments).await?;
+
+        // Contacts are a convenience; a failure here must not fail the sync. Only new
+        // messages count, or every re-sync would count the same addresses again.
+        if inserted {
+            if let Err(e) = contacts::harvest_received(&db.pool, email).await {
+                tracing::warn!("Failed to update contacts: {}", e);
+            }
+        }
+        emails.push(saved);
     }
 
-    let emails = imap_emails.into_iter().map(|e| Email {
-        id: format!("{}-{}-{}", account_id, folder_name, e.uid),
-        account_id,
-        folder_id: folder_name,
-        uid: e.uid as i64,
-        message_id: Some(e.id),
-        subject: Some(e.subject),
-        from_addr: Some(e.from),
-        to_addr: Some(e.to.join(",")),
-        date: Some(e.date),
-        is_read: e.read,
-        is_starred: e.starred,
-        has_attachments: e.has_attachments,
-        preview: Some(e.body.chars().take(100).collect::<String>()),
-    }).collect();
+    // Messages left unthreaded are picked up again by the next sync
+    if let Err(e) = threads::assign_threads(&db.pool, account_id).await {
+        tracing::warn!("Failed to thread messages: {}", e);
+    }
 
     Ok(emails)
 }
 
 #[command]
-pub async fn send_email_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, message: EmailMessage) -> Result<(), String> {
+pub async fn send_email_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, message: EmailMessage) -> MailResult<SendReceipt> {
+    // Messages are held in the outbox until the undo window (or their scheduled time) has passed
+    let undo_seconds = outbox::undo_send_delay(&db.pool).await?;
+    if let Some(send_at) = outbox::due_time(message.send_at.as_deref(), undo_seconds)? {
+        let outbox_id = outbox::enqueue(&db.pool, &account_id, &message, send_at).await?;
+        return Ok(SendReceipt {
+            outbox_id: Some(outbox_id),
+            send_at: Some(outbox::format_timestamp(send_at)),
+        });
+    }
+
+    deliver_message(db, app_handle, account_id, message).await?;
+
+    Ok(SendReceipt {
+        outbox_id: None,
+        send_at: None,
+    })
+}
+
+// Sends a message immediately; used directly and by the outbox scheduler
+pub async fn deliver_message(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String, message: EmailMessage) -> MailResult<()> {
+    let db_pool = db.pool.clone();
+    let identity = resolve_identity(&db_pool, &account_id, message.identity_id.as_deref()).await?;
+
     // Get account with credentials
-    let config = get_account_with_credentials(db, app_handle, account_id).await?;
+    let config = get_account_with_credentials(db, app_handle, account_id.clone()).await?;
+
+    let sender = match &identity {
+        Some(identity) => {
+            let signature = match &identity.signature_id {
+                Some(signature_id) => load_rich_signature(&db_pool, signature_id).await?,
+                None => None,
+            };
+            Sender::from(identity).with_signature(signature)
+        }
+        None => Sender::from_address(&config.smtp_config.from),
+    };
 
     let client = SmtpClient::new(config.smtp_config);
-    client.send_email(message)
-        .map_err(|e| format!("Failed to send email: {}", e))?;
+    let message_id = client.send_email_as(message.clone(), &sender)
+        .context("Failed to send email")?;
+
+    // Remembered so incoming bounces and read receipts can be matched to it. The server has
+    // already accepted the message, so a failure here must not report (or retry) the send.
+    if let Err(e) = record_sent_message(&db_pool, &account_id, &message_id, &message).await {
+        tracing::warn!("Failed to record sent message: {}", e);
+    }
+
+    if let Err(e) = contacts::harvest_sent(&db_pool, &message).await {
+        tracing::warn!("Failed to update contacts: {}", e);
+    }
 
     Ok(())
 }
 
 #[command]
-pub async fn test_imap_connection_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> Result<String, String> {
+pub async fn test_imap_connection_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<String> {
     // Get account with credentials
     let config = get_account_with_credentials(db, app_handle, account_id).await?;
 
     let mut client = ImapClient::new(config.imap_config);
     client.connect()
-        .map_err(|e| format!("Connection test failed: {}", e))?;
+        .context("Connection test failed")?;
     
     client.disconnect()
-        .map_err(|e| format!("Failed to disconnect after test: {}", e))?;
+        .context("Failed to disconnect after test")?;
     
     Ok("IMAP connection test successful".to_string())
 }
 
 #[command]
-pub async fn test_smtp_connection_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> Result<String, String> {
+pub async fn test_smtp_connection_secure(db: tauri::State<'_, Database>, app_handle: tauri::AppHandle, account_id: String) -> MailResult<String> {
     // Get account with credentials
     let config = get_account_with_credentials(db, app_handle, account_id).await?;
 
+    let from = config.smtp_config.from.clone();
     let client = SmtpClient::new(config.smtp_config);
     
     // Try to create a test message to verify connection
     let test_message = EmailMessage {
-        to: vec![config.smtp_config.from.clone()],
+        to: vec![from],
         cc: vec![],
         bcc: vec![],
         subject: "Connection Test".to_string(),
         body_text: "This is a connection test message.".to_string(),
         body_html: None,
         attachments: vec![],
+        identity_id: None,
+        compose_mode: ComposeMode::New,
+        in_reply_to: None,
+        references: vec![],
+        quoted_text: None,
+        quoted_html: None,
+        send_at: None,
+        request_dsn: false,
+        request_read_receipt: false,
     };
 
     // Note: This would actually send a test email. For real implementation,
     // we might want to just test the connection without sending.
     client.send_email(test_message)
-        .map_err(|e| format!("SMTP connection test failed: {}", e))?;
+        .context("SMTP connection test failed")?;
 
     Ok("SMTP connection test successful".to_string())
 }
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::email_secure::store_fetched;
+    use crate::db::blobs::BlobStore;
+    use crate::db::Database;
+    use crate::email::address::Mailbox;
+    use crate::imap_client::ImapEmail;
+    use crate::test_utils::seeded_pool;
+    use tempfile::TempDir;
+
+    fn imap_email(uid: u32) -> ImapEmail {
+        ImapEmail {
+            id: uid.to_string(),
+            uid,
+            message_id: Some(format!("<{}@example.com>", uid)),
+            in_reply_to: None,
+            references: vec![],
+            from: "Alice <alice@example.com>".to_string(),
+            to: vec![Mailbox { name: None, address: "me@example.com".to_string() }],
+            cc: vec![Mailbox { name: None, address: "bob@example.com".to_string() }],
+            subject: "Plans".to_string(),
+            body: "Hello there".to_string(),
+            date: Some("2026-03-01T10:00:00Z".to_string()),
+            size: 1200,
+            read: false,
+            starred: false,
+            has_attachments: false,
+            attachments: vec![],
+            folder: "INBOX".to_string(),
+            report: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resync_does_not_count_contacts_again() {
+        let dir = TempDir::new().unwrap();
+        let pool = seeded_pool("INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com')").await;
+        let db = Database { pool, blobs: BlobStore::new(dir.path()) };
+
+        store_fetched(&db, "acc", "INBOX", &[imap_email(1)]).await.unwrap();
+        // The same message again, plus one that is new
+        let mut read = imap_email(1);
+        read.read = true;
+        store_fetched(&db, "acc", "INBOX", &[read, imap_email(2)]).await.unwrap();
+        store_fetched(&db, "acc", "INBOX", &[imap_email(2)]).await.unwrap();
+
+        let seen: i64 = sqlx::query_scalar("SELECT times_seen FROM contact_emails WHERE email = 'alice@example.com'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(seen, 2);
+        let is_read: bool = sqlx::query_scalar("SELECT is_read FROM emails WHERE uid = 1")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert!(is_read);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::db::Database;
+use crate::email::address::{self, Mailbox};
+use crate::email::signature::SignaturePlacement;
+use crate::error::{Context, MailError, MailResult};
+use crate::models::Identity;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite, SqliteConnection};
+use tauri::command;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentityInput {
+    pub id: Option<String>,
+    pub account_id: String,
+    pub display_name: Option<String>,
+    pub email: String,
+    pub reply_to: Option<String>,
+    pub signature_id: Option<String>,
+    pub reply_placement: Option<SignaturePlacement>,
+    pub forward_placement: Option<SignaturePlacement>,
+    pub default_bcc: Option<String>,
+    pub is_default: bool,
+}
+
+#[command]
+pub async fn list_identities(db: tauri::State<'_, Database>, account_id: String) -> MailResult<Vec<Identity>> {
+    load_identities(&db.pool, &account_id).await
+}
+
+#[command]
+pub async fn save_identity(db: tauri::State<'_, Database>, identity: IdentityInput) -> MailResult<String> {
+    save(&db.pool, &identity).await
+}
+
+#[command]
+pub async fn delete_identity(db: tauri::State<'_, Database>, identity_id: String) -> MailResult<()> {
+    delete(&db.pool, &identity_id).await
+}
+
+// Every account has exactly one default identity: setting one clears the others, and
+// an account left without one gets its oldest identity back as the default
+pub async fn save(pool: &Pool<Sqlite>, identity: &IdentityInput) -> MailResult<String> {
+    let email = identity.email.trim().to_string();
+    if email.is_empty() || !email.contains('@') {
+        return Err(MailError::validation(format!("Invalid identity address: {}", identity.email)));
+    }
+
+    let identity_id = identity.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    if identity.is_default {
+        sqlx::query("UPDATE identities SET is_default = 0 WHERE account_id = ?")
+            .bind(&identity.account_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to clear default identity")?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO identities (id, account_id, display_name, email, reply_to, signature_id,
+                                reply_placement, forward_placement, default_bcc, is_default)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            display_name = excluded.display_name,
+            email = excluded.email,
+            reply_to = excluded.reply_to,
+            signature_id = excluded.signature_id,
+            reply_placement = excluded.reply_placement,
+            forward_placement = excluded.forward_placement,
+            default_bcc = excluded.default_bcc,
+            is_default = excluded.is_default
+        "#
+    )
+    .bind(&identity_id)
+    .bind(&identity.account_id)
+    .bind(&identity.display_name)
+    .bind(&email)
+    .bind(&identity.reply_to)
+    .bind(&identity.signature_id)
+    .bind(identity.reply_placement.unwrap_or(SignaturePlacement::AboveQuote).as_str())
+    .bind(identity.forward_placement.unwrap_or(SignaturePlacement::AboveQuote).as_str())
+    .bind(&identity.default_bcc)
+    .bind(identity.is_default)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to save identity")?;
+
+    ensure_default(&mut tx, &identity.account_id).await?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(identity_id)
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, identity_id: &str) -> MailResult<()> {
+    let account_id: String = sqlx::query_scalar("SELECT account_id FROM identities WHERE id = ?")
+        .bind(identity_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to get identity")?
+        .ok_or_else(|| MailError::NotFound(format!("Identity not found: {}", identity_id)))?;
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    sqlx::query("DELETE FROM identities WHERE id = ?")
+        .bind(identity_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete identity")?;
+
+    ensure_default(&mut tx, &account_id).await?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+// Promotes the oldest identity when the account has none marked as default
+async fn ensure_default(conn: &mut SqliteConnection, account_id: &str) -> MailResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE identities SET is_default = 1
+        WHERE id = (SELECT id FROM identities WHERE account_id = ? ORDER BY created_at, id LIMIT 1)
+          AND NOT EXISTS (SELECT 1 FROM identities WHERE account_id = ? AND is_default)
+        "#
+    )
+    .bind(account_id)
+    .bind(account_id)
+    .execute(conn)
+    .await
+    .context("Failed to update default identity")?;
+    Ok(())
+}
+
+// Picks the identity to reply with, based on which of our aliases the original was sent to
+#[command]
+pub async fn get_reply_identity(db: tauri::State<'_, Database>, email_id: String) -> MailResult<Option<Identity>> {
+    reply_identity(&db.pool, &email_id).await
+}
+
+pub async fn reply_identity(pool: &Pool<Sqlite>, email_id: &str) -> MailResult<Option<Identity>> {
+    let email = sqlx::query("SELECT account_id, to_addr, cc_addr FROM emails WHERE id = ?")
+        .bind(email_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to get email")?;
+
+    let account_id: String = email.get("account_id");
+    let mut recipients = Vec::new();
+    for column in ["to_addr", "cc_addr"] {
+        if let Some(value) = email.get::<Option<String>, _>(column) {
+            recipients.extend(address::parse_list(&value).0);
+        }
+    }
+
+    let identities = load_identities(pool, &account_id).await?;
+    Ok(pick_reply_identity(&identities, &recipients)
+        .or_else(|| identities.iter().find(|i| i.is_default))
+        .cloned())
+}
+
+pub async fn load_identities(pool: &Pool<Sqlite>, account_id: &str) -> MailResult<Vec<Identity>> {
+    sqlx::query_as::<_, Identity>(
+        r#"
+        SELECT id, account_id, display_name, email, reply_to, signature_id, reply_placement,
+               forward_placement, default_bcc, is_default
+        FROM identities WHERE account_id = ? ORDER BY is_default DESC, created_at
+        "#
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch identities")
+}
+
+// Resolves the identity a message should be sent as: the requested one if given,
+// otherwise the account's default. Returns None for accounts without identities.
+pub async fn resolve_identity(pool: &Pool<Sqlite>, account_id: &str, identity_id: Option<&str>) -> MailResult<Option<Identity>> {
+    let identities = load_identities(pool, account_id).await?;
+
+    match identity_id {
+        Some(identity_id) => identities.into_iter()
+            .find(|i| i.id == identity_id)
+            .map(Some)
+            .ok_or_else(|| MailError::validation(format!("Identity {} does not belong to account {}", identity_id, account_id))),
+        None => Ok(identities.into_iter().find(|i| i.is_default)),
+    }
+}
+
+// Every account starts with a default identity for its primary address
+pub async fn create_default_identity(pool: &Pool<Sqlite>, account_id: &str, name: &str, email: &str) -> MailResult<()> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO identities (id, account_id, display_name, email, is_default) VALUES (?, ?, ?, ?, 1)"
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(account_id)
+    .bind(name)
+    .bind(email)
+    .execute(pool)
+    .await
+    .context("Failed to create default identity")?;
+
+    Ok(())
+}
+
+pub fn pick_reply_identity<'a>(identities: &'a [Identity], recipients: &[Mailbox]) -> Option<&'a Identity> {
+    identities.iter().find(|identity| {
+        address::parse_mailbox(&identity.email)
+            .map(|own| recipients.iter().any(|recipient| recipient.same_address(&own)))
+            .unwrap_or(false)
+    })
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::identities::*;
+    use crate::email::address::parse_list;
+    use crate::error::MailError;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com'), ('other', 'them@example.org');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-INBOX', 'acc', 'INBOX');
+            INSERT INTO identities (id, account_id, email, is_default, created_at) VALUES
+                ('main', 'acc', 'me@example.com', 1, '2026-01-01 00:00:00'),
+                ('sales', 'acc', 'sales@example.com', 0, '2026-01-02 00:00:00'),
+                ('theirs', 'other', 'them@example.org', 1, '2026-01-01 00:00:00');
+            "#
+        )
+        .await
+    }
+
+    fn input(id: Option<&str>, email: &str, is_default: bool) -> IdentityInput {
+        IdentityInput {
+            id: id.map(|id| id.to_string()),
+            account_id: "acc".to_string(),
+            display_name: None,
+            email: email.to_string(),
+            reply_to: None,
+            signature_id: None,
+            reply_placement: None,
+            forward_placement: None,
+            default_bcc: None,
+            is_default,
+        }
+    }
+
+    async fn defaults(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT id FROM identities WHERE account_id = 'acc' AND is_default ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_email(pool: &Pool<Sqlite>, id: &str, to: &str, cc: Option<&str>) {
+        sqlx::query("INSERT INTO emails (id, account_id, folder_id, uid, to_addr, cc_addr) VALUES (?, 'acc', 'acc-INBOX', (SELECT COUNT(*) FROM emails) + 1, ?, ?)")
+            .bind(id)
+            .bind(to)
+            .bind(cc)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pick_reply_identity_matches_alias() {
+        let pool = setup_pool().await;
+        let identities = load_identities(&pool, "acc").await.unwrap();
+
+        let (recipients, _) = parse_list("Team <team@example.org>, Sales <SALES@Example.com>");
+        assert_eq!(pick_reply_identity(&identities, &recipients).map(|i| i.id.as_str()), Some("sales"));
+
+        let (recipients, _) = parse_list("someone@example.net");
+        assert!(pick_reply_identity(&identities, &recipients).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reply_identity_falls_back_to_default() {
+        let pool = setup_pool().await;
+        insert_email(&pool, "to-alias", "team@example.org", Some("sales@example.com")).await;
+        insert_email(&pool, "to-list", "list@example.net", None).await;
+
+        assert_eq!(reply_identity(&pool, "to-alias").await.unwrap().unwrap().id, "sales");
+        assert_eq!(reply_identity(&pool, "to-list").await.unwrap().unwrap().id, "main");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_identity() {
+        let pool = setup_pool().await;
+
+        assert_eq!(resolve_identity(&pool, "acc", None).await.unwrap().unwrap().id, "main");
+        assert_eq!(resolve_identity(&pool, "acc", Some("sales")).await.unwrap().unwrap().id, "sales");
+        // Another account's identity can't be used to send
+        let error = resolve_identity(&pool, "acc", Some("theirs")).await.unwrap_err();
+        assert!(matches!(error, MailError::Validation { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_exactly_one_default() {
+        let pool = setup_pool().await;
+
+        save(&pool, &input(Some("sales"), "sales@example.com", true)).await.unwrap();
+        assert_eq!(defaults(&pool).await, vec!["sales"]);
+
+        // Unsetting the only default hands it to the oldest identity
+        save(&pool, &input(Some("sales"), "sales@example.com", false)).await.unwrap();
+        assert_eq!(defaults(&pool).await, vec!["main"]);
+
+        let new_id = save(&pool, &input(None, "support@example.com", false)).await.unwrap();
+        assert_eq!(defaults(&pool).await, vec!["main"]);
+
+        delete(&pool, "main").await.unwrap();
+        assert_eq!(defaults(&pool).await, vec!["sales"]);
+        delete(&pool, "sales").await.unwrap();
+        assert_eq!(defaults(&pool).await, vec![new_id]);
+
+        // The other account is left alone throughout
+        let theirs: bool = sqlx::query_scalar("SELECT is_default FROM identities WHERE id = 'theirs'").fetch_one(&pool).await.unwrap();
+        assert!(theirs);
+    }
+
+    #[tokio::test]
+    async fn test_first_identity_becomes_default() {
+        let pool = seeded_pool("INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com')").await;
+
+        let id = save(&pool, &input(None, "me@example.com", false)).await.unwrap();
+        assert_eq!(defaults(&pool).await, vec![id]);
+        assert!(matches!(delete(&pool, "missing").await.unwrap_err(), MailError::NotFound(_)));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::commands::search::SortField;
+use crate::db::Database;
+use crate::email::folder_role::FolderRole;
+use crate::error::{Context, MailError, MailResult};
+use crate::models::Email;
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, QueryBuilder, Row, Sqlite};
+use tauri::command;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+// Lists messages from the local cache, one page at a time, without touching the network
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageListRequest {
+    // Both unset lists every account
+    pub account_id: Option<String>,
+    pub folder_id: Option<String>,
+    // Only folders with one of these roles, in every account unless account_id is set
+    #[serde(default)]
+    pub roles: Vec<FolderRole>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub ascending: bool,
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+    pub has_attachments: Option<bool>,
+    // `next_cursor` of the previous page; None for the first page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessagePage {
+    pub emails: Vec<Email>,
+    // None on the last page
+    pub next_cursor: Option<String>,
+}
+
+// Position after the last message of a page. Pages are keyed on (sort value, id) rather
+// than an offset, so deep pages cost the same as the first and new mail doesn't shift them.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: SortField,
+    ascending: bool,
+    key: CursorKey,
+    id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CursorKey {
+    Number(i64),
+    Text(String),
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(value: &str) -> MailResult<Self> {
+        general_purpose::URL_SAFE_NO_PAD.decode(value)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| MailError::validation("Invalid page cursor"))
+    }
+}
+
+// Must match the expressions of the idx_emails_folder_* indexes in schema.sql
+fn sort_key(sort: SortField) -> &'static str {
+    match sort {
+        SortField::Date => "COALESCE(e.date, '')",
+        SortField::Sender => "COALESCE(e.from_addr, '') COLLATE NOCASE",
+        SortField::Subject => "COALESCE(e.subject, '') COLLATE NOCASE",
+        SortField::Size => "COALESCE(e.size, 0)",
+    }
+}
+
+fn push_key(query: &mut QueryBuilder<'_, Sqlite>, key: &CursorKey) {
+    match key {
+        CursorKey::Number(value) => query.push_bind(*value),
+        CursorKey::Text(value) => query.push_bind(value.clone()),
+    };
+}
+
+pub async fn list(pool: &Pool<Sqlite>, request: &MessageListRequest) -> MailResult<MessagePage> {
+    let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let key = sort_key(request.sort);
+
+    let cursor = request.cursor.as_deref().map(Cursor::decode).transpose()?;
+    if let Some(cursor) = &cursor {
+        if cursor.sort != request.sort || cursor.ascending != request.ascending {
+            return Err(MailError::validation("Page cursor belongs to a different sort order"));
+        }
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT e.id, e.account_id, e.folder_id, e.uid, e.message_id, e.subject, e.from_addr, e.to_addr, \
+         e.date, e.is_read, e.is_starred, e.has_attachments, e.preview, e.size, {} AS sort_key \
+         FROM emails e WHERE 1 = 1",
+        key
+    ));
+    if let Some(account_id) = &request.account_id {
+        query.push(" AND e.account_id = ").push_bind(account_id.clone());
+    }
+    if let Some(folder_id) = &request.folder_id {
+        query.push(" AND e.folder_id = ").push_bind(folder_id.clone());
+    }
+    if !request.roles.is_empty() {
+        query.push(" AND e.folder_id IN (SELECT f.id FROM folders f WHERE f.role IN (");
+        let mut roles = query.separated(", ");
+        for role in &request.roles {
+            roles.push_bind(role.as_str());
+        }
+        roles.push_unseparated("))");
+    }
+    if let Some(is_read) = request.is_read {
+        query.push(" AND e.is_read = ").push_bind(is_read);
+    }
+    if let Some(is_starred) = request.is_starred {
+        query.push(" AND e.is_starred = ").push_bind(is_starred);
+    }
+    if let Some(has_attachments) = request.has_attachments {
+        query.push(" AND e.has_attachments = ").push_bind(has_attachments);
+    }
+
+    let (direction, strict, inclusive) = if request.ascending { ("ASC", ">", ">=") } else { ("DESC", "<", "<=") };
+    if let Some(cursor) = cursor {
+        // Spelled out rather than as a row value `(key, id) < (?, ?)`, which SQLite can't
+        // turn into an index range
+        query.push(format!(" AND {} {} ", key, inclusive));
+        push_key(&mut query, &cursor.key);
+        query.push(format!(" AND ({} {} ", key, strict));
+        push_key(&mut query, &cursor.key);
+        query.push(format!(" OR e.id {} ", strict)).push_bind(cursor.id).push(")");
+    }
+    query.push(format!(" ORDER BY {} {}, e.id {}", key, direction, direction));
+    // One extra row tells whether there is another page
+    query.push(" LIMIT ").push_bind(limit as i64 + 1);
+
+    let rows = query.build()
+        .fetch_all(pool)
+        .await
+        .context("Failed to list messages")?;
+
+    let has_more = rows.len() > limit as usize;
+    let mut emails = Vec::with_capacity(limit as usize);
+    let mut next_cursor = None;
+    for row in rows.iter().take(limit as usize) {
+        let email = Email::from_row(row).context("Failed to read message")?;
+        if has_more && emails.len() + 1 == limit as usize {
+            let key = match request.sort {
+                SortField::Size => CursorKey::Number(row.get("sort_key")),
+                _ => CursorKey::Text(row.get("sort_key")),
+            };
+            next_cursor = Some(Cursor { sort: request.sort, ascending: request.ascending, key, id: email.id.clone() }.encode());
+        }
+        emails.push(email);
+    }
+
+    Ok(MessagePage { emails, next_cursor })
+}
+
+#[command]
+pub async fn list_messages(db: tauri::State<'_, Database>, request: MessageListRequest) -> MailResult<MessagePage> {
+    list(&db.pool, &request).await
+}
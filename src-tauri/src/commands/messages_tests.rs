@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::messages::*;
+    use crate::commands::search::SortField;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('INBOX', 'acc', 'INBOX');
+            "#
+        )
+        .await
+    }
+
+    async fn store(pool: &Pool<Sqlite>, id: &str, from: &str, date: Option<&str>, size: i64, is_read: bool) {
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, from_addr, date, size, is_read)
+            VALUES (?, 'acc', 'INBOX', (SELECT COUNT(*) FROM emails) + 1, ?, ?, ?, ?)
+            "#
+        )
+        .bind(id)
+        .bind(from)
+        .bind(date)
+        .bind(size)
+        .bind(is_read)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn all_pages(pool: &Pool<Sqlite>, mut request: MessageListRequest) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        loop {
+            let page = list(pool, &request).await.unwrap();
+            pages.push(page.emails.iter().map(|email| email.id.clone()).collect());
+            match page.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor),
+                None => return pages,
+            }
+        }
+    }
+
+    async fn seed(pool: &Pool<Sqlite>) {
+        store(pool, "a", "carol@example.com", Some("2024-01-05T00:00:00Z"), 300, false).await;
+        store(pool, "b", "Alice@example.com", Some("2024-01-04T00:00:00Z"), 100, true).await;
+        store(pool, "c", "alice@example.com", Some("2024-01-04T00:00:00Z"), 500, false).await;
+        store(pool, "d", "bob@example.com", None, 200, true).await;
+        store(pool, "e", "bob@example.com", Some("2024-01-01T00:00:00Z"), 400, false).await;
+    }
+
+    fn request(sort: SortField, ascending: bool) -> MessageListRequest {
+        MessageListRequest {
+            account_id: Some("acc".to_string()),
+            folder_id: Some("INBOX".to_string()),
+            sort,
+            ascending,
+            limit: Some(2),
+            ..MessageListRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pages_cover_folder_once_in_order() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        // Equal dates fall back to the id; a missing date sorts as the oldest
+        assert_eq!(all_pages(&pool, request(SortField::Date, false)).await, vec![
+            vec!["a", "c"], vec!["b", "e"], vec!["d"],
+        ]);
+        // Sender sorts ignore case
+        assert_eq!(all_pages(&pool, request(SortField::Sender, true)).await, vec![
+            vec!["b", "c"], vec!["d", "e"], vec!["a"],
+        ]);
+        assert_eq!(all_pages(&pool, request(SortField::Size, false)).await, vec![
+            vec!["c", "e"], vec!["a", "d"], vec!["b"],
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_cursor_checks() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let mut unread = request(SortField::Date, false);
+        unread.is_read = Some(false);
+        unread.limit = Some(10);
+        assert_eq!(all_pages(&pool, unread).await, vec![vec!["a", "c", "e"]]);
+
+        // New mail arriving between pages doesn't shift the next page
+        let first = list(&pool, &request(SortField::Date, false)).await.unwrap();
+        store(&pool, "f", "dave@example.com", Some("2024-02-01T00:00:00Z"), 10, false).await;
+        let mut next = request(SortField::Date, false);
+        next.cursor = first.next_cursor.clone();
+        let second = list(&pool, &next).await.unwrap();
+        assert_eq!(second.emails.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["b", "e"]);
+
+        let mut mismatched = request(SortField::Size, false);
+        mismatched.cursor = first.next_cursor;
+        assert!(list(&pool, &mismatched).await.is_err());
+        let mut garbage = request(SortField::Date, false);
+        garbage.cursor = Some("not a cursor".to_string());
+        assert!(list(&pool, &garbage).await.is_err());
+    }
+}
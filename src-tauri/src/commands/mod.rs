@@ -1,10 +1,32 @@
 pub mod email;
-pub mod email_ops;
 pub mod email_secure;
 pub mod folder_ops;
 pub mod email_actions;
 pub mod attachments;
 pub mod search;
+pub mod saved_searches;
+pub mod messages;
+pub mod unified;
+pub mod identities;
+pub mod signatures;
+pub mod scheduled;
+pub mod delivery_status;
+pub mod contacts;
+pub mod threads;
 
 #[cfg(test)]
-mod email_ops_tests;
+mod threads_tests;
+#[cfg(test)]
+mod search_tests;
+#[cfg(test)]
+mod saved_searches_tests;
+#[cfg(test)]
+mod messages_tests;
+#[cfg(test)]
+mod unified_tests;
+#[cfg(test)]
+mod email_actions_tests;
+#[cfg(test)]
+mod identities_tests;
+#[cfg(test)]
+mod email_secure_tests;
@@ -0,0 +1,221 @@
+use crate::commands::search::{self, SearchOptions, SearchQuery, SearchResult, SortField};
+use crate::db::Database;
+use crate::email::search_query;
+use crate::error::{Context, MailError, MailResult};
+use crate::models::SavedSearch;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use tauri::command;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearchInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+    #[serde(default)]
+    pub folder_ids: Vec<String>,
+}
+
+// One row of the folder list: a real folder or a saved search shown as a virtual folder
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FolderEntry {
+    Folder {
+        id: String,
+        account_id: String,
+        name: String,
+        unread_count: i64,
+        total_count: i64,
+    },
+    SavedSearch {
+        id: String,
+        name: String,
+        unread_count: i64,
+        total_count: i64,
+    },
+}
+
+pub async fn save(pool: &Pool<Sqlite>, input: &SavedSearchInput) -> MailResult<String> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(MailError::validation("Saved search needs a name"));
+    }
+    search_query::parse(&input.query).context("Invalid search query")?;
+
+    let account_ids = serde_json::to_string(&input.account_ids)
+        .context("Failed to encode saved search scope")?;
+    let folder_ids = serde_json::to_string(&input.folder_ids)
+        .context("Failed to encode saved search scope")?;
+    let id = input.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // New searches go to the end of the list; edits keep their place
+    sqlx::query(
+        r#"
+        INSERT INTO saved_searches (id, name, query, account_ids, folder_ids, position)
+        VALUES (?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position), -1) + 1 FROM saved_searches))
+        ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            query = excluded.query,
+            account_ids = excluded.account_ids,
+            folder_ids = excluded.folder_ids
+        "#
+    )
+    .bind(&id)
+    .bind(name)
+    .bind(&input.query)
+    .bind(&account_ids)
+    .bind(&folder_ids)
+    .execute(pool)
+    .await
+    .context("Failed to save search")?;
+
+    Ok(id)
+}
+
+pub async fn list(pool: &Pool<Sqlite>) -> MailResult<Vec<SavedSearch>> {
+    let rows = sqlx::query("SELECT id, name, query, account_ids, folder_ids, position FROM saved_searches ORDER BY position, name")
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch saved searches")?;
+    rows.iter().map(from_row).collect()
+}
+
+pub async fn get(pool: &Pool<Sqlite>, id: &str) -> MailResult<Option<SavedSearch>> {
+    let row = sqlx::query("SELECT id, name, query, account_ids, folder_ids, position FROM saved_searches WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch saved search")?;
+    row.as_ref().map(from_row).transpose()
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> MailResult<SavedSearch> {
+    let ids = |column: &str| -> MailResult<Vec<String>> {
+        serde_json::from_str(&row.get::<String, _>(column))
+            .context("Invalid saved search scope")
+    };
+    Ok(SavedSearch {
+        id: row.get("id"),
+        name: row.get("name"),
+        query: row.get("query"),
+        account_ids: ids("account_ids")?,
+        folder_ids: ids("folder_ids")?,
+        position: row.get("position"),
+    })
+}
+
+fn options(saved: &SavedSearch, sort: Option<SortField>, ascending: bool) -> SearchOptions {
+    SearchOptions {
+        account_ids: saved.account_ids.clone(),
+        folder_ids: saved.folder_ids.clone(),
+        sort,
+        ascending,
+    }
+}
+
+// (unread, total) messages matching each search right now, in one query
+async fn counts_of(pool: &Pool<Sqlite>, searches: &[SavedSearch]) -> MailResult<Vec<(i64, i64)>> {
+    let queries: Vec<(SearchQuery, SearchOptions)> = searches.iter()
+        .map(|saved| (SearchQuery::new(saved.query.clone()), options(saved, None, false)))
+        .collect();
+    search::count_unread_and_total(pool, &queries).await
+}
+
+pub async fn messages(
+    pool: &Pool<Sqlite>,
+    saved: &SavedSearch,
+    sort: Option<SortField>,
+    ascending: bool,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> MailResult<SearchResult> {
+    let mut query = SearchQuery::new(saved.query.clone());
+    query.limit = limit;
+    query.offset = offset;
+    search::search_with(pool, &query, &options(saved, sort, ascending)).await
+}
+
+#[command]
+pub async fn list_saved_searches(db: tauri::State<'_, Database>) -> MailResult<Vec<SavedSearch>> {
+    list(&db.pool).await
+}
+
+#[command]
+pub async fn save_saved_search(db: tauri::State<'_, Database>, search: SavedSearchInput) -> MailResult<String> {
+    save(&db.pool, &search).await
+}
+
+#[command]
+pub async fn delete_saved_search(db: tauri::State<'_, Database>, search_id: String) -> MailResult<()> {
+    sqlx::query("DELETE FROM saved_searches WHERE id = ?")
+        .bind(&search_id)
+        .execute(&db.pool)
+        .await
+        .context("Failed to delete saved search")?;
+    Ok(())
+}
+
+#[command]
+pub async fn list_saved_search_messages(
+    db: tauri::State<'_, Database>,
+    search_id: String,
+    sort: Option<SortField>,
+    ascending: Option<bool>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> MailResult<SearchResult> {
+    let saved = get(&db.pool, &search_id)
+        .await?
+        .ok_or_else(|| MailError::NotFound(format!("Saved search '{}' not found", search_id)))?;
+    messages(&db.pool, &saved, sort, ascending.unwrap_or(false), limit, offset).await
+}
+
+// Real folders (of one account, or of all) followed by the saved searches, each with
+// its current unread and total counts
+#[command]
+pub async fn list_folder_entries(db: tauri::State<'_, Database>, account_id: Option<String>) -> MailResult<Vec<FolderEntry>> {
+    folder_entries(&db.pool, account_id.as_deref()).await
+}
+
+pub async fn folder_entries(pool: &Pool<Sqlite>, account_id: Option<&str>) -> MailResult<Vec<FolderEntry>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT f.id, f.account_id, f.name,
+               COUNT(e.id) AS total_count,
+               COALESCE(SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END), 0) AS unread_count
+        FROM folders f
+        LEFT JOIN emails e ON e.folder_id = f.id AND e.account_id = f.account_id
+        WHERE ?1 IS NULL OR f.account_id = ?1
+        GROUP BY f.id
+        ORDER BY f.account_id, f.name
+        "#
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch folders")?;
+
+    let mut entries: Vec<FolderEntry> = rows.into_iter()
+        .map(|row| FolderEntry::Folder {
+            id: row.get("id"),
+            account_id: row.get("account_id"),
+            name: row.get("name"),
+            unread_count: row.get("unread_count"),
+            total_count: row.get("total_count"),
+        })
+        .collect();
+
+    let searches = list(pool).await?;
+    let counts = counts_of(pool, &searches).await?;
+    for (saved, (unread_count, total_count)) in searches.into_iter().zip(counts) {
+        entries.push(FolderEntry::SavedSearch {
+            id: saved.id,
+            name: saved.name,
+            unread_count,
+            total_count,
+        });
+    }
+    Ok(entries)
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::saved_searches::*;
+    use crate::commands::search::SortField;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('work', 'me@work.example.com'), ('home', 'me@home.example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('work-inbox', 'work', 'INBOX'), ('home-inbox', 'home', 'INBOX');
+            "#
+        )
+        .await
+    }
+
+    async fn store(pool: &Pool<Sqlite>, id: &str, account: &str, subject: &str, is_read: bool, date: &str) {
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, subject, from_addr, is_read, date)
+            VALUES (?, ?, ?, (SELECT COUNT(*) FROM emails) + 1, ?, 'boss@example.com', ?, ?)
+            "#
+        )
+        .bind(id)
+        .bind(account)
+        .bind(format!("{}-inbox", account))
+        .bind(subject)
+        .bind(is_read)
+        .bind(date)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn input(name: &str, query: &str, account_ids: &[&str]) -> SavedSearchInput {
+        SavedSearchInput {
+            id: None,
+            name: name.to_string(),
+            query: query.to_string(),
+            account_ids: account_ids.iter().map(|id| id.to_string()).collect(),
+            folder_ids: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_validates_and_keeps_order() {
+        let pool = setup_pool().await;
+        let error = save(&pool, &input("Broken", "from:", &[])).await.unwrap_err();
+        assert_eq!(error.kind(), "validation");
+        assert_eq!(error.details().unwrap()["position"], 5, "{}", error);
+        assert!(save(&pool, &input("  ", "invoice", &[])).await.is_err());
+
+        let first = save(&pool, &input("Invoices", "invoice", &[])).await.unwrap();
+        save(&pool, &input("Boss", "from:boss", &["work"])).await.unwrap();
+        // Editing keeps the position
+        let mut edited = input("Bills", "invoice OR bill", &[]);
+        edited.id = Some(first.clone());
+        save(&pool, &edited).await.unwrap();
+
+        let searches = list(&pool).await.unwrap();
+        assert_eq!(searches.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["Bills", "Boss"]);
+        assert_eq!(searches[1].account_ids, vec!["work"]);
+    }
+
+    #[tokio::test]
+    async fn test_virtual_folder_counts_and_messages() {
+        let pool = setup_pool().await;
+        store(&pool, "w1", "work", "Quarterly report", false, "2024-01-01T00:00:00Z").await;
+        store(&pool, "w2", "work", "Annual report", true, "2024-01-03T00:00:00Z").await;
+        store(&pool, "h1", "home", "School report", false, "2024-01-02T00:00:00Z").await;
+
+        let id = save(&pool, &input("Work reports", "report", &["work"])).await.unwrap();
+
+        let entries = folder_entries(&pool, Some("work")).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        match &entries[1] {
+            FolderEntry::SavedSearch { id: entry_id, unread_count, total_count, .. } => {
+                assert_eq!((entry_id.as_str(), *unread_count, *total_count), (id.as_str(), 1, 2));
+            }
+            other => panic!("expected a saved search, got {:?}", other),
+        }
+        match &entries[0] {
+            FolderEntry::Folder { unread_count, total_count, .. } => assert_eq!((*unread_count, *total_count), (1, 2)),
+            other => panic!("expected a folder, got {:?}", other),
+        }
+
+        // Marking a message read shows up straight away
+        sqlx::query("UPDATE emails SET is_read = 1 WHERE id = 'w1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let saved = get(&pool, &id).await.unwrap().unwrap();
+        match &folder_entries(&pool, Some("work")).await.unwrap()[1] {
+            FolderEntry::SavedSearch { unread_count, total_count, .. } => assert_eq!((*unread_count, *total_count), (0, 2)),
+            other => panic!("expected a saved search, got {:?}", other),
+        }
+
+        let page = messages(&pool, &saved, Some(SortField::Subject), true, Some(1), Some(1)).await.unwrap();
+        assert_eq!(page.total_count, 2);
+        assert_eq!(page.emails.iter().map(|hit| hit.email.id.as_str()).collect::<Vec<_>>(), vec!["w1"]);
+    }
+
+    #[tokio::test]
+    async fn test_every_saved_search_gets_its_own_counts() {
+        let pool = setup_pool().await;
+        store(&pool, "w1", "work", "Quarterly report", false, "2024-01-01T00:00:00Z").await;
+        store(&pool, "w2", "work", "Annual report", true, "2024-01-03T00:00:00Z").await;
+        store(&pool, "h1", "home", "School trip", false, "2024-01-02T00:00:00Z").await;
+
+        save(&pool, &input("Reports", "report", &[])).await.unwrap();
+        save(&pool, &input("Home", "school", &["home"])).await.unwrap();
+        save(&pool, &input("Nothing", "holiday", &[])).await.unwrap();
+
+        let counts: Vec<(String, i64, i64)> = folder_entries(&pool, None).await.unwrap()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                FolderEntry::SavedSearch { name, unread_count, total_count, .. } => Some((name, unread_count, total_count)),
+                FolderEntry::Folder { .. } => None,
+            })
+            .collect();
+        assert_eq!(counts, vec![
+            ("Reports".to_string(), 1, 2),
+            ("Home".to_string(), 1, 1),
+            ("Nothing".to_string(), 0, 0),
+        ]);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::db::Database;
+use crate::error::MailResult;
+use crate::outbox::{self, ScheduledEmail};
+use crate::smtp_client::EmailMessage;
+use tauri::command;
+
+// Pulls a queued message back before it is sent; the frontend reopens it as a draft
+#[command]
+pub async fn undo_send(db: tauri::State<'_, Database>, outbox_id: String) -> MailResult<EmailMessage> {
+    outbox::withdraw(&db.pool, &outbox_id).await
+}
+
+#[command]
+pub async fn list_scheduled_emails(db: tauri::State<'_, Database>, account_id: Option<String>) -> MailResult<Vec<ScheduledEmail>> {
+    outbox::list(&db.pool, account_id.as_deref()).await
+}
+
+#[command]
+pub async fn reschedule_email(db: tauri::State<'_, Database>, outbox_id: String, send_at: String) -> MailResult<()> {
+    let send_at = outbox::parse_timestamp(&send_at)?;
+    outbox::reschedule(&db.pool, &outbox_id, send_at).await
+}
+
+#[command]
+pub async fn get_undo_send_delay(db: tauri::State<'_, Database>) -> MailResult<u32> {
+    outbox::undo_send_delay(&db.pool).await
+}
+
+#[command]
+pub async fn set_undo_send_delay(db: tauri::State<'_, Database>, seconds: u32) -> MailResult<()> {
+    outbox::set_undo_send_delay(&db.pool, seconds).await
+}
@@ -1,9 +1,24 @@
 use crate::db::Database;
-use crate::models::{Email, MailAccount, MailFolder};
+use crate::email::search_query::{self, Flag, Query, QueryError, Term};
+use crate::error::{Context, MailResult};
+use crate::models::Email;
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{FromRow, Pool, QueryBuilder, Row, Sqlite};
 use tauri::command;
-use chrono::{DateTime, Utc, NaiveDateTime};
+
+// The trigram tokenizer indexes three character sequences, so shorter terms (two
+// character Chinese words, say) can't use the index and are matched with LIKE instead
+const MIN_INDEXED_CHARS: usize = 3;
+
+// Column weights for bm25(), in emails_fts column order: subject, sender, recipients,
+// body, attachments
+const RANK_WEIGHTS: &str = "10.0, 5.0, 3.0, 1.0, 2.0";
+
+// FTS5 wraps matches in these before the text is HTML-escaped; neither can occur in mail text
+const MARK_START: &str = "\u{2}";
+const MARK_END: &str = "\u{3}";
+
+const FTS_COLUMNS: [&str; 5] = ["subject", "sender", "recipients", "body", "attachments"];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchQuery {
@@ -22,203 +37,419 @@ pub struct SearchQuery {
     pub offset: Option<u32>,
 }
 
+impl SearchQuery {
+    // A query with nothing but the search text set
+    pub fn new(query: impl Into<String>) -> Self {
+        SearchQuery {
+            query: query.into(),
+            account_id: None,
+            folder_id: None,
+            date_from: None,
+            date_to: None,
+            sender: None,
+            subject_contains: None,
+            body_contains: None,
+            has_attachments: None,
+            is_read: None,
+            is_starred: None,
+            limit: None,
+            offset: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub email: Email,
+    // bm25() score, lower is better; 0 when no term could use the index
+    pub rank: f64,
+    // HTML-escaped, with matches wrapped in <mark>
+    pub subject_highlight: Option<String>,
+    pub snippet: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResult {
-    pub emails: Vec<Email>,
+    pub emails: Vec<SearchHit>,
     pub total_count: u32,
     pub query_time_ms: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FilterOptions {
-    pub date_range: Option<DateRange>,
-    pub sender_filter: Option<StringFilter>,
-    pub subject_filter: Option<StringFilter>,
-    pub body_filter: Option<StringFilter>,
-    pub attachment_filter: Option<bool>,
-    pub read_status_filter: Option<bool>,
-    pub starred_filter: Option<bool>,
+// Scope and order beyond what a SearchQuery holds, used by saved searches
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchOptions {
+    // Empty means every account or folder
+    pub account_ids: Vec<String>,
+    pub folder_ids: Vec<String>,
+    // None sorts by relevance when there is text to rank, otherwise by date
+    pub sort: Option<SortField>,
+    pub ascending: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DateRange {
-    pub from: Option<String>,
-    pub to: Option<String>,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Date,
+    Sender,
+    Subject,
+    Size,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct StringFilter {
-    pub contains: Option<String>,
-    pub starts_with: Option<String>,
-    pub ends_with: Option<String>,
-    pub exact: Option<String>,
+impl SortField {
+    fn column(self) -> &'static str {
+        match self {
+            SortField::Date => "e.date",
+            SortField::Sender => "e.from_addr COLLATE NOCASE",
+            SortField::Subject => "e.subject COLLATE NOCASE",
+            SortField::Size => "e.size",
+        }
+    }
 }
 
-#[command]
-pub async fn search_emails(db: tauri::State<'_, Database>, search_query: SearchQuery) -> Result<SearchResult, String> {
-    let start_time = std::time::Instant::now();
-    
-    let mut query_builder = String::from(
-        r#"
-        SELECT e.id, e.folder_id, e.uid, e.from_addr, e.to_addr, e.cc_addr, e.bcc_addr, 
-               e.subject, e.body, e.html_body, e.date, e.is_read, e.is_starred, 
-               e.has_attachments, e.message_id, e.in_reply_to, e.references
-        FROM emails e
-        WHERE 1=1
-        "#
-    );
+// A search ready to run: the positive text terms of the top level go into one MATCH
+// against the index, which also ranks the results, and everything else becomes a
+// condition of its own
+#[derive(Debug, Default, PartialEq)]
+pub struct CompiledSearch {
+    pub rank_match: Option<String>,
+    pub conditions: Vec<Query>,
+}
 
-    let mut params = Vec::new();
-    let mut param_index = 1;
+pub fn compile(query: &SearchQuery) -> Result<CompiledSearch, QueryError> {
+    let mut items = match search_query::parse(&query.query)? {
+        Query::And(items) => items,
+        other => vec![other],
+    };
 
-    // Add search conditions
-    if !search_query.query.is_empty() {
-        query_builder.push_str(&format!(
-            " AND (e.subject LIKE ?{} OR e.body LIKE ?{} OR e.from_addr LIKE ?{})",
-            param_index, param_index + 1, param_index + 2
-        ));
-        let search_pattern = format!("%{}%", search_query.query);
-        params.push(search_pattern.clone());
-        params.push(search_pattern.clone());
-        params.push(search_pattern);
-        param_index += 3;
+    if let Some(sender) = &query.sender {
+        items.push(Query::Term(Term::From(sender.clone())));
+    }
+    if let Some(subject) = &query.subject_contains {
+        items.push(Query::Term(Term::Subject(subject.clone())));
+    }
+    if let Some(body) = &query.body_contains {
+        items.push(Query::Term(Term::Body(body.clone())));
+    }
+    if let Some(has_attachments) = query.has_attachments {
+        items.push(negated_unless(has_attachments, Term::HasAttachment));
+    }
+    if let Some(is_read) = query.is_read {
+        items.push(Query::Term(Term::Is(if is_read { Flag::Read } else { Flag::Unread })));
+    }
+    if let Some(is_starred) = query.is_starred {
+        items.push(negated_unless(is_starred, Term::Is(Flag::Starred)));
     }
 
-    if let Some(account_id) = &search_query.account_id {
-        query_builder.push_str(&format!(" AND e.folder_id LIKE ?{}", param_index));
-        params.push(format!("{}-%", account_id));
-        param_index += 1;
+    let mut compiled = CompiledSearch::default();
+    let mut phrases = Vec::new();
+    for item in items {
+        match &item {
+            Query::Term(term) => match text_field(term).and_then(|(column, text)| fts_phrase(column, text)) {
+                Some(phrase) => phrases.push(phrase),
+                None => compiled.conditions.push(item),
+            },
+            _ => compiled.conditions.push(item),
+        }
+    }
+    if !phrases.is_empty() {
+        compiled.rank_match = Some(phrases.join(" AND "));
     }
+    Ok(compiled)
+}
 
-    if let Some(folder_id) = &search_query.folder_id {
-        query_builder.push_str(&format!(" AND e.folder_id = ?{}", param_index));
-        params.push(folder_id.clone());
-        param_index += 1;
+fn negated_unless(positive: bool, term: Term) -> Query {
+    if positive {
+        Query::Term(term)
+    } else {
+        Query::Not(Box::new(Query::Term(term)))
     }
+}
 
-    if let Some(date_from) = &search_query.date_from {
-        query_builder.push_str(&format!(" AND e.date >= ?{}", param_index));
-        params.push(date_from.clone());
-        param_index += 1;
+// The index column a text term searches (None for all of them) and its text
+fn text_field(term: &Term) -> Option<(Option<&'static str>, &str)> {
+    match term {
+        Term::Text(text) => Some((None, text)),
+        Term::From(text) => Some((Some("sender"), text)),
+        Term::To(text) => Some((Some("recipients"), text)),
+        Term::Subject(text) => Some((Some("subject"), text)),
+        Term::Body(text) => Some((Some("body"), text)),
+        Term::Filename(text) => Some((Some("attachments"), text)),
+        _ => None,
     }
+}
 
-    if let Some(date_to) = &search_query.date_to {
-        query_builder.push_str(&format!(" AND e.date <= ?{}", param_index));
-        params.push(date_to.clone());
-        param_index += 1;
+// The FTS5 phrase for a term, or None when it is too short for the trigram index
+fn fts_phrase(column: Option<&str>, text: &str) -> Option<String> {
+    if text.chars().count() < MIN_INDEXED_CHARS {
+        return None;
     }
+    let quoted = format!("\"{}\"", text.replace('"', "\"\""));
+    Some(match column {
+        Some(column) => format!("{} : {}", column, quoted),
+        None => quoted,
+    })
+}
 
-    if let Some(sender) = &search_query.sender {
-        query_builder.push_str(&format!(" AND e.from_addr LIKE ?{}", param_index));
-        params.push(format!("%{}%", sender));
-        param_index += 1;
+// Escapes FTS5 output for HTML and turns its match markers into <mark> tags
+pub fn render_highlight(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
     }
+    html.replace(MARK_START, "<mark>").replace(MARK_END, "</mark>")
+}
 
-    if let Some(subject_contains) = &search_query.subject_contains {
-        query_builder.push_str(&format!(" AND e.subject LIKE ?{}", param_index));
-        params.push(format!("%{}%", subject_contains));
-        param_index += 1;
+fn like_pattern(term: &str) -> String {
+    let escaped = term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn push_condition(builder: &mut QueryBuilder<'_, Sqlite>, query: &Query) {
+    match query {
+        Query::And(items) | Query::Or(items) if items.is_empty() => {
+            builder.push("1 = 1");
+        }
+        Query::And(items) | Query::Or(items) => {
+            let separator = if matches!(query, Query::And(_)) { " AND " } else { " OR " };
+            builder.push("(");
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    builder.push(separator);
+                }
+                push_condition(builder, item);
+            }
+            builder.push(")");
+        }
+        Query::Not(inner) => {
+            builder.push("NOT (");
+            push_condition(builder, inner);
+            builder.push(")");
+        }
+        Query::Term(term) => push_term(builder, term),
     }
+}
 
-    if let Some(body_contains) = &search_query.body_contains {
-        query_builder.push_str(&format!(" AND e.body LIKE ?{}", param_index));
-        params.push(format!("%{}%", body_contains));
-        param_index += 1;
+fn push_term(builder: &mut QueryBuilder<'_, Sqlite>, term: &Term) {
+    if let Some((column, text)) = text_field(term) {
+        builder.push("e.rowid IN (SELECT rowid FROM emails_fts WHERE ");
+        match fts_phrase(column, text) {
+            Some(phrase) => {
+                builder.push("emails_fts MATCH ").push_bind(phrase);
+            }
+            None => {
+                let columns = column.map(|column| vec![column]).unwrap_or_else(|| FTS_COLUMNS.to_vec());
+                for (index, column) in columns.into_iter().enumerate() {
+                    if index > 0 {
+                        builder.push(" OR ");
+                    }
+                    builder.push(format!("{} LIKE ", column))
+                        .push_bind(like_pattern(text))
+                        .push(" ESCAPE '\\'");
+                }
+            }
+        }
+        builder.push(")");
+        return;
     }
 
-    if let Some(has_attachments) = search_query.has_attachments {
-        query_builder.push_str(&format!(" AND e.has_attachments = ?{}", param_index));
-        params.push(has_attachments.to_string());
-        param_index += 1;
+    match term {
+        Term::HasAttachment => {
+            builder.push("e.has_attachments = 1");
+        }
+        Term::Is(Flag::Read) => {
+            builder.push("e.is_read = 1");
+        }
+        Term::Is(Flag::Unread) => {
+            builder.push("e.is_read = 0");
+        }
+        Term::Is(Flag::Starred) => {
+            builder.push("e.is_starred = 1");
+        }
+        // Dates are stored as RFC 3339, so a bare date compares as the start of that day
+        Term::Before(date) => {
+            builder.push("e.date < ").push_bind(date.format("%Y-%m-%d").to_string());
+        }
+        Term::After(date) => {
+            builder.push("e.date >= ").push_bind(date.format("%Y-%m-%d").to_string());
+        }
+        Term::In(folder) => {
+            builder.push("(e.folder_id = ").push_bind(folder.clone())
+                .push(" COLLATE NOCASE OR e.folder_id IN (SELECT id FROM folders WHERE name = ")
+                .push_bind(folder.clone())
+                .push(" COLLATE NOCASE))");
+        }
+        Term::Larger(bytes) => {
+            builder.push("COALESCE(e.size, 0) > ").push_bind(*bytes as i64);
+        }
+        Term::Smaller(bytes) => {
+            builder.push("COALESCE(e.size, 0) < ").push_bind(*bytes as i64);
+        }
+        _ => unreachable!("text terms are handled above"),
     }
+}
 
-    if let Some(is_read) = search_query.is_read {
-        query_builder.push_str(&format!(" AND e.is_read = ?{}", param_index));
-        params.push(is_read.to_string());
-        param_index += 1;
+fn push_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &SearchQuery, options: &SearchOptions, compiled: &CompiledSearch) {
+    builder.push(" FROM emails e");
+    if let Some(rank_match) = &compiled.rank_match {
+        builder.push(" JOIN emails_fts ON emails_fts.rowid = e.rowid WHERE emails_fts MATCH ")
+            .push_bind(rank_match.clone());
+    } else {
+        builder.push(" WHERE 1 = 1");
     }
 
-    if let Some(is_starred) = search_query.is_starred {
-        query_builder.push_str(&format!(" AND e.is_starred = ?{}", param_index));
-        params.push(is_starred.to_string());
-        param_index += 1;
+    for condition in &compiled.conditions {
+        builder.push(" AND ");
+        push_condition(builder, condition);
     }
 
-    // Add ordering
-    query_builder.push_str(" ORDER BY e.date DESC");
+    if let Some(account_id) = &query.account_id {
+        builder.push(" AND e.account_id = ").push_bind(account_id.clone());
+    }
+    if let Some(folder_id) = &query.folder_id {
+        builder.push(" AND e.folder_id = ").push_bind(folder_id.clone());
+    }
+    if let Some(date_from) = &query.date_from {
+        builder.push(" AND e.date >= ").push_bind(date_from.clone());
+    }
+    if let Some(date_to) = &query.date_to {
+        builder.push(" AND e.date <= ").push_bind(date_to.clone());
+    }
 
-    // Get total count
-    let count_query = query_builder.replace(
-        "SELECT e.id, e.folder_id, e.uid, e.from_addr, e.to_addr, e.cc_addr, e.bcc_addr, 
-               e.subject, e.body, e.html_body, e.date, e.is_read, e.is_starred, 
-               e.has_attachments, e.message_id, e.in_reply_to, e.references",
-        "SELECT COUNT(*) as count"
-    );
+    for (column, ids) in [("e.account_id", &options.account_ids), ("e.folder_id", &options.folder_ids)] {
+        if ids.is_empty() {
+            continue;
+        }
+        builder.push(format!(" AND {} IN (", column));
+        let mut separated = builder.separated(", ");
+        for id in ids {
+            separated.push_bind(id.clone());
+        }
+        separated.push_unseparated(")");
+    }
+}
+
+// Ranked search over the full-text index. `query.query` uses the search syntax of
+// `email::search_query`; the other fields narrow it further.
+pub async fn search(pool: &Pool<Sqlite>, query: &SearchQuery) -> MailResult<SearchResult> {
+    search_with(pool, query, &SearchOptions::default()).await
+}
+
+async fn count(pool: &Pool<Sqlite>, query: &SearchQuery, options: &SearchOptions, compiled: &CompiledSearch) -> MailResult<u32> {
+    let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*)");
+    push_filters(&mut count, query, options, compiled);
+    let total = count.build_query_scalar::<i64>()
+        .fetch_one(pool)
+        .await
+        .context("Failed to get search count")?;
+    Ok(total as u32)
+}
+
+// (unread, total) matches of each search, from one query: a COUNT per search joined by
+// UNION ALL
+pub async fn count_unread_and_total(pool: &Pool<Sqlite>, searches: &[(SearchQuery, SearchOptions)]) -> MailResult<Vec<(i64, i64)>> {
+    if searches.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    let mut count_query_builder = sqlx::query(&count_query);
-    for param in &params {
-        count_query_builder = count_query_builder.bind(param);
+    let mut select = QueryBuilder::<Sqlite>::new("");
+    for (position, (query, options)) in searches.iter().enumerate() {
+        let compiled = compile(query).context("Invalid search query")?;
+        if position > 0 {
+            select.push(" UNION ALL ");
+        }
+        select.push(format!(
+            "SELECT {} AS position, COUNT(*) AS total_count, \
+             COALESCE(SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END), 0) AS unread_count",
+            position
+        ));
+        push_filters(&mut select, query, options, &compiled);
     }
 
-    let total_count = count_query_builder
-        .fetch_one(&db.pool)
+    let rows = select.build()
+        .fetch_all(pool)
         .await
-        .map_err(|e| format!("Failed to get search count: {}", e))?
-        .get::<i64, _>("count") as u32;
-
-    // Add pagination
-    let limit = search_query.limit.unwrap_or(50);
-    let offset = search_query.offset.unwrap_or(0);
-    query_builder.push_str(&format!(" LIMIT ?{} OFFSET ?{}", param_index, param_index + 1));
-    params.push(limit.to_string());
-    params.push(offset.to_string());
-
-    // Execute search query
-    let mut query_builder = sqlx::query(&query_builder);
-    for param in &params {
-        query_builder = query_builder.bind(param);
+        .context("Failed to get search counts")?;
+    let mut counts = vec![(0, 0); searches.len()];
+    for row in rows {
+        let position: i64 = row.get("position");
+        counts[position as usize] = (row.get("unread_count"), row.get("total_count"));
     }
+    Ok(counts)
+}
 
-    let rows = query_builder
-        .fetch_all(&db.pool)
+pub async fn search_with(pool: &Pool<Sqlite>, query: &SearchQuery, options: &SearchOptions) -> MailResult<SearchResult> {
+    let start_time = std::time::Instant::now();
+    let compiled = compile(query).context("Invalid search query")?;
+    let total_count = count(pool, query, options, &compiled).await?;
+
+    let mut select = QueryBuilder::<Sqlite>::new(
+        "SELECT e.id, e.account_id, e.folder_id, e.uid, e.message_id, e.subject, e.from_addr, e.to_addr, \
+         e.date, e.is_read, e.is_starred, e.has_attachments, e.preview, e.size"
+    );
+    // The ranking functions are only available when the index is queried with MATCH
+    if compiled.rank_match.is_some() {
+        select.push(format!(", bm25(emails_fts, {}) AS score", RANK_WEIGHTS))
+            .push(", highlight(emails_fts, 0, ").push_bind(MARK_START).push(", ").push_bind(MARK_END)
+            .push(") AS subject_highlight")
+            .push(", snippet(emails_fts, -1, ").push_bind(MARK_START).push(", ").push_bind(MARK_END)
+            .push(", '…', 64) AS snippet");
+    } else {
+        select.push(", 0.0 AS score, NULL AS subject_highlight, NULL AS snippet");
+    }
+    push_filters(&mut select, query, options, &compiled);
+    let direction = if options.ascending { "ASC" } else { "DESC" };
+    match options.sort {
+        Some(field) => select.push(format!(" ORDER BY {} {}, e.id {}", field.column(), direction, direction)),
+        None if compiled.rank_match.is_some() => select.push(" ORDER BY score, e.date DESC"),
+        None => select.push(format!(" ORDER BY e.date {}", direction)),
+    };
+    select.push(" LIMIT ").push_bind(query.limit.unwrap_or(50) as i64)
+        .push(" OFFSET ").push_bind(query.offset.unwrap_or(0) as i64);
+
+    let rows = select.build()
+        .fetch_all(pool)
         .await
-        .map_err(|e| format!("Failed to search emails: {}", e))?;
+        .context("Failed to search emails")?;
 
-    let mut emails = Vec::new();
+    let mut emails = Vec::with_capacity(rows.len());
     for row in rows {
-        let email = Email {
-            id: row.get("id"),
-            folder_id: row.get("folder_id"),
-            uid: row.get("uid"),
-            from: row.get("from_addr"),
-            to: row.get("to_addr"),
-            cc: row.get("cc_addr"),
-            bcc: row.get("bcc_addr"),
-            subject: row.get("subject"),
-            body: row.get("body"),
-            html_body: row.get("html_body"),
-            date: row.get("date"),
-            read: row.get("is_read"),
-            starred: row.get("is_starred"),
-            has_attachments: row.get("has_attachments"),
-            message_id: row.get("message_id"),
-            in_reply_to: row.get("in_reply_to"),
-            references: row.get("references"),
-        };
-        emails.push(email);
+        emails.push(SearchHit {
+            email: Email::from_row(&row).context("Failed to read search result")?,
+            rank: row.get("score"),
+            subject_highlight: row.get::<Option<String>, _>("subject_highlight").map(|s| render_highlight(&s)),
+            snippet: row.get::<Option<String>, _>("snippet").map(|s| render_highlight(&s)),
+        });
     }
 
-    let query_time = start_time.elapsed().as_millis() as u64;
-
     Ok(SearchResult {
         emails,
         total_count,
-        query_time_ms: query_time,
+        query_time_ms: start_time.elapsed().as_millis() as u64,
     })
 }
 
 #[command]
-pub async fn quick_search(db: tauri::State<'_, Database>, query: String, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_emails(db: tauri::State<'_, Database>, search_query: SearchQuery) -> MailResult<SearchResult> {
+    search(&db.pool, &search_query).await
+}
+
+// Parses a query without running it, so the search box can point at mistakes as they are typed
+#[command]
+pub async fn parse_search_query(query: String) -> MailResult<Query> {
+    Ok(search_query::parse(&query)?)
+}
+
+#[command]
+pub async fn quick_search(db: tauri::State<'_, Database>, query: String, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query,
         account_id: None,
@@ -236,11 +467,11 @@ pub async fn quick_search(db: tauri::State<'_, Database>, query: String, limit:
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn search_by_sender(db: tauri::State<'_, Database>, sender: String, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_by_sender(db: tauri::State<'_, Database>, sender: String, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id: None,
@@ -258,11 +489,11 @@ pub async fn search_by_sender(db: tauri::State<'_, Database>, sender: String, li
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn search_by_subject(db: tauri::State<'_, Database>, subject: String, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_by_subject(db: tauri::State<'_, Database>, subject: String, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id: None,
@@ -280,11 +511,11 @@ pub async fn search_by_subject(db: tauri::State<'_, Database>, subject: String,
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn search_with_attachments(db: tauri::State<'_, Database>, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_with_attachments(db: tauri::State<'_, Database>, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id: None,
@@ -302,11 +533,11 @@ pub async fn search_with_attachments(db: tauri::State<'_, Database>, limit: Opti
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn search_unread_emails(db: tauri::State<'_, Database>, account_id: Option<String>, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_unread_emails(db: tauri::State<'_, Database>, account_id: Option<String>, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id,
@@ -324,11 +555,11 @@ pub async fn search_unread_emails(db: tauri::State<'_, Database>, account_id: Op
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn search_starred_emails(db: tauri::State<'_, Database>, account_id: Option<String>, limit: Option<u32>) -> Result<Vec<Email>, String> {
+pub async fn search_starred_emails(db: tauri::State<'_, Database>, account_id: Option<String>, limit: Option<u32>) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id,
@@ -346,7 +577,7 @@ pub async fn search_starred_emails(db: tauri::State<'_, Database>, account_id: O
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
@@ -356,7 +587,7 @@ pub async fn search_by_date_range(
     date_to: String, 
     account_id: Option<String>,
     limit: Option<u32>
-) -> Result<Vec<Email>, String> {
+) -> MailResult<Vec<Email>> {
     let search_query = SearchQuery {
         query: String::new(),
         account_id,
@@ -374,48 +605,35 @@ pub async fn search_by_date_range(
     };
 
     let result = search_emails(db, search_query).await?;
-    Ok(result.emails)
+    Ok(result.emails.into_iter().map(|hit| hit.email).collect())
 }
 
 #[command]
-pub async fn get_search_suggestions(db: tauri::State<'_, Database>, query: String, limit: Option<u32>) -> Result<Vec<String>, String> {
+pub async fn get_search_suggestions(db: tauri::State<'_, Database>, query: String, limit: Option<u32>) -> MailResult<Vec<String>> {
     let limit = limit.unwrap_or(10);
     let search_pattern = format!("%{}%", query);
 
     // Get subject suggestions
-    let subjects = sqlx::query_scalar!(
-        "SELECT DISTINCT subject FROM emails WHERE subject LIKE ? LIMIT ?",
-        search_pattern,
-        limit as i64
-    )
-    .fetch_all(&db.pool)
-    .await
-    .map_err(|e| format!("Failed to get subject suggestions: {}", e))?;
+    let subjects = sqlx::query_scalar::<_, Option<String>>("SELECT DISTINCT subject FROM emails WHERE subject LIKE ? LIMIT ?")
+        .bind(&search_pattern)
+        .bind(limit as i64)
+        .fetch_all(&db.pool)
+        .await
+        .context("Failed to get subject suggestions")?;
 
     // Get sender suggestions
-    let senders = sqlx::query_scalar!(
-        "SELECT DISTINCT from_addr FROM emails WHERE from_addr LIKE ? LIMIT ?",
-        search_pattern,
-        limit as i64
-    )
-    .fetch_all(&db.pool)
-    .await
-    .map_err(|e| format!("Failed to get sender suggestions: {}", e))?;
+    let senders = sqlx::query_scalar::<_, Option<String>>("SELECT DISTINCT from_addr FROM emails WHERE from_addr LIKE ? LIMIT ?")
+        .bind(&search_pattern)
+        .bind(limit as i64)
+        .fetch_all(&db.pool)
+        .await
+        .context("Failed to get sender suggestions")?;
 
     let mut suggestions = Vec::new();
     
     // Add unique suggestions
-    for subject in subjects {
-        if let Some(subject) = subject {
-            suggestions.push(subject);
-        }
-    }
-    
-    for sender in senders {
-        if let Some(sender) = sender {
-            suggestions.push(sender);
-        }
-    }
+    suggestions.extend(subjects.into_iter().flatten());
+    suggestions.extend(senders.into_iter().flatten());
 
     // Remove duplicates and limit
     suggestions.sort();
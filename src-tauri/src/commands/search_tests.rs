@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::search::*;
+    use crate::email::search_query::{Flag, Query, Term};
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('INBOX', 'acc', 'INBOX');
+            "#
+        )
+        .await
+    }
+
+    async fn store(pool: &Pool<Sqlite>, id: &str, subject: &str, from: &str, body: &str, date: &str) {
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, subject, from_addr, to_addr, body_text, date)
+            VALUES (?, 'acc', 'INBOX', (SELECT COUNT(*) FROM emails) + 1, ?, ?, 'me@example.com', ?, ?)
+            "#
+        )
+        .bind(id)
+        .bind(subject)
+        .bind(from)
+        .bind(body)
+        .bind(date)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            query: text.to_string(),
+            account_id: Some("acc".to_string()),
+            folder_id: None,
+            date_from: None,
+            date_to: None,
+            sender: None,
+            subject_contains: None,
+            body_contains: None,
+            has_attachments: None,
+            is_read: None,
+            is_starred: None,
+            limit: None,
+            offset: None,
+        }
+    }
+
+    fn ids(result: &SearchResult) -> Vec<&str> {
+        result.emails.iter().map(|hit| hit.email.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_compile_splits_ranked_terms_from_conditions() {
+        let mut search = query("budget 会议 -draft is:unread");
+        search.sender = Some("alice".to_string());
+        let compiled = compile(&search).unwrap();
+
+        assert_eq!(compiled.rank_match.as_deref(), Some(r#""budget" AND sender : "alice""#));
+        assert_eq!(compiled.conditions, vec![
+            Query::Term(Term::Text("会议".to_string())),
+            Query::Not(Box::new(Query::Term(Term::Text("draft".to_string())))),
+            Query::Term(Term::Is(Flag::Unread)),
+        ]);
+        assert!(compile(&query("from:")).is_err());
+    }
+
+    #[test]
+    fn test_render_highlight_escapes_html() {
+        assert_eq!(render_highlight("a <b> \u{2}match\u{3} & more"), "a &lt;b&gt; <mark>match</mark> &amp; more");
+    }
+
+    #[tokio::test]
+    async fn test_ranks_subject_matches_first() {
+        let pool = setup_pool().await;
+        store(&pool, "body", "Lunch", "bob@example.com", "the budget came up briefly", "2024-01-03T00:00:00Z").await;
+        store(&pool, "subject", "Budget review", "carol@example.com", "numbers attached", "2024-01-01T00:00:00Z").await;
+        store(&pool, "other", "Holiday", "dave@example.com", "nothing relevant", "2024-01-02T00:00:00Z").await;
+
+        let result = search(&pool, &query("budget")).await.unwrap();
+        assert_eq!(ids(&result), vec!["subject", "body"]);
+        assert_eq!(result.total_count, 2);
+        assert_eq!(result.emails[0].subject_highlight.as_deref(), Some("<mark>Budget</mark> review"));
+        assert!(result.emails[1].snippet.as_deref().unwrap().contains("<mark>budget</mark>"));
+    }
+
+    #[tokio::test]
+    async fn test_chinese_text_and_short_terms() {
+        let pool = setup_pool().await;
+        store(&pool, "minutes", "关于季度会议纪要", "li@example.com", "请查收会议纪要", "2024-01-01T00:00:00Z").await;
+        store(&pool, "plan", "季度计划", "wang@example.com", "下周讨论", "2024-01-02T00:00:00Z").await;
+
+        assert_eq!(ids(&search(&pool, &query("会议纪要")).await.unwrap()), vec!["minutes"]);
+        // Two characters are below the trigram size and go through LIKE instead
+        assert_eq!(ids(&search(&pool, &query("季度")).await.unwrap()), vec!["plan", "minutes"]);
+        assert_eq!(ids(&search(&pool, &query("季度 计划")).await.unwrap()), vec!["plan"]);
+    }
+
+    #[tokio::test]
+    async fn test_index_follows_updates_and_attachments() {
+        let pool = setup_pool().await;
+        store(&pool, "1", "Draft", "bob@example.com", "see attached", "2024-01-01T00:00:00Z").await;
+        sqlx::query("INSERT INTO attachments (id, email_id, filename) VALUES ('a1', '1', 'invoice-2024.pdf')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(&search(&pool, &query("invoice")).await.unwrap()), vec!["1"]);
+
+        sqlx::query("UPDATE emails SET subject = 'Final' WHERE id = '1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(search(&pool, &query("draft")).await.unwrap().emails.is_empty());
+        assert_eq!(ids(&search(&pool, &query("final")).await.unwrap()), vec!["1"]);
+
+        sqlx::query("DELETE FROM emails WHERE id = '1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let indexed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM emails_fts")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(indexed, 0);
+    }
+    #[tokio::test]
+    async fn test_query_syntax() {
+        let pool = setup_pool().await;
+        store(&pool, "1", "Q3 plan", "alice@example.com", "draft numbers", "2025-12-01T00:00:00Z").await;
+        store(&pool, "2", "Q3 plan", "bob@example.com", "final numbers", "2026-01-05T00:00:00Z").await;
+        store(&pool, "3", "Forecast", "carol@example.com", "see the plan", "2026-01-06T00:00:00Z").await;
+        sqlx::query("UPDATE emails SET is_read = 1, size = 6000000 WHERE id = '2'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&search(&pool, &query(r#"subject:"q3 plan" -draft"#)).await.unwrap()), vec!["2"]);
+        assert_eq!(ids(&search(&pool, &query("from:alice OR from:carol")).await.unwrap()), vec!["3", "1"]);
+        assert_eq!(ids(&search(&pool, &query("is:unread before:2026-01-01")).await.unwrap()), vec!["1"]);
+        assert_eq!(ids(&search(&pool, &query("larger:5M in:inbox")).await.unwrap()), vec!["2"]);
+        assert!(search(&pool, &query("(plan")).await.unwrap_err().to_string().contains("position 0"));
+    }
+}
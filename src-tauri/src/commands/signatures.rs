@@ -0,0 +1,186 @@
+use crate::db::Database;
+use crate::email::mime_sniff::resolve_mime_type;
+use crate::email::signature::{cid_references, RichSignature};
+use crate::error::{Context, MailError, MailResult};
+use crate::models::{Signature, SignatureImage};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use tauri::command;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureInput {
+    pub id: Option<String>,
+    pub account_id: String,
+    pub name: String,
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+    pub images: Vec<SignatureImageInput>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureImageInput {
+    pub content_id: String,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub content: Vec<u8>,
+}
+
+#[command]
+pub async fn list_signatures(db: tauri::State<'_, Database>, account_id: String) -> MailResult<Vec<Signature>> {
+    sqlx::query_as::<_, Signature>(
+        "SELECT id, account_id, name, body_text, body_html FROM signatures WHERE account_id = ? ORDER BY name"
+    )
+    .bind(&account_id)
+    .fetch_all(&db.pool)
+    .await
+    .context("Failed to fetch signatures")
+}
+
+#[command]
+pub async fn get_signature_images(db: tauri::State<'_, Database>, signature_id: String) -> MailResult<Vec<SignatureImage>> {
+    load_signature_images(&db.pool, &signature_id).await
+}
+
+#[command]
+pub async fn save_signature(db: tauri::State<'_, Database>, signature: SignatureInput) -> MailResult<String> {
+    if signature.body_text.is_none() && signature.body_html.is_none() {
+        return Err(MailError::validation("Signature needs a plain text or HTML body"));
+    }
+
+    // Every cid: reference in the HTML must be backed by an image, or it is sent broken,
+    // and every image must be used somewhere
+    if let Some(html) = &signature.body_html {
+        let references = cid_references(html);
+        for reference in &references {
+            if !signature.images.iter().any(|image| image.content_id == *reference) {
+                return Err(MailError::validation(format!("Signature HTML refers to image '{}', which isn't attached", reference)));
+            }
+        }
+        for image in &signature.images {
+            if !references.contains(&image.content_id.as_str()) {
+                return Err(MailError::validation(format!("Signature image '{}' is not referenced from the HTML body", image.content_id)));
+            }
+        }
+    }
+
+    let signature_id = signature.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut tx = db.pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO signatures (id, account_id, name, body_text, body_html)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            body_text = excluded.body_text,
+            body_html = excluded.body_html
+        "#
+    )
+    .bind(&signature_id)
+    .bind(&signature.account_id)
+    .bind(&signature.name)
+    .bind(&signature.body_text)
+    .bind(&signature.body_html)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to save signature")?;
+
+    // Images are replaced wholesale on every save
+    sqlx::query("DELETE FROM signature_images WHERE signature_id = ?")
+        .bind(&signature_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to clear signature images")?;
+
+    for image in &signature.images {
+        sqlx::query(
+            "INSERT INTO signature_images (id, signature_id, content_id, filename, mime_type, content) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(&signature_id)
+        .bind(&image.content_id)
+        .bind(&image.filename)
+        .bind(resolve_mime_type(
+            image.mime_type.as_deref().unwrap_or_default(),
+            image.filename.as_deref().unwrap_or_default(),
+            &image.content,
+        ))
+        .bind(&image.content)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to save signature image")?;
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(signature_id)
+}
+
+#[command]
+pub async fn delete_signature(db: tauri::State<'_, Database>, signature_id: String) -> MailResult<()> {
+    let mut tx = db.pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    // Identities using this signature fall back to sending without one
+    sqlx::query("UPDATE identities SET signature_id = NULL WHERE signature_id = ?")
+        .bind(&signature_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to detach signature")?;
+
+    sqlx::query("DELETE FROM signature_images WHERE signature_id = ?")
+        .bind(&signature_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete signature images")?;
+
+    sqlx::query("DELETE FROM signatures WHERE id = ?")
+        .bind(&signature_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete signature")?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+pub async fn load_rich_signature(pool: &Pool<Sqlite>, signature_id: &str) -> MailResult<Option<RichSignature>> {
+    let signature = sqlx::query_as::<_, Signature>(
+        "SELECT id, account_id, name, body_text, body_html FROM signatures WHERE id = ?"
+    )
+    .bind(signature_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get signature")?;
+
+    let Some(signature) = signature else {
+        return Ok(None);
+    };
+
+    let images = load_signature_images(pool, signature_id).await?;
+
+    Ok(Some(RichSignature {
+        body_text: signature.body_text,
+        body_html: signature.body_html,
+        images,
+    }))
+}
+
+async fn load_signature_images(pool: &Pool<Sqlite>, signature_id: &str) -> MailResult<Vec<SignatureImage>> {
+    sqlx::query_as::<_, SignatureImage>(
+        "SELECT id, signature_id, content_id, filename, mime_type, content FROM signature_images WHERE signature_id = ?"
+    )
+    .bind(signature_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch signature images")
+}
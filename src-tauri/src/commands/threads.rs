@@ -0,0 +1,283 @@
+use crate::db::Database;
+use crate::email::threading::{self, ThreadInput};
+use crate::error::{Context, MailResult};
+use crate::models::Email;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::collections::{HashMap, HashSet};
+use tauri::command;
+
+// With more unthreaded messages than this (say after an upgrade) the whole account is
+// rethreaded in one pass instead of looking up the relatives of each message
+const FULL_RETHREAD_THRESHOLD: i64 = 500;
+// Keeps IN (...) lists well below SQLite's bound parameter limit
+const LOOKUP_CHUNK: usize = 400;
+
+const THREAD_COLUMNS: &str = "id, message_id, in_reply_to, reference_ids, subject, thread_id";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    pub thread_id: String,
+    // Subject of the first message
+    pub subject: Option<String>,
+    pub senders: Vec<String>,
+    pub message_count: i64,
+    pub unread_count: i64,
+    pub is_starred: bool,
+    pub has_attachments: bool,
+    pub latest_date: Option<String>,
+    // Preview of the latest message
+    pub preview: Option<String>,
+}
+
+struct ThreadRow {
+    id: String,
+    thread_id: Option<String>,
+    input: ThreadInput,
+}
+
+// Files newly stored messages into conversations. Only the new messages and the threads
+// they could join (by Message-ID, In-Reply-To or subject) are rethreaded, and existing
+// thread ids are kept wherever a thread merely grows.
+pub async fn assign_threads(pool: &Pool<Sqlite>, account_id: &str) -> MailResult<usize> {
+    let pending = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM emails WHERE account_id = ? AND thread_id IS NULL")
+        .bind(account_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count unthreaded emails")?;
+    if pending == 0 {
+        return Ok(0);
+    }
+
+    let rows = if pending > FULL_RETHREAD_THRESHOLD {
+        load_rows(pool, account_id, "1 = 1", &[]).await?
+    } else {
+        let new_rows = load_rows(pool, account_id, "thread_id IS NULL", &[]).await?;
+        with_relatives(pool, account_id, new_rows).await?
+    };
+
+    let inputs: Vec<ThreadInput> = rows.iter().map(|row| row.input.clone()).collect();
+    let groups = threading::thread(&inputs);
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    let mut used = HashSet::new();
+    let mut updated = 0;
+    for group in groups {
+        let thread_id = pick_thread_id(&rows, &group, &used);
+        used.insert(thread_id.clone());
+
+        for index in group {
+            let row = &rows[index];
+            if row.thread_id.as_deref() == Some(thread_id.as_str()) {
+                continue;
+            }
+            sqlx::query("UPDATE emails SET thread_id = ? WHERE id = ?")
+                .bind(&thread_id)
+                .bind(&row.id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to save thread")?;
+            updated += 1;
+        }
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(updated)
+}
+
+#[command]
+pub async fn list_threads(
+    db: tauri::State<'_, Database>,
+    account_id: String,
+    folder_id: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> MailResult<Vec<ThreadSummary>> {
+    // Threads are listed in a folder when any of their messages is in it, but the
+    // counts cover the whole conversation
+    let folder_filter = if folder_id.is_some() {
+        "AND e.thread_id IN (SELECT thread_id FROM emails WHERE account_id = ?1 AND folder_id = ?2)"
+    } else {
+        ""
+    };
+
+    // Copies of one message in several folders count once
+    let sql = format!(
+        r#"
+        SELECT e.thread_id,
+               COUNT(DISTINCT COALESCE(e.message_id, e.id)) AS message_count,
+               COUNT(DISTINCT CASE WHEN e.is_read = 0 THEN COALESCE(e.message_id, e.id) END) AS unread_count,
+               MAX(e.is_starred) AS is_starred,
+               MAX(e.has_attachments) AS has_attachments,
+               MAX(e.date) AS latest_date,
+               GROUP_CONCAT(e.from_addr, char(10)) AS senders,
+               (SELECT subject FROM emails earliest WHERE earliest.account_id = e.account_id AND earliest.thread_id = e.thread_id
+                ORDER BY earliest.date LIMIT 1) AS subject,
+               (SELECT preview FROM emails newest WHERE newest.account_id = e.account_id AND newest.thread_id = e.thread_id
+                ORDER BY newest.date DESC LIMIT 1) AS preview
+        FROM emails e
+        WHERE e.account_id = ?1 AND e.thread_id IS NOT NULL {}
+        GROUP BY e.thread_id
+        ORDER BY latest_date DESC
+        LIMIT ?3 OFFSET ?4
+        "#,
+        folder_filter
+    );
+
+    let rows = sqlx::query(&sql)
+        .bind(&account_id)
+        .bind(&folder_id)
+        .bind(limit.unwrap_or(50) as i64)
+        .bind(offset.unwrap_or(0) as i64)
+        .fetch_all(&db.pool)
+        .await
+        .context("Failed to list threads")?;
+
+    Ok(rows.into_iter()
+        .map(|row| {
+            let mut senders: Vec<String> = Vec::new();
+            for sender in row.get::<Option<String>, _>("senders").unwrap_or_default().lines() {
+                if !senders.iter().any(|known| known == sender) {
+                    senders.push(sender.to_string());
+                }
+            }
+            ThreadSummary {
+                thread_id: row.get("thread_id"),
+                subject: row.get("subject"),
+                senders,
+                message_count: row.get("message_count"),
+                unread_count: row.get("unread_count"),
+                is_starred: row.get("is_starred"),
+                has_attachments: row.get("has_attachments"),
+                latest_date: row.get("latest_date"),
+                preview: row.get("preview"),
+            }
+        })
+        .collect())
+}
+
+// All messages of a conversation, oldest first. A message filed in several folders is
+// returned once.
+#[command]
+pub async fn get_thread_messages(db: tauri::State<'_, Database>, thread_id: String) -> MailResult<Vec<Email>> {
+    let emails = sqlx::query_as::<_, Email>(
+        r#"
+        SELECT id, account_id, folder_id, uid, message_id, subject, from_addr, to_addr, date,
+               is_read, is_starred, has_attachments, preview, size
+        FROM emails WHERE thread_id = ? ORDER BY date, id
+        "#
+    )
+    .bind(&thread_id)
+    .fetch_all(&db.pool)
+    .await
+    .context("Failed to get thread messages")?;
+
+    let mut seen = HashSet::new();
+    Ok(emails.into_iter()
+        .filter(|email| match &email.message_id {
+            Some(message_id) => seen.insert(message_id.clone()),
+            None => true,
+        })
+        .collect())
+}
+
+// The new messages plus every message of the threads they may belong to
+async fn with_relatives(pool: &Pool<Sqlite>, account_id: &str, new_rows: Vec<ThreadRow>) -> MailResult<Vec<ThreadRow>> {
+    let mut parents = HashSet::new();
+    let mut own_ids = HashSet::new();
+    let mut subjects = HashSet::new();
+    for row in &new_rows {
+        parents.extend(row.input.references.iter().cloned());
+        parents.extend(row.input.in_reply_to.iter().cloned());
+        own_ids.extend(row.input.message_id.iter().cloned());
+        if let Some(subject) = &row.input.subject {
+            let base = threading::base_subject(subject);
+            if !base.is_empty() {
+                subjects.insert(base);
+            }
+        }
+    }
+
+    // Replies that arrived before their parent point at it with In-Reply-To
+    let mut thread_ids = HashSet::new();
+    for (column, values) in [("message_id", parents), ("in_reply_to", own_ids), ("base_subject", subjects)] {
+        let values: Vec<String> = values.into_iter().collect();
+        for chunk in values.chunks(LOOKUP_CHUNK) {
+            let sql = format!(
+                "SELECT DISTINCT thread_id FROM emails WHERE account_id = ? AND thread_id IS NOT NULL AND {} IN ({})",
+                column,
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut query = sqlx::query_scalar::<_, String>(&sql).bind(account_id);
+            for value in chunk {
+                query = query.bind(value);
+            }
+            thread_ids.extend(query.fetch_all(pool)
+                .await
+                .context("Failed to look up related emails")?);
+        }
+    }
+
+    let mut rows = new_rows;
+    let thread_ids: Vec<String> = thread_ids.into_iter().collect();
+    for chunk in thread_ids.chunks(LOOKUP_CHUNK) {
+        let filter = format!("thread_id IN ({})", vec!["?"; chunk.len()].join(", "));
+        rows.extend(load_rows(pool, account_id, &filter, chunk).await?);
+    }
+    Ok(rows)
+}
+
+async fn load_rows(pool: &Pool<Sqlite>, account_id: &str, filter: &str, values: &[String]) -> MailResult<Vec<ThreadRow>> {
+    let sql = format!(
+        "SELECT {} FROM emails WHERE account_id = ? AND {} ORDER BY date, id",
+        THREAD_COLUMNS, filter
+    );
+    let mut query = sqlx::query(&sql).bind(account_id);
+    for value in values {
+        query = query.bind(value);
+    }
+
+    let rows = query.fetch_all(pool)
+        .await
+        .context("Failed to load emails for threading")?;
+
+    Ok(rows.into_iter()
+        .map(|row| ThreadRow {
+            id: row.get("id"),
+            thread_id: row.get("thread_id"),
+            input: ThreadInput {
+                message_id: row.get("message_id"),
+                in_reply_to: row.get("in_reply_to"),
+                references: row.get::<Option<String>, _>("reference_ids")
+                    .map(|ids| ids.split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default(),
+                subject: row.get("subject"),
+            },
+        })
+        .collect())
+}
+
+// Keeps the id most of the group already has, so a growing thread stays the same thread
+fn pick_thread_id(rows: &[ThreadRow], group: &[usize], used: &HashSet<String>) -> String {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for &index in group {
+        if let Some(thread_id) = rows[index].thread_id.as_deref() {
+            *counts.entry(thread_id).or_default() += 1;
+        }
+    }
+
+    let mut candidates: Vec<(&str, usize)> = counts.into_iter()
+        .filter(|(thread_id, _)| !used.contains(*thread_id))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    candidates.first()
+        .map(|(thread_id, _)| thread_id.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::threads::*;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('INBOX', 'acc', 'INBOX'), ('Archive', 'acc', 'Archive');
+            "#
+        )
+        .await
+    }
+
+    async fn store(pool: &Pool<Sqlite>, id: &str, folder: &str, message_id: &str, in_reply_to: Option<&str>, subject: &str, date: &str) {
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, message_id, in_reply_to, reference_ids, subject, base_subject, date)
+            VALUES (?, 'acc', ?, (SELECT COUNT(*) FROM emails) + 1, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(id)
+        .bind(folder)
+        .bind(message_id)
+        .bind(in_reply_to)
+        .bind(in_reply_to)
+        .bind(subject)
+        .bind(crate::email::threading::base_subject(subject))
+        .bind(date)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn thread_of(pool: &Pool<Sqlite>, id: &str) -> Option<String> {
+        sqlx::query_scalar::<_, Option<String>>("SELECT thread_id FROM emails WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_threads_grow_incrementally() {
+        let pool = setup_pool().await;
+        store(&pool, "1", "INBOX", "a@x", None, "Plan", "2024-01-01T10:00:00Z").await;
+        store(&pool, "2", "INBOX", "other@x", None, "Unrelated", "2024-01-01T11:00:00Z").await;
+        assign_threads(&pool, "acc").await.unwrap();
+
+        let plan = thread_of(&pool, "1").await.unwrap();
+        assert_ne!(Some(plan.clone()), thread_of(&pool, "2").await);
+
+        // A reply filed in another folder joins the existing thread without renaming it
+        store(&pool, "3", "Archive", "b@x", Some("a@x"), "Re: Plan", "2024-01-02T09:00:00Z").await;
+        assign_threads(&pool, "acc").await.unwrap();
+        assert_eq!(thread_of(&pool, "3").await.as_deref(), Some(plan.as_str()));
+        assert_eq!(thread_of(&pool, "1").await.as_deref(), Some(plan.as_str()));
+
+        // Nothing left to do
+        assert_eq!(assign_threads(&pool, "acc").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_parent_arriving_after_reply_joins_it() {
+        let pool = setup_pool().await;
+        store(&pool, "1", "INBOX", "b@x", Some("a@x"), "Re: Trip", "2024-01-02T09:00:00Z").await;
+        assign_threads(&pool, "acc").await.unwrap();
+        let trip = thread_of(&pool, "1").await.unwrap();
+
+        // Re-synced with a different subject, so only In-Reply-To can link them
+        store(&pool, "2", "INBOX", "a@x", None, "Travel plans", "2024-01-01T09:00:00Z").await;
+        assign_threads(&pool, "acc").await.unwrap();
+        assert_eq!(thread_of(&pool, "2").await.as_deref(), Some(trip.as_str()));
+    }
+}
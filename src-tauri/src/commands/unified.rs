@@ -0,0 +1,142 @@
+use crate::commands::messages::{self, MessageListRequest};
+use crate::commands::search::SortField;
+use crate::db::Database;
+use crate::email::folder_role::FolderRole;
+use crate::error::{Context, MailResult};
+use crate::models::Email;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+use std::collections::HashMap;
+use tauri::command;
+
+// Which account a message in a unified list came from, for the badge next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountBadge {
+    pub account_id: String,
+    pub email: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnifiedMessage {
+    #[serde(flatten)]
+    pub email: Email,
+    pub account: AccountBadge,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnifiedPage {
+    pub emails: Vec<UnifiedMessage>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnifiedCount {
+    pub role: FolderRole,
+    pub unread_count: i64,
+    pub total_count: i64,
+    // Unread messages per account id
+    pub unread_by_account: HashMap<String, i64>,
+}
+
+// One stream over the folders with the given roles (the inbox by default) of every account
+pub async fn list(pool: &Pool<Sqlite>, request: &MessageListRequest) -> MailResult<UnifiedPage> {
+    let mut request = request.clone();
+    request.account_id = None;
+    request.folder_id = None;
+    if request.roles.is_empty() {
+        request.roles = vec![FolderRole::Inbox];
+    }
+
+    let page = messages::list(pool, &request).await?;
+    let badges = account_badges(pool).await?;
+    let emails = page.emails.into_iter()
+        .map(|email| {
+            let account = badges.get(&email.account_id).cloned().unwrap_or_else(|| AccountBadge {
+                account_id: email.account_id.clone(),
+                email: String::new(),
+                name: None,
+            });
+            UnifiedMessage { email, account }
+        })
+        .collect();
+
+    Ok(UnifiedPage { emails, next_cursor: page.next_cursor })
+}
+
+async fn account_badges(pool: &Pool<Sqlite>) -> MailResult<HashMap<String, AccountBadge>> {
+    let rows = sqlx::query("SELECT id, email, name FROM accounts")
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch accounts")?;
+    Ok(rows.into_iter()
+        .map(|row| {
+            let badge = AccountBadge { account_id: row.get("id"), email: row.get("email"), name: row.get("name") };
+            (badge.account_id.clone(), badge)
+        })
+        .collect())
+}
+
+pub async fn counts(pool: &Pool<Sqlite>, roles: &[FolderRole]) -> MailResult<Vec<UnifiedCount>> {
+    let mut query = QueryBuilder::<Sqlite>::new(
+        r#"
+        SELECT f.role, e.account_id,
+               COUNT(e.id) AS total_count,
+               COALESCE(SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END), 0) AS unread_count
+        FROM folders f
+        JOIN emails e ON e.folder_id = f.id
+        WHERE f.role IN (
+        "#
+    );
+    let mut separated = query.separated(", ");
+    for role in roles {
+        separated.push_bind(role.as_str());
+    }
+    separated.push_unseparated(") GROUP BY f.role, e.account_id");
+
+    let rows = query.build()
+        .fetch_all(pool)
+        .await
+        .context("Failed to count unified folders")?;
+
+    let mut counts: Vec<UnifiedCount> = roles.iter()
+        .map(|&role| UnifiedCount { role, unread_count: 0, total_count: 0, unread_by_account: HashMap::new() })
+        .collect();
+    for row in rows {
+        let Some(role) = FolderRole::parse(row.get::<&str, _>("role")) else { continue };
+        let Some(count) = counts.iter_mut().find(|count| count.role == role) else { continue };
+        let unread: i64 = row.get("unread_count");
+        count.unread_count += unread;
+        count.total_count += row.get::<i64, _>("total_count");
+        count.unread_by_account.insert(row.get("account_id"), unread);
+    }
+    Ok(counts)
+}
+
+#[command]
+pub async fn list_unified_messages(
+    db: tauri::State<'_, Database>,
+    roles: Option<Vec<FolderRole>>,
+    sort: Option<SortField>,
+    ascending: Option<bool>,
+    is_read: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<u32>,
+) -> MailResult<UnifiedPage> {
+    let request = MessageListRequest {
+        roles: roles.unwrap_or_default(),
+        sort: sort.unwrap_or_default(),
+        ascending: ascending.unwrap_or(false),
+        is_read,
+        cursor,
+        limit,
+        ..MessageListRequest::default()
+    };
+    list(&db.pool, &request).await
+}
+
+#[command]
+pub async fn get_unified_counts(db: tauri::State<'_, Database>, roles: Option<Vec<FolderRole>>) -> MailResult<Vec<UnifiedCount>> {
+    let roles = roles.filter(|roles| !roles.is_empty()).unwrap_or_else(|| vec![FolderRole::Inbox]);
+    counts(&db.pool, &roles).await
+}
@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::messages::MessageListRequest;
+    use crate::commands::unified::*;
+    use crate::email::folder_role::FolderRole;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email, name) VALUES
+                ('work', 'me@work.example', 'Work'),
+                ('home', 'me@home.example', NULL);
+            INSERT INTO folders (id, account_id, name, role) VALUES
+                ('work-INBOX', 'work', 'INBOX', 'inbox'),
+                ('work-Sent', 'work', 'Sent', 'sent'),
+                ('home-INBOX', 'home', 'INBOX', 'inbox'),
+                ('home-Posteingang', 'home', 'Posteingang', NULL),
+                ('home-Gesendet', 'home', 'Gesendet', 'sent');
+            "#
+        )
+        .await
+    }
+
+    async fn store(pool: &Pool<Sqlite>, id: &str, account_id: &str, folder_id: &str, date: &str, is_read: bool) {
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, date, is_read)
+            VALUES (?, ?, ?, (SELECT COUNT(*) FROM emails) + 1, ?, ?)
+            "#
+        )
+        .bind(id)
+        .bind(account_id)
+        .bind(folder_id)
+        .bind(date)
+        .bind(is_read)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn seed(pool: &Pool<Sqlite>) {
+        store(pool, "w1", "work", "work-INBOX", "2024-01-05T00:00:00Z", false).await;
+        store(pool, "h1", "home", "home-INBOX", "2024-01-04T00:00:00Z", true).await;
+        store(pool, "w2", "work", "work-INBOX", "2024-01-03T00:00:00Z", true).await;
+        store(pool, "h2", "home", "home-INBOX", "2024-01-02T00:00:00Z", false).await;
+        store(pool, "h3", "home", "home-Posteingang", "2024-01-06T00:00:00Z", false).await;
+        store(pool, "ws", "work", "work-Sent", "2024-01-07T00:00:00Z", true).await;
+        store(pool, "hs", "home", "home-Gesendet", "2024-01-01T00:00:00Z", false).await;
+    }
+
+    fn ids(page: &UnifiedPage) -> Vec<&str> {
+        page.emails.iter().map(|message| message.email.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_inboxes_merge_by_role_not_name() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let page = list(&pool, &MessageListRequest::default()).await.unwrap();
+        assert_eq!(ids(&page), vec!["w1", "h1", "w2", "h2"]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_messages_carry_account_badge() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let page = list(&pool, &MessageListRequest::default()).await.unwrap();
+        let work = &page.emails[0].account;
+        assert_eq!((work.account_id.as_str(), work.email.as_str(), work.name.as_deref()), ("work", "me@work.example", Some("Work")));
+        let home = &page.emails[1].account;
+        assert_eq!((home.account_id.as_str(), home.email.as_str(), home.name.as_deref()), ("home", "me@home.example", None));
+    }
+
+    #[tokio::test]
+    async fn test_other_roles_and_paging() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let mut request = MessageListRequest {
+            roles: vec![FolderRole::Inbox, FolderRole::Sent],
+            limit: Some(4),
+            ..MessageListRequest::default()
+        };
+        let first = list(&pool, &request).await.unwrap();
+        assert_eq!(ids(&first), vec!["ws", "w1", "h1", "w2"]);
+
+        request.cursor = first.next_cursor;
+        let second = list(&pool, &request).await.unwrap();
+        assert_eq!(ids(&second), vec!["h2", "hs"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_account_and_folder_are_ignored() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let request = MessageListRequest {
+            account_id: Some("work".to_string()),
+            folder_id: Some("work-Sent".to_string()),
+            is_read: Some(false),
+            ..MessageListRequest::default()
+        };
+        let page = list(&pool, &request).await.unwrap();
+        assert_eq!(ids(&page), vec!["w1", "h2"]);
+    }
+
+    #[tokio::test]
+    async fn test_counts_per_role_and_account() {
+        let pool = setup_pool().await;
+        seed(&pool).await;
+
+        let counts = counts(&pool, &[FolderRole::Inbox, FolderRole::Sent, FolderRole::Trash]).await.unwrap();
+        assert_eq!(counts.len(), 3);
+
+        let inbox = &counts[0];
+        assert_eq!(inbox.role, FolderRole::Inbox);
+        assert_eq!((inbox.unread_count, inbox.total_count), (2, 4));
+        assert_eq!(inbox.unread_by_account.get("work"), Some(&1));
+        assert_eq!(inbox.unread_by_account.get("home"), Some(&1));
+
+        let sent = &counts[1];
+        assert_eq!((sent.unread_count, sent.total_count), (1, 2));
+
+        let trash = &counts[2];
+        assert_eq!((trash.unread_count, trash.total_count), (0, 0));
+        assert!(trash.unread_by_account.is_empty());
+    }
+}
@@ -0,0 +1,448 @@
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::{header, redirect, Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+// Sabre (Nextcloud) and Radicale both answer multigets of this size comfortably
+pub const MULTIGET_BATCH: usize = 50;
+// Redirects are followed by hand: reqwest turns a redirected PROPFIND into a GET
+const MAX_REDIRECTS: usize = 5;
+
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+const VCARD_CONTENT_TYPE: &str = "text/vcard; charset=utf-8";
+
+#[derive(Debug, Error)]
+pub enum CardDavError {
+    #[error("CardDAV request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("CardDAV server answered {status} for {url}")]
+    Status { status: u16, url: String },
+    #[error("Card at {0} was changed on the server")]
+    PreconditionFailed(String),
+    // The server no longer knows our token, so a full listing is needed
+    #[error("CardDAV sync token is no longer valid")]
+    InvalidSyncToken,
+    #[error("Invalid CardDAV response: {0}")]
+    Xml(String),
+    #[error("Invalid CardDAV URL '{0}'")]
+    Url(String),
+    #[error("No address book found at {0}")]
+    NoAddressBook(String),
+    #[error("Too many redirects from {0}")]
+    TooManyRedirects(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressBook {
+    pub url: String,
+    pub display_name: Option<String>,
+}
+
+// A card as listed by the server: its path and the ETag of its current version
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardRef {
+    pub href: String,
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteCard {
+    pub href: String,
+    pub etag: Option<String>,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CardListing {
+    pub sync_token: Option<String>,
+    pub changed: Vec<CardRef>,
+    // Hrefs of cards deleted since the previous token; always empty for full listings
+    pub removed: Vec<String>,
+    // The server truncated the report; ask again with the new token for the rest
+    pub more: bool,
+}
+
+// One <response> of a multistatus body, keeping only properties that came back as 2xx
+#[derive(Debug, Default)]
+struct DavResponse {
+    href: String,
+    status: Option<u16>,
+    props: HashMap<String, String>,
+    resource_types: Vec<String>,
+}
+
+pub struct CardDavClient {
+    http: Client,
+    username: String,
+    password: String,
+}
+
+impl CardDavClient {
+    pub fn new(username: &str, password: &str) -> Result<Self, CardDavError> {
+        let http = Client::builder()
+            .redirect(redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            http,
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    // Finds the address books of the signed-in user, starting from either the server root,
+    // a principal URL or an address book URL (RFC 6764 well-known lookup, then RFC 6352)
+    pub async fn discover(&self, server_url: &str) -> Result<Vec<AddressBook>, CardDavError> {
+        let start = Url::parse(server_url).map_err(|_| CardDavError::Url(server_url.to_string()))?;
+
+        let body = propfind_body(&["<d:resourcetype/>", "<d:displayname/>", "<d:current-user-principal/>"]);
+        let (context, responses) = match self.propfind(&start, "0", &body).await {
+            Ok(found) => found,
+            Err(CardDavError::Status { .. }) if start.path() == "/" => {
+                let well_known = start.join("/.well-known/carddav").map_err(|e| CardDavError::Url(e.to_string()))?;
+                self.propfind(&well_known, "0", &body).await?
+            }
+            Err(e) => return Err(e),
+        };
+
+        if let Some(response) = responses.iter().find(|r| r.is_addressbook()) {
+            return Ok(vec![AddressBook {
+                url: collection_url(context.as_str())?.to_string(),
+                display_name: response.props.get("displayname").cloned(),
+            }]);
+        }
+
+        let principal = match responses.iter().find_map(|r| r.props.get("current-user-principal")) {
+            Some(href) => resolve(&context, href)?,
+            None => context.clone(),
+        };
+
+        let body = propfind_body(&["<c:addressbook-home-set/>"]);
+        let (principal, responses) = self.propfind(&principal, "0", &body).await?;
+        let home = match responses.iter().find_map(|r| r.props.get("addressbook-home-set")) {
+            Some(href) => resolve(&principal, href)?,
+            None => principal,
+        };
+
+        let body = propfind_body(&["<d:resourcetype/>", "<d:displayname/>"]);
+        let (home, responses) = self.propfind(&home, "1", &body).await?;
+        let books: Vec<AddressBook> = responses.iter()
+            .filter(|r| r.is_addressbook())
+            .map(|r| Ok(AddressBook {
+                url: collection_url(resolve(&home, &r.href)?.as_str())?.to_string(),
+                display_name: r.props.get("displayname").cloned(),
+            }))
+            .collect::<Result<_, CardDavError>>()?;
+
+        if books.is_empty() {
+            return Err(CardDavError::NoAddressBook(server_url.to_string()));
+        }
+        Ok(books)
+    }
+
+    // Every card in the address book with its ETag, plus the collection's current sync token
+    pub async fn list_cards(&self, book: &Url) -> Result<CardListing, CardDavError> {
+        let body = propfind_body(&["<d:resourcetype/>", "<d:getetag/>", "<d:sync-token/>"]);
+        let (book, responses) = self.propfind(book, "1", &body).await?;
+
+        let mut listing = CardListing::default();
+        for response in responses {
+            let url = resolve(&book, &response.href)?;
+            if url.path() == book.path() {
+                listing.sync_token = response.props.get("sync-token").cloned();
+            } else if response.resource_types.is_empty() {
+                listing.changed.push(CardRef {
+                    href: url.path().to_string(),
+                    etag: response.props.get("getetag").cloned(),
+                });
+            }
+        }
+        Ok(listing)
+    }
+
+    // RFC 6578 sync-collection: what changed since `token`, or everything without one
+    pub async fn sync_collection(&self, book: &Url, token: Option<&str>) -> Result<CardListing, CardDavError> {
+        let body = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<d:sync-collection xmlns:d="DAV:"><d:sync-token>{}</d:sync-token><d:sync-level>1</d:sync-level>"#,
+                r#"<d:prop><d:getetag/></d:prop></d:sync-collection>"#
+            ),
+            escape(token.unwrap_or_default())
+        );
+
+        let response = self.send(report(), book, Some("0"), None, Some((XML_CONTENT_TYPE, body))).await?;
+        let status = response.status();
+        // RFC 6578 3.2: an expired token fails the DAV:valid-sync-token precondition
+        if token.is_some() && (status == StatusCode::FORBIDDEN || status == StatusCode::CONFLICT) {
+            let text = response.text().await?;
+            if text.contains("valid-sync-token") {
+                return Err(CardDavError::InvalidSyncToken);
+            }
+            return Err(CardDavError::Status { status: status.as_u16(), url: book.to_string() });
+        }
+        let text = expect_multistatus(response, book).await?;
+        let (responses, sync_token) = parse_multistatus(&text)?;
+
+        let mut listing = CardListing { sync_token, ..CardListing::default() };
+        for response in responses {
+            let url = resolve(book, &response.href)?;
+            match response.status {
+                Some(404) => listing.removed.push(url.path().to_string()),
+                Some(507) => listing.more = true,
+                _ if url.path() == book.path() => {}
+                _ => listing.changed.push(CardRef {
+                    href: url.path().to_string(),
+                    etag: response.props.get("getetag").cloned(),
+                }),
+            }
+        }
+        Ok(listing)
+    }
+
+    // RFC 6352 addressbook-multiget. Cards the server could not return are left out.
+    pub async fn multiget(&self, book: &Url, hrefs: &[String]) -> Result<Vec<RemoteCard>, CardDavError> {
+        let mut cards = Vec::with_capacity(hrefs.len());
+        for batch in hrefs.chunks(MULTIGET_BATCH) {
+            let mut body = String::from(concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<c:addressbook-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav">"#,
+                r#"<d:prop><d:getetag/><c:address-data/></d:prop>"#
+            ));
+            for href in batch {
+                body.push_str(&format!("<d:href>{}</d:href>", escape(href)));
+            }
+            body.push_str("</c:addressbook-multiget>");
+
+            let response = self.send(report(), book, Some("1"), None, Some((XML_CONTENT_TYPE, body))).await?;
+            let text = expect_multistatus(response, book).await?;
+            let (responses, _) = parse_multistatus(&text)?;
+
+            for response in responses {
+                if let Some(data) = response.props.get("address-data") {
+                    cards.push(RemoteCard {
+                        href: resolve(book, &response.href)?.path().to_string(),
+                        etag: response.props.get("getetag").cloned(),
+                        data: data.clone(),
+                    });
+                }
+            }
+        }
+        Ok(cards)
+    }
+
+    // Uploads a card. With an ETag the server only accepts it over that exact version;
+    // without one it must not overwrite an existing card. Returns the new ETag if given.
+    pub async fn put_card(&self, url: &Url, data: &str, etag: Option<&str>) -> Result<Option<String>, CardDavError> {
+        let precondition = match etag {
+            Some(etag) => (header::IF_MATCH, etag),
+            None => (header::IF_NONE_MATCH, "*"),
+        };
+
+        let response = self.send(Method::PUT, url, None, Some(precondition), Some((VCARD_CONTENT_TYPE, data.to_string()))).await?;
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED => Err(CardDavError::PreconditionFailed(url.path().to_string())),
+            status if status.is_success() => Ok(strong_etag(&response)),
+            status => Err(CardDavError::Status { status: status.as_u16(), url: url.to_string() }),
+        }
+    }
+
+    pub async fn delete_card(&self, url: &Url, etag: Option<&str>) -> Result<(), CardDavError> {
+        let precondition = etag.map(|etag| (header::IF_MATCH, etag));
+        let response = self.send(Method::DELETE, url, None, precondition, None).await?;
+        match response.status() {
+            // Already gone is as good as deleted
+            StatusCode::NOT_FOUND => Ok(()),
+            StatusCode::PRECONDITION_FAILED => Err(CardDavError::PreconditionFailed(url.path().to_string())),
+            status if status.is_success() => Ok(()),
+            status => Err(CardDavError::Status { status: status.as_u16(), url: url.to_string() }),
+        }
+    }
+
+    // PROPFIND following redirects; returns the URL that finally answered with the responses
+    async fn propfind(&self, url: &Url, depth: &str, body: &str) -> Result<(Url, Vec<DavResponse>), CardDavError> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send(propfind(), &url, Some(depth), None, Some((XML_CONTENT_TYPE, body.to_string()))).await?;
+            if response.status().is_redirection() {
+                let location = response.headers()
+                    .get(header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| CardDavError::Status { status: response.status().as_u16(), url: url.to_string() })?;
+                url = resolve(&url, location)?;
+                continue;
+            }
+
+            let text = expect_multistatus(response, &url).await?;
+            let (responses, _) = parse_multistatus(&text)?;
+            return Ok((url, responses));
+        }
+        Err(CardDavError::TooManyRedirects(url.to_string()))
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        depth: Option<&str>,
+        precondition: Option<(header::HeaderName, &str)>,
+        body: Option<(&str, String)>,
+    ) -> Result<reqwest::Response, CardDavError> {
+        let mut request = self.http
+            .request(method, url.clone())
+            .basic_auth(&self.username, Some(&self.password));
+        if let Some(depth) = depth {
+            request = request.header("Depth", depth);
+        }
+        if let Some((name, value)) = precondition {
+            request = request.header(name, value);
+        }
+        if let Some((content_type, body)) = body {
+            request = request.header(header::CONTENT_TYPE, content_type).body(body);
+        }
+        Ok(request.send().await?)
+    }
+}
+
+impl DavResponse {
+    fn is_addressbook(&self) -> bool {
+        self.resource_types.iter().any(|t| t == "addressbook")
+    }
+}
+
+// Hrefs are usually absolute paths, sometimes full URLs
+pub fn resolve(base: &Url, href: &str) -> Result<Url, CardDavError> {
+    base.join(href.trim()).map_err(|_| CardDavError::Url(href.to_string()))
+}
+
+// The URL of a collection, ending in '/' so that card names resolve inside it rather than
+// replacing its last segment
+pub fn collection_url(url: &str) -> Result<Url, CardDavError> {
+    let mut url = Url::parse(url).map_err(|_| CardDavError::Url(url.to_string()))?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").expect("valid method name")
+}
+
+fn report() -> Method {
+    Method::from_bytes(b"REPORT").expect("valid method name")
+}
+
+fn propfind_body(props: &[&str]) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav"><d:prop>{}</d:prop></d:propfind>"#,
+        props.concat()
+    )
+}
+
+async fn expect_multistatus(response: reqwest::Response, url: &Url) -> Result<String, CardDavError> {
+    if response.status() != StatusCode::MULTI_STATUS {
+        return Err(CardDavError::Status { status: response.status().as_u16(), url: url.to_string() });
+    }
+    Ok(response.text().await?)
+}
+
+// Weak ETags cannot be used with If-Match, so they are treated as unknown
+fn strong_etag(response: &reqwest::Response) -> Option<String> {
+    response.headers()
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .map(str::to_string)
+}
+
+// Parses a DAV:multistatus body. Elements are matched on their local names, since servers
+// pick their own namespace prefixes. Also returns the report-level sync token, if any.
+fn parse_multistatus(xml: &str) -> Result<(Vec<DavResponse>, Option<String>), CardDavError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut responses = Vec::new();
+    let mut sync_token = None;
+
+    let mut current = DavResponse::default();
+    let mut propstat_status: Option<u16> = None;
+    let mut propstat_props: HashMap<String, String> = HashMap::new();
+    let mut propstat_types: Vec<String> = Vec::new();
+
+    loop {
+        let event = reader.read_event().map_err(|e| CardDavError::Xml(e.to_string()))?;
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "response" => current = DavResponse::default(),
+                    "propstat" => {
+                        propstat_status = None;
+                        propstat_props.clear();
+                        propstat_types.clear();
+                    }
+                    _ => {}
+                }
+                if stack.last().map(String::as_str) == Some("resourcetype") {
+                    propstat_types.push(name.clone());
+                }
+                stack.push(name);
+                text.clear();
+            }
+            Event::Empty(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                if stack.last().map(String::as_str) == Some("resourcetype") {
+                    propstat_types.push(name);
+                }
+            }
+            Event::Text(content) => {
+                text.push_str(&content.unescape().map_err(|e| CardDavError::Xml(e.to_string()))?);
+            }
+            Event::CData(content) => {
+                text.push_str(&String::from_utf8_lossy(&content.into_inner()));
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                let parent = stack.last().map(String::as_str).unwrap_or_default();
+                let value = text.trim().to_string();
+
+                match (name.as_str(), parent) {
+                    ("href", "response") => current.href = value,
+                    // Properties such as current-user-principal wrap an href
+                    ("href", _) if stack.iter().any(|n| n == "prop") => {
+                        propstat_props.entry(parent.to_string()).or_insert(value);
+                    }
+                    ("status", "propstat") => propstat_status = parse_status(&value),
+                    ("status", "response") => current.status = parse_status(&value),
+                    ("sync-token", "multistatus") => sync_token = Some(value).filter(|t| !t.is_empty()),
+                    ("resourcetype", "prop") => {}
+                    (_, "prop") if !value.is_empty() => {
+                        propstat_props.entry(name.clone()).or_insert(value);
+                    }
+                    ("propstat", _) if propstat_status.is_none_or(|status| (200..300).contains(&status)) => {
+                        current.props.extend(propstat_props.drain());
+                        current.resource_types.append(&mut propstat_types);
+                    }
+                    ("response", _) => responses.push(std::mem::take(&mut current)),
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok((responses, sync_token))
+}
+
+// "HTTP/1.1 404 Not Found" -> 404
+fn parse_status(line: &str) -> Option<u16> {
+    line.split_whitespace().nth(1)?.parse().ok()
+}
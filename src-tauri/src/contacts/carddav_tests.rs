@@ -0,0 +1,369 @@
+#[cfg(test)]
+mod tests {
+    use crate::contacts::carddav::*;
+    use crate::contacts::store::{self, ContactInput};
+    use crate::contacts::sync;
+    use crate::error::MailError;
+    use crate::test_utils::memory_pool;
+    use sqlx::{Pool, Sqlite};
+    use wiremock::matchers::{body_string_contains, header, method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn multistatus(body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(207).set_body_string(format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">{}</d:multistatus>"#,
+            body
+        ))
+    }
+
+    fn card_response(href: &str, etag: &str, card: &str) -> String {
+        format!(
+            "<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag><card:address-data>{}</card:address-data></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+            href, etag, card
+        )
+    }
+
+    async fn link_account(pool: &Pool<Sqlite>, server: &MockServer, sync_token: Option<&str>) -> crate::models::CardDavAccount {
+        let book = AddressBook { url: format!("{}/book/", server.uri()), display_name: None };
+        sync::create_account(pool, "acc", &server.uri(), "alice", &book).await.unwrap();
+        sqlx::query("UPDATE carddav_accounts SET sync_token = ? WHERE id = 'acc'")
+            .bind(sync_token)
+            .execute(pool)
+            .await
+            .unwrap();
+        sync::get_account(pool, "acc").await.unwrap().unwrap()
+    }
+
+    // A contact already in sync with the card at `href`
+    async fn synced_contact(pool: &Pool<Sqlite>, name: &str, email: &str, href: &str) -> String {
+        let contact_id = store::save(pool, &ContactInput {
+            id: None,
+            display_name: Some(name.to_string()),
+            organization: None,
+            notes: None,
+            emails: vec![email.to_string()],
+            phones: vec![],
+        }).await.unwrap();
+        sqlx::query(
+            "INSERT INTO carddav_cards (account_id, href, contact_id, etag, synced_revision) VALUES ('acc', ?, ?, '\"1\"', (SELECT updated_at FROM contacts WHERE id = ?))"
+        )
+        .bind(href)
+        .bind(&contact_id)
+        .bind(&contact_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        contact_id
+    }
+
+    #[tokio::test]
+    async fn test_discover_follows_principal_and_home_set() {
+        let server = MockServer::start().await;
+        Mock::given(method("PROPFIND")).and(path("/")).and(header("Depth", "0"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype><d:current-user-principal><d:href>/principals/alice/</d:href></d:current-user-principal></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND")).and(path("/principals/alice/"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/principals/alice/</d:href><d:propstat><d:prop><card:addressbook-home-set><d:href>/addressbooks/alice/</d:href></card:addressbook-home-set></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND")).and(path("/addressbooks/alice/")).and(header("Depth", "1"))
+            .respond_with(multistatus(concat!(
+                "<d:response><d:href>/addressbooks/alice/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                "<d:response><d:href>/addressbooks/alice/contacts/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/><card:addressbook/></d:resourcetype><d:displayname>Contacts</d:displayname></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                "<d:response><d:href>/addressbooks/alice/inbox/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>",
+                "<d:propstat><d:prop><d:displayname/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat></d:response>"
+            )))
+            .mount(&server)
+            .await;
+
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let books = client.discover(&server.uri()).await.unwrap();
+
+        assert_eq!(books, vec![AddressBook {
+            url: format!("{}/addressbooks/alice/contacts/", server.uri()),
+            display_name: Some("Contacts".to_string()),
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_address_book_url_without_trailing_slash() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("PROPFIND")).and(path("/dav/contacts")).and(header("Depth", "0"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/dav/contacts/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/><card:addressbook/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("REPORT")).and(path("/dav/contacts/"))
+            .respond_with(multistatus("<d:sync-token>tok-1</d:sync-token>"))
+            .mount(&server)
+            .await;
+        // Cards go inside the address book, not next to it
+        Mock::given(method("PUT")).and(path_regex(r"^/dav/contacts/[0-9a-f-]+\.vcf$"))
+            .respond_with(ResponseTemplate::new(201).insert_header("ETag", "\"f1\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let books = client.discover(&format!("{}/dav/contacts", server.uri())).await.unwrap();
+        assert_eq!(books[0].url, format!("{}/dav/contacts/", server.uri()));
+
+        // Accounts linked with a slash-less URL are stored with the slash
+        let book = AddressBook { url: format!("{}/dav/contacts", server.uri()), display_name: None };
+        let account = sync::create_account(&pool, "acc", &server.uri(), "alice", &book).await.unwrap();
+        assert_eq!(account.addressbook_url, format!("{}/dav/contacts/", server.uri()));
+
+        store::save(&pool, &ContactInput {
+            id: None,
+            display_name: Some("Frank".to_string()),
+            organization: None,
+            notes: None,
+            emails: vec!["frank@example.com".to_string()],
+            phones: vec![],
+        }).await.unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+        assert_eq!(summary.uploaded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_first_sync_pulls_cards_and_uploads_local_contacts() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("sync-collection"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/book/erin.vcf</d:href><d:propstat><d:prop><d:getetag>\"1\"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response><d:sync-token>tok-1</d:sync-token>"
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("addressbook-multiget"))
+            .respond_with(multistatus(&card_response(
+                "/book/erin.vcf",
+                "\"1\"",
+                "BEGIN:VCARD\r\nVERSION:3.0\r\nUID:erin-uid\r\nFN:Erin Remote\r\nEMAIL:erin@example.com\r\nEND:VCARD\r\n",
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT")).and(path_regex(r"^/book/[0-9a-f-]+\.vcf$")).and(header("If-None-Match", "*")).and(body_string_contains("FN:Frank"))
+            .respond_with(ResponseTemplate::new(201).insert_header("ETag", "\"f1\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        store::save(&pool, &ContactInput {
+            id: None,
+            display_name: Some("Frank".to_string()),
+            organization: None,
+            notes: None,
+            emails: vec!["frank@example.com".to_string()],
+            phones: vec![],
+        }).await.unwrap();
+
+        let account = link_account(&pool, &server, None).await;
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+
+        assert_eq!((summary.downloaded, summary.uploaded, summary.conflicts), (1, 1, 0));
+        let erin = store::autocomplete(&pool, "erin", 1).await.unwrap().remove(0);
+        assert_eq!(erin.name.as_deref(), Some("Erin Remote"));
+        let account = sync::get_account(&pool, "acc").await.unwrap().unwrap();
+        assert_eq!(account.sync_token.as_deref(), Some("tok-1"));
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_falls_back_to_full_listing() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                r#"<d:error xmlns:d="DAV:"><d:valid-sync-token/></d:error>"#
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND")).and(path("/book/")).and(header("Depth", "1"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/book/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/><card:addressbook/></d:resourcetype><d:sync-token>tok-2</d:sync-token></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            ))
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("expired")).await;
+        let contact_id = synced_contact(&pool, "Gone", "gone@example.com", "/book/gone.vcf").await;
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+
+        // The card vanished from the listing, so it was deleted on the server
+        assert_eq!(summary.deleted_locally, 1);
+        assert!(store::get(&pool, &contact_id).await.unwrap().is_none());
+        let account = sync::get_account(&pool, "acc").await.unwrap().unwrap();
+        assert_eq!(account.sync_token.as_deref(), Some("tok-2"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_upload_keeps_server_copy() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("sync-collection"))
+            .respond_with(multistatus("<d:sync-token>tok</d:sync-token>"))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT")).and(path("/book/grace.vcf")).and(header("If-Match", "\"1\""))
+            .respond_with(ResponseTemplate::new(412))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("addressbook-multiget"))
+            .respond_with(multistatus(&card_response(
+                "/book/grace.vcf",
+                "\"2\"",
+                "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Grace Server\r\nEMAIL:grace@example.com\r\nEND:VCARD\r\n",
+            )))
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let contact_id = synced_contact(&pool, "Grace", "grace@example.com", "/book/grace.vcf").await;
+        // Edited locally since the last sync
+        sqlx::query("UPDATE carddav_cards SET synced_revision = 'stale'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+
+        assert_eq!((summary.uploaded, summary.downloaded, summary.conflicts), (0, 1, 1));
+        let contact = store::get(&pool, &contact_id).await.unwrap().unwrap();
+        assert_eq!(contact.contact.display_name.as_deref(), Some("Grace Server"));
+    }
+
+    #[tokio::test]
+    async fn test_only_unsupported_sync_falls_back_to_full_listing() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/"))
+            .respond_with(ResponseTemplate::new(501))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND")).and(path("/book/")).and(header("Depth", "1"))
+            .respond_with(multistatus(
+                "<d:response><d:href>/book/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/><card:addressbook/></d:resourcetype><d:sync-token>tok-2</d:sync-token></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        sync::sync_account(&pool, &client, &account).await.unwrap();
+        let account = sync::get_account(&pool, "acc").await.unwrap().unwrap();
+        assert_eq!(account.sync_token.as_deref(), Some("tok-2"));
+
+        // A failing server is an error, not a reason to compare against an empty listing
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND"))
+            .respond_with(multistatus(""))
+            .expect(0)
+            .mount(&server)
+            .await;
+        sync::delete_account(&pool, "acc").await.unwrap();
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let contact_id = synced_contact(&pool, "Kept", "kept@example.com", "/book/kept.vcf").await;
+
+        let error = sync::sync_account(&pool, &client, &account).await.unwrap_err();
+        assert!(error.to_string().contains("500"), "{}", error);
+        assert!(store::get(&pool, &contact_id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials_are_an_auth_error() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let client = CardDavClient::new("alice", "wrong").unwrap();
+        let error = sync::sync_account(&pool, &client, &account).await.unwrap_err();
+        assert!(matches!(error, MailError::Auth(_)), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_local_delete_is_pushed() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("sync-collection"))
+            .respond_with(multistatus("<d:sync-token>tok-2</d:sync-token>"))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE")).and(path("/book/henry.vcf")).and(header("If-Match", "\"1\""))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let contact_id = synced_contact(&pool, "Henry", "henry@example.com", "/book/henry.vcf").await;
+        store::delete(&pool, &contact_id).await.unwrap();
+
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+
+        assert_eq!((summary.deleted_remotely, summary.conflicts), (1, 0));
+        let cards: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM carddav_cards").fetch_one(&pool).await.unwrap();
+        assert_eq!(cards, 0);
+    }
+
+    #[tokio::test]
+    async fn test_delete_of_card_changed_on_server_restores_it() {
+        let pool = memory_pool().await;
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("sync-collection"))
+            .respond_with(multistatus("<d:sync-token>tok-2</d:sync-token>"))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE")).and(path("/book/iris.vcf"))
+            .respond_with(ResponseTemplate::new(412))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("REPORT")).and(path("/book/")).and(body_string_contains("addressbook-multiget"))
+            .respond_with(multistatus(&card_response(
+                "/book/iris.vcf",
+                "\"2\"",
+                "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Iris Server\r\nEMAIL:iris@example.com\r\nEND:VCARD\r\n",
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let account = link_account(&pool, &server, Some("tok")).await;
+        let contact_id = synced_contact(&pool, "Iris", "iris@example.com", "/book/iris.vcf").await;
+        store::delete(&pool, &contact_id).await.unwrap();
+
+        let client = CardDavClient::new("alice", "secret").unwrap();
+        let summary = sync::sync_account(&pool, &client, &account).await.unwrap();
+
+        assert_eq!((summary.deleted_remotely, summary.downloaded, summary.conflicts), (0, 1, 1));
+        let iris = store::autocomplete(&pool, "iris", 1).await.unwrap().remove(0);
+        assert_eq!(iris.name.as_deref(), Some("Iris Server"));
+        let etag: String = sqlx::query_scalar("SELECT etag FROM carddav_cards WHERE href = '/book/iris.vcf'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(etag, "\"2\"");
+    }
+}
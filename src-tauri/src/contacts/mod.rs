@@ -0,0 +1,11 @@
+pub mod carddav;
+pub mod store;
+pub mod sync;
+pub mod vcard;
+
+#[cfg(test)]
+mod carddav_tests;
+#[cfg(test)]
+mod store_tests;
+#[cfg(test)]
+mod vcard_tests;
@@ -0,0 +1,812 @@
+use crate::contacts::vcard::{VCard, VCardEmail, VCardPhone, VCardPhoto};
+use crate::email::address::{self, Mailbox};
+use crate::error::{Context, MailError, MailResult};
+use crate::imap_client::ImapEmail;
+use crate::models::{Contact, ContactEmail, ContactPhone};
+use crate::smtp_client::EmailMessage;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::collections::HashSet;
+
+// Sending to someone says more about who matters than receiving from them
+const CONTACTED_WEIGHT: f64 = 3.0;
+// Usage counts lose half their weight every 30 days without contact
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+// Keeps hand-entered contacts above harvested ones nobody has written to yet
+const MANUAL_BONUS: f64 = 0.5;
+// Upper bound on rows ranked per keystroke
+const MAX_CANDIDATES: i64 = 500;
+
+const CONTACT_COLUMNS: &str = "id, uid, display_name, organization, notes, photo_mime_type, is_manual";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interaction {
+    // We sent a message to the address
+    Contacted,
+    // The address appeared on a message we received
+    Seen,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactDetail {
+    #[serde(flatten)]
+    pub contact: Contact,
+    pub emails: Vec<ContactEmail>,
+    pub phones: Vec<ContactPhone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactSuggestion {
+    pub contact_id: String,
+    pub name: Option<String>,
+    pub email: String,
+    // Ready to drop into a recipient field, e.g. `"Doe, John" <john@example.com>`
+    pub formatted: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactInput {
+    pub id: Option<String>,
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub organization: Option<String>,
+    pub notes: Option<String>,
+    // The first address becomes the primary one
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub phones: Vec<PhoneInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhoneInput {
+    pub number: String,
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Created,
+    Updated,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    // Cards without a name, email address or phone number
+    pub skipped: usize,
+}
+
+pub fn rank_score(times_contacted: i64, times_seen: i64, last_used: Option<&str>, is_manual: bool, now: DateTime<Utc>) -> f64 {
+    let frequency = times_contacted as f64 * CONTACTED_WEIGHT + times_seen as f64;
+    let age_days = last_used
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| (now - time.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0);
+
+    let recency = match age_days {
+        Some(days) => 0.5f64.powf(days / RECENCY_HALF_LIFE_DAYS),
+        None => 0.0,
+    };
+
+    (1.0 + frequency).ln() * recency + if is_manual { MANUAL_BONUS } else { 0.0 }
+}
+
+// Records that the given addresses took part in a message. Unknown addresses become
+// new contacts; the user's own identities are never harvested.
+pub async fn harvest(pool: &Pool<Sqlite>, mailboxes: &[Mailbox], interaction: Interaction, when: DateTime<Utc>) -> MailResult<()> {
+    if mailboxes.is_empty() {
+        return Ok(());
+    }
+
+    let own: HashSet<String> = sqlx::query_scalar::<_, String>("SELECT email FROM identities")
+        .fetch_all(pool)
+        .await
+        .context("Failed to load identities")?
+        .into_iter()
+        .map(|email| email.to_lowercase())
+        .collect();
+
+    let counter = match interaction {
+        Interaction::Contacted => "times_contacted",
+        Interaction::Seen => "times_seen",
+    };
+    let when = when.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    let mut done = HashSet::new();
+    for mailbox in mailboxes {
+        let key = mailbox.address.to_lowercase();
+        if own.contains(&key) || !done.insert(key) {
+            continue;
+        }
+
+        let contact_id = sqlx::query_scalar::<_, String>("SELECT contact_id FROM contact_emails WHERE email = ?")
+            .bind(&mailbox.address)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to look up contact")?;
+
+        match contact_id {
+            Some(contact_id) => {
+                sqlx::query(&format!(
+                    "UPDATE contact_emails SET {counter} = {counter} + 1, last_used = MAX(COALESCE(last_used, ''), ?) WHERE email = ?"
+                ))
+                .bind(&when)
+                .bind(&mailbox.address)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to update contact")?;
+
+                // Harvested contacts pick up a name the first time one is seen
+                if let Some(name) = &mailbox.name {
+                    sqlx::query("UPDATE contacts SET display_name = ? WHERE id = ? AND display_name IS NULL AND is_manual = 0")
+                        .bind(name)
+                        .bind(&contact_id)
+                        .execute(&mut *tx)
+                        .await
+                        .context("Failed to update contact name")?;
+                }
+            }
+            None => {
+                let contact_id = uuid::Uuid::new_v4().to_string();
+                sqlx::query("INSERT INTO contacts (id, display_name) VALUES (?, ?)")
+                    .bind(&contact_id)
+                    .bind(&mailbox.name)
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to create contact")?;
+
+                sqlx::query(&format!(
+                    "INSERT INTO contact_emails (email, contact_id, is_primary, {counter}, last_used) VALUES (?, ?, 1, 1, ?)"
+                ))
+                .bind(&mailbox.address)
+                .bind(&contact_id)
+                .bind(&when)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to create contact address")?;
+            }
+        }
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")
+}
+
+// Learns every recipient of a message that was just sent
+pub async fn harvest_sent(pool: &Pool<Sqlite>, message: &EmailMessage) -> MailResult<()> {
+    let recipients: Vec<Mailbox> = message.to.iter()
+        .chain(message.cc.iter())
+        .chain(message.bcc.iter())
+        .flat_map(|recipient| address::parse_list(recipient).0)
+        .collect();
+    harvest(pool, &recipients, Interaction::Contacted, Utc::now()).await
+}
+
+// Learns the sender and recipients of a synced message, dated by its Date header
+pub async fn harvest_received(pool: &Pool<Sqlite>, email: &ImapEmail) -> MailResult<()> {
+    let mut mailboxes = address::parse_list(&email.from).0;
+    mailboxes.extend(email.to.iter().cloned());
+    mailboxes.extend(email.cc.iter().cloned());
+
+    let when = email.date.as_deref()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+    harvest(pool, &mailboxes, Interaction::Seen, when).await
+}
+
+// Matches the prefix against addresses and against the start of any word of the
+// name, best ranked first
+pub async fn autocomplete(pool: &Pool<Sqlite>, prefix: &str, limit: usize) -> MailResult<Vec<ContactSuggestion>> {
+    let prefix = prefix.trim();
+    if prefix.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let pattern = escape_like(prefix);
+    let rows = sqlx::query(
+        r#"
+        SELECT ce.email, ce.contact_id, ce.times_contacted, ce.times_seen, ce.last_used,
+               c.display_name, c.is_manual
+        FROM contact_emails ce
+        JOIN contacts c ON c.id = ce.contact_id
+        WHERE ce.email LIKE ?1 || '%' ESCAPE '\'
+           OR c.display_name LIKE ?1 || '%' ESCAPE '\'
+           OR c.display_name LIKE '% ' || ?1 || '%' ESCAPE '\'
+        ORDER BY ce.last_used DESC
+        LIMIT ?2
+        "#
+    )
+    .bind(&pattern)
+    .bind(MAX_CANDIDATES)
+    .fetch_all(pool)
+    .await
+    .context("Failed to search contacts")?;
+
+    let now = Utc::now();
+    let mut suggestions: Vec<ContactSuggestion> = rows.into_iter()
+        .map(|row| {
+            let name: Option<String> = row.get("display_name");
+            let email: String = row.get("email");
+            let last_used: Option<String> = row.get("last_used");
+            ContactSuggestion {
+                contact_id: row.get("contact_id"),
+                formatted: Mailbox { name: name.clone(), address: email.clone() }.to_display(),
+                score: rank_score(row.get("times_contacted"), row.get("times_seen"), last_used.as_deref(), row.get("is_manual"), now),
+                name,
+                email,
+            }
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.email.cmp(&b.email)));
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+pub async fn get(pool: &Pool<Sqlite>, contact_id: &str) -> MailResult<Option<ContactDetail>> {
+    let contact = sqlx::query_as::<_, Contact>(&format!("SELECT {CONTACT_COLUMNS} FROM contacts WHERE id = ?"))
+        .bind(contact_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to get contact")?;
+
+    match contact {
+        Some(contact) => Ok(Some(load_detail(pool, contact).await?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn list(pool: &Pool<Sqlite>, limit: i64, offset: i64) -> MailResult<Vec<ContactDetail>> {
+    let contacts = sqlx::query_as::<_, Contact>(&format!(
+        "SELECT {CONTACT_COLUMNS} FROM contacts ORDER BY display_name IS NULL, display_name COLLATE NOCASE, id LIMIT ? OFFSET ?"
+    ))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list contacts")?;
+
+    let mut details = Vec::with_capacity(contacts.len());
+    for contact in contacts {
+        details.push(load_detail(pool, contact).await?);
+    }
+    Ok(details)
+}
+
+// Creates or updates a contact from the editor. Addresses already belonging to
+// another contact are moved over with their history.
+pub async fn save(pool: &Pool<Sqlite>, input: &ContactInput) -> MailResult<String> {
+    let mut emails = Vec::with_capacity(input.emails.len());
+    for email in &input.emails {
+        let mailbox = address::parse_mailbox(email)?;
+        if !emails.iter().any(|existing: &Mailbox| existing.same_address(&mailbox)) {
+            emails.push(mailbox);
+        }
+    }
+    if emails.is_empty() {
+        return Err(MailError::validation("Contact needs at least one email address"));
+    }
+
+    let contact_id = input.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO contacts (id, display_name, organization, notes, is_manual)
+        VALUES (?, ?, ?, ?, 1)
+        ON CONFLICT(id) DO UPDATE SET
+            display_name = excluded.display_name,
+            organization = excluded.organization,
+            notes = excluded.notes,
+            is_manual = 1,
+            updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(&contact_id)
+    .bind(input.display_name.as_deref().map(str::trim).filter(|n| !n.is_empty()))
+    .bind(input.organization.as_deref().map(str::trim).filter(|o| !o.is_empty()))
+    .bind(&input.notes)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to save contact")?;
+
+    // Phone numbers are replaced wholesale on every save
+    sqlx::query("DELETE FROM contact_phones WHERE contact_id = ?")
+        .bind(&contact_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to clear contact phones")?;
+    for phone in input.phones.iter().filter(|p| !p.number.trim().is_empty()) {
+        insert_phone(&mut tx, &contact_id, phone.number.trim(), phone.kind.as_deref()).await?;
+    }
+
+    let previous_owners = replace_emails(&mut tx, &contact_id, &emails).await?;
+
+    // A contact whose last address was taken over has nothing left to show
+    for owner in previous_owners {
+        delete_if_empty(&mut tx, &owner).await?;
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(contact_id)
+}
+
+// Folds the source contacts into the target: their addresses and usage history move
+// over, and the target keeps its own name and notes where it has them
+pub async fn merge(pool: &Pool<Sqlite>, target_id: &str, source_ids: &[String]) -> MailResult<()> {
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM contacts WHERE id = ?")
+        .bind(target_id)
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to get contact")?;
+    if exists == 0 {
+        return Err(MailError::NotFound(format!("Contact '{}' not found", target_id)));
+    }
+
+    for source_id in source_ids.iter().filter(|id| id.as_str() != target_id) {
+        sqlx::query(
+            r#"
+            UPDATE contacts SET
+                display_name = COALESCE(display_name, (SELECT display_name FROM contacts WHERE id = ?1)),
+                organization = COALESCE(organization, (SELECT organization FROM contacts WHERE id = ?1)),
+                notes = COALESCE(notes, (SELECT notes FROM contacts WHERE id = ?1)),
+                photo_mime_type = COALESCE(photo_mime_type, (SELECT photo_mime_type FROM contacts WHERE id = ?1)),
+                photo = COALESCE(photo, (SELECT photo FROM contacts WHERE id = ?1)),
+                is_manual = 1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?2
+            "#
+        )
+        .bind(source_id)
+        .bind(target_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to merge contact")?;
+
+        sqlx::query("UPDATE contact_emails SET contact_id = ?, is_primary = 0 WHERE contact_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move contact addresses")?;
+
+        sqlx::query("UPDATE contact_phones SET contact_id = ? WHERE contact_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move contact phones")?;
+
+        sqlx::query("DELETE FROM contacts WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete merged contact")?;
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")
+}
+
+pub async fn delete(pool: &Pool<Sqlite>, contact_id: &str) -> MailResult<()> {
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    sqlx::query("DELETE FROM contact_emails WHERE contact_id = ?")
+        .bind(contact_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete contact addresses")?;
+
+    sqlx::query("DELETE FROM contact_phones WHERE contact_id = ?")
+        .bind(contact_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete contact phones")?;
+
+    sqlx::query("DELETE FROM contacts WHERE id = ?")
+        .bind(contact_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete contact")?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")
+}
+
+// Adds a vCard to the address book. A card sharing a UID or any email address with
+// an existing contact is folded into it; only fields the contact lacks are filled in.
+pub async fn import_card(pool: &Pool<Sqlite>, card: &VCard) -> MailResult<ImportOutcome> {
+    let mailboxes = card_mailboxes(card);
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    let existing = find_card_match(&mut tx, card.uid.as_deref(), &mailboxes).await?;
+
+    let (contact_id, outcome) = match existing {
+        Some(contact_id) => {
+            sqlx::query(
+                r#"
+                UPDATE contacts SET
+                    uid = COALESCE(uid, ?),
+                    display_name = COALESCE(display_name, ?),
+                    organization = COALESCE(organization, ?),
+                    notes = COALESCE(notes, ?),
+                    photo_mime_type = COALESCE(photo_mime_type, ?),
+                    photo = COALESCE(photo, ?),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = ?
+                "#
+            )
+            .bind(&card.uid)
+            .bind(&card.formatted_name)
+            .bind(&card.organization)
+            .bind(&card.note)
+            .bind(card.photo.as_ref().map(|p| &p.mime_type))
+            .bind(card.photo.as_ref().map(|p| &p.data))
+            .bind(&contact_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update contact")?;
+            (contact_id, ImportOutcome::Updated)
+        }
+        None => {
+            let contact_id = uuid::Uuid::new_v4().to_string();
+            sqlx::query(
+                r#"
+                INSERT INTO contacts (id, uid, display_name, organization, notes, photo_mime_type, photo, is_manual)
+                VALUES (?, ?, ?, ?, ?, ?, ?, 1)
+                "#
+            )
+            .bind(&contact_id)
+            .bind(&card.uid)
+            .bind(&card.formatted_name)
+            .bind(&card.organization)
+            .bind(&card.note)
+            .bind(card.photo.as_ref().map(|p| &p.mime_type))
+            .bind(card.photo.as_ref().map(|p| &p.data))
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create contact")?;
+            (contact_id, ImportOutcome::Created)
+        }
+    };
+
+    let has_primary = outcome == ImportOutcome::Updated;
+    for (index, mailbox) in mailboxes.iter().enumerate() {
+        // Addresses already filed under another contact stay where they are
+        sqlx::query("INSERT INTO contact_emails (email, contact_id, is_primary) VALUES (?, ?, ?) ON CONFLICT(email) DO NOTHING")
+            .bind(&mailbox.address)
+            .bind(&contact_id)
+            .bind(index == 0 && !has_primary)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save contact address")?;
+    }
+
+    let known: Vec<String> = sqlx::query_scalar::<_, String>("SELECT number FROM contact_phones WHERE contact_id = ?")
+        .bind(&contact_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("Failed to load contact phones")?
+        .iter()
+        .map(|number| phone_digits(number))
+        .collect();
+    for phone in card.phones.iter().filter(|phone| !known.contains(&phone_digits(&phone.number))) {
+        insert_phone(&mut tx, &contact_id, &phone.number, Some(&phone.types.join(","))).await?;
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(outcome)
+}
+
+pub async fn import_cards(pool: &Pool<Sqlite>, cards: &[VCard]) -> MailResult<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    for card in cards {
+        if card.formatted_name.is_none() && card.emails.is_empty() && card.phones.is_empty() {
+            summary.skipped += 1;
+            continue;
+        }
+        // Cards are imported one by one, so duplicates within the same file merge too
+        match import_card(pool, card).await? {
+            ImportOutcome::Created => summary.created += 1,
+            ImportOutcome::Updated => summary.updated += 1,
+        }
+    }
+    Ok(summary)
+}
+
+// Makes a contact match a card from a synced address book, field for field. Without a
+// known contact the card is matched the way an import would be, or filed as a new one.
+pub async fn apply_card(pool: &Pool<Sqlite>, contact_id: Option<&str>, card: &VCard) -> MailResult<String> {
+    let mailboxes = card_mailboxes(card);
+
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    let known = match contact_id {
+        Some(contact_id) => sqlx::query_scalar::<_, String>("SELECT id FROM contacts WHERE id = ?")
+            .bind(contact_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to look up contact")?,
+        None => None,
+    };
+    let contact_id = match known {
+        Some(contact_id) => contact_id,
+        None => find_card_match(&mut tx, card.uid.as_deref(), &mailboxes)
+            .await?
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO contacts (id, uid, display_name, organization, notes, photo_mime_type, photo, is_manual)
+        VALUES (?, ?, ?, ?, ?, ?, ?, 1)
+        ON CONFLICT(id) DO UPDATE SET
+            uid = COALESCE(excluded.uid, uid),
+            display_name = excluded.display_name,
+            organization = excluded.organization,
+            notes = excluded.notes,
+            photo_mime_type = excluded.photo_mime_type,
+            photo = excluded.photo,
+            is_manual = 1,
+            updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(&contact_id)
+    .bind(&card.uid)
+    .bind(&card.formatted_name)
+    .bind(&card.organization)
+    .bind(&card.note)
+    .bind(card.photo.as_ref().map(|p| &p.mime_type))
+    .bind(card.photo.as_ref().map(|p| &p.data))
+    .execute(&mut *tx)
+    .await
+    .context("Failed to save contact")?;
+
+    sqlx::query("DELETE FROM contact_phones WHERE contact_id = ?")
+        .bind(&contact_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to clear contact phones")?;
+    for phone in &card.phones {
+        insert_phone(&mut tx, &contact_id, &phone.number, Some(&phone.types.join(","))).await?;
+    }
+
+    let previous_owners = replace_emails(&mut tx, &contact_id, &mailboxes).await?;
+    for owner in previous_owners {
+        delete_if_empty(&mut tx, &owner).await?;
+    }
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(contact_id)
+}
+
+// Builds vCards for the given contacts, or for the whole address book
+pub async fn export_cards(pool: &Pool<Sqlite>, contact_ids: Option<&[String]>) -> MailResult<Vec<VCard>> {
+    let contacts = match contact_ids {
+        Some(ids) => {
+            let mut contacts = Vec::with_capacity(ids.len());
+            for id in ids {
+                let contact = get(pool, id).await?
+                    .ok_or_else(|| MailError::NotFound(format!("Contact '{}' not found", id)))?;
+                contacts.push(contact);
+            }
+            contacts
+        }
+        None => list(pool, i64::MAX, 0).await?,
+    };
+
+    let mut cards = Vec::with_capacity(contacts.len());
+    for detail in contacts {
+        let photo = sqlx::query_scalar::<_, Option<Vec<u8>>>("SELECT photo FROM contacts WHERE id = ?")
+            .bind(&detail.contact.id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to load contact photo")?;
+
+        cards.push(VCard {
+            uid: Some(detail.contact.uid.clone().unwrap_or_else(|| detail.contact.id.clone())),
+            formatted_name: detail.contact.display_name.clone(),
+            emails: detail.emails.iter()
+                .map(|email| VCardEmail {
+                    address: email.email.clone(),
+                    types: vec![],
+                    preferred: email.is_primary,
+                })
+                .collect(),
+            phones: detail.phones.iter()
+                .map(|phone| VCardPhone {
+                    number: phone.number.clone(),
+                    types: phone.kind.as_deref()
+                        .map(|kind| kind.split(',').filter(|k| !k.is_empty()).map(str::to_string).collect())
+                        .unwrap_or_default(),
+                })
+                .collect(),
+            organization: detail.contact.organization.clone(),
+            note: detail.contact.notes.clone(),
+            photo: photo.zip(detail.contact.photo_mime_type.clone())
+                .map(|(data, mime_type)| VCardPhoto { mime_type, data }),
+        });
+    }
+
+    Ok(cards)
+}
+
+async fn load_detail(pool: &Pool<Sqlite>, contact: Contact) -> MailResult<ContactDetail> {
+    let emails = load_emails(pool, &contact.id).await?;
+    let phones = sqlx::query_as::<_, ContactPhone>(
+        "SELECT id, contact_id, number, kind FROM contact_phones WHERE contact_id = ? ORDER BY rowid"
+    )
+    .bind(&contact.id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch contact phones")?;
+
+    Ok(ContactDetail { contact, emails, phones })
+}
+
+// Files the addresses under the contact, taking them over from whichever contact had them,
+// and forgets the contact's other addresses. Returns the contacts that lost an address.
+async fn replace_emails(tx: &mut sqlx::Transaction<'_, Sqlite>, contact_id: &str, emails: &[Mailbox]) -> MailResult<HashSet<String>> {
+    let mut previous_owners = HashSet::new();
+    for (index, mailbox) in emails.iter().enumerate() {
+        let owner = sqlx::query_scalar::<_, String>("SELECT contact_id FROM contact_emails WHERE email = ?")
+            .bind(&mailbox.address)
+            .fetch_optional(&mut **tx)
+            .await
+            .context("Failed to look up contact address")?;
+        if let Some(owner) = owner.filter(|owner| owner != contact_id) {
+            previous_owners.insert(owner);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO contact_emails (email, contact_id, is_primary)
+            VALUES (?, ?, ?)
+            ON CONFLICT(email) DO UPDATE SET
+                contact_id = excluded.contact_id,
+                is_primary = excluded.is_primary
+            "#
+        )
+        .bind(&mailbox.address)
+        .bind(contact_id)
+        .bind(index == 0)
+        .execute(&mut **tx)
+        .await
+        .context("Failed to save contact address")?;
+    }
+
+    let kept: Vec<String> = emails.iter().map(|m| m.address.to_lowercase()).collect();
+    let current = sqlx::query_scalar::<_, String>("SELECT email FROM contact_emails WHERE contact_id = ?")
+        .bind(contact_id)
+        .fetch_all(&mut **tx)
+        .await
+        .context("Failed to load contact addresses")?;
+    for email in current.into_iter().filter(|email| !kept.contains(&email.to_lowercase())) {
+        sqlx::query("DELETE FROM contact_emails WHERE email = ?")
+            .bind(&email)
+            .execute(&mut **tx)
+            .await
+            .context("Failed to remove contact address")?;
+    }
+
+    Ok(previous_owners)
+}
+
+// A card belongs to the contact with its UID, or else to the owner of any of its addresses
+async fn find_card_match(tx: &mut sqlx::Transaction<'_, Sqlite>, uid: Option<&str>, mailboxes: &[Mailbox]) -> MailResult<Option<String>> {
+    if let Some(uid) = uid {
+        let existing = sqlx::query_scalar::<_, String>("SELECT id FROM contacts WHERE uid = ?")
+            .bind(uid)
+            .fetch_optional(&mut **tx)
+            .await
+            .context("Failed to look up contact")?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+
+    for mailbox in mailboxes {
+        let existing = sqlx::query_scalar::<_, String>("SELECT contact_id FROM contact_emails WHERE email = ?")
+            .bind(&mailbox.address)
+            .fetch_optional(&mut **tx)
+            .await
+            .context("Failed to look up contact")?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+
+    Ok(None)
+}
+
+// Preferred address first so it becomes the primary one
+fn card_mailboxes(card: &VCard) -> Vec<Mailbox> {
+    let mut emails: Vec<&VCardEmail> = card.emails.iter().collect();
+    emails.sort_by_key(|email| !email.preferred);
+    emails.iter()
+        .filter_map(|email| address::parse_mailbox(&email.address).ok())
+        .collect()
+}
+
+async fn insert_phone(tx: &mut sqlx::Transaction<'_, Sqlite>, contact_id: &str, number: &str, kind: Option<&str>) -> MailResult<()> {
+    sqlx::query("INSERT INTO contact_phones (id, contact_id, number, kind) VALUES (?, ?, ?, ?)")
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(contact_id)
+        .bind(number)
+        .bind(kind.filter(|k| !k.is_empty()))
+        .execute(&mut **tx)
+        .await
+        .context("Failed to save contact phone")?;
+    Ok(())
+}
+
+async fn load_emails(pool: &Pool<Sqlite>, contact_id: &str) -> MailResult<Vec<ContactEmail>> {
+    sqlx::query_as::<_, ContactEmail>(
+        r#"
+        SELECT email, contact_id, is_primary, times_contacted, times_seen, last_used
+        FROM contact_emails WHERE contact_id = ? ORDER BY is_primary DESC, times_contacted DESC, email
+        "#
+    )
+    .bind(contact_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch contact addresses")
+}
+
+async fn delete_if_empty(tx: &mut sqlx::Transaction<'_, Sqlite>, contact_id: &str) -> MailResult<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM contacts WHERE id = ?1
+          AND NOT EXISTS (SELECT 1 FROM contact_emails WHERE contact_id = ?1)
+          AND NOT EXISTS (SELECT 1 FROM contact_phones WHERE contact_id = ?1)
+        "#
+    )
+    .bind(contact_id)
+    .execute(&mut **tx)
+    .await
+    .context("Failed to delete empty contact")?;
+    Ok(())
+}
+
+// Phone numbers are compared on their digits only, so "+1 (555) 010-0100" matches "+15550100100"
+fn phone_digits(number: &str) -> String {
+    number.chars().filter(|c| c.is_ascii_digit() || *c == '+').collect()
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use crate::contacts::store::*;
+    use crate::contacts::vcard;
+    use crate::email::address::Mailbox;
+    use crate::error::MailError;
+    use crate::test_utils::memory_pool;
+    use chrono::{Duration, Utc};
+
+    fn mailbox(name: Option<&str>, address: &str) -> Mailbox {
+        Mailbox {
+            name: name.map(|s| s.to_string()),
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rank_prefers_sent_and_recent() {
+        let now = Utc::now();
+        let recent = (now - Duration::days(1)).to_rfc3339();
+        let old = (now - Duration::days(120)).to_rfc3339();
+
+        assert!(rank_score(5, 0, Some(&recent), false, now) > rank_score(0, 5, Some(&recent), false, now));
+        assert!(rank_score(5, 0, Some(&recent), false, now) > rank_score(5, 0, Some(&old), false, now));
+        assert!(rank_score(0, 0, None, true, now) > rank_score(0, 0, None, false, now));
+    }
+
+    #[tokio::test]
+    async fn test_harvest_and_autocomplete() {
+        let pool = memory_pool().await;
+        let now = Utc::now();
+
+        harvest(&pool, &[mailbox(Some("Alice Smith"), "alice@example.com"), mailbox(None, "albert@example.com")], Interaction::Seen, now).await.unwrap();
+        harvest(&pool, &[mailbox(None, "albert@example.com")], Interaction::Contacted, now).await.unwrap();
+
+        let suggestions = autocomplete(&pool, "al", 10).await.unwrap();
+        let emails: Vec<&str> = suggestions.iter().map(|s| s.email.as_str()).collect();
+        assert_eq!(emails, vec!["albert@example.com", "alice@example.com"]);
+
+        // Later words of the name match too
+        let suggestions = autocomplete(&pool, "smi", 10).await.unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].formatted, "Alice Smith <alice@example.com>");
+    }
+
+    #[tokio::test]
+    async fn test_own_identities_are_not_harvested() {
+        let pool = memory_pool().await;
+        sqlx::query("INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO identities (id, account_id, email, is_default) VALUES ('id', 'acc', 'Me@Example.com', 1)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        harvest(&pool, &[mailbox(None, "me@example.com")], Interaction::Seen, Utc::now()).await.unwrap();
+        assert!(autocomplete(&pool, "me", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_merge_moves_addresses() {
+        let pool = memory_pool().await;
+        let now = Utc::now();
+        harvest(&pool, &[mailbox(Some("Bob"), "bob@work.example"), mailbox(None, "bob@home.example")], Interaction::Seen, now).await.unwrap();
+
+        let work = autocomplete(&pool, "bob@work", 1).await.unwrap().remove(0);
+        let home = autocomplete(&pool, "bob@home", 1).await.unwrap().remove(0);
+        merge(&pool, &work.contact_id, std::slice::from_ref(&home.contact_id)).await.unwrap();
+
+        let contact = get(&pool, &work.contact_id).await.unwrap().unwrap();
+        assert_eq!(contact.emails.len(), 2);
+        assert_eq!(contact.contact.display_name.as_deref(), Some("Bob"));
+        assert!(get(&pool, &home.contact_id).await.unwrap().is_none());
+
+        let error = merge(&pool, "missing", std::slice::from_ref(&work.contact_id)).await.unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+        let error = export_cards(&pool, Some(&["missing".to_string()])).await.unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_save_takes_over_addresses() {
+        let pool = memory_pool().await;
+        harvest(&pool, &[mailbox(None, "carol@example.com")], Interaction::Contacted, Utc::now()).await.unwrap();
+        let harvested = autocomplete(&pool, "carol", 1).await.unwrap().remove(0);
+
+        let contact_id = save(&pool, &ContactInput {
+            id: None,
+            display_name: Some("Carol".to_string()),
+            organization: None,
+            notes: None,
+            emails: vec!["carol@example.com".to_string()],
+            phones: vec![],
+        }).await.unwrap();
+
+        let contact = get(&pool, &contact_id).await.unwrap().unwrap();
+        assert_eq!(contact.emails[0].times_contacted, 1);
+        assert!(get(&pool, &harvested.contact_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_merges_with_harvested_contact() {
+        let pool = memory_pool().await;
+        harvest(&pool, &[mailbox(None, "dave@example.com")], Interaction::Seen, Utc::now()).await.unwrap();
+
+        let cards = vcard::parse("BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Dave Jones\r\nEMAIL:Dave@Example.com\r\nTEL:+1 555 0199\r\nEND:VCARD\r\nBEGIN:VCARD\r\nVERSION:4.0\r\nNOTE:nothing to import\r\nEND:VCARD\r\n").unwrap();
+        let summary = import_cards(&pool, &cards).await.unwrap();
+        assert_eq!((summary.created, summary.updated, summary.skipped), (0, 1, 1));
+
+        let suggestion = autocomplete(&pool, "dave", 1).await.unwrap().remove(0);
+        let contact = get(&pool, &suggestion.contact_id).await.unwrap().unwrap();
+        assert_eq!(contact.contact.display_name.as_deref(), Some("Dave Jones"));
+        assert_eq!(contact.phones.len(), 1);
+
+        let exported = export_cards(&pool, None).await.unwrap();
+        assert_eq!(exported[0].formatted_name.as_deref(), Some("Dave Jones"));
+        assert_eq!(exported[0].emails[0].address, "dave@example.com");
+    }
+}
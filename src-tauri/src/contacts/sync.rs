@@ -0,0 +1,338 @@
+use crate::contacts::carddav::{self, AddressBook, CardDavClient, CardDavError, CardListing};
+use crate::contacts::store;
+use crate::contacts::vcard::{self, VCardVersion};
+use crate::error::{Context, MailError, MailResult};
+use crate::models::CardDavAccount;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::collections::{HashMap, HashSet};
+
+// 3.0 is the one version every CardDAV server accepts
+const UPLOAD_VERSION: VCardVersion = VCardVersion::V3;
+
+const ACCOUNT_COLUMNS: &str = "id, server_url, username, addressbook_url, display_name, sync_token, last_synced_at";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSummary {
+    pub downloaded: usize,
+    pub uploaded: usize,
+    pub deleted_locally: usize,
+    pub deleted_remotely: usize,
+    // Cards edited on both sides since the last sync; the server's copy was kept
+    pub conflicts: usize,
+}
+
+// What we last knew about a card on the server
+struct SyncedCard {
+    contact_id: String,
+    etag: Option<String>,
+    // Edited locally since it was last uploaded or downloaded
+    dirty: bool,
+    // Contact was deleted locally
+    deleted: bool,
+}
+
+pub async fn create_account(pool: &Pool<Sqlite>, account_id: &str, server_url: &str, username: &str, book: &AddressBook) -> MailResult<CardDavAccount> {
+    let addressbook_url = carddav::collection_url(&book.url)?;
+    sqlx::query(
+        "INSERT INTO carddav_accounts (id, server_url, username, addressbook_url, display_name) VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(account_id)
+    .bind(server_url)
+    .bind(username)
+    .bind(addressbook_url.as_str())
+    .bind(&book.display_name)
+    .execute(pool)
+    .await
+    .context("Failed to save CardDAV account")?;
+
+    get_account(pool, account_id)
+        .await?
+        .ok_or_else(|| MailError::NotFound(format!("CardDAV account '{}' not found", account_id)))
+}
+
+pub async fn get_account(pool: &Pool<Sqlite>, account_id: &str) -> MailResult<Option<CardDavAccount>> {
+    sqlx::query_as::<_, CardDavAccount>(&format!("SELECT {} FROM carddav_accounts WHERE id = ?", ACCOUNT_COLUMNS))
+        .bind(account_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to get CardDAV account")
+}
+
+pub async fn list_accounts(pool: &Pool<Sqlite>) -> MailResult<Vec<CardDavAccount>> {
+    sqlx::query_as::<_, CardDavAccount>(&format!("SELECT {} FROM carddav_accounts ORDER BY created_at", ACCOUNT_COLUMNS))
+        .fetch_all(pool)
+        .await
+        .context("Failed to list CardDAV accounts")
+}
+
+// Unlinks the address book. Contacts it brought in stay in the local address book.
+pub async fn delete_account(pool: &Pool<Sqlite>, account_id: &str) -> MailResult<()> {
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+
+    sqlx::query("DELETE FROM carddav_cards WHERE account_id = ?")
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete CardDAV cards")?;
+
+    sqlx::query("DELETE FROM carddav_accounts WHERE id = ?")
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete CardDAV account")?;
+
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")
+}
+
+// Two-way sync of one address book. Server changes are pulled first, and where a card was
+// edited on both sides the server's copy wins; local edits, deletions and new contacts are
+// pushed afterwards with ETag preconditions so nothing changed meanwhile is overwritten.
+pub async fn sync_account(pool: &Pool<Sqlite>, client: &CardDavClient, account: &CardDavAccount) -> MailResult<SyncSummary> {
+    let book = carddav::collection_url(&account.addressbook_url)?;
+    let mut summary = SyncSummary::default();
+    let mut known = load_cards(pool, &account.id).await?;
+
+    // Pull
+    let listing = fetch_changes(client, &book, account.sync_token.as_deref(), &known).await?;
+
+    for href in &listing.removed {
+        if let Some(card) = known.remove(href) {
+            if !card.deleted {
+                if card.dirty {
+                    summary.conflicts += 1;
+                }
+                store::delete(pool, &card.contact_id).await?;
+                summary.deleted_locally += 1;
+            }
+            forget_card(pool, &account.id, href).await?;
+        }
+    }
+
+    let outdated: Vec<String> = listing.changed.iter()
+        .filter(|remote| match known.get(&remote.href) {
+            Some(card) => remote.etag.is_none() || card.etag != remote.etag,
+            None => true,
+        })
+        .map(|remote| remote.href.clone())
+        .collect();
+    for href in &outdated {
+        if known.get(href).is_some_and(|card| card.dirty || card.deleted) {
+            summary.conflicts += 1;
+        }
+    }
+    summary.downloaded += download(pool, client, account, &book, &outdated, &mut known).await?;
+
+    // Push
+    for (href, card) in &known {
+        let url = carddav::resolve(&book, href)?;
+
+        if card.deleted {
+            match client.delete_card(&url, card.etag.as_deref()).await {
+                Ok(()) => {
+                    forget_card(pool, &account.id, href).await?;
+                    summary.deleted_remotely += 1;
+                }
+                // Changed on the server after we deleted it: bring it back
+                Err(CardDavError::PreconditionFailed(_)) => {
+                    summary.conflicts += 1;
+                    forget_card(pool, &account.id, href).await?;
+                    let mut recovered = HashMap::new();
+                    summary.downloaded += download(pool, client, account, &book, std::slice::from_ref(href), &mut recovered).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+            continue;
+        }
+
+        if card.dirty {
+            let revision = contact_revision(pool, &card.contact_id).await?;
+            let data = export_card(pool, &card.contact_id).await?;
+            match client.put_card(&url, &data, card.etag.as_deref()).await {
+                Ok(etag) => {
+                    remember_card(pool, &account.id, href, &card.contact_id, etag.as_deref(), revision.as_deref()).await?;
+                    summary.uploaded += 1;
+                }
+                Err(CardDavError::PreconditionFailed(_)) => {
+                    summary.conflicts += 1;
+                    let mut current = HashMap::new();
+                    current.insert(href.clone(), SyncedCard { contact_id: card.contact_id.clone(), etag: None, dirty: false, deleted: false });
+                    summary.downloaded += download(pool, client, account, &book, std::slice::from_ref(href), &mut current).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Hand-entered contacts that no address book knows about yet go to this one.
+    // Harvested addresses stay local; they are autocomplete history, not contacts.
+    let unsynced = sqlx::query_scalar::<_, String>(
+        "SELECT id FROM contacts WHERE is_manual = 1 AND id NOT IN (SELECT contact_id FROM carddav_cards)"
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to load unsynced contacts")?;
+
+    for contact_id in unsynced {
+        let revision = contact_revision(pool, &contact_id).await?;
+        let data = export_card(pool, &contact_id).await?;
+        let url = carddav::resolve(&book, &format!("{}.vcf", contact_id))?;
+        let etag = client.put_card(&url, &data, None).await?;
+        remember_card(pool, &account.id, url.path(), &contact_id, etag.as_deref(), revision.as_deref()).await?;
+        summary.uploaded += 1;
+    }
+
+    sqlx::query("UPDATE carddav_accounts SET sync_token = ?, last_synced_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(&listing.sync_token)
+        .bind(&account.id)
+        .execute(pool)
+        .await
+        .context("Failed to save sync token")?;
+
+    Ok(summary)
+}
+
+// Changes since the stored token. Servers without sync-collection support (405 or 501 to
+// the REPORT), or that have expired the token, get a full listing compared against what we
+// already have. Any other failure is passed on rather than papered over with a listing.
+async fn fetch_changes(client: &CardDavClient, book: &Url, token: Option<&str>, known: &HashMap<String, SyncedCard>) -> MailResult<CardListing> {
+    let mut token = token.map(str::to_string);
+    let mut listing = CardListing::default();
+
+    loop {
+        match client.sync_collection(book, token.as_deref()).await {
+            Ok(page) => {
+                listing.changed.extend(page.changed);
+                listing.removed.extend(page.removed);
+                listing.sync_token = page.sync_token.clone();
+                if !page.more || page.sync_token.is_none() || page.sync_token == token {
+                    return Ok(listing);
+                }
+                token = page.sync_token;
+            }
+            Err(CardDavError::InvalidSyncToken) | Err(CardDavError::Status { status: 405 | 501, .. }) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut listing = client.list_cards(book).await?;
+    let present: HashSet<&str> = listing.changed.iter().map(|card| card.href.as_str()).collect();
+    listing.removed = known.keys()
+        .filter(|href| !present.contains(href.as_str()))
+        .cloned()
+        .collect();
+    Ok(listing)
+}
+
+// Fetches the cards and writes them into the address book over any local copy
+async fn download(
+    pool: &Pool<Sqlite>,
+    client: &CardDavClient,
+    account: &CardDavAccount,
+    book: &Url,
+    hrefs: &[String],
+    known: &mut HashMap<String, SyncedCard>,
+) -> MailResult<usize> {
+    if hrefs.is_empty() {
+        return Ok(0);
+    }
+
+    let mut applied = 0;
+    for remote in client.multiget(book, hrefs).await? {
+        let card = match vcard::parse(&remote.data) {
+            Ok(mut cards) if !cards.is_empty() => cards.remove(0),
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("Skipping unreadable card {}: {}", remote.href, e);
+                continue;
+            }
+        };
+
+        let existing = known.remove(&remote.href).filter(|card| !card.deleted);
+        let contact_id = store::apply_card(pool, existing.as_ref().map(|card| card.contact_id.as_str()), &card).await?;
+        let revision = contact_revision(pool, &contact_id).await?;
+        remember_card(pool, &account.id, &remote.href, &contact_id, remote.etag.as_deref(), revision.as_deref()).await?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+async fn load_cards(pool: &Pool<Sqlite>, account_id: &str) -> MailResult<HashMap<String, SyncedCard>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT cards.href, cards.contact_id, cards.etag,
+               contacts.id IS NULL AS deleted,
+               contacts.updated_at IS NOT cards.synced_revision AS dirty
+        FROM carddav_cards cards
+        LEFT JOIN contacts ON contacts.id = cards.contact_id
+        WHERE cards.account_id = ?
+        "#
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to load CardDAV cards")?;
+
+    Ok(rows.into_iter()
+        .map(|row| {
+            let deleted: bool = row.get("deleted");
+            (row.get("href"), SyncedCard {
+                contact_id: row.get("contact_id"),
+                etag: row.get("etag"),
+                dirty: !deleted && row.get::<bool, _>("dirty"),
+                deleted,
+            })
+        })
+        .collect())
+}
+
+async fn remember_card(pool: &Pool<Sqlite>, account_id: &str, href: &str, contact_id: &str, etag: Option<&str>, revision: Option<&str>) -> MailResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO carddav_cards (account_id, href, contact_id, etag, synced_revision)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(account_id, href) DO UPDATE SET
+            contact_id = excluded.contact_id,
+            etag = excluded.etag,
+            synced_revision = excluded.synced_revision
+        "#
+    )
+    .bind(account_id)
+    .bind(href)
+    .bind(contact_id)
+    .bind(etag)
+    .bind(revision)
+    .execute(pool)
+    .await
+    .context("Failed to save CardDAV card")?;
+    Ok(())
+}
+
+async fn forget_card(pool: &Pool<Sqlite>, account_id: &str, href: &str) -> MailResult<()> {
+    sqlx::query("DELETE FROM carddav_cards WHERE account_id = ? AND href = ?")
+        .bind(account_id)
+        .bind(href)
+        .execute(pool)
+        .await
+        .context("Failed to delete CardDAV card")?;
+    Ok(())
+}
+
+async fn contact_revision(pool: &Pool<Sqlite>, contact_id: &str) -> MailResult<Option<String>> {
+    sqlx::query_scalar::<_, Option<String>>("SELECT updated_at FROM contacts WHERE id = ?")
+        .bind(contact_id)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+        .context("Failed to get contact")
+}
+
+async fn export_card(pool: &Pool<Sqlite>, contact_id: &str) -> MailResult<String> {
+    let cards = store::export_cards(pool, Some(&[contact_id.to_string()])).await?;
+    Ok(vcard::serialize_all(&cards, UPLOAD_VERSION))
+}
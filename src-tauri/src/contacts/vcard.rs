@@ -0,0 +1,452 @@
+// vCard 3.0 (RFC 2426) and 4.0 (RFC 6350) reading and writing. Older 2.1 cards, as
+// still exported by some phones, are read on a best-effort basis.
+
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+
+// Lines are folded at 75 octets, not counting the CRLF
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VCardVersion {
+    #[serde(rename = "3.0")]
+    V3,
+    #[default]
+    #[serde(rename = "4.0")]
+    V4,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VCard {
+    pub uid: Option<String>,
+    pub formatted_name: Option<String>,
+    pub emails: Vec<VCardEmail>,
+    pub phones: Vec<VCardPhone>,
+    pub organization: Option<String>,
+    pub note: Option<String>,
+    pub photo: Option<VCardPhoto>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VCardEmail {
+    pub address: String,
+    pub types: Vec<String>,
+    pub preferred: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VCardPhone {
+    pub number: String,
+    pub types: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VCardPhoto {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // TYPE values, from TYPE=a,b, repeated TYPE params or bare 2.1 style params
+    fn types(&self) -> Vec<String> {
+        self.params.iter()
+            .filter(|(key, value)| key == "TYPE" || (value.is_empty() && key != "PREF"))
+            .flat_map(|(key, value)| {
+                let values = if value.is_empty() { key.as_str() } else { value.as_str() };
+                values.split(',').map(|v| v.trim().to_ascii_lowercase()).collect::<Vec<_>>()
+            })
+            .filter(|v| !v.is_empty() && !["pref", "internet", "voice", "quoted-printable", "base64"].contains(&v.as_str()))
+            .collect()
+    }
+
+    // 4.0 has PREF=n, 3.0 has TYPE=pref and 2.1 a bare PREF
+    fn preferred(&self) -> bool {
+        self.params.iter().any(|(key, value)| {
+            key == "PREF" || (key == "TYPE" && value.split(',').any(|v| v.trim().eq_ignore_ascii_case("pref")))
+        })
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<VCard>, String> {
+    let mut cards = Vec::new();
+    let mut current: Option<(VCard, Option<(String, String)>)> = None;
+    let mut begin_line = 0;
+
+    for (line_number, line) in unfold(text) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let property = parse_property(&line)
+            .ok_or_else(|| format!("Malformed vCard line {}: '{}'", line_number, line))?;
+
+        match property.name.as_str() {
+            "BEGIN" if property.value.eq_ignore_ascii_case("VCARD") => {
+                if current.is_some() {
+                    return Err(format!("vCard starting at line {} is missing END:VCARD", begin_line));
+                }
+                current = Some((VCard::default(), None));
+                begin_line = line_number;
+            }
+            "END" if property.value.eq_ignore_ascii_case("VCARD") => {
+                let (mut card, structured_name) = current.take()
+                    .ok_or_else(|| format!("END:VCARD without BEGIN at line {}", line_number))?;
+                if card.formatted_name.is_none() {
+                    card.formatted_name = structured_name.map(|(family, given)| {
+                        [given, family].iter().filter(|s| !s.is_empty()).cloned().collect::<Vec<_>>().join(" ")
+                    }).filter(|name| !name.is_empty());
+                }
+                cards.push(card);
+            }
+            _ => {
+                if let Some((card, structured_name)) = current.as_mut() {
+                    apply_property(card, structured_name, property);
+                }
+            }
+        }
+    }
+
+    if current.is_some() {
+        return Err(format!("vCard starting at line {} is missing END:VCARD", begin_line));
+    }
+
+    Ok(cards)
+}
+
+fn apply_property(card: &mut VCard, structured_name: &mut Option<(String, String)>, property: Property) {
+    let value = decode_value(&property);
+
+    match property.name.as_str() {
+        "UID" => card.uid = Some(value.trim().trim_start_matches("urn:uuid:").to_string()).filter(|v| !v.is_empty()),
+        "FN" => card.formatted_name = Some(unescape(&value)).filter(|v| !v.trim().is_empty()),
+        "N" => {
+            let parts = split_unescaped(&value, ';');
+            let family = parts.first().map(|s| unescape(s)).unwrap_or_default();
+            let given = parts.get(1).map(|s| unescape(s)).unwrap_or_default();
+            *structured_name = Some((family, given));
+        }
+        "EMAIL" => {
+            let address = value.trim().trim_start_matches("mailto:").to_string();
+            if !address.is_empty() {
+                card.emails.push(VCardEmail {
+                    address,
+                    types: property.types(),
+                    preferred: property.preferred(),
+                });
+            }
+        }
+        "TEL" => {
+            let number = value.trim().trim_start_matches("tel:").to_string();
+            if !number.is_empty() {
+                card.phones.push(VCardPhone { number, types: property.types() });
+            }
+        }
+        "ORG" => {
+            let units: Vec<String> = split_unescaped(&value, ';').iter()
+                .map(|unit| unescape(unit))
+                .filter(|unit| !unit.trim().is_empty())
+                .collect();
+            card.organization = Some(units.join(", ")).filter(|org| !org.is_empty());
+        }
+        "NOTE" => card.note = Some(unescape(&value)).filter(|v| !v.trim().is_empty()),
+        "PHOTO" => card.photo = parse_photo(&property),
+        _ => {}
+    }
+}
+
+// 4.0 uses data: URIs, 3.0 uses ENCODING=b with an image TYPE
+fn parse_photo(property: &Property) -> Option<VCardPhoto> {
+    let value = property.value.trim();
+
+    if let Some(uri) = value.strip_prefix("data:") {
+        let (meta, data) = uri.split_once(',')?;
+        let mime_type = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("image/jpeg");
+        if !meta.ends_with(";base64") {
+            return None;
+        }
+        let data = general_purpose::STANDARD.decode(data.trim()).ok()?;
+        return Some(VCardPhoto { mime_type: mime_type.to_string(), data });
+    }
+
+    let encoding = property.param("ENCODING").unwrap_or_default();
+    let inline = encoding.eq_ignore_ascii_case("b") || encoding.eq_ignore_ascii_case("base64")
+        || property.params.iter().any(|(key, value)| key == "BASE64" && value.is_empty());
+    if !inline {
+        // Photos by URL are not fetched
+        return None;
+    }
+
+    let data = general_purpose::STANDARD.decode(value.replace(char::is_whitespace, "")).ok()?;
+    let subtype = property.param("TYPE")
+        .or_else(|| property.params.iter().find(|(k, v)| v.is_empty() && ["JPEG", "PNG", "GIF"].contains(&k.as_str())).map(|(k, _)| k.as_str()))
+        .unwrap_or("JPEG")
+        .to_ascii_lowercase();
+    let mime_type = if subtype.contains('/') { subtype } else { format!("image/{}", subtype) };
+    Some(VCardPhoto { mime_type, data })
+}
+
+pub fn serialize(card: &VCard, version: VCardVersion) -> String {
+    let mut lines = vec!["BEGIN:VCARD".to_string()];
+    lines.push(match version {
+        VCardVersion::V3 => "VERSION:3.0".to_string(),
+        VCardVersion::V4 => "VERSION:4.0".to_string(),
+    });
+
+    if let Some(uid) = &card.uid {
+        lines.push(format!("UID:{}", uid));
+    }
+
+    let name = card.formatted_name.clone()
+        .or_else(|| card.emails.first().map(|email| email.address.clone()))
+        .unwrap_or_default();
+    lines.push(format!("FN:{}", escape(&name)));
+
+    // N is mandatory in 3.0; the last word is taken as the family name
+    let (given, family) = match name.trim().rsplit_once(' ') {
+        Some((given, family)) => (given, family),
+        None => ("", name.trim()),
+    };
+    lines.push(format!("N:{};{};;;", escape(family), escape(given)));
+
+    if let Some(organization) = &card.organization {
+        lines.push(format!("ORG:{}", escape(organization)));
+    }
+
+    for email in &card.emails {
+        lines.push(format!("EMAIL{}:{}", type_params(&email.types, email.preferred, version, Some("INTERNET")), email.address));
+    }
+    for phone in &card.phones {
+        lines.push(format!("TEL{}:{}", type_params(&phone.types, false, version, None), phone.number));
+    }
+
+    if let Some(note) = &card.note {
+        lines.push(format!("NOTE:{}", escape(note)));
+    }
+
+    if let Some(photo) = &card.photo {
+        let data = general_purpose::STANDARD.encode(&photo.data);
+        lines.push(match version {
+            VCardVersion::V4 => format!("PHOTO:data:{};base64,{}", photo.mime_type, data),
+            VCardVersion::V3 => {
+                let subtype = photo.mime_type.rsplit('/').next().unwrap_or("jpeg").to_ascii_uppercase();
+                format!("PHOTO;ENCODING=b;TYPE={}:{}", subtype, data)
+            }
+        });
+    }
+
+    lines.push("END:VCARD".to_string());
+
+    lines.iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+pub fn serialize_all(cards: &[VCard], version: VCardVersion) -> String {
+    cards.iter().map(|card| serialize(card, version)).collect()
+}
+
+fn type_params(types: &[String], preferred: bool, version: VCardVersion, v3_default: Option<&str>) -> String {
+    let mut values: Vec<String> = Vec::new();
+    if version == VCardVersion::V3 {
+        values.extend(v3_default.map(str::to_string));
+    }
+    values.extend(types.iter().cloned());
+    if preferred && version == VCardVersion::V3 {
+        values.push("PREF".to_string());
+    }
+
+    let mut params = String::new();
+    if !values.is_empty() {
+        params.push_str(";TYPE=");
+        params.push_str(&values.join(","));
+    }
+    if preferred && version == VCardVersion::V4 {
+        params.push_str(";PREF=1");
+    }
+    params
+}
+
+// Joins folded lines (and 2.1 quoted-printable soft breaks), keeping the number of
+// the line each logical line started on for error messages
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        let continues_qp = lines.last()
+            .map(|(_, line)| line.to_ascii_uppercase().contains("QUOTED-PRINTABLE") && line.ends_with('='))
+            .unwrap_or(false);
+
+        match lines.last_mut() {
+            Some((_, line)) if raw.starts_with(' ') || raw.starts_with('\t') => line.push_str(&raw[1..]),
+            Some((_, line)) if continues_qp => {
+                line.pop();
+                line.push_str(raw);
+            }
+            _ => lines.push((index + 1, raw.to_string())),
+        }
+    }
+
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts at the first colon outside a quoted parameter value
+    let mut in_quotes = false;
+    let colon = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ':' && !in_quotes
+    })?.0;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = split_quoted(head, ';').into_iter();
+
+    // Drop any group prefix, e.g. item1.EMAIL
+    let name = parts.next()?;
+    let name = name.rsplit('.').next().unwrap_or(&name).trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+
+    let params = parts
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.trim().to_ascii_uppercase(), value.trim().trim_matches('"').to_string()),
+            None => (param.trim().to_ascii_uppercase(), String::new()),
+        })
+        .collect();
+
+    Some(Property { name, params, value: value.to_string() })
+}
+
+fn decode_value(property: &Property) -> String {
+    let quoted_printable = property.param("ENCODING")
+        .map(|e| e.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        .unwrap_or(false)
+        || property.params.iter().any(|(key, value)| key == "QUOTED-PRINTABLE" && value.is_empty());
+
+    if quoted_printable {
+        decode_quoted_printable(&property.value)
+    } else {
+        property.value.clone()
+    }
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'=' && index + 3 <= bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[index + 1..index + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn split_quoted(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            _ if c == separator && !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+// Splits a structured value on separators that are not backslash-escaped
+fn split_unescaped(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in text.chars() {
+        if escaped {
+            current.push('\\');
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn fold(line: &str) -> String {
+    if line.len() <= MAX_LINE_OCTETS {
+        return line.to_string();
+    }
+
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        // Continuation lines start with a space, which counts towards their length
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
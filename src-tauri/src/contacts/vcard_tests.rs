@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::contacts::vcard::*;
+
+    const V3_CARD: &str = "BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+N:Doe;John;;;\r\n\
+ORG:Example Corp;Sales\r\n\
+item1.EMAIL;TYPE=INTERNET,WORK,PREF:john@work.example\r\n\
+EMAIL;TYPE=INTERNET,HOME:john@home.example\r\n\
+TEL;TYPE=CELL:+1 555 0100\r\n\
+NOTE:Met at the conference\\, ask about\\nthe contract\r\n\
+PHOTO;ENCODING=b;TYPE=PNG:iVBORw0K\r\n\
+\x20Ggo=\r\n\
+END:VCARD\r\n";
+
+    #[test]
+    fn test_parse_v3_card() {
+        let cards = parse(V3_CARD).unwrap();
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+
+        assert_eq!(card.formatted_name.as_deref(), Some("John Doe"));
+        assert_eq!(card.organization.as_deref(), Some("Example Corp, Sales"));
+        assert_eq!(card.emails.len(), 2);
+        assert_eq!(card.emails[0].address, "john@work.example");
+        assert!(card.emails[0].preferred);
+        assert_eq!(card.emails[0].types, vec!["work"]);
+        assert_eq!(card.phones[0].types, vec!["cell"]);
+        assert_eq!(card.note.as_deref(), Some("Met at the conference, ask about\nthe contract"));
+
+        let photo = card.photo.as_ref().unwrap();
+        assert_eq!(photo.mime_type, "image/png");
+        assert_eq!(photo.data, vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+    }
+
+    #[test]
+    fn test_parse_v4_card_with_data_uri() {
+        let text = "BEGIN:VCARD\r\nVERSION:4.0\r\nUID:urn:uuid:1234\r\nFN:Jane\r\nEMAIL;PREF=1:jane@example.com\r\nPHOTO:data:image/jpeg;base64,/9j/\r\nEND:VCARD\r\n";
+        let card = parse(text).unwrap().remove(0);
+
+        assert_eq!(card.uid.as_deref(), Some("1234"));
+        assert!(card.emails[0].preferred);
+        assert_eq!(card.photo.unwrap().data, vec![0xFF, 0xD8, 0xFF]);
+    }
+
+    #[test]
+    fn test_missing_end_is_an_error() {
+        let error = parse("BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Broken\r\n").unwrap_err();
+        assert!(error.contains("line 1"));
+    }
+
+    #[test]
+    fn test_round_trip_both_versions() {
+        let card = parse(V3_CARD).unwrap().remove(0);
+        for version in [VCardVersion::V3, VCardVersion::V4] {
+            let text = serialize(&card, version);
+            assert!(text.lines().all(|line| line.len() <= 76));
+            assert_eq!(parse(&text).unwrap().remove(0), card);
+        }
+    }
+
+    #[test]
+    fn test_long_lines_are_folded() {
+        let card = VCard {
+            formatted_name: Some("Ünïcödé ".repeat(20).trim_end().to_string()),
+            ..VCard::default()
+        };
+        let text = serialize(&card, VCardVersion::V4);
+        assert!(text.contains("\r\n "));
+        assert_eq!(parse(&text).unwrap()[0].formatted_name, card.formatted_name);
+    }
+}
@@ -1,12 +1,53 @@
+use crate::db::repo::AccountRepo;
+use crate::error::{Context, MailError, MailResult};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
-use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::aead::{Aead, KeyInit};
 use base64::{Engine as _, engine::general_purpose};
 use rand::{RngCore, thread_rng};
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
 use std::fs;
-use std::path::Path;
-use tauri::AppHandle;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
 
+const CREDENTIALS_FILE: &str = "credentials.enc";
+
+// What a stored secret is used for; an account can have one of each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretPurpose {
+    Imap,
+    Smtp,
+    OauthRefreshToken,
+    #[serde(rename = "carddav")]
+    CardDav,
+}
+
+impl SecretPurpose {
+    const ALL: [SecretPurpose; 4] = [
+        SecretPurpose::Imap,
+        SecretPurpose::Smtp,
+        SecretPurpose::OauthRefreshToken,
+        SecretPurpose::CardDav,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecretPurpose::Imap => "imap",
+            SecretPurpose::Smtp => "smtp",
+            SecretPurpose::OauthRefreshToken => "oauth_refresh_token",
+            SecretPurpose::CardDav => "carddav",
+        }
+    }
+
+    // The IMAP and SMTP logins, which the single password stored before used to serve
+    fn is_password(&self) -> bool {
+        matches!(self, SecretPurpose::Imap | SecretPurpose::Smtp)
+    }
+}
+
+// Secrets are stored under "<account id>:<purpose>". Entries written before that are keyed
+// by the bare account id and hold the one password that was used for both logins.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CredentialStore {
     pub encrypted_passwords: std::collections::HashMap<String, String>,
@@ -25,60 +66,76 @@ impl CredentialStore {
         }
     }
 
-    pub fn load_or_create(app_handle: &AppHandle) -> Result<Self, String> {
-        let app_dir = app_handle.path().app_data_dir()
-            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-        
-        let credentials_path = app_dir.join("credentials.enc");
-        
+    pub fn load_or_create(app_handle: &AppHandle) -> MailResult<Self> {
+        Self::load_or_create_at(&credentials_path(app_handle)?)
+    }
+
+    pub fn load_or_create_at(credentials_path: &Path) -> MailResult<Self> {
         if credentials_path.exists() {
-            let content = fs::read_to_string(&credentials_path)
-                .map_err(|e| format!("Failed to read credentials file: {}", e))?;
+            let content = fs::read_to_string(credentials_path)
+                .context("Failed to read credentials file")?;
             
             serde_json::from_str(&content)
-                .map_err(|e| format!("Failed to parse credentials: {}", e))
+                .context("Failed to parse credentials")
         } else {
             Ok(Self::new())
         }
     }
 
-    pub fn save(&self, app_handle: &AppHandle) -> Result<(), String> {
-        let app_dir = app_handle.path().app_data_dir()
-            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-        
-        if !app_dir.exists() {
-            fs::create_dir_all(&app_dir)
-                .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+    pub fn save(&self, app_handle: &AppHandle) -> MailResult<()> {
+        self.save_at(&credentials_path(app_handle)?)
+    }
+
+    pub fn save_at(&self, credentials_path: &Path) -> MailResult<()> {
+        if let Some(app_dir) = credentials_path.parent() {
+            if !app_dir.exists() {
+                fs::create_dir_all(app_dir)
+                    .context("Failed to create app data dir")?;
+            }
         }
 
-        let credentials_path = app_dir.join("credentials.enc");
         let content = serde_json::to_string_pretty(self)
-            .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+            .context("Failed to serialize credentials")?;
         
-        fs::write(&credentials_path, content)
-            .map_err(|e| format!("Failed to write credentials file: {}", e))?;
+        fs::write(credentials_path, content)
+            .context("Failed to write credentials file")?;
 
         // Set file permissions to be readable only by owner
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&credentials_path)
-                .map_err(|e| format!("Failed to get file metadata: {}", e))?
+            let mut perms = fs::metadata(credentials_path)
+                .context("Failed to get file metadata")?
                 .permissions();
             perms.set_mode(0o600);
-            fs::set_permissions(&credentials_path, perms)
-                .map_err(|e| format!("Failed to set file permissions: {}", e))?;
+            fs::set_permissions(credentials_path, perms)
+                .context("Failed to set file permissions")?;
         }
 
         Ok(())
     }
 
-    pub fn encrypt_password(&mut self, account_id: &str, password: &str) -> Result<(), String> {
+    pub fn set_secret(&mut self, account_id: &str, purpose: SecretPurpose, secret: &str) -> MailResult<()> {
+        self.encrypt_password(&secret_key(account_id, purpose), secret)
+    }
+
+    pub fn secret(&self, account_id: &str, purpose: SecretPurpose) -> MailResult<String> {
+        let key = secret_key(account_id, purpose);
+        if self.encrypted_passwords.contains_key(&key) {
+            self.decrypt_password(&key)
+        } else if purpose.is_password() && self.encrypted_passwords.contains_key(account_id) {
+            self.decrypt_password(account_id)
+        } else {
+            Err(MailError::NotFound(format!("No stored {} secret for account: {}", purpose.as_str(), account_id)))
+        }
+    }
+
+    fn encrypt_password(&mut self, key: &str, password: &str) -> MailResult<()> {
         let key_bytes = general_purpose::STANDARD.decode(&self.encryption_key)
-            .map_err(|e| format!("Failed to decode encryption key: {}", e))?;
+            .map_err(|e| MailError::Internal(format!("Failed to decode encryption key: {}", e)))?;
         
-        let key = Key::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
+        let cipher_key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+        let cipher = Aes256Gcm::new(cipher_key);
         
         let mut rng = thread_rng();
         let mut nonce_bytes = [0u8; 12];
@@ -86,66 +143,105 @@ impl CredentialStore {
         let nonce = Nonce::from_slice(&nonce_bytes);
         
         let ciphertext = cipher.encrypt(nonce, password.as_bytes())
-            .map_err(|e| format!("Failed to encrypt password: {}", e))?;
+            .map_err(|e| MailError::Internal(format!("Failed to encrypt password: {}", e)))?;
         
         // Combine nonce and ciphertext
         let mut encrypted_data = nonce_bytes.to_vec();
         encrypted_data.extend_from_slice(&ciphertext);
         
         let encrypted_base64 = general_purpose::STANDARD.encode(encrypted_data);
-        self.encrypted_passwords.insert(account_id.to_string(), encrypted_base64);
+        self.encrypted_passwords.insert(key.to_string(), encrypted_base64);
         
         Ok(())
     }
 
-    pub fn decrypt_password(&self, account_id: &str) -> Result<String, String> {
-        let encrypted_base64 = self.encrypted_passwords.get(account_id)
-            .ok_or_else(|| format!("No encrypted password found for account: {}", account_id))?;
+    fn decrypt_password(&self, key: &str) -> MailResult<String> {
+        let encrypted_base64 = self.encrypted_passwords.get(key)
+            .ok_or_else(|| MailError::NotFound(format!("No encrypted password found for: {}", key)))?;
         
         let encrypted_data = general_purpose::STANDARD.decode(encrypted_base64)
-            .map_err(|e| format!("Failed to decode encrypted data: {}", e))?;
+            .map_err(|e| MailError::Internal(format!("Failed to decode encrypted data: {}", e)))?;
         
         if encrypted_data.len() < 12 {
-            return Err("Invalid encrypted data format".to_string());
+            return Err(MailError::Internal("Invalid encrypted data format".to_string()));
         }
         
         let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
         let nonce = Nonce::from_slice(nonce_bytes);
         
         let key_bytes = general_purpose::STANDARD.decode(&self.encryption_key)
-            .map_err(|e| format!("Failed to decode encryption key: {}", e))?;
+            .map_err(|e| MailError::Internal(format!("Failed to decode encryption key: {}", e)))?;
         
-        let key = Key::from_slice(&key_bytes);
+        let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
         let cipher = Aes256Gcm::new(key);
         
         let decrypted_bytes = cipher.decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Failed to decrypt password: {}", e))?;
+            .map_err(|e| MailError::Internal(format!("Failed to decrypt password: {}", e)))?;
         
         String::from_utf8(decrypted_bytes)
-            .map_err(|e| format!("Failed to convert decrypted bytes to string: {}", e))
+            .map_err(|e| MailError::Internal(format!("Failed to convert decrypted bytes to string: {}", e)))
     }
 
-    pub fn remove_password(&mut self, account_id: &str) {
+    // Every secret of the account
+    pub fn remove_account(&mut self, account_id: &str) {
         self.encrypted_passwords.remove(account_id);
+        for purpose in SecretPurpose::ALL {
+            self.encrypted_passwords.remove(&secret_key(account_id, purpose));
+        }
+    }
+}
+
+pub fn credentials_path(app_handle: &AppHandle) -> MailResult<PathBuf> {
+    let app_dir = app_handle.path().app_data_dir()
+        .map_err(|e| MailError::Io(format!("Failed to get app data dir: {}", e)))?;
+    Ok(app_dir.join(CREDENTIALS_FILE))
+}
+
+fn secret_key(account_id: &str, purpose: SecretPurpose) -> String {
+    format!("{}:{}", account_id, purpose.as_str())
+}
+
+// Moves passwords left in the accounts table by the removed plaintext commands into the
+// store. The store is written before the columns are cleared, so an interrupted run only
+// repeats itself on the next start. Returns how many accounts were moved.
+pub async fn move_plaintext_passwords(pool: &Pool<Sqlite>, credentials_path: &Path) -> MailResult<usize> {
+    let accounts = AccountRepo::new(pool).plaintext_passwords().await?;
+    if accounts.is_empty() {
+        return Ok(0);
+    }
+
+    let mut store = CredentialStore::load_or_create_at(credentials_path)?;
+    for (account_id, imap_password, smtp_password) in &accounts {
+        for (purpose, password) in [(SecretPurpose::Imap, imap_password), (SecretPurpose::Smtp, smtp_password)] {
+            if let Some(password) = password.as_deref().filter(|p| !p.is_empty()) {
+                store.set_secret(account_id, purpose, password)?;
+            }
+        }
+    }
+    store.save_at(credentials_path)?;
+
+    for (account_id, _, _) in &accounts {
+        AccountRepo::new(pool).clear_plaintext_passwords(account_id).await?;
     }
+    Ok(accounts.len())
 }
 
 // Helper functions to work with credentials
-pub async fn store_credentials(app_handle: &AppHandle, account_id: &str, password: &str) -> Result<(), String> {
+pub async fn store_secret(app_handle: &AppHandle, account_id: &str, purpose: SecretPurpose, secret: &str) -> MailResult<()> {
     let mut store = CredentialStore::load_or_create(app_handle)?;
-    store.encrypt_password(account_id, password)?;
+    store.set_secret(account_id, purpose, secret)?;
     store.save(app_handle)?;
     Ok(())
 }
 
-pub async fn retrieve_credentials(app_handle: &AppHandle, account_id: &str) -> Result<String, String> {
+pub async fn retrieve_secret(app_handle: &AppHandle, account_id: &str, purpose: SecretPurpose) -> MailResult<String> {
     let store = CredentialStore::load_or_create(app_handle)?;
-    store.decrypt_password(account_id)
+    store.secret(account_id, purpose)
 }
 
-pub async fn delete_credentials(app_handle: &AppHandle, account_id: &str) -> Result<(), String> {
+pub async fn delete_credentials(app_handle: &AppHandle, account_id: &str) -> MailResult<()> {
     let mut store = CredentialStore::load_or_create(app_handle)?;
-    store.remove_password(account_id);
+    store.remove_account(account_id);
     store.save(app_handle)?;
     Ok(())
 }
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use crate::credentials::{move_plaintext_passwords, CredentialStore, SecretPurpose};
+    use crate::db::repo::AccountRepo;
+    use crate::error::MailError;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+    use tempfile::TempDir;
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email, imap_password, smtp_password) VALUES ('same', 'a@example.com', 'secret', 'secret');
+            INSERT INTO accounts (id, email, imap_password, smtp_password) VALUES ('split', 'b@example.com', 'imap-secret', 'app-password');
+            INSERT INTO accounts (id, email) VALUES ('secure', 'c@example.com');
+            "#
+        )
+        .await
+    }
+
+    #[test]
+    fn test_secrets_are_kept_per_purpose() {
+        let mut store = CredentialStore::new();
+        store.set_secret("acc", SecretPurpose::Imap, "imap-secret").unwrap();
+        store.set_secret("acc", SecretPurpose::Smtp, "app-password").unwrap();
+        assert_ne!(store.encrypted_passwords["acc:imap"], "imap-secret");
+
+        assert_eq!(store.secret("acc", SecretPurpose::Imap).unwrap(), "imap-secret");
+        assert_eq!(store.secret("acc", SecretPurpose::Smtp).unwrap(), "app-password");
+        assert!(matches!(store.secret("acc", SecretPurpose::OauthRefreshToken).unwrap_err(), MailError::NotFound(_)));
+
+        // Updating one leaves the other alone
+        store.set_secret("acc", SecretPurpose::Smtp, "new-app-password").unwrap();
+        assert_eq!(store.secret("acc", SecretPurpose::Imap).unwrap(), "imap-secret");
+        assert_eq!(store.secret("acc", SecretPurpose::Smtp).unwrap(), "new-app-password");
+
+        store.set_secret("other", SecretPurpose::Imap, "x").unwrap();
+        store.remove_account("acc");
+        let keys: Vec<&String> = store.encrypted_passwords.keys().collect();
+        assert_eq!(keys, vec!["other:imap"]);
+    }
+
+    #[test]
+    fn test_entries_without_purpose_serve_both_logins() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("credentials.enc");
+        let mut store = CredentialStore::new();
+        store.set_secret("acc", SecretPurpose::Imap, "secret").unwrap();
+        // As written before secrets were kept per purpose
+        let entry = store.encrypted_passwords.remove("acc:imap").unwrap();
+        store.encrypted_passwords.insert("acc".to_string(), entry);
+        store.save_at(&path).unwrap();
+
+        let mut store = CredentialStore::load_or_create_at(&path).unwrap();
+        assert_eq!(store.secret("acc", SecretPurpose::Imap).unwrap(), "secret");
+        assert_eq!(store.secret("acc", SecretPurpose::Smtp).unwrap(), "secret");
+        // The old password is no token and no address book login
+        let error = store.secret("acc", SecretPurpose::OauthRefreshToken).unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+        assert!(matches!(store.secret("acc", SecretPurpose::CardDav).unwrap_err(), MailError::NotFound(_)));
+
+        store.set_secret("acc", SecretPurpose::Smtp, "app-password").unwrap();
+        assert_eq!(store.secret("acc", SecretPurpose::Imap).unwrap(), "secret");
+        assert_eq!(store.secret("acc", SecretPurpose::Smtp).unwrap(), "app-password");
+
+        store.remove_account("acc");
+        assert!(store.encrypted_passwords.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_move_plaintext_passwords() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("credentials.enc");
+
+        assert_eq!(move_plaintext_passwords(&pool, &path).await.unwrap(), 2);
+
+        let store = CredentialStore::load_or_create_at(&path).unwrap();
+        assert_eq!(store.secret("same", SecretPurpose::Imap).unwrap(), "secret");
+        assert_eq!(store.secret("same", SecretPurpose::Smtp).unwrap(), "secret");
+        assert_eq!(store.secret("split", SecretPurpose::Imap).unwrap(), "imap-secret");
+        assert_eq!(store.secret("split", SecretPurpose::Smtp).unwrap(), "app-password");
+        assert!(store.secret("secure", SecretPurpose::Imap).is_err());
+
+        assert!(AccountRepo::new(&pool).plaintext_passwords().await.unwrap().is_empty());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret") && !content.contains("app-password"));
+
+        // Nothing left to move, so the store is not touched again
+        assert_eq!(move_plaintext_passwords(&pool, &path).await.unwrap(), 0);
+    }
+}
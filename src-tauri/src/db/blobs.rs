@@ -0,0 +1,258 @@
+use crate::error::{Context, MailError, MailResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Row, Sqlite};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Blobs nobody references are only collected after this long, so a sync that has written
+// a blob but not yet the attachment row pointing at it doesn't lose it
+pub const GC_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+// Attachment contents on disk, one file per distinct content named by its SHA-256, so the
+// same file received in many emails is stored once. Which rows use a blob is tracked by
+// the `blobs` table in the database.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GcReport {
+    pub removed: u64,
+    pub freed_bytes: u64,
+}
+
+pub fn hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+// "ab/abcdef…": blobs are spread over 256 directories by their first byte
+pub fn relative_path(hash: &str) -> MailResult<PathBuf> {
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return Err(MailError::validation(format!("Invalid blob hash '{}'", hash)));
+    }
+    Ok(Path::new(&hash[..2]).join(hash))
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn path(&self, hash: &str) -> MailResult<PathBuf> {
+        Ok(self.root.join(relative_path(hash)?))
+    }
+
+    // Writes the content unless a blob with the same hash is already there
+    fn write(&self, hash: &str, content: &[u8]) -> MailResult<()> {
+        let path = self.path(hash)?;
+        if path.exists() {
+            return Ok(());
+        }
+
+        let dir = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir)
+            .context("Failed to create attachment directory")?;
+        // Written next to its final name and renamed, so a crash never leaves a partial blob
+        let temp = dir.join(format!(".{}.{}", hash, uuid::Uuid::new_v4()));
+        let written = File::create(&temp)
+            .and_then(|mut file| file.write_all(content).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp, &path));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(MailError::from(e).context("Failed to write attachment"));
+        }
+        Ok(())
+    }
+
+    pub fn open(&self, hash: &str) -> MailResult<File> {
+        File::open(self.path(hash)?).context("Failed to open attachment")
+    }
+
+    pub fn read(&self, hash: &str) -> MailResult<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(hash)?
+            .read_to_end(&mut content)
+            .context("Failed to read attachment")?;
+        Ok(content)
+    }
+
+    // At most `limit` bytes from the start of the blob
+    pub fn read_prefix(&self, hash: &str, limit: u64) -> MailResult<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(hash)?
+            .take(limit)
+            .read_to_end(&mut content)
+            .context("Failed to read attachment")?;
+        Ok(content)
+    }
+
+    // Streams the blob to `destination` without holding it in memory
+    pub fn copy_to(&self, hash: &str, destination: &Path) -> MailResult<u64> {
+        let mut source = self.open(hash)?;
+        let mut target = File::create(destination)
+            .context("Failed to create file")?;
+        io::copy(&mut source, &mut target).context("Failed to write file")
+    }
+
+    pub fn remove(&self, hash: &str) -> MailResult<()> {
+        match fs::remove_file(self.path(hash)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(MailError::from(e).context("Failed to remove attachment")),
+            _ => Ok(()),
+        }
+    }
+
+    // Every file in the store: its name, path, size and modification time
+    fn entries(&self) -> MailResult<Vec<(String, PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        let shards = match fs::read_dir(&self.root) {
+            Ok(shards) => shards,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(MailError::from(e).context("Failed to list attachments")),
+        };
+        for shard in shards.flatten() {
+            let Ok(files) = fs::read_dir(shard.path()) else { continue };
+            for file in files.flatten() {
+                let name = file.file_name().to_string_lossy().to_string();
+                let Ok(metadata) = file.metadata() else { continue };
+                // Leftover temporary files are named ".<hash>.<uuid>" and collected like blobs
+                if relative_path(&name).is_ok() || name.starts_with('.') {
+                    entries.push((name, file.path(), metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Puts the content in the store and registers the blob, returning its hash. The reference
+// is counted once an attachment row with that content_hash is inserted.
+//
+// The blob is registered before its file is written: a collection already removing it
+// holds the write lock, so the registration waits for it and the file is written afresh,
+// and one that comes later sees the blob as just touched and leaves it alone.
+pub async fn store(pool: &Pool<Sqlite>, blobs: &BlobStore, content: &[u8]) -> MailResult<String> {
+    let hash = hash(content);
+    sqlx::query(
+        r#"
+        INSERT INTO blobs (hash, size) VALUES (?, ?)
+        ON CONFLICT(hash) DO UPDATE SET touched_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(&hash)
+    .bind(content.len() as i64)
+    .execute(pool)
+    .await
+    .context("Failed to register attachment")?;
+    blobs.write(&hash, content)?;
+    Ok(hash)
+}
+
+// Recomputes every reference count from the attachments table. The triggers keep the counts
+// current; this repairs them after rows were removed without firing those.
+pub async fn recount_references(pool: &Pool<Sqlite>) -> MailResult<()> {
+    sqlx::query("UPDATE blobs SET ref_count = (SELECT COUNT(*) FROM attachments WHERE content_hash = blobs.hash)")
+        .execute(pool)
+        .await
+        .context("Failed to count attachment references")?;
+    Ok(())
+}
+
+// Moves contents still held in attachments.content into the store
+pub async fn move_inline_contents(pool: &Pool<Sqlite>, blobs: &BlobStore) -> MailResult<u64> {
+    let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM attachments WHERE content IS NOT NULL")
+        .fetch_all(pool)
+        .await
+        .context("Failed to list attachments")?;
+
+    for id in &ids {
+        let content: Vec<u8> = sqlx::query_scalar("SELECT content FROM attachments WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to read attachment")?;
+        let hash = store(pool, blobs, &content).await?;
+        sqlx::query("UPDATE attachments SET content_hash = ?, path = ?, size = ?, content = NULL WHERE id = ?")
+            .bind(&hash)
+            .bind(relative_path(&hash)?.to_string_lossy().to_string())
+            .bind(content.len() as i64)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update attachment")?;
+    }
+
+    if !ids.is_empty() {
+        recount_references(pool).await?;
+    }
+    Ok(ids.len() as u64)
+}
+
+// Deletes the blob if it is still unused and untouched for `grace`, checked again now in
+// case it was reused since it was listed. The file goes while the row's deletion holds the
+// write lock, so no store() can register the blob in between and find the file gone.
+pub async fn remove_unused(pool: &Pool<Sqlite>, blobs: &BlobStore, hash: &str, grace: Duration) -> MailResult<bool> {
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start transaction")?;
+    let deleted = sqlx::query("DELETE FROM blobs WHERE hash = ? AND ref_count <= 0 AND touched_at <= datetime('now', ?)")
+        .bind(hash)
+        .bind(format!("-{} seconds", grace.as_secs()))
+        .execute(&mut *tx)
+        .await
+        .context("Failed to remove attachment")?;
+    if deleted.rows_affected() == 0 {
+        return Ok(false);
+    }
+    // Dropping the transaction on failure keeps the row, so the blob isn't left half removed
+    blobs.remove(hash)?;
+    tx.commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(true)
+}
+
+// Deletes blobs no attachment refers to any more, and files on disk the database doesn't
+// know about, once they are older than `grace`
+pub async fn collect_garbage(pool: &Pool<Sqlite>, blobs: &BlobStore, grace: Duration) -> MailResult<GcReport> {
+    recount_references(pool).await?;
+    let mut report = GcReport::default();
+
+    let unused = sqlx::query(
+        "SELECT hash, size FROM blobs WHERE ref_count <= 0 AND touched_at <= datetime('now', ?)"
+    )
+    .bind(format!("-{} seconds", grace.as_secs()))
+    .fetch_all(pool)
+    .await
+    .context("Failed to list unused attachments")?;
+
+    for row in unused {
+        let hash: String = row.get("hash");
+        if remove_unused(pool, blobs, &hash, grace).await? {
+            report.removed += 1;
+            report.freed_bytes += row.get::<i64, _>("size") as u64;
+        }
+    }
+
+    let known: std::collections::HashSet<String> = sqlx::query_scalar("SELECT hash FROM blobs")
+        .fetch_all(pool)
+        .await
+        .context("Failed to list attachments")?
+        .into_iter()
+        .collect();
+    let cutoff = SystemTime::now().checked_sub(grace).unwrap_or(SystemTime::UNIX_EPOCH);
+    for (name, path, size, modified) in blobs.entries()? {
+        if known.contains(&name) || modified > cutoff {
+            continue;
+        }
+        if fs::remove_file(&path).is_ok() {
+            report.removed += 1;
+            report.freed_bytes += size;
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,195 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::blobs::*;
+    use crate::error::MailError;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-INBOX', 'acc', 'INBOX');
+            INSERT INTO emails (id, account_id, folder_id, uid) VALUES ('m1', 'acc', 'acc-INBOX', 1), ('m2', 'acc', 'acc-INBOX', 2);
+            "#
+        )
+        .await
+    }
+
+    async fn attach(pool: &Pool<Sqlite>, id: &str, email_id: &str, content_hash: &str) {
+        sqlx::query("INSERT INTO attachments (id, email_id, filename, content_hash) VALUES (?, ?, 'file', ?)")
+            .bind(id)
+            .bind(email_id)
+            .bind(content_hash)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn ref_count(pool: &Pool<Sqlite>, hash: &str) -> Option<i64> {
+        sqlx::query_scalar("SELECT ref_count FROM blobs WHERE hash = ?")
+            .bind(hash)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hash_is_sha256_hex() {
+        assert_eq!(hash(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_relative_path_rejects_anything_but_a_hash() {
+        let abc = hash(b"abc");
+        assert_eq!(relative_path(&abc).unwrap(), std::path::Path::new("ba").join(&abc));
+        assert!(relative_path("../../etc/passwd").is_err());
+        assert!(relative_path(&abc.to_uppercase()).is_err());
+        assert!(relative_path(&abc[..63]).is_err());
+    }
+
+    #[test]
+    fn test_missing_blob_is_not_found() {
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        assert!(matches!(blobs.read(&hash(b"abc")).unwrap_err(), MailError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_store_writes_each_content_once() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        let first = store(&pool, &blobs, b"hello").await.unwrap();
+        let second = store(&pool, &blobs, b"hello").await.unwrap();
+        assert_eq!(first, second);
+        assert!(blobs.path(&first).unwrap().starts_with(dir.path()));
+        assert_eq!(blobs.read(&first).unwrap(), b"hello");
+        assert_eq!(blobs.read_prefix(&first, 2).unwrap(), b"he");
+
+        let files = std::fs::read_dir(dir.path().join(&first[..2])).unwrap().count();
+        assert_eq!(files, 1);
+    }
+
+    #[tokio::test]
+    async fn test_copy_to_streams_to_file() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path().join("store"));
+        let content = vec![7u8; 200_000];
+
+        let hash = store(&pool, &blobs, &content).await.unwrap();
+        let target = dir.path().join("saved.bin");
+        assert_eq!(blobs.copy_to(&hash, &target).unwrap(), 200_000);
+        assert_eq!(std::fs::read(&target).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_references_follow_attachment_rows() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        let hash = store(&pool, &blobs, b"shared").await.unwrap();
+        assert_eq!(ref_count(&pool, &hash).await, Some(0));
+
+        attach(&pool, "a1", "m1", &hash).await;
+        attach(&pool, "a2", "m2", &hash).await;
+        assert_eq!(ref_count(&pool, &hash).await, Some(2));
+
+        sqlx::query("DELETE FROM emails WHERE id = 'm1'").execute(&pool).await.unwrap();
+        assert_eq!(ref_count(&pool, &hash).await, Some(1));
+
+        let other = store(&pool, &blobs, b"other").await.unwrap();
+        sqlx::query("UPDATE attachments SET content_hash = ? WHERE id = 'a2'")
+            .bind(&other)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ref_count(&pool, &hash).await, Some(0));
+        assert_eq!(ref_count(&pool, &other).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collection() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        let used = store(&pool, &blobs, b"used").await.unwrap();
+        attach(&pool, "a1", "m1", &used).await;
+        let unused = store(&pool, &blobs, b"unused").await.unwrap();
+        // A file the database never heard of
+        let stray = hash(b"stray");
+        let stray_path = blobs.path(&stray).unwrap();
+        std::fs::create_dir_all(stray_path.parent().unwrap()).unwrap();
+        std::fs::write(&stray_path, b"stray").unwrap();
+
+        // Nothing is old enough yet
+        let report = collect_garbage(&pool, &blobs, Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.removed, 0);
+
+        let report = collect_garbage(&pool, &blobs, Duration::ZERO).await.unwrap();
+        assert_eq!(report.removed, 2);
+        assert_eq!(report.freed_bytes, 6 + 5);
+        assert!(blobs.path(&used).unwrap().exists());
+        assert!(!blobs.path(&unused).unwrap().exists());
+        assert!(!blobs.path(&stray).unwrap().exists());
+        assert_eq!(ref_count(&pool, &unused).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_blob_reused_after_listing_is_kept() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let old = "UPDATE blobs SET touched_at = datetime('now', '-2 hours')";
+
+        let hash = store(&pool, &blobs, b"report").await.unwrap();
+        sqlx::query(old).execute(&pool).await.unwrap();
+        // Stored again, as a sync receiving the same file would, after the blob was listed
+        assert_eq!(store(&pool, &blobs, b"report").await.unwrap(), hash);
+        assert!(!remove_unused(&pool, &blobs, &hash, Duration::from_secs(3600)).await.unwrap());
+        assert!(blobs.path(&hash).unwrap().exists());
+
+        sqlx::query(old).execute(&pool).await.unwrap();
+        attach(&pool, "a1", "m1", &hash).await;
+        assert!(!remove_unused(&pool, &blobs, &hash, Duration::from_secs(3600)).await.unwrap());
+
+        sqlx::query("DELETE FROM attachments").execute(&pool).await.unwrap();
+        assert!(remove_unused(&pool, &blobs, &hash, Duration::from_secs(3600)).await.unwrap());
+        assert!(!blobs.path(&hash).unwrap().exists());
+        assert_eq!(ref_count(&pool, &hash).await, None);
+
+        // Registering it anew brings the file back
+        store(&pool, &blobs, b"report").await.unwrap();
+        assert_eq!(blobs.read(&hash).unwrap(), b"report");
+    }
+
+    #[tokio::test]
+    async fn test_inline_contents_move_to_store() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        sqlx::query("INSERT INTO attachments (id, email_id, filename, content) VALUES ('a1', 'm1', 'a.txt', X'6869'), ('a2', 'm2', 'b.txt', X'6869')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(move_inline_contents(&pool, &blobs).await.unwrap(), 2);
+        assert_eq!(move_inline_contents(&pool, &blobs).await.unwrap(), 0);
+
+        let hash = hash(b"hi");
+        let rows: Vec<(Option<String>, Option<String>, i64)> = sqlx::query_as("SELECT content_hash, path, size FROM attachments WHERE content IS NULL")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.0.as_deref() == Some(hash.as_str()) && row.2 == 2));
+        assert_eq!(ref_count(&pool, &hash).await, Some(2));
+        assert_eq!(blobs.read(&hash).unwrap(), b"hi");
+    }
+}
@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::{connect, migrations};
+    use sqlx::{Pool, Row, Sqlite};
+    use tempfile::TempDir;
+
+    async fn database(dir: &TempDir) -> Pool<Sqlite> {
+        let pool = connect(&dir.path().join("mail.db"), 2).await.unwrap();
+        migrations::migrate(&pool, None).await.unwrap();
+        pool
+    }
+
+    async fn count(pool: &Pool<Sqlite>, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_account(pool: &Pool<Sqlite>) {
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name, role) VALUES ('acc-INBOX', 'acc', 'INBOX', 'inbox');
+            INSERT INTO emails (id, account_id, folder_id, uid, subject) VALUES ('m1', 'acc', 'acc-INBOX', 1, 'Invoice');
+            INSERT INTO blobs (hash, size) VALUES ('hash', 2);
+            INSERT INTO attachments (id, email_id, filename, content_hash) VALUES ('a1', 'm1', 'invoice.pdf', 'hash');
+            INSERT INTO signatures (id, account_id, name) VALUES ('sig', 'acc', 'Work');
+            INSERT INTO identities (id, account_id, email, signature_id) VALUES ('id1', 'acc', 'me@example.com', 'sig');
+            INSERT INTO outbox (id, account_id, message, send_at) VALUES ('o1', 'acc', '{}', '2026-01-01T00:00:00Z');
+            INSERT INTO sent_messages (message_id, account_id) VALUES ('<sent@example.com>', 'acc');
+            INSERT INTO delivery_reports (id, account_id, email_id, kind, recipient) VALUES ('r1', 'acc', 'm1', 'dsn', 'you@example.com');
+            "#
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connection_pragmas() {
+        let dir = TempDir::new().unwrap();
+        let pool = database(&dir).await;
+
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys").fetch_one(&pool).await.unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&pool).await.unwrap();
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous").fetch_one(&pool).await.unwrap();
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout").fetch_one(&pool).await.unwrap();
+
+        assert_eq!(foreign_keys, 1);
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(synchronous, 1);
+        assert_eq!(busy_timeout, 5000);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_account_cascades() {
+        let dir = TempDir::new().unwrap();
+        let pool = database(&dir).await;
+        insert_account(&pool).await;
+
+        sqlx::query("DELETE FROM accounts WHERE id = 'acc'").execute(&pool).await.unwrap();
+
+        for table in ["folders", "emails", "attachments", "signatures", "identities", "outbox", "sent_messages", "delivery_reports"] {
+            assert_eq!(count(&pool, table).await, 0, "{} left behind", table);
+        }
+        // The attachment's blob is released for garbage collection, and the message leaves the search index
+        let ref_count: i64 = sqlx::query_scalar("SELECT ref_count FROM blobs WHERE hash = 'hash'").fetch_one(&pool).await.unwrap();
+        assert_eq!(ref_count, 0);
+        assert_eq!(count(&pool, "emails_fts").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_signature_keeps_identity() {
+        let dir = TempDir::new().unwrap();
+        let pool = database(&dir).await;
+        insert_account(&pool).await;
+
+        sqlx::query("DELETE FROM signatures WHERE id = 'sig'").execute(&pool).await.unwrap();
+
+        let signature: Option<String> = sqlx::query_scalar("SELECT signature_id FROM identities WHERE id = 'id1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(signature, None);
+    }
+
+    #[tokio::test]
+    async fn test_rows_of_unknown_parents_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let pool = database(&dir).await;
+
+        let orphan = sqlx::query("INSERT INTO folders (id, account_id, name) VALUES ('x-INBOX', 'missing', 'INBOX')")
+            .execute(&pool)
+            .await;
+        assert!(orphan.is_err());
+    }
+
+    // Every table that points at another gets cleaned up with it, and the column it points
+    // through is indexed so the cleanup doesn't scan the whole table. New tables included.
+    #[tokio::test]
+    async fn test_every_foreign_key_has_delete_action_and_index() {
+        let dir = TempDir::new().unwrap();
+        let pool = database(&dir).await;
+
+        let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        for table in tables {
+            let keys = sqlx::query(&format!("SELECT \"from\", \"table\", on_delete FROM pragma_foreign_key_list('{}')", table))
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+            for key in keys {
+                let column: String = key.get("from");
+                let on_delete: String = key.get("on_delete");
+                assert!(
+                    on_delete == "CASCADE" || on_delete == "SET NULL",
+                    "{}.{} -> {} has ON DELETE {}", table, column, key.get::<String, _>("table"), on_delete
+                );
+
+                let indexed: i64 = sqlx::query_scalar(&format!(
+                    "SELECT COUNT(*) FROM pragma_index_list('{}') AS l, pragma_index_info(l.name) AS i WHERE i.seqno = 0 AND i.name = ?",
+                    table
+                ))
+                .bind(&column)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                assert!(indexed > 0, "{}.{} has no index", table, column);
+            }
+        }
+    }
+}
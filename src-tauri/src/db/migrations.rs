@@ -0,0 +1,238 @@
+use crate::error::{Context, MailError, MailResult};
+use chrono::Utc;
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::path::{Path, PathBuf};
+
+// Versioned schema changes, applied in order at startup. Each one runs in its own
+// transaction and is recorded in `schema_version`, so it is applied exactly once.
+//
+// A change to the schema is a new migration at the end of this list, never an edit to an
+// existing one; schema.sql is then updated to match (migrations_tests checks that it does).
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    // The schema before versioning. Databases from that time already have these tables,
+    // which the IF NOT EXISTS leaves alone, so they simply continue from version 1.
+    Migration {
+        version: 1,
+        description: "baseline",
+        sql: include_str!("migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "threads and message lists",
+        sql: include_str!("migrations/0002_threads_and_message_lists.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "attachment store",
+        sql: include_str!("migrations/0003_attachment_store.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "search index",
+        sql: include_str!("migrations/0004_search_index.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "compose and contacts",
+        sql: include_str!("migrations/0005_compose_and_contacts.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "foreign key indexes",
+        sql: include_str!("migrations/0006_foreign_key_indexes.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "default identities",
+        sql: include_str!("migrations/0007_default_identities.sql"),
+    },
+];
+
+// Columns that builds from before versioning added to tables they had already created, by
+// editing schema.sql. A database from such a build can have the table without them, which
+// the CREATE TABLE IF NOT EXISTS in the migrations leaves alone, so they're added first.
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("identities", "signature_id", "TEXT REFERENCES signatures(id) ON DELETE SET NULL"),
+    ("identities", "reply_placement", "TEXT NOT NULL DEFAULT 'above_quote'"),
+    ("identities", "forward_placement", "TEXT NOT NULL DEFAULT 'above_quote'"),
+    ("contacts", "uid", "TEXT"),
+    ("contacts", "organization", "TEXT"),
+    ("contacts", "photo", "BLOB"),
+    ("contacts", "photo_mime_type", "TEXT"),
+];
+
+pub fn latest_version(migrations: &[Migration]) -> i64 {
+    migrations.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+async fn ensure_version_table(pool: &Pool<Sqlite>) -> MailResult<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create schema_version table")?;
+    Ok(())
+}
+
+// 0 for a database no migration has run on yet
+pub async fn current_version(pool: &Pool<Sqlite>) -> MailResult<i64> {
+    ensure_version_table(pool).await?;
+    sqlx::query_scalar::<_, Option<i64>>("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await
+        .map(Option::unwrap_or_default)
+        .context("Failed to read schema version")
+}
+
+// Brings the database up to the latest version. When there is something to migrate in a
+// database that already holds data, a copy is first written to `backup_dir`.
+pub async fn migrate(pool: &Pool<Sqlite>, backup_dir: Option<&Path>) -> MailResult<i64> {
+    migrate_with(pool, MIGRATIONS, backup_dir).await
+}
+
+pub async fn migrate_with(pool: &Pool<Sqlite>, migrations: &[Migration], backup_dir: Option<&Path>) -> MailResult<i64> {
+    let mut version = current_version(pool).await?;
+    let latest = latest_version(migrations);
+    if version > latest {
+        return Err(MailError::Internal(format!(
+            "The database is at schema version {}, newer than this version of the app supports ({})",
+            version, latest
+        )));
+    }
+
+    let pending: Vec<&Migration> = migrations.iter().filter(|migration| migration.version > version).collect();
+    if pending.is_empty() {
+        return Ok(version);
+    }
+
+    if let Some(dir) = backup_dir {
+        if has_data(pool).await? {
+            backup(pool, dir, version).await?;
+        }
+    }
+
+    let before_versioning = version == 0;
+    if before_versioning {
+        add_legacy_columns(pool).await?;
+    }
+
+    for migration in pending {
+        let mut tx = pool.begin()
+            .await
+            .context(&format!("Failed to start migration {}", migration.version))?;
+        let sql = if before_versioning {
+            without_existing_columns(&mut tx, migration.sql).await?
+        } else {
+            migration.sql.to_string()
+        };
+        sqlx::query(&sql)
+            .execute(&mut *tx)
+            .await
+            .context(&format!("Migration {} ({}) failed", migration.version, migration.description))?;
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .execute(&mut *tx)
+            .await
+            .context(&format!("Failed to record migration {}", migration.version))?;
+        tx.commit()
+            .await
+            .context(&format!("Failed to commit migration {}", migration.version))?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+async fn add_legacy_columns(pool: &Pool<Sqlite>) -> MailResult<()> {
+    let mut tx = pool.begin()
+        .await
+        .context("Failed to start updating old tables")?;
+    for (table, column, definition) in LEGACY_COLUMNS {
+        let (has_table, has_column): (bool, bool) = sqlx::query_as(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1),
+                   EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)
+            "#
+        )
+        .bind(*table)
+        .bind(*column)
+        .fetch_one(&mut *tx)
+        .await
+        .context(&format!("Failed to inspect table {}", table))?;
+        if has_table && !has_column {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&mut *tx)
+                .await
+                .context(&format!("Failed to add {}.{}", table, column))?;
+            // schema.sql declares uid UNIQUE, which ALTER TABLE can't add
+            if (*table, *column) == ("contacts", "uid") {
+                sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_contacts_uid ON contacts(uid)")
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to index contacts.uid")?;
+            }
+        }
+    }
+    tx.commit()
+        .await
+        .context("Failed to commit updating old tables")
+}
+
+// Builds before versioning also created some of the columns that the migrations add, so
+// for such a database an ADD COLUMN of a column that is already there is left out
+async fn without_existing_columns(conn: &mut SqliteConnection, sql: &str) -> MailResult<String> {
+    let mut kept = Vec::new();
+    for line in sql.lines() {
+        let words: Vec<&str> = line.trim().trim_end_matches(';').split_whitespace().collect();
+        if let ["ALTER", "TABLE", table, "ADD", "COLUMN", column, ..] = words.as_slice() {
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pragma_table_info(?) WHERE name = ?)")
+                .bind(*table)
+                .bind(*column)
+                .fetch_one(&mut *conn)
+                .await
+                .context(&format!("Failed to inspect table {}", table))?;
+            if exists {
+                continue;
+            }
+        }
+        kept.push(line);
+    }
+    Ok(kept.join("\n"))
+}
+
+async fn has_data(pool: &Pool<Sqlite>) -> MailResult<bool> {
+    let tables: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT IN ('schema_version') AND name NOT LIKE 'sqlite_%'"
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to inspect database")?;
+    Ok(tables > 0)
+}
+
+// A consistent copy of the whole database, e.g. mail-v3-20261018T120000Z.db
+pub async fn backup(pool: &Pool<Sqlite>, dir: &Path, version: i64) -> MailResult<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .context("Failed to create backup directory")?;
+    let path = dir.join(format!("mail-v{}-{}.db", version, Utc::now().format("%Y%m%dT%H%M%SZ")));
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .context("Failed to back up database before migrating")?;
+    Ok(path)
+}
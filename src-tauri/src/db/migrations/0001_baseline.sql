@@ -0,0 +1,59 @@
+CREATE TABLE IF NOT EXISTS accounts (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL,
+    name TEXT,
+    provider TEXT,
+    imap_host TEXT,
+    imap_port INTEGER,
+    imap_username TEXT,
+    imap_password TEXT,
+    smtp_host TEXT,
+    smtp_port INTEGER,
+    smtp_username TEXT,
+    smtp_password TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS folders (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    delimiter TEXT,
+    UNIQUE(account_id, name),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS emails (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    folder_id TEXT NOT NULL,
+    uid INTEGER NOT NULL,
+    message_id TEXT,
+    subject TEXT,
+    from_addr TEXT,
+    to_addr TEXT,
+    cc_addr TEXT,
+    bcc_addr TEXT,
+    date DATETIME,
+    body_text TEXT,
+    body_html TEXT,
+    is_read BOOLEAN DEFAULT 0,
+    is_starred BOOLEAN DEFAULT 0,
+    has_attachments BOOLEAN DEFAULT 0,
+    preview TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(account_id, folder_id, uid),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY(folder_id) REFERENCES folders(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS attachments (
+    id TEXT PRIMARY KEY,
+    email_id TEXT NOT NULL,
+    filename TEXT,
+    mime_type TEXT,
+    size INTEGER,
+    content BLOB,
+    path TEXT,
+    FOREIGN KEY(email_id) REFERENCES emails(id) ON DELETE CASCADE
+);
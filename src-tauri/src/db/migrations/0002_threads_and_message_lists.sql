@@ -0,0 +1,24 @@
+-- Threading, sizes, folder roles and the indexes behind message lists
+
+ALTER TABLE folders ADD COLUMN role TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_folders_role ON folders(role, account_id);
+
+ALTER TABLE emails ADD COLUMN in_reply_to TEXT;
+ALTER TABLE emails ADD COLUMN reference_ids TEXT;
+ALTER TABLE emails ADD COLUMN base_subject TEXT;
+ALTER TABLE emails ADD COLUMN thread_id TEXT;
+ALTER TABLE emails ADD COLUMN size INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_emails_thread ON emails(account_id, thread_id);
+CREATE INDEX IF NOT EXISTS idx_emails_message_id ON emails(account_id, message_id);
+CREATE INDEX IF NOT EXISTS idx_emails_in_reply_to ON emails(account_id, in_reply_to);
+CREATE INDEX IF NOT EXISTS idx_emails_base_subject ON emails(account_id, base_subject);
+
+-- Keyset pagination of message lists (commands::messages). The sort expressions must stay
+-- identical to the ones in the queries, or SQLite won't use these indexes.
+CREATE INDEX IF NOT EXISTS idx_emails_folder_date ON emails(account_id, folder_id, COALESCE(date, ''), id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_sender ON emails(account_id, folder_id, COALESCE(from_addr, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_subject ON emails(account_id, folder_id, COALESCE(subject, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_size ON emails(account_id, folder_id, COALESCE(size, 0), id);
+CREATE INDEX IF NOT EXISTS idx_emails_date ON emails(COALESCE(date, ''), id);
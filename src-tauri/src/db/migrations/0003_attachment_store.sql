@@ -0,0 +1,32 @@
+-- Attachments of synced mail, with their contents in the blob store
+
+ALTER TABLE attachments ADD COLUMN section TEXT;
+ALTER TABLE attachments ADD COLUMN content_id TEXT;
+ALTER TABLE attachments ADD COLUMN disposition TEXT;
+ALTER TABLE attachments ADD COLUMN content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_attachments_email ON attachments(email_id);
+CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(content_hash);
+
+-- Attachment contents live in the blob store on disk (db::blobs), one file per content hash.
+-- ref_count is the number of attachment rows using the blob; GC removes blobs left at zero.
+CREATE TABLE IF NOT EXISTS blobs (
+    hash TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0,
+    touched_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_insert AFTER INSERT ON attachments WHEN new.content_hash IS NOT NULL BEGIN
+    UPDATE blobs SET ref_count = ref_count + 1 WHERE hash = new.content_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_update AFTER UPDATE OF content_hash ON attachments
+WHEN old.content_hash IS NOT new.content_hash BEGIN
+    UPDATE blobs SET ref_count = ref_count - 1 WHERE hash = old.content_hash;
+    UPDATE blobs SET ref_count = ref_count + 1 WHERE hash = new.content_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_delete AFTER DELETE ON attachments WHEN old.content_hash IS NOT NULL BEGIN
+    UPDATE blobs SET ref_count = ref_count - 1 WHERE hash = old.content_hash;
+END;
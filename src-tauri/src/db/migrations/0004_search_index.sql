@@ -0,0 +1,65 @@
+-- Full-text search
+
+-- Full-text index over emails, keyed by emails.rowid. The trigram tokenizer matches any
+-- substring of three or more characters, which also works for Chinese and Japanese text
+-- that has no spaces between words.
+CREATE VIRTUAL TABLE IF NOT EXISTS emails_fts USING fts5(
+    subject,
+    sender,
+    recipients,
+    body,
+    attachments,
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_insert AFTER INSERT ON emails BEGIN
+    INSERT INTO emails_fts (rowid, subject, sender, recipients, body, attachments)
+    VALUES (
+        new.rowid,
+        new.subject,
+        new.from_addr,
+        COALESCE(new.to_addr, '') || ' ' || COALESCE(new.cc_addr, '') || ' ' || COALESCE(new.bcc_addr, ''),
+        new.body_text,
+        (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.id)
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_update AFTER UPDATE OF subject, from_addr, to_addr, cc_addr, bcc_addr, body_text ON emails BEGIN
+    UPDATE emails_fts SET
+        subject = new.subject,
+        sender = new.from_addr,
+        recipients = COALESCE(new.to_addr, '') || ' ' || COALESCE(new.cc_addr, '') || ' ' || COALESCE(new.bcc_addr, ''),
+        body = new.body_text
+    WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_delete AFTER DELETE ON emails BEGIN
+    DELETE FROM emails_fts WHERE rowid = old.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_insert AFTER INSERT ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = new.email_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_update AFTER UPDATE OF filename ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = new.email_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_delete AFTER DELETE ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = old.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = old.email_id);
+END;
+
+-- Messages synced before this migration
+INSERT INTO emails_fts (rowid, subject, sender, recipients, body, attachments)
+SELECT e.rowid, e.subject, e.from_addr,
+       COALESCE(e.to_addr, '') || ' ' || COALESCE(e.cc_addr, '') || ' ' || COALESCE(e.bcc_addr, ''),
+       e.body_text,
+       (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = e.id)
+FROM emails e
+WHERE e.rowid NOT IN (SELECT rowid FROM emails_fts);
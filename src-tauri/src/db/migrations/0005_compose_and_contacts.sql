@@ -0,0 +1,166 @@
+-- Identities, signatures, scheduled sending, settings, saved searches, delivery status
+-- and contacts
+
+CREATE TABLE IF NOT EXISTS identities (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    display_name TEXT,
+    email TEXT NOT NULL,
+    reply_to TEXT,
+    signature_id TEXT,
+    reply_placement TEXT NOT NULL DEFAULT 'above_quote',
+    forward_placement TEXT NOT NULL DEFAULT 'above_quote',
+    default_bcc TEXT,
+    is_default BOOLEAN DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(account_id, email),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY(signature_id) REFERENCES signatures(id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS signatures (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    body_text TEXT,
+    body_html TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS signature_images (
+    id TEXT PRIMARY KEY,
+    signature_id TEXT NOT NULL,
+    content_id TEXT NOT NULL,
+    filename TEXT,
+    mime_type TEXT,
+    content BLOB NOT NULL,
+    UNIQUE(signature_id, content_id),
+    FOREIGN KEY(signature_id) REFERENCES signatures(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    send_at TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(status, send_at);
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- In the search syntax of email::search_query
+    query TEXT NOT NULL,
+    -- JSON arrays of account and folder ids; empty searches everywhere
+    account_ids TEXT NOT NULL DEFAULT '[]',
+    folder_ids TEXT NOT NULL DEFAULT '[]',
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sent_messages (
+    message_id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    subject TEXT,
+    recipients TEXT,
+    dsn_requested BOOLEAN DEFAULT 0,
+    read_receipt_requested BOOLEAN DEFAULT 0,
+    sent_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS delivery_reports (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    email_id TEXT NOT NULL,
+    original_message_id TEXT,
+    kind TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    action TEXT,
+    status TEXT,
+    diagnostic TEXT,
+    received_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(email_id, recipient),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY(email_id) REFERENCES emails(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_reports_original ON delivery_reports(original_message_id);
+
+CREATE TABLE IF NOT EXISTS contacts (
+    id TEXT PRIMARY KEY,
+    -- vCard UID, kept stable across import and export
+    uid TEXT UNIQUE,
+    display_name TEXT,
+    organization TEXT,
+    notes TEXT,
+    photo BLOB,
+    photo_mime_type TEXT,
+    -- Set once the user edits the contact; harvesting then leaves the name alone
+    is_manual BOOLEAN DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS contact_emails (
+    email TEXT PRIMARY KEY COLLATE NOCASE,
+    contact_id TEXT NOT NULL,
+    is_primary BOOLEAN DEFAULT 0,
+    -- Messages we sent to the address vs. messages it appeared on
+    times_contacted INTEGER DEFAULT 0,
+    times_seen INTEGER DEFAULT 0,
+    last_used TEXT,
+    FOREIGN KEY(contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS contact_phones (
+    id TEXT PRIMARY KEY,
+    contact_id TEXT NOT NULL,
+    number TEXT NOT NULL,
+    -- vCard TYPE values, comma separated (cell, work, home, ...)
+    kind TEXT,
+    FOREIGN KEY(contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_contact_emails_contact ON contact_emails(contact_id);
+CREATE INDEX IF NOT EXISTS idx_contact_phones_contact ON contact_phones(contact_id);
+CREATE INDEX IF NOT EXISTS idx_contacts_name ON contacts(display_name COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS carddav_accounts (
+    id TEXT PRIMARY KEY,
+    server_url TEXT NOT NULL,
+    username TEXT NOT NULL,
+    addressbook_url TEXT NOT NULL,
+    display_name TEXT,
+    -- RFC 6578 token from the last sync-collection report
+    sync_token TEXT,
+    last_synced_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One row per card on the server. There is deliberately no foreign key to contacts:
+-- a row whose contact is gone marks a deletion that still has to be pushed.
+CREATE TABLE IF NOT EXISTS carddav_cards (
+    account_id TEXT NOT NULL,
+    href TEXT NOT NULL,
+    contact_id TEXT NOT NULL,
+    etag TEXT,
+    -- contacts.updated_at as of the last upload or download; differs once edited locally
+    synced_revision TEXT,
+    PRIMARY KEY(account_id, href),
+    FOREIGN KEY(account_id) REFERENCES carddav_accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_carddav_cards_contact ON carddav_cards(contact_id);
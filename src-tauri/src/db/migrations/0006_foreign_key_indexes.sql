@@ -0,0 +1,12 @@
+-- Indexes on the remaining foreign key columns, so the ON DELETE actions don't scan the
+-- child tables, and for unread counts and lists that filter on folder_id alone
+
+CREATE INDEX IF NOT EXISTS idx_emails_folder_unread ON emails(folder_id, is_read);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_id_date ON emails(folder_id, COALESCE(date, ''), id);
+CREATE INDEX IF NOT EXISTS idx_emails_unread ON emails(account_id, is_read);
+
+CREATE INDEX IF NOT EXISTS idx_identities_signature ON identities(signature_id);
+CREATE INDEX IF NOT EXISTS idx_signatures_account ON signatures(account_id);
+CREATE INDEX IF NOT EXISTS idx_outbox_account ON outbox(account_id);
+CREATE INDEX IF NOT EXISTS idx_sent_messages_account ON sent_messages(account_id);
+CREATE INDEX IF NOT EXISTS idx_delivery_reports_account ON delivery_reports(account_id);
@@ -0,0 +1,25 @@
+-- Every account has exactly one default identity (commands::identities)
+
+-- Accounts with several defaults keep the oldest
+UPDATE identities SET is_default = 0
+WHERE is_default AND id != (
+    SELECT i.id FROM identities i
+    WHERE i.account_id = identities.account_id AND i.is_default
+    ORDER BY i.created_at, i.id LIMIT 1
+);
+
+-- Accounts with identities but no default get the oldest one as default
+UPDATE identities SET is_default = 1
+WHERE id IN (
+    SELECT (SELECT i.id FROM identities i WHERE i.account_id = a.id ORDER BY i.created_at, i.id LIMIT 1)
+    FROM accounts a
+    WHERE NOT EXISTS (SELECT 1 FROM identities i WHERE i.account_id = a.id AND i.is_default)
+);
+
+-- Accounts added before identities existed get one for their own address
+INSERT INTO identities (id, account_id, display_name, email, is_default)
+SELECT lower(hex(randomblob(16))), a.id, a.name, a.email, 1
+FROM accounts a
+WHERE NOT EXISTS (SELECT 1 FROM identities i WHERE i.account_id = a.id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_identities_default ON identities(account_id) WHERE is_default;
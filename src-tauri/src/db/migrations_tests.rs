@@ -0,0 +1,383 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::migrations::*;
+    use sqlx::{sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    async fn empty_pool() -> Pool<Sqlite> {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    // Tables with their columns and foreign keys, indexes with their columns, and triggers.
+    // Column positions are left out: ALTER TABLE appends where schema.sql lists in place.
+    async fn fingerprint(pool: &Pool<Sqlite>) -> BTreeMap<String, Vec<String>> {
+        let objects = sqlx::query(
+            r#"
+            SELECT type, name FROM sqlite_master
+            WHERE name NOT LIKE 'sqlite_%' AND name NOT LIKE 'emails_fts_%' AND name != 'schema_version'
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+
+        let mut fingerprint = BTreeMap::new();
+        for object in objects {
+            let kind: String = object.get("type");
+            let name: String = object.get("name");
+            let mut details = Vec::new();
+            match kind.as_str() {
+                "table" => {
+                    for column in sqlx::query(&format!("SELECT * FROM pragma_table_info('{}')", name)).fetch_all(pool).await.unwrap() {
+                        details.push(format!(
+                            "{} {} notnull={} default={:?} pk={}",
+                            column.get::<String, _>("name"),
+                            column.get::<String, _>("type"),
+                            column.get::<i64, _>("notnull"),
+                            column.get::<Option<String>, _>("dflt_value"),
+                            column.get::<i64, _>("pk"),
+                        ));
+                    }
+                    for key in sqlx::query(&format!("SELECT * FROM pragma_foreign_key_list('{}')", name)).fetch_all(pool).await.unwrap() {
+                        details.push(format!(
+                            "fk {} -> {}({}) on delete {}",
+                            key.get::<String, _>("from"),
+                            key.get::<String, _>("table"),
+                            key.get::<Option<String>, _>("to").unwrap_or_default(),
+                            key.get::<String, _>("on_delete"),
+                        ));
+                    }
+                }
+                "index" => {
+                    for column in sqlx::query(&format!("SELECT * FROM pragma_index_xinfo('{}')", name)).fetch_all(pool).await.unwrap() {
+                        details.push(format!(
+                            "{} {:?} {}",
+                            column.get::<i64, _>("seqno"),
+                            column.get::<Option<String>, _>("name"),
+                            column.get::<Option<String>, _>("coll").unwrap_or_default(),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+            details.sort();
+            fingerprint.insert(format!("{} {}", kind, name), details);
+        }
+        fingerprint
+    }
+
+    async fn reference_fingerprint() -> BTreeMap<String, Vec<String>> {
+        let pool = empty_pool().await;
+        sqlx::query(include_str!("schema.sql")).execute(&pool).await.unwrap();
+        fingerprint(&pool).await
+    }
+
+    fn up_to(version: i64) -> &'static [Migration] {
+        let end = MIGRATIONS.iter().position(|migration| migration.version > version).unwrap_or(MIGRATIONS.len());
+        &MIGRATIONS[..end]
+    }
+
+    // Rows only using the tables and columns of the baseline, so they fit every version
+    async fn insert_fixture(pool: &Pool<Sqlite>) {
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email, name) VALUES ('acc', 'me@example.com', 'Me');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-INBOX', 'acc', 'INBOX');
+            INSERT INTO emails (id, account_id, folder_id, uid, subject, from_addr, body_text)
+            VALUES ('m1', 'acc', 'acc-INBOX', 1, 'Quarterly numbers', 'alice@example.com', 'See the report');
+            INSERT INTO attachments (id, email_id, filename, mime_type, size, content)
+            VALUES ('a1', 'm1', 'report.pdf', 'application/pdf', 2, X'6869');
+            "#
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "{}", migration.description);
+        }
+        assert_eq!(latest_version(MIGRATIONS), MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_new_database_matches_schema_sql() {
+        let pool = empty_pool().await;
+
+        assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS));
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version(MIGRATIONS));
+        assert_eq!(fingerprint(&pool).await, reference_fingerprint().await);
+    }
+
+    #[tokio::test]
+    async fn test_every_version_migrates_to_latest() {
+        let reference = reference_fingerprint().await;
+
+        for start in 1..=latest_version(MIGRATIONS) {
+            let pool = empty_pool().await;
+            assert_eq!(migrate_with(&pool, up_to(start), None).await.unwrap(), start);
+            insert_fixture(&pool).await;
+
+            assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS), "from version {}", start);
+            assert_eq!(fingerprint(&pool).await, reference, "from version {}", start);
+
+            let subject: String = sqlx::query_scalar("SELECT subject FROM emails WHERE id = 'm1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(subject, "Quarterly numbers", "from version {}", start);
+
+            // Messages from before the search index are searchable afterwards
+            let found: Vec<String> = sqlx::query_scalar(
+                "SELECT e.id FROM emails e JOIN emails_fts ON emails_fts.rowid = e.rowid WHERE emails_fts MATCH 'Quarterly'"
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            assert_eq!(found, vec!["m1"], "from version {}", start);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_database_from_before_versioning() {
+        let pool = empty_pool().await;
+        // What every install had before schema_version existed
+        sqlx::query(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+        insert_fixture(&pool).await;
+
+        assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS));
+        assert_eq!(fingerprint(&pool).await, reference_fingerprint().await);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachments").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_tables_from_builds_before_versioning_get_new_columns() {
+        let pool = empty_pool().await;
+        sqlx::query(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+        // identities and contacts as the first builds that had them created them
+        sqlx::query(
+            r#"
+            CREATE TABLE identities (
+                id TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL,
+                display_name TEXT,
+                email TEXT NOT NULL,
+                reply_to TEXT,
+                signature TEXT,
+                default_bcc TEXT,
+                is_default BOOLEAN DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(account_id, email),
+                FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            );
+            CREATE TABLE contacts (
+                id TEXT PRIMARY KEY,
+                display_name TEXT,
+                notes TEXT,
+                is_manual BOOLEAN DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            "#
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        insert_fixture(&pool).await;
+        sqlx::query(
+            r#"
+            INSERT INTO identities (id, account_id, email, is_default) VALUES ('i1', 'acc', 'me@example.com', 1);
+            INSERT INTO contacts (id, display_name) VALUES ('c1', 'Alice'), ('c2', 'Bob');
+            "#
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS));
+
+        let (placement, signature_id): (String, Option<String>) =
+            sqlx::query_as("SELECT reply_placement, signature_id FROM identities WHERE id = 'i1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((placement.as_str(), signature_id), ("above_quote", None));
+
+        sqlx::query("UPDATE contacts SET uid = 'same' WHERE id = 'c1'").execute(&pool).await.unwrap();
+        assert!(sqlx::query("UPDATE contacts SET uid = 'same' WHERE id = 'c2'").execute(&pool).await.is_err());
+
+        // Everything the later migrations index is there
+        let indexes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_identities_signature'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(indexes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_columns_created_before_versioning_are_kept() {
+        let pool = empty_pool().await;
+        // emails as the build that added threading created it, with the columns migration 2 adds
+        sqlx::query(
+            r#"
+            CREATE TABLE emails (
+                id TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL,
+                folder_id TEXT NOT NULL,
+                uid INTEGER NOT NULL,
+                message_id TEXT,
+                in_reply_to TEXT,
+                reference_ids TEXT,
+                subject TEXT,
+                base_subject TEXT,
+                thread_id TEXT,
+                from_addr TEXT,
+                to_addr TEXT,
+                cc_addr TEXT,
+                bcc_addr TEXT,
+                date DATETIME,
+                body_text TEXT,
+                body_html TEXT,
+                is_read BOOLEAN DEFAULT 0,
+                is_starred BOOLEAN DEFAULT 0,
+                has_attachments BOOLEAN DEFAULT 0,
+                preview TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(account_id, folder_id, uid),
+                FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+                FOREIGN KEY(folder_id) REFERENCES folders(id) ON DELETE CASCADE
+            );
+            "#
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+        insert_fixture(&pool).await;
+        sqlx::query("UPDATE emails SET thread_id = 't1', base_subject = 'quarterly numbers' WHERE id = 'm1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS));
+        assert_eq!(fingerprint(&pool).await, reference_fingerprint().await);
+        let thread_id: String = sqlx::query_scalar("SELECT thread_id FROM emails WHERE id = 'm1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(thread_id, "t1");
+    }
+
+    #[tokio::test]
+    async fn test_accounts_get_a_default_identity() {
+        let pool = empty_pool().await;
+        migrate_with(&pool, up_to(6), None).await.unwrap();
+        insert_fixture(&pool).await;
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('multi', 'multi@example.com');
+            INSERT INTO identities (id, account_id, email, is_default, created_at) VALUES
+                ('first', 'multi', 'multi@example.com', 1, '2026-01-01 00:00:00'),
+                ('second', 'multi', 'alias@example.com', 1, '2026-01-02 00:00:00');
+            "#
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        migrate(&pool, None).await.unwrap();
+
+        let defaults: Vec<(String, String)> = sqlx::query_as("SELECT account_id, email FROM identities WHERE is_default ORDER BY account_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(defaults, vec![
+            ("acc".to_string(), "me@example.com".to_string()),
+            ("multi".to_string(), "multi@example.com".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_migrating_twice_does_nothing() {
+        let pool = empty_pool().await;
+
+        migrate(&pool, None).await.unwrap();
+        assert_eq!(migrate(&pool, None).await.unwrap(), latest_version(MIGRATIONS));
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(applied, latest_version(MIGRATIONS));
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_is_rolled_back() {
+        let pool = empty_pool().await;
+        let migrations = [
+            Migration { version: 1, description: "first", sql: "CREATE TABLE first (id TEXT)" },
+            Migration { version: 2, description: "broken", sql: "CREATE TABLE second (id TEXT); INSERT INTO missing VALUES (1)" },
+        ];
+
+        let error = migrate_with(&pool, &migrations, None).await.unwrap_err();
+        assert!(error.to_string().contains("Migration 2 (broken) failed"));
+        assert_eq!(current_version(&pool).await.unwrap(), 1);
+        let second: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name = 'second'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(second, 0);
+    }
+
+    #[tokio::test]
+    async fn test_newer_database_is_refused() {
+        let pool = empty_pool().await;
+        migrate(&pool, None).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES (?, 'from the future')")
+            .bind(latest_version(MIGRATIONS) + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(migrate(&pool, None).await.unwrap_err().to_string().contains("newer"));
+    }
+
+    #[tokio::test]
+    async fn test_backup_before_migrating_existing_database() {
+        let dir = TempDir::new().unwrap();
+        let backups = dir.path().join("backups");
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&format!("sqlite://{}?mode=rwc", dir.path().join("mail.db").display()))
+            .await
+            .unwrap();
+
+        // A new database has nothing worth backing up
+        migrate_with(&pool, up_to(1), Some(&backups)).await.unwrap();
+        assert!(!backups.exists());
+
+        insert_fixture(&pool).await;
+        migrate(&pool, Some(&backups)).await.unwrap();
+        let files: Vec<_> = std::fs::read_dir(&backups).unwrap().flatten().map(|entry| entry.path()).collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].file_name().unwrap().to_string_lossy().starts_with("mail-v1-"));
+
+        // The backup is the database as it was before migrating
+        let backup = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&format!("sqlite://{}", files[0].display()))
+            .await
+            .unwrap();
+        assert_eq!(current_version(&backup).await.unwrap(), 1);
+        let subject: String = sqlx::query_scalar("SELECT subject FROM emails").fetch_one(&backup).await.unwrap();
+        assert_eq!(subject, "Quarterly numbers");
+
+        // Nothing left to migrate, so no new backup
+        migrate(&pool, Some(&backups)).await.unwrap();
+        assert_eq!(std::fs::read_dir(&backups).unwrap().count(), 1);
+    }
+}
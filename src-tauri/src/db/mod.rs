@@ -1,41 +1,110 @@
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use crate::credentials;
+use crate::error::{Context, MailError, MailResult};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Pool, Sqlite};
 use tauri::{AppHandle, Manager};
 use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod blobs;
+pub mod migrations;
+pub mod repo;
+
+#[cfg(test)]
+mod blobs_tests;
+#[cfg(test)]
+mod connection_tests;
+#[cfg(test)]
+mod migrations_tests;
+
+use blobs::BlobStore;
+use repo::{AccountRepo, AttachmentRepo, FolderRepo, MessageRepo};
 
 #[derive(Clone)]
 pub struct Database {
     pub pool: Pool<Sqlite>,
+    // Attachment contents
+    pub blobs: BlobStore,
 }
 
 impl Database {
-    pub async fn init(app_handle: &AppHandle) -> Result<Self, String> {
+    pub async fn init(app_handle: &AppHandle) -> MailResult<Self> {
         let app_dir = app_handle.path().app_data_dir()
-            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+            .map_err(|e| MailError::Io(format!("Failed to get app data dir: {}", e)))?;
             
         if !app_dir.exists() {
             fs::create_dir_all(&app_dir)
-                .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+                .context("Failed to create app data dir")?;
         }
 
-        let db_path = app_dir.join("mail.db");
-        // Helper to create file if not exists, though sqlite mode=rwc should handle it.
-        
-        let db_url = format!("sqlite://{}?mode=rwc", db_path.to_string_lossy());
-
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(&db_url)
-            .await
-            .map_err(|e| format!("Failed to connect to database: {}", e))?;
-
-        let schema = include_str!("schema.sql");
-        
-        // Execute schema
-        sqlx::query(schema)
-            .execute(&pool)
-            .await
-            .map_err(|e| format!("Failed to initialize schema: {}", e))?;
-
-        Ok(Database { pool })
+        let pool = connect(&app_dir.join("mail.db"), 5).await?;
+
+        // Existing databases are copied to backups/ before a migration touches them
+        migrations::migrate(&pool, Some(&app_dir.join("backups"))).await?;
+
+        // Passwords saved by the old plaintext commands move to the encrypted store
+        credentials::move_plaintext_passwords(&pool, &credentials::credentials_path(app_handle)?).await?;
+
+        let blobs = BlobStore::new(app_dir.join("attachments"));
+        blobs::move_inline_contents(&pool, &blobs).await?;
+
+        Ok(Database { pool, blobs })
+    }
+
+    pub fn accounts(&self) -> AccountRepo<'_> {
+        AccountRepo::new(&self.pool)
     }
+
+    pub fn folders(&self) -> FolderRepo<'_> {
+        FolderRepo::new(&self.pool)
+    }
+
+    pub fn messages(&self) -> MessageRepo<'_> {
+        MessageRepo::new(&self.pool)
+    }
+
+    pub fn attachments(&self) -> AttachmentRepo<'_> {
+        AttachmentRepo::new(&self.pool, &self.blobs)
+    }
+}
+
+// Every connection enforces foreign keys, so deleting an account or email removes what
+// depends on it through ON DELETE CASCADE. WAL lets the UI read while a sync writes, and
+// with it synchronous=NORMAL is still safe against corruption (only the last commits can
+// be lost on power failure). A writer waits for a busy database instead of failing at once.
+pub fn connect_options(path: &Path) -> SqliteConnectOptions {
+    SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(5))
+}
+
+pub async fn connect(path: &Path, max_connections: u32) -> MailResult<Pool<Sqlite>> {
+    SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(connect_options(path))
+        .await
+        .context("Failed to connect to database")
+}
+
+pub async fn get_setting(pool: &Pool<Sqlite>, key: &str) -> MailResult<Option<String>> {
+    sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .context(&format!("Failed to read setting '{}'", key))
+}
+
+pub async fn set_setting(pool: &Pool<Sqlite>, key: &str, value: &str) -> MailResult<()> {
+    sqlx::query("INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .bind(key)
+        .bind(value)
+        .execute(pool)
+        .await
+        .context(&format!("Failed to write setting '{}'", key))?;
+    Ok(())
 }
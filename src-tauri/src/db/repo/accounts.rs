@@ -0,0 +1,107 @@
+use crate::error::{Context, MailError, MailResult};
+use crate::models::{Account, MailAccount};
+use sqlx::{Pool, Sqlite};
+
+const ACCOUNT_COLUMNS: &str =
+    "id, email, name, provider, imap_host, imap_port, imap_username, smtp_host, smtp_port, smtp_username, created_at";
+
+#[derive(Debug, Clone)]
+pub struct NewAccount {
+    pub id: String,
+    pub email: String,
+    pub name: String,
+    pub provider: String,
+    pub imap_host: String,
+    pub imap_port: u16,
+    pub imap_username: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+}
+
+pub struct AccountRepo<'a> {
+    pool: &'a Pool<Sqlite>,
+}
+
+impl<'a> AccountRepo<'a> {
+    pub fn new(pool: &'a Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, account: &NewAccount) -> MailResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email, name, provider, imap_host, imap_port, imap_username,
+                                  smtp_host, smtp_port, smtp_username)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&account.id)
+        .bind(&account.email)
+        .bind(&account.name)
+        .bind(&account.provider)
+        .bind(&account.imap_host)
+        .bind(account.imap_port as i64)
+        .bind(&account.imap_username)
+        .bind(&account.smtp_host)
+        .bind(account.smtp_port as i64)
+        .bind(&account.smtp_username)
+        .execute(self.pool)
+        .await
+        .context("Failed to save account")?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> MailResult<Vec<Account>> {
+        sqlx::query_as::<_, Account>(
+            "SELECT id, email, name, provider, imap_host, imap_port, smtp_host, smtp_port FROM accounts ORDER BY created_at, id"
+        )
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to fetch accounts")
+    }
+
+    pub async fn find(&self, account_id: &str) -> MailResult<Option<MailAccount>> {
+        sqlx::query_as::<_, MailAccount>(&format!("SELECT {} FROM accounts WHERE id = ?", ACCOUNT_COLUMNS))
+            .bind(account_id)
+            .fetch_optional(self.pool)
+            .await
+            .context("Failed to get account")
+    }
+
+    pub async fn get(&self, account_id: &str) -> MailResult<MailAccount> {
+        self.find(account_id)
+            .await?
+            .ok_or_else(|| MailError::NotFound(format!("Account {} not found", account_id)))
+    }
+
+    // Everything else of the account goes with it (ON DELETE CASCADE). Returns whether it existed.
+    pub async fn delete(&self, account_id: &str) -> MailResult<bool> {
+        let deleted = sqlx::query("DELETE FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .execute(self.pool)
+            .await
+            .context("Failed to delete account")?;
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    // Accounts saved by the old plaintext commands whose passwords are still in the table,
+    // as (id, imap_password, smtp_password)
+    pub async fn plaintext_passwords(&self) -> MailResult<Vec<(String, Option<String>, Option<String>)>> {
+        sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT id, imap_password, smtp_password FROM accounts WHERE imap_password IS NOT NULL OR smtp_password IS NOT NULL ORDER BY id"
+        )
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to read stored passwords")
+    }
+
+    pub async fn clear_plaintext_passwords(&self, account_id: &str) -> MailResult<()> {
+        sqlx::query("UPDATE accounts SET imap_password = NULL, smtp_password = NULL WHERE id = ?")
+            .bind(account_id)
+            .execute(self.pool)
+            .await
+            .context("Failed to clear stored passwords")?;
+        Ok(())
+    }
+}
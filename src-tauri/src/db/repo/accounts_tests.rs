@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::repo::{AccountRepo, NewAccount};
+    use crate::test_utils::memory_pool;
+
+    fn account(id: &str, email: &str) -> NewAccount {
+        NewAccount {
+            id: id.to_string(),
+            email: email.to_string(),
+            name: "Me".to_string(),
+            provider: "custom".to_string(),
+            imap_host: "imap.example.com".to_string(),
+            imap_port: 993,
+            imap_username: email.to_string(),
+            smtp_host: "smtp.example.com".to_string(),
+            smtp_port: 587,
+            smtp_username: email.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get() {
+        let pool = memory_pool().await;
+        let repo = AccountRepo::new(&pool);
+
+        repo.insert(&account("acc", "me@example.com")).await.unwrap();
+
+        let stored = repo.get("acc").await.unwrap();
+        assert_eq!(stored.email, "me@example.com");
+        assert_eq!(stored.name.as_deref(), Some("Me"));
+        assert_eq!(stored.imap_host.as_deref(), Some("imap.example.com"));
+        assert_eq!(stored.imap_port, Some(993));
+        assert_eq!(stored.smtp_username.as_deref(), Some("me@example.com"));
+        assert!(stored.created_at.is_some());
+
+        // No password is written by insert
+        assert!(repo.plaintext_passwords().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_account() {
+        let pool = memory_pool().await;
+        let repo = AccountRepo::new(&pool);
+
+        assert!(repo.find("nope").await.unwrap().is_none());
+        let error = repo.get("nope").await.unwrap_err();
+        assert_eq!(error.kind(), "not_found");
+        assert_eq!(error.to_string(), "Account nope not found");
+        assert!(!repo.delete("nope").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete() {
+        let pool = memory_pool().await;
+        let repo = AccountRepo::new(&pool);
+        repo.insert(&account("a1", "one@example.com")).await.unwrap();
+        repo.insert(&account("a2", "two@example.com")).await.unwrap();
+
+        let emails: Vec<String> = repo.list().await.unwrap().into_iter().map(|a| a.email).collect();
+        assert_eq!(emails, vec!["one@example.com", "two@example.com"]);
+
+        assert!(repo.delete("a1").await.unwrap());
+        let ids: Vec<String> = repo.list().await.unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec!["a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_passwords() {
+        let pool = memory_pool().await;
+        let repo = AccountRepo::new(&pool);
+        repo.insert(&account("a1", "one@example.com")).await.unwrap();
+        repo.insert(&account("a2", "two@example.com")).await.unwrap();
+        sqlx::query("UPDATE accounts SET imap_password = 'imap-secret', smtp_password = 'smtp-secret' WHERE id = 'a1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repo.plaintext_passwords().await.unwrap(),
+            vec![("a1".to_string(), Some("imap-secret".to_string()), Some("smtp-secret".to_string()))]
+        );
+
+        repo.clear_plaintext_passwords("a1").await.unwrap();
+        assert!(repo.plaintext_passwords().await.unwrap().is_empty());
+    }
+}
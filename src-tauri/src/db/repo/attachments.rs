@@ -0,0 +1,214 @@
+use crate::db::blobs::{self, BlobStore};
+use crate::email::parser::{Disposition, EmailAttachment};
+use crate::error::{Context, MailError, MailResult};
+use crate::models::MailAttachment;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+
+const ATTACHMENT_COLUMNS: &str = "id, email_id, section, filename, mime_type, size, content_id, disposition, content_hash";
+
+// A file the user attached to a message
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AttachmentStats {
+    pub total_attachments: u32,
+    pub total_size: u64,
+    pub image_count: u32,
+    pub pdf_count: u32,
+    pub text_count: u32,
+}
+
+pub struct AttachmentRepo<'a> {
+    pool: &'a Pool<Sqlite>,
+    blobs: &'a BlobStore,
+}
+
+impl<'a> AttachmentRepo<'a> {
+    pub fn new(pool: &'a Pool<Sqlite>, blobs: &'a BlobStore) -> Self {
+        Self { pool, blobs }
+    }
+
+    // Stores the attachments of a synced message, one row per MIME part, with the contents in
+    // the blob store. A message synced again keeps its rows.
+    pub async fn save_received(&self, email_id: &str, attachments: &[EmailAttachment]) -> MailResult<()> {
+        for attachment in attachments {
+            let hash = blobs::store(self.pool, self.blobs, &attachment.content).await?;
+            let disposition = match attachment.disposition {
+                Disposition::Inline => "inline",
+                Disposition::Attachment => "attachment",
+            };
+            sqlx::query(
+                r#"
+                INSERT INTO attachments (id, email_id, section, filename, mime_type, size, content_id, disposition, content_hash, path)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET
+                    filename = excluded.filename,
+                    mime_type = excluded.mime_type,
+                    size = excluded.size,
+                    content_id = excluded.content_id,
+                    disposition = excluded.disposition,
+                    content_hash = excluded.content_hash,
+                    path = excluded.path,
+                    content = NULL
+                "#
+            )
+            .bind(format!("{}-{}", email_id, attachment.section))
+            .bind(email_id)
+            .bind(&attachment.section)
+            .bind(&attachment.filename)
+            .bind(&attachment.mime_type)
+            .bind(attachment.size as i64)
+            .bind(&attachment.content_id)
+            .bind(disposition)
+            .bind(&hash)
+            .bind(blobs::relative_path(&hash)?.to_string_lossy().to_string())
+            .execute(self.pool)
+            .await
+            .context("Failed to store attachment")?;
+        }
+
+        Ok(())
+    }
+
+    // Adds the files to the message in one transaction and returns their ids
+    pub async fn add(&self, email_id: &str, attachments: &[NewAttachment]) -> MailResult<Vec<String>> {
+        // Blobs are written first; any left behind by a failed transaction are collected later
+        let mut hashes = Vec::new();
+        for attachment in attachments {
+            hashes.push(blobs::store(self.pool, self.blobs, &attachment.content).await?);
+        }
+
+        let mut tx = self.pool.begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        let mut attachment_ids = Vec::new();
+        for (attachment, hash) in attachments.iter().zip(&hashes) {
+            let attachment_id = format!("{}-{}", email_id, uuid::Uuid::new_v4());
+            sqlx::query(
+                "INSERT INTO attachments (id, email_id, filename, mime_type, size, content_hash, path) VALUES (?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&attachment_id)
+            .bind(email_id)
+            .bind(&attachment.filename)
+            .bind(&attachment.mime_type)
+            .bind(attachment.content.len() as i64)
+            .bind(hash)
+            .bind(blobs::relative_path(hash)?.to_string_lossy().to_string())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save attachment to database")?;
+            attachment_ids.push(attachment_id);
+        }
+
+        if !attachment_ids.is_empty() {
+            sqlx::query("UPDATE emails SET has_attachments = 1 WHERE id = ?")
+                .bind(email_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to update email attachment flag")?;
+        }
+
+        tx.commit()
+            .await
+            .context("Failed to commit transaction")?;
+        Ok(attachment_ids)
+    }
+
+    pub async fn list(&self, email_id: &str) -> MailResult<Vec<MailAttachment>> {
+        sqlx::query_as::<_, MailAttachment>(&format!(
+            "SELECT {} FROM attachments WHERE email_id = ? ORDER BY section, filename",
+            ATTACHMENT_COLUMNS
+        ))
+        .bind(email_id)
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to get attachments")
+    }
+
+    pub async fn get(&self, attachment_id: &str) -> MailResult<MailAttachment> {
+        sqlx::query_as::<_, MailAttachment>(&format!("SELECT {} FROM attachments WHERE id = ?", ATTACHMENT_COLUMNS))
+            .bind(attachment_id)
+            .fetch_optional(self.pool)
+            .await
+            .context("Failed to get attachment")?
+            .ok_or_else(|| MailError::NotFound(format!("Attachment {} not found", attachment_id)))
+    }
+
+    // Hash of the attachment's content in the blob store
+    pub async fn content_hash(&self, attachment_id: &str) -> MailResult<String> {
+        sqlx::query_scalar::<_, Option<String>>("SELECT content_hash FROM attachments WHERE id = ?")
+            .bind(attachment_id)
+            .fetch_optional(self.pool)
+            .await
+            .context("Failed to get attachment")?
+            .flatten()
+            .ok_or_else(|| MailError::NotFound(format!("Attachment {} not found", attachment_id)))
+    }
+
+    // Clears the message's attachment flag along with its last attachment
+    pub async fn delete(&self, attachment_id: &str) -> MailResult<()> {
+        let mut tx = self.pool.begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        let email_id: String = sqlx::query_scalar("SELECT email_id FROM attachments WHERE id = ?")
+            .bind(attachment_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to get attachment email_id")?
+            .ok_or_else(|| MailError::NotFound(format!("Attachment {} not found", attachment_id)))?;
+
+        sqlx::query("DELETE FROM attachments WHERE id = ?")
+            .bind(attachment_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete attachment")?;
+
+        sqlx::query(
+            "UPDATE emails SET has_attachments = 0 WHERE id = ? AND NOT EXISTS (SELECT 1 FROM attachments WHERE email_id = ?)"
+        )
+        .bind(&email_id)
+        .bind(&email_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update email attachment flag")?;
+
+        tx.commit()
+            .await
+            .context("Failed to commit transaction")
+    }
+
+    pub async fn stats(&self, email_id: &str) -> MailResult<AttachmentStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS total_attachments,
+                COALESCE(SUM(size), 0) AS total_size,
+                COUNT(CASE WHEN mime_type LIKE 'image/%' THEN 1 END) AS image_count,
+                COUNT(CASE WHEN mime_type = 'application/pdf' THEN 1 END) AS pdf_count,
+                COUNT(CASE WHEN mime_type LIKE 'text/%' THEN 1 END) AS text_count
+            FROM attachments
+            WHERE email_id = ?
+            "#
+        )
+        .bind(email_id)
+        .fetch_one(self.pool)
+        .await
+        .context("Failed to get attachment stats")?;
+
+        Ok(AttachmentStats {
+            total_attachments: row.get::<i64, _>("total_attachments") as u32,
+            total_size: row.get::<i64, _>("total_size") as u64,
+            image_count: row.get::<i64, _>("image_count") as u32,
+            pdf_count: row.get::<i64, _>("pdf_count") as u32,
+            text_count: row.get::<i64, _>("text_count") as u32,
+        })
+    }
+}
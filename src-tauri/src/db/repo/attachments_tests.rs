@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::repo::{AttachmentRepo, NewAttachment};
+    use crate::db::blobs::{hash, BlobStore};
+    use crate::email::parser::{parse_email, Disposition, EmailAttachment};
+    use crate::error::MailError;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Row, Sqlite};
+    use tempfile::TempDir;
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-INBOX', 'acc', 'INBOX');
+            INSERT INTO emails (id, account_id, folder_id, uid, subject) VALUES ('m1', 'acc', 'acc-INBOX', 1, 'Invoice');
+            "#
+        )
+        .await
+    }
+
+    fn attachment(section: &str, filename: &str, content: &[u8]) -> EmailAttachment {
+        EmailAttachment {
+            section: section.to_string(),
+            filename: Some(filename.to_string()),
+            mime_type: "application/pdf".to_string(),
+            disposition: Disposition::Attachment,
+            size: content.len(),
+            content_id: None,
+            content: content.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_received_links_parts_to_email() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let raw = "From: a@example.com\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+See attached.\r\n\
+--b\r\n\
+Content-Type: application/pdf; name=\"invoice.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"invoice.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQK\r\n\
+--b--\r\n";
+        let parsed = parse_email(raw.as_bytes()).unwrap();
+
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &parsed.attachments).await.unwrap();
+
+        let row = sqlx::query("SELECT id, section, filename, mime_type, size, disposition, content_hash, path, content FROM attachments WHERE email_id = 'm1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<String, _>("id"), "m1-2");
+        assert_eq!(row.get::<String, _>("section"), "2");
+        assert_eq!(row.get::<String, _>("filename"), "invoice.pdf");
+        assert_eq!(row.get::<String, _>("mime_type"), "application/pdf");
+        assert_eq!(row.get::<i64, _>("size"), 9);
+        assert_eq!(row.get::<String, _>("disposition"), "attachment");
+        let content_hash = row.get::<String, _>("content_hash");
+        assert_eq!(content_hash, hash(b"%PDF-1.4\n"));
+        assert_eq!(row.get::<String, _>("path"), format!("{}/{}", &content_hash[..2], content_hash));
+        assert!(row.get::<Option<Vec<u8>>, _>("content").is_none());
+        assert_eq!(blobs.read(&content_hash).unwrap(), b"%PDF-1.4\n");
+    }
+
+    #[tokio::test]
+    async fn test_resync_keeps_one_row_per_part() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &[attachment("2", "a.pdf", b"first")]).await.unwrap();
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &[attachment("2", "a.pdf", b"first")]).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachments")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &[attachment("2", "b.pdf", b"second")]).await.unwrap();
+        let row = sqlx::query("SELECT filename, content_hash FROM attachments")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<String, _>("filename"), "b.pdf");
+        assert_eq!(row.get::<String, _>("content_hash"), hash(b"second"));
+
+        let counts: Vec<(String, i64)> = sqlx::query_as("SELECT hash, ref_count FROM blobs ORDER BY ref_count")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(counts, vec![(hash(b"first"), 0), (hash(b"second"), 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_same_content_is_stored_once() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &[attachment("2", "a.pdf", b"same"), attachment("3", "copy.pdf", b"same")]).await.unwrap();
+        let hashes: Vec<String> = sqlx::query_scalar("SELECT DISTINCT content_hash FROM attachments")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(hashes, vec![hash(b"same")]);
+
+        let ref_count: i64 = sqlx::query_scalar("SELECT ref_count FROM blobs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ref_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_filenames_are_searchable() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+
+        AttachmentRepo::new(&pool, &blobs).save_received("m1", &[attachment("2", "quarterly.pdf", b"x")]).await.unwrap();
+        let found: Vec<String> = sqlx::query_scalar(
+            "SELECT e.id FROM emails e JOIN emails_fts ON emails_fts.rowid = e.rowid WHERE emails_fts MATCH 'attachments : quarterly'"
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(found, vec!["m1"]);
+    }
+
+    fn upload(filename: &str, mime_type: &str, content: &[u8]) -> NewAttachment {
+        NewAttachment {
+            filename: filename.to_string(),
+            mime_type: mime_type.to_string(),
+            content: content.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_added_files_are_listed_and_flag_the_email() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let repo = AttachmentRepo::new(&pool, &blobs);
+
+        let ids = repo.add("m1", &[upload("b.txt", "text/plain", b"hello"), upload("a.png", "image/png", b"png")]).await.unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let listed = repo.list("m1").await.unwrap();
+        let filenames: Vec<_> = listed.iter().map(|a| a.filename.clone().unwrap()).collect();
+        assert_eq!(filenames, vec!["a.png", "b.txt"]);
+        assert!(listed.iter().all(|a| a.section.is_none()));
+
+        let text = repo.get(&ids[0]).await.unwrap();
+        assert_eq!(text.mime_type.as_deref(), Some("text/plain"));
+        assert_eq!(text.size, Some(5));
+        assert_eq!(blobs.read(&repo.content_hash(&ids[0]).await.unwrap()).unwrap(), b"hello");
+
+        let flagged: bool = sqlx::query_scalar("SELECT has_attachments FROM emails WHERE id = 'm1'").fetch_one(&pool).await.unwrap();
+        assert!(flagged);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_last_attachment_clears_flag() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let repo = AttachmentRepo::new(&pool, &blobs);
+        let ids = repo.add("m1", &[upload("a.pdf", "application/pdf", b"a"), upload("b.pdf", "application/pdf", b"b")]).await.unwrap();
+
+        repo.delete(&ids[0]).await.unwrap();
+        let flagged: bool = sqlx::query_scalar("SELECT has_attachments FROM emails WHERE id = 'm1'").fetch_one(&pool).await.unwrap();
+        assert!(flagged);
+
+        repo.delete(&ids[1]).await.unwrap();
+        let flagged: bool = sqlx::query_scalar("SELECT has_attachments FROM emails WHERE id = 'm1'").fetch_one(&pool).await.unwrap();
+        assert!(!flagged);
+
+        assert!(matches!(repo.get(&ids[0]).await.unwrap_err(), MailError::NotFound(_)));
+        assert!(matches!(repo.delete(&ids[0]).await.unwrap_err(), MailError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_stats_by_mime_type() {
+        let pool = setup_pool().await;
+        let dir = TempDir::new().unwrap();
+        let blobs = BlobStore::new(dir.path());
+        let repo = AttachmentRepo::new(&pool, &blobs);
+
+        let empty = repo.stats("m1").await.unwrap();
+        assert_eq!((empty.total_attachments, empty.total_size), (0, 0));
+
+        repo.add("m1", &[
+            upload("a.png", "image/png", b"123"),
+            upload("b.pdf", "application/pdf", b"45"),
+            upload("c.txt", "text/plain", b"6"),
+            upload("d.zip", "application/zip", b"7890"),
+        ]).await.unwrap();
+
+        let stats = repo.stats("m1").await.unwrap();
+        assert_eq!(stats.total_attachments, 4);
+        assert_eq!(stats.total_size, 10);
+        assert_eq!((stats.image_count, stats.pdf_count, stats.text_count), (1, 1, 1));
+    }
+}
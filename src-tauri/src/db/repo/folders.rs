@@ -0,0 +1,169 @@
+use crate::email::folder_role::FolderRole;
+use crate::error::{Context, MailError, MailResult};
+use crate::imap_client::ImapFolder;
+use crate::models::{Folder, MailFolder};
+use sqlx::{Pool, Sqlite};
+
+const FOLDER_COLUMNS: &str = "id, account_id, name, delimiter, role";
+
+pub struct FolderRepo<'a> {
+    pool: &'a Pool<Sqlite>,
+}
+
+impl<'a> FolderRepo<'a> {
+    pub fn new(pool: &'a Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    // Folders are keyed by account and name, e.g. "<account>-INBOX"
+    pub fn id_for(account_id: &str, name: &str) -> String {
+        format!("{}-{}", account_id, name)
+    }
+
+    // Records a folder listed by the server, with its role
+    pub async fn save_synced(&self, account_id: &str, folder: &ImapFolder) -> MailResult<Folder> {
+        let saved = Folder {
+            id: Self::id_for(account_id, &folder.name),
+            account_id: account_id.to_string(),
+            name: folder.name.clone(),
+            delimiter: Some(folder.delimiter.clone()),
+            role: FolderRole::detect(&folder.name, &folder.flags).map(|role| role.as_str().to_string()),
+        };
+        // An upsert, as REPLACE would first delete the folder and cascade to its emails
+        sqlx::query(
+            r#"
+            INSERT INTO folders (id, account_id, name, delimiter, role) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET delimiter = excluded.delimiter, role = excluded.role
+            "#
+        )
+        .bind(&saved.id)
+        .bind(&saved.account_id)
+        .bind(&saved.name)
+        .bind(&saved.delimiter)
+        .bind(&saved.role)
+        .execute(self.pool)
+        .await
+        .context("Failed to save folder")?;
+        Ok(saved)
+    }
+
+    pub async fn insert(&self, account_id: &str, name: &str, delimiter: &str) -> MailResult<String> {
+        let folder_id = Self::id_for(account_id, name);
+        sqlx::query("INSERT INTO folders (id, account_id, name, delimiter) VALUES (?, ?, ?, ?)")
+            .bind(&folder_id)
+            .bind(account_id)
+            .bind(name)
+            .bind(delimiter)
+            .execute(self.pool)
+            .await
+            .context("Failed to save folder to database")?;
+        Ok(folder_id)
+    }
+
+    // The folder's id, creating its row if the folder list hasn't been synced yet
+    pub async fn ensure(&self, account_id: &str, name: &str) -> MailResult<String> {
+        let folder_id = Self::id_for(account_id, name);
+        sqlx::query("INSERT INTO folders (id, account_id, name) VALUES (?, ?, ?) ON CONFLICT DO NOTHING")
+            .bind(&folder_id)
+            .bind(account_id)
+            .bind(name)
+            .execute(self.pool)
+            .await
+            .context("Failed to save folder")?;
+        Ok(folder_id)
+    }
+
+    pub async fn list(&self, account_id: &str) -> MailResult<Vec<MailFolder>> {
+        sqlx::query_as::<_, MailFolder>(
+            r#"
+            SELECT f.id, f.account_id, f.name, f.delimiter, f.role,
+                   COUNT(e.id) AS total_count,
+                   COALESCE(SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END), 0) AS unread_count
+            FROM folders f
+            LEFT JOIN emails e ON e.folder_id = f.id
+            WHERE f.account_id = ?
+            GROUP BY f.id
+            ORDER BY f.name
+            "#
+        )
+        .bind(account_id)
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to fetch folders")
+    }
+
+    pub async fn find_by_name(&self, account_id: &str, name: &str) -> MailResult<Option<Folder>> {
+        sqlx::query_as::<_, Folder>(&format!(
+            "SELECT {} FROM folders WHERE account_id = ? AND name = ?",
+            FOLDER_COLUMNS
+        ))
+        .bind(account_id)
+        .bind(name)
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to look up folder")
+    }
+
+    pub async fn find_by_role(&self, account_id: &str, role: FolderRole) -> MailResult<Option<Folder>> {
+        sqlx::query_as::<_, Folder>(&format!(
+            "SELECT {} FROM folders WHERE account_id = ? AND role = ? ORDER BY name LIMIT 1",
+            FOLDER_COLUMNS
+        ))
+        .bind(account_id)
+        .bind(role.as_str())
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to look up folder")
+    }
+
+    // Folder ids are derived from the name, so the row is replaced by one under the new id
+    // and the emails moved over before the old row goes, keeping every foreign key valid
+    pub async fn rename(&self, account_id: &str, name: &str, new_name: &str) -> MailResult<String> {
+        let old_id = Self::id_for(account_id, name);
+        let new_id = Self::id_for(account_id, new_name);
+        let mut tx = self.pool.begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        let inserted = sqlx::query(
+            "INSERT INTO folders (id, account_id, name, delimiter, role) SELECT ?, account_id, ?, delimiter, role FROM folders WHERE id = ?"
+        )
+        .bind(&new_id)
+        .bind(new_name)
+        .bind(&old_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update folder")?;
+        if inserted.rows_affected() == 0 {
+            return Err(MailError::NotFound(format!("Folder {} not found", name)));
+        }
+
+        sqlx::query("UPDATE emails SET folder_id = ? WHERE folder_id = ?")
+            .bind(&new_id)
+            .bind(&old_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update emails")?;
+
+        sqlx::query("DELETE FROM folders WHERE id = ?")
+            .bind(&old_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update folder")?;
+
+        tx.commit()
+            .await
+            .context("Failed to commit transaction")?;
+        Ok(new_id)
+    }
+
+    // The folder's emails go with it (ON DELETE CASCADE)
+    pub async fn delete(&self, folder_id: &str) -> MailResult<()> {
+        sqlx::query("DELETE FROM folders WHERE id = ?")
+            .bind(folder_id)
+            .execute(self.pool)
+            .await
+            .context("Failed to delete folder")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::repo::FolderRepo;
+    use crate::email::folder_role::FolderRole;
+    use crate::error::MailError;
+    use crate::imap_client::ImapFolder;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool("INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com')").await
+    }
+
+    fn imap_folder(name: &str, flags: &[&str]) -> ImapFolder {
+        ImapFolder {
+            name: name.to_string(),
+            delimiter: "/".to_string(),
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            message_count: None,
+        }
+    }
+
+    async fn insert_email(pool: &Pool<Sqlite>, id: &str, folder_id: &str, uid: i64, is_read: bool) {
+        sqlx::query("INSERT INTO emails (id, account_id, folder_id, uid, is_read) VALUES (?, 'acc', ?, ?, ?)")
+            .bind(id)
+            .bind(folder_id)
+            .bind(uid)
+            .bind(is_read)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_synced_detects_role_and_updates() {
+        let pool = setup_pool().await;
+        let repo = FolderRepo::new(&pool);
+
+        let saved = repo.save_synced("acc", &imap_folder("Archive", &["\\Archive"])).await.unwrap();
+        assert_eq!(saved.id, "acc-Archive");
+        assert_eq!(saved.role.as_deref(), Some("archive"));
+
+        let mut again = imap_folder("Archive", &["\\Archive"]);
+        again.delimiter = ".".to_string();
+        repo.save_synced("acc", &again).await.unwrap();
+        let folders = repo.list("acc").await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].folder.delimiter.as_deref(), Some("."));
+        assert_eq!(repo.find_by_role("acc", FolderRole::Archive).await.unwrap().map(|f| f.id), Some("acc-Archive".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_list_counts_messages() {
+        let pool = setup_pool().await;
+        let repo = FolderRepo::new(&pool);
+        let inbox = repo.ensure("acc", "INBOX").await.unwrap();
+        repo.ensure("acc", "Empty").await.unwrap();
+        insert_email(&pool, "m1", &inbox, 1, false).await;
+        insert_email(&pool, "m2", &inbox, 2, true).await;
+
+        let folders = repo.list("acc").await.unwrap();
+        let counts: Vec<(String, i64, i64)> = folders.into_iter()
+            .map(|f| (f.folder.name, f.total_count, f.unread_count))
+            .collect();
+        assert_eq!(counts, vec![("Empty".to_string(), 0, 0), ("INBOX".to_string(), 2, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_keeps_existing_folder() {
+        let pool = setup_pool().await;
+        let repo = FolderRepo::new(&pool);
+        repo.save_synced("acc", &imap_folder("INBOX", &[])).await.unwrap();
+
+        assert_eq!(repo.ensure("acc", "INBOX").await.unwrap(), "acc-INBOX");
+        let folders = repo.list("acc").await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].folder.role.as_deref(), Some("inbox"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_moves_emails() {
+        let pool = setup_pool().await;
+        let repo = FolderRepo::new(&pool);
+        let old = repo.insert("acc", "Projects", ".").await.unwrap();
+        insert_email(&pool, "m1", &old, 1, false).await;
+
+        let renamed = repo.rename("acc", "Projects", "Work").await.unwrap();
+        assert_eq!(renamed, "acc-Work");
+
+        let folder: String = sqlx::query_scalar("SELECT folder_id FROM emails WHERE id = 'm1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(folder, "acc-Work");
+        let names: Vec<String> = repo.list("acc").await.unwrap().into_iter().map(|f| f.folder.name).collect();
+        assert_eq!(names, vec!["Work"]);
+
+        assert!(matches!(repo.rename("acc", "Projects", "Other").await.unwrap_err(), MailError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_emails() {
+        let pool = setup_pool().await;
+        let repo = FolderRepo::new(&pool);
+        let folder = repo.insert("acc", "Old", ".").await.unwrap();
+        insert_email(&pool, "m1", &folder, 1, false).await;
+
+        repo.delete(&folder).await.unwrap();
+
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM emails").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 0);
+        assert!(repo.list("acc").await.unwrap().is_empty());
+    }
+}
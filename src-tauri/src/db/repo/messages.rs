@@ -0,0 +1,228 @@
+use super::push_ids;
+use crate::email::{address, threading};
+use crate::error::{Context, MailResult};
+use crate::imap_client::ImapEmail;
+use crate::models::Email;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+
+// Where a message lives on its server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageLocation {
+    pub id: String,
+    pub account_id: String,
+    pub folder: String,
+    pub uid: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FolderStats {
+    pub total_emails: u32,
+    pub unread_emails: u32,
+    pub starred_emails: u32,
+    pub emails_with_attachments: u32,
+}
+
+pub struct MessageRepo<'a> {
+    pool: &'a Pool<Sqlite>,
+}
+
+impl<'a> MessageRepo<'a> {
+    pub fn new(pool: &'a Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    // Synced messages are keyed by account, folder and UID
+    pub fn id_for(account_id: &str, folder_name: &str, uid: u32) -> String {
+        format!("{}-{}-{}", account_id, folder_name, uid)
+    }
+
+    // Stores a message fetched from `folder_name`, whose row in `folders` is `folder_id`.
+    // The flag is true when the message wasn't stored yet, false for a re-synced one.
+    pub async fn save_synced(&self, account_id: &str, folder_id: &str, folder_name: &str, email: &ImapEmail) -> MailResult<(Email, bool)> {
+        let saved = Email {
+            id: Self::id_for(account_id, folder_name, email.uid),
+            account_id: account_id.to_string(),
+            folder_id: folder_id.to_string(),
+            uid: email.uid as i64,
+            message_id: email.message_id.clone(),
+            subject: Some(email.subject.clone()),
+            from_addr: Some(email.from.clone()),
+            to_addr: Some(address::format_list(&email.to)),
+            date: email.date.clone(),
+            is_read: email.read,
+            is_starred: email.starred,
+            has_attachments: email.has_attachments,
+            preview: Some(email.body.chars().take(100).collect::<String>()),
+            size: Some(email.size as i64),
+        };
+
+        let existed: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM emails WHERE id = ?)")
+            .bind(&saved.id)
+            .fetch_one(self.pool)
+            .await
+            .context("Failed to look up email")?;
+
+        // An upsert rather than REPLACE, so a re-synced message keeps its thread and search entry
+        sqlx::query(
+            r#"
+            INSERT INTO emails (id, account_id, folder_id, uid, message_id, in_reply_to, reference_ids,
+                                subject, base_subject, from_addr, to_addr, cc_addr, date, size, body_text, is_read,
+                                is_starred, has_attachments, preview)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                folder_id = excluded.folder_id,
+                message_id = excluded.message_id,
+                in_reply_to = excluded.in_reply_to,
+                reference_ids = excluded.reference_ids,
+                subject = excluded.subject,
+                base_subject = excluded.base_subject,
+                from_addr = excluded.from_addr,
+                to_addr = excluded.to_addr,
+                cc_addr = excluded.cc_addr,
+                date = excluded.date,
+                size = excluded.size,
+                body_text = excluded.body_text,
+                is_read = excluded.is_read,
+                is_starred = excluded.is_starred,
+                has_attachments = excluded.has_attachments,
+                preview = excluded.preview
+            "#
+        )
+        .bind(&saved.id)
+        .bind(&saved.account_id)
+        .bind(&saved.folder_id)
+        .bind(saved.uid)
+        .bind(&saved.message_id)
+        .bind(&email.in_reply_to)
+        .bind(Some(email.references.join(" ")).filter(|ids| !ids.is_empty()))
+        .bind(&email.subject)
+        .bind(threading::base_subject(&email.subject))
+        .bind(&email.from)
+        .bind(&saved.to_addr)
+        .bind(address::format_list(&email.cc))
+        .bind(&email.date)
+        .bind(saved.size)
+        .bind(&email.body)
+        .bind(saved.is_read)
+        .bind(saved.is_starred)
+        .bind(saved.has_attachments)
+        .bind(&saved.preview)
+        .execute(self.pool)
+        .await
+        .context("Failed to save email")?;
+
+        Ok((saved, !existed))
+    }
+
+    // The messages that exist, ordered by account, folder and UID
+    pub async fn locate(&self, email_ids: &[String]) -> MailResult<Vec<MessageLocation>> {
+        if email_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Messages synced before folder ids were fixed hold the folder name in folder_id
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT e.id, e.account_id, COALESCE(f.name, e.folder_id) AS folder, e.uid \
+             FROM emails e LEFT JOIN folders f ON f.id = e.folder_id WHERE e.id IN "
+        );
+        push_ids(&mut query, email_ids);
+        query.push(" ORDER BY e.account_id, folder, e.uid");
+
+        let rows = query.build()
+            .fetch_all(self.pool)
+            .await
+            .context("Failed to look up emails")?;
+
+        Ok(rows.into_iter()
+            .map(|row| MessageLocation {
+                id: row.get("id"),
+                account_id: row.get("account_id"),
+                folder: row.get("folder"),
+                uid: row.get::<i64, _>("uid") as u32,
+            })
+            .collect())
+    }
+
+    // Runs `statement` (an UPDATE or DELETE on emails) for the given ids
+    async fn for_ids(&self, statement: &str, email_ids: &[String]) -> MailResult<u64> {
+        if email_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(statement);
+        query.push(" WHERE id IN ");
+        push_ids(&mut query, email_ids);
+
+        let result = query.build()
+            .execute(self.pool)
+            .await
+            .context("Failed to update emails in database")?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn set_read(&self, email_ids: &[String], is_read: bool) -> MailResult<u64> {
+        let statement = if is_read { "UPDATE emails SET is_read = 1" } else { "UPDATE emails SET is_read = 0" };
+        self.for_ids(statement, email_ids).await
+    }
+
+    pub async fn set_starred(&self, email_ids: &[String], starred: bool) -> MailResult<u64> {
+        let statement = if starred { "UPDATE emails SET is_starred = 1" } else { "UPDATE emails SET is_starred = 0" };
+        self.for_ids(statement, email_ids).await
+    }
+
+    pub async fn move_to(&self, email_ids: &[String], folder_id: &str) -> MailResult<u64> {
+        if email_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE emails SET folder_id = ");
+        query.push_bind(folder_id.to_string()).push(" WHERE id IN ");
+        push_ids(&mut query, email_ids);
+
+        let result = query.build()
+            .execute(self.pool)
+            .await
+            .context("Failed to update emails in database")?;
+        Ok(result.rows_affected())
+    }
+
+    // Their attachments and delivery reports go with them (ON DELETE CASCADE)
+    pub async fn delete(&self, email_ids: &[String]) -> MailResult<u64> {
+        self.for_ids("DELETE FROM emails", email_ids).await
+    }
+
+    pub async fn delete_in_folder(&self, folder_id: &str) -> MailResult<u64> {
+        let result = sqlx::query("DELETE FROM emails WHERE folder_id = ?")
+            .bind(folder_id)
+            .execute(self.pool)
+            .await
+            .context("Failed to delete emails from database")?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn stats(&self, folder_id: &str) -> MailResult<FolderStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS total_emails,
+                COUNT(CASE WHEN is_read = 0 THEN 1 END) AS unread_emails,
+                COUNT(CASE WHEN is_starred = 1 THEN 1 END) AS starred_emails,
+                COUNT(CASE WHEN has_attachments = 1 THEN 1 END) AS emails_with_attachments
+            FROM emails
+            WHERE folder_id = ?
+            "#
+        )
+        .bind(folder_id)
+        .fetch_one(self.pool)
+        .await
+        .context("Failed to get folder stats")?;
+
+        Ok(FolderStats {
+            total_emails: row.get::<i64, _>("total_emails") as u32,
+            unread_emails: row.get::<i64, _>("unread_emails") as u32,
+            starred_emails: row.get::<i64, _>("starred_emails") as u32,
+            emails_with_attachments: row.get::<i64, _>("emails_with_attachments") as u32,
+        })
+    }
+}
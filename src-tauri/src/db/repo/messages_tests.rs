@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::repo::{MessageLocation, MessageRepo};
+    use crate::email::address::Mailbox;
+    use crate::imap_client::ImapEmail;
+    use crate::test_utils::seeded_pool;
+    use sqlx::{Pool, Row, Sqlite};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool(
+            r#"
+            INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-INBOX', 'acc', 'INBOX');
+            INSERT INTO folders (id, account_id, name) VALUES ('acc-Archive', 'acc', 'Archive');
+            "#
+        )
+        .await
+    }
+
+    fn imap_email(uid: u32, subject: &str) -> ImapEmail {
+        ImapEmail {
+            id: uid.to_string(),
+            uid,
+            message_id: Some(format!("<{}@example.com>", uid)),
+            in_reply_to: None,
+            references: vec!["<root@example.com>".to_string()],
+            from: "Alice <alice@example.com>".to_string(),
+            to: vec![Mailbox { name: None, address: "me@example.com".to_string() }],
+            cc: vec![],
+            subject: subject.to_string(),
+            body: "Hello there".to_string(),
+            date: Some("2026-03-01T10:00:00Z".to_string()),
+            size: 1200,
+            read: false,
+            starred: false,
+            has_attachments: false,
+            attachments: vec![],
+            folder: "INBOX".to_string(),
+            report: None,
+        }
+    }
+
+    async fn saved(pool: &Pool<Sqlite>, uids: &[u32]) -> Vec<String> {
+        let repo = MessageRepo::new(pool);
+        let mut ids = Vec::new();
+        for &uid in uids {
+            ids.push(repo.save_synced("acc", "acc-INBOX", "INBOX", &imap_email(uid, "Hi")).await.unwrap().0.id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_save_synced_upserts() {
+        let pool = setup_pool().await;
+        let repo = MessageRepo::new(&pool);
+
+        let (email, inserted) = repo.save_synced("acc", "acc-INBOX", "INBOX", &imap_email(7, "Re: Plans")).await.unwrap();
+        assert!(inserted);
+        assert_eq!(email.id, "acc-INBOX-7");
+        assert_eq!(email.to_addr.as_deref(), Some("me@example.com"));
+        assert_eq!(email.preview.as_deref(), Some("Hello there"));
+
+        sqlx::query("UPDATE emails SET thread_id = 't1' WHERE id = 'acc-INBOX-7'").execute(&pool).await.unwrap();
+        let mut changed = imap_email(7, "Re: Plans");
+        changed.read = true;
+        let (_, inserted) = repo.save_synced("acc", "acc-INBOX", "INBOX", &changed).await.unwrap();
+        assert!(!inserted);
+
+        let row = sqlx::query("SELECT COUNT(*) AS n, MAX(is_read) AS is_read, MAX(thread_id) AS thread_id, MAX(base_subject) AS base_subject, MAX(reference_ids) AS refs FROM emails")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>("n"), 1);
+        assert!(row.get::<bool, _>("is_read"));
+        // A re-synced message keeps its thread
+        assert_eq!(row.get::<String, _>("thread_id"), "t1");
+        assert_eq!(row.get::<String, _>("base_subject"), "plans");
+        assert_eq!(row.get::<String, _>("refs"), "<root@example.com>");
+    }
+
+    #[tokio::test]
+    async fn test_locate_skips_unknown_ids() {
+        let pool = setup_pool().await;
+        let ids = saved(&pool, &[2, 1]).await;
+        let repo = MessageRepo::new(&pool);
+
+        let mut wanted = ids.clone();
+        wanted.push("missing".to_string());
+        let locations = repo.locate(&wanted).await.unwrap();
+        assert_eq!(locations, vec![
+            MessageLocation { id: ids[1].clone(), account_id: "acc".to_string(), folder: "INBOX".to_string(), uid: 1 },
+            MessageLocation { id: ids[0].clone(), account_id: "acc".to_string(), folder: "INBOX".to_string(), uid: 2 },
+        ]);
+        assert!(repo.locate(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flags_move_and_stats() {
+        let pool = setup_pool().await;
+        let ids = saved(&pool, &[1, 2, 3]).await;
+        let repo = MessageRepo::new(&pool);
+
+        assert_eq!(repo.set_read(&ids[..2], true).await.unwrap(), 2);
+        assert_eq!(repo.set_starred(&ids[2..], true).await.unwrap(), 1);
+        assert_eq!(repo.set_read(&[], true).await.unwrap(), 0);
+
+        let stats = repo.stats("acc-INBOX").await.unwrap();
+        assert_eq!((stats.total_emails, stats.unread_emails, stats.starred_emails), (3, 1, 1));
+
+        assert_eq!(repo.move_to(&ids[..1], "acc-Archive").await.unwrap(), 1);
+        assert_eq!(repo.stats("acc-INBOX").await.unwrap().total_emails, 2);
+        assert_eq!(repo.stats("acc-Archive").await.unwrap().total_emails, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let pool = setup_pool().await;
+        let ids = saved(&pool, &[1, 2, 3]).await;
+        let repo = MessageRepo::new(&pool);
+
+        assert_eq!(repo.delete(&ids[..1]).await.unwrap(), 1);
+        assert_eq!(repo.delete_in_folder("acc-INBOX").await.unwrap(), 2);
+        assert_eq!(repo.stats("acc-INBOX").await.unwrap().total_emails, 0);
+    }
+}
@@ -0,0 +1,33 @@
+use sqlx::{QueryBuilder, Sqlite};
+
+mod accounts;
+mod attachments;
+mod folders;
+mod messages;
+
+#[cfg(test)]
+mod accounts_tests;
+#[cfg(test)]
+mod attachments_tests;
+#[cfg(test)]
+mod folders_tests;
+#[cfg(test)]
+mod messages_tests;
+
+// Typed access to accounts, folders, messages and attachments, shared by every command
+// module. Queries that belong to a single feature (search, threads, message lists) stay
+// with that feature.
+pub use accounts::{AccountRepo, NewAccount};
+pub use attachments::{AttachmentRepo, AttachmentStats, NewAttachment};
+pub use folders::FolderRepo;
+pub use messages::{FolderStats, MessageLocation, MessageRepo};
+
+// Appends `(?, ?, …)` with the ids bound
+fn push_ids(query: &mut QueryBuilder<'_, Sqlite>, ids: &[String]) {
+    query.push("(");
+    let mut separated = query.separated(", ");
+    for id in ids {
+        separated.push_bind(id.clone());
+    }
+    separated.push_unseparated(")");
+}
@@ -19,22 +19,34 @@ CREATE TABLE IF NOT EXISTS folders (
     account_id TEXT NOT NULL,
     name TEXT NOT NULL,
     delimiter TEXT,
+    -- SPECIAL-USE role (email::folder_role), e.g. 'inbox' or 'sent'
+    role TEXT,
     UNIQUE(account_id, name),
     FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
 );
 
+CREATE INDEX IF NOT EXISTS idx_folders_role ON folders(role, account_id);
+
 CREATE TABLE IF NOT EXISTS emails (
     id TEXT PRIMARY KEY,
     account_id TEXT NOT NULL,
     folder_id TEXT NOT NULL,
     uid INTEGER NOT NULL,
     message_id TEXT,
+    in_reply_to TEXT,
+    -- Message ids from the References header, space separated, oldest first
+    reference_ids TEXT,
     subject TEXT,
+    -- Subject without Re:/Fwd: prefixes and list tags, for threading by subject
+    base_subject TEXT,
+    thread_id TEXT,
     from_addr TEXT,
     to_addr TEXT,
     cc_addr TEXT,
     bcc_addr TEXT,
     date DATETIME,
+    -- Size of the whole message in bytes
+    size INTEGER,
     body_text TEXT,
     body_html TEXT,
     is_read BOOLEAN DEFAULT 0,
@@ -47,13 +59,293 @@ CREATE TABLE IF NOT EXISTS emails (
     FOREIGN KEY(folder_id) REFERENCES folders(id) ON DELETE CASCADE
 );
 
+CREATE INDEX IF NOT EXISTS idx_emails_thread ON emails(account_id, thread_id);
+CREATE INDEX IF NOT EXISTS idx_emails_message_id ON emails(account_id, message_id);
+CREATE INDEX IF NOT EXISTS idx_emails_in_reply_to ON emails(account_id, in_reply_to);
+CREATE INDEX IF NOT EXISTS idx_emails_base_subject ON emails(account_id, base_subject);
+
+-- Keyset pagination of message lists (commands::messages). The sort expressions must stay
+-- identical to the ones in the queries, or SQLite won't use these indexes.
+CREATE INDEX IF NOT EXISTS idx_emails_folder_date ON emails(account_id, folder_id, COALESCE(date, ''), id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_sender ON emails(account_id, folder_id, COALESCE(from_addr, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_subject ON emails(account_id, folder_id, COALESCE(subject, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_size ON emails(account_id, folder_id, COALESCE(size, 0), id);
+CREATE INDEX IF NOT EXISTS idx_emails_date ON emails(COALESCE(date, ''), id);
+-- Lists and unread counts over folders of several accounts (unified inbox, folder roles)
+CREATE INDEX IF NOT EXISTS idx_emails_folder_unread ON emails(folder_id, is_read);
+CREATE INDEX IF NOT EXISTS idx_emails_folder_id_date ON emails(folder_id, COALESCE(date, ''), id);
+CREATE INDEX IF NOT EXISTS idx_emails_unread ON emails(account_id, is_read);
+
 CREATE TABLE IF NOT EXISTS attachments (
     id TEXT PRIMARY KEY,
     email_id TEXT NOT NULL,
+    -- IMAP section of the part within its message; NULL for uploaded files
+    section TEXT,
     filename TEXT,
     mime_type TEXT,
     size INTEGER,
+    content_id TEXT,
+    disposition TEXT,
+    -- SHA-256 of the content, hex; names the file in the blob store
+    content_hash TEXT,
+    -- Only for rows written before the blob store, moved out at startup
     content BLOB,
+    -- Location of the blob relative to the store's root
     path TEXT,
     FOREIGN KEY(email_id) REFERENCES emails(id) ON DELETE CASCADE
 );
+
+CREATE INDEX IF NOT EXISTS idx_attachments_email ON attachments(email_id);
+CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(content_hash);
+
+-- Attachment contents live in the blob store on disk (db::blobs), one file per content hash.
+-- ref_count is the number of attachment rows using the blob; GC removes blobs left at zero.
+CREATE TABLE IF NOT EXISTS blobs (
+    hash TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0,
+    touched_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_insert AFTER INSERT ON attachments WHEN new.content_hash IS NOT NULL BEGIN
+    UPDATE blobs SET ref_count = ref_count + 1 WHERE hash = new.content_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_update AFTER UPDATE OF content_hash ON attachments
+WHEN old.content_hash IS NOT new.content_hash BEGIN
+    UPDATE blobs SET ref_count = ref_count - 1 WHERE hash = old.content_hash;
+    UPDATE blobs SET ref_count = ref_count + 1 WHERE hash = new.content_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS blobs_ref_delete AFTER DELETE ON attachments WHEN old.content_hash IS NOT NULL BEGIN
+    UPDATE blobs SET ref_count = ref_count - 1 WHERE hash = old.content_hash;
+END;
+
+-- Full-text index over emails, keyed by emails.rowid. The trigram tokenizer matches any
+-- substring of three or more characters, which also works for Chinese and Japanese text
+-- that has no spaces between words.
+CREATE VIRTUAL TABLE IF NOT EXISTS emails_fts USING fts5(
+    subject,
+    sender,
+    recipients,
+    body,
+    attachments,
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_insert AFTER INSERT ON emails BEGIN
+    INSERT INTO emails_fts (rowid, subject, sender, recipients, body, attachments)
+    VALUES (
+        new.rowid,
+        new.subject,
+        new.from_addr,
+        COALESCE(new.to_addr, '') || ' ' || COALESCE(new.cc_addr, '') || ' ' || COALESCE(new.bcc_addr, ''),
+        new.body_text,
+        (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.id)
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_update AFTER UPDATE OF subject, from_addr, to_addr, cc_addr, bcc_addr, body_text ON emails BEGIN
+    UPDATE emails_fts SET
+        subject = new.subject,
+        sender = new.from_addr,
+        recipients = COALESCE(new.to_addr, '') || ' ' || COALESCE(new.cc_addr, '') || ' ' || COALESCE(new.bcc_addr, ''),
+        body = new.body_text
+    WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS emails_fts_delete AFTER DELETE ON emails BEGIN
+    DELETE FROM emails_fts WHERE rowid = old.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_insert AFTER INSERT ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = new.email_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_update AFTER UPDATE OF filename ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = new.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = new.email_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_delete AFTER DELETE ON attachments BEGIN
+    UPDATE emails_fts
+    SET attachments = (SELECT group_concat(filename, ' ') FROM attachments WHERE email_id = old.email_id)
+    WHERE rowid = (SELECT rowid FROM emails WHERE id = old.email_id);
+END;
+
+CREATE TABLE IF NOT EXISTS identities (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    display_name TEXT,
+    email TEXT NOT NULL,
+    reply_to TEXT,
+    signature_id TEXT,
+    reply_placement TEXT NOT NULL DEFAULT 'above_quote',
+    forward_placement TEXT NOT NULL DEFAULT 'above_quote',
+    default_bcc TEXT,
+    is_default BOOLEAN DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(account_id, email),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY(signature_id) REFERENCES signatures(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_identities_signature ON identities(signature_id);
+-- At most one default identity per account; commands::identities keeps it at exactly one
+CREATE UNIQUE INDEX IF NOT EXISTS idx_identities_default ON identities(account_id) WHERE is_default;
+
+CREATE TABLE IF NOT EXISTS signatures (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    body_text TEXT,
+    body_html TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_signatures_account ON signatures(account_id);
+
+CREATE TABLE IF NOT EXISTS signature_images (
+    id TEXT PRIMARY KEY,
+    signature_id TEXT NOT NULL,
+    content_id TEXT NOT NULL,
+    filename TEXT,
+    mime_type TEXT,
+    content BLOB NOT NULL,
+    UNIQUE(signature_id, content_id),
+    FOREIGN KEY(signature_id) REFERENCES signatures(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    send_at TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(status, send_at);
+CREATE INDEX IF NOT EXISTS idx_outbox_account ON outbox(account_id);
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- In the search syntax of email::search_query
+    query TEXT NOT NULL,
+    -- JSON arrays of account and folder ids; empty searches everywhere
+    account_ids TEXT NOT NULL DEFAULT '[]',
+    folder_ids TEXT NOT NULL DEFAULT '[]',
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sent_messages (
+    message_id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    subject TEXT,
+    recipients TEXT,
+    dsn_requested BOOLEAN DEFAULT 0,
+    read_receipt_requested BOOLEAN DEFAULT 0,
+    sent_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sent_messages_account ON sent_messages(account_id);
+
+CREATE TABLE IF NOT EXISTS delivery_reports (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    email_id TEXT NOT NULL,
+    original_message_id TEXT,
+    kind TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    action TEXT,
+    status TEXT,
+    diagnostic TEXT,
+    received_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(email_id, recipient),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY(email_id) REFERENCES emails(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_reports_original ON delivery_reports(original_message_id);
+CREATE INDEX IF NOT EXISTS idx_delivery_reports_account ON delivery_reports(account_id);
+
+CREATE TABLE IF NOT EXISTS contacts (
+    id TEXT PRIMARY KEY,
+    -- vCard UID, kept stable across import and export
+    uid TEXT UNIQUE,
+    display_name TEXT,
+    organization TEXT,
+    notes TEXT,
+    photo BLOB,
+    photo_mime_type TEXT,
+    -- Set once the user edits the contact; harvesting then leaves the name alone
+    is_manual BOOLEAN DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS contact_emails (
+    email TEXT PRIMARY KEY COLLATE NOCASE,
+    contact_id TEXT NOT NULL,
+    is_primary BOOLEAN DEFAULT 0,
+    -- Messages we sent to the address vs. messages it appeared on
+    times_contacted INTEGER DEFAULT 0,
+    times_seen INTEGER DEFAULT 0,
+    last_used TEXT,
+    FOREIGN KEY(contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS contact_phones (
+    id TEXT PRIMARY KEY,
+    contact_id TEXT NOT NULL,
+    number TEXT NOT NULL,
+    -- vCard TYPE values, comma separated (cell, work, home, ...)
+    kind TEXT,
+    FOREIGN KEY(contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_contact_emails_contact ON contact_emails(contact_id);
+CREATE INDEX IF NOT EXISTS idx_contact_phones_contact ON contact_phones(contact_id);
+CREATE INDEX IF NOT EXISTS idx_contacts_name ON contacts(display_name COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS carddav_accounts (
+    id TEXT PRIMARY KEY,
+    server_url TEXT NOT NULL,
+    username TEXT NOT NULL,
+    addressbook_url TEXT NOT NULL,
+    display_name TEXT,
+    -- RFC 6578 token from the last sync-collection report
+    sync_token TEXT,
+    last_synced_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One row per card on the server. There is deliberately no foreign key to contacts:
+-- a row whose contact is gone marks a deletion that still has to be pushed.
+CREATE TABLE IF NOT EXISTS carddav_cards (
+    account_id TEXT NOT NULL,
+    href TEXT NOT NULL,
+    contact_id TEXT NOT NULL,
+    etag TEXT,
+    -- contacts.updated_at as of the last upload or download; differs once edited locally
+    synced_revision TEXT,
+    PRIMARY KEY(account_id, href),
+    FOREIGN KEY(account_id) REFERENCES carddav_accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_carddav_cards_contact ON carddav_cards(contact_id);
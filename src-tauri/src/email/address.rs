@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+use crate::email::encoding::{decode_words, format_mailbox};
+
+const MAX_LOCAL_PART_BYTES: usize = 64;
+const MAX_DOMAIN_BYTES: usize = 253;
+const MAX_LABEL_BYTES: usize = 63;
+
+// A single `"Name" <local@domain>` mailbox. The domain is kept as written (it may be
+// Unicode); use `ascii_address` wherever the wire format needs punycode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[error("Invalid address '{input}': {reason}")]
+pub struct AddressError {
+    pub input: String,
+    pub reason: String,
+}
+
+// Every address in a recipient list that failed to parse, so the composer can mark
+// each of them instead of failing on the first
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("{}", .errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+pub struct InvalidAddresses {
+    pub errors: Vec<AddressError>,
+}
+
+impl Mailbox {
+    pub fn local_part(&self) -> &str {
+        self.address.rsplit_once('@').map(|(local, _)| local).unwrap_or(&self.address)
+    }
+
+    pub fn domain(&self) -> &str {
+        self.address.rsplit_once('@').map(|(_, domain)| domain).unwrap_or_default()
+    }
+
+    // A non-ASCII local part can only be delivered over SMTPUTF8 (RFC 6531);
+    // a non-ASCII domain can always be sent as punycode instead
+    pub fn requires_smtputf8(&self) -> bool {
+        !self.local_part().is_ascii()
+    }
+
+    // The address with its domain in IDNA A-label form, e.g. user@xn--mnchen-3ya.de
+    pub fn ascii_address(&self) -> String {
+        match to_ascii_domain(self.domain()) {
+            Ok(domain) => format!("{}@{}", self.local_part(), domain),
+            Err(_) => self.address.clone(),
+        }
+    }
+
+    // Header form, with the name RFC 2047 encoded where needed
+    pub fn to_header(&self) -> String {
+        format_mailbox(self.name.as_deref(), &self.ascii_address())
+    }
+
+    // Readable form with UTF-8 kept as is; the name is quoted whenever it contains
+    // anything but atoms, so the result still parses back unambiguously
+    pub fn to_display(&self) -> String {
+        match self.name.as_deref().filter(|n| !n.is_empty()) {
+            Some(name) if name.split(' ').all(|word| !word.is_empty() && word.chars().all(is_atext)) => {
+                format!("{} <{}>", name, self.address)
+            }
+            Some(name) => format!("\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), self.address),
+            None => self.address.clone(),
+        }
+    }
+
+    // Case-insensitive, as practically every server treats local parts that way
+    pub fn same_address(&self, other: &Mailbox) -> bool {
+        self.address.to_lowercase() == other.address.to_lowercase()
+    }
+}
+
+// Parses a single mailbox, such as an identity's From or Reply-To
+pub fn parse_mailbox(input: &str) -> Result<Mailbox, AddressError> {
+    let (mut mailboxes, mut errors) = parse_list(input);
+    if let Some(error) = errors.pop() {
+        return Err(error);
+    }
+    match (mailboxes.pop(), mailboxes.is_empty()) {
+        (Some(mailbox), true) => Ok(mailbox),
+        (Some(_), false) => Err(error(input, "expected a single address")),
+        (None, _) => Err(error(input, "address is empty")),
+    }
+}
+
+// Parses a header-style address list: `A <a@x>, "Doe, John" <j@y>, Team: b@z, c@z;`.
+// Groups are flattened into their members. Every entry is parsed independently so
+// one typo does not hide the rest.
+pub fn parse_list(input: &str) -> (Vec<Mailbox>, Vec<AddressError>) {
+    let mut mailboxes = Vec::new();
+    let mut errors = Vec::new();
+
+    for item in split_top_level(input) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        match parse_item(item) {
+            Ok(mailbox) => mailboxes.push(mailbox),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (mailboxes, errors)
+}
+
+// Parses the recipient fields of an outgoing message. Entries may themselves be
+// lists. Fails with every invalid address at once.
+pub fn parse_recipients(inputs: &[String]) -> Result<Vec<Mailbox>, InvalidAddresses> {
+    let mut mailboxes: Vec<Mailbox> = Vec::new();
+    let mut errors = Vec::new();
+
+    for input in inputs {
+        let (parsed, failed) = parse_list(input);
+        for mailbox in parsed {
+            if !mailboxes.iter().any(|existing| existing.same_address(&mailbox)) {
+                mailboxes.push(mailbox);
+            }
+        }
+        errors.extend(failed);
+    }
+
+    if errors.is_empty() {
+        Ok(mailboxes)
+    } else {
+        Err(InvalidAddresses { errors })
+    }
+}
+
+// Display form of a list, as stored in the to_addr/cc_addr columns. Names are
+// quoted, so it parses back with `parse_list` without losing entries to embedded commas.
+pub fn format_list(mailboxes: &[Mailbox]) -> String {
+    mailboxes.iter()
+        .map(Mailbox::to_display)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Splits on commas outside quotes, comments and angle brackets, and drops
+// group syntax (`name:` ... `;`) so only the member entries remain
+fn split_top_level(input: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut comment_depth = 0usize;
+    let mut in_angle = false;
+    let mut in_literal = false;
+
+    for c in input.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes || comment_depth > 0 => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' if comment_depth == 0 => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            '(' if !in_quotes => {
+                comment_depth += 1;
+                current.push(c);
+            }
+            ')' if !in_quotes && comment_depth > 0 => {
+                comment_depth -= 1;
+                current.push(c);
+            }
+            '<' if !in_quotes && comment_depth == 0 => {
+                in_angle = true;
+                current.push(c);
+            }
+            '>' if !in_quotes && comment_depth == 0 => {
+                in_angle = false;
+                current.push(c);
+            }
+            // Domain literals like [IPv6:2001:db8::1] contain colons of their own
+            '[' if !in_quotes && comment_depth == 0 => {
+                in_literal = true;
+                current.push(c);
+            }
+            ']' if !in_quotes && comment_depth == 0 => {
+                in_literal = false;
+                current.push(c);
+            }
+            ',' | ';' if !in_quotes && comment_depth == 0 && !in_angle => {
+                items.push(std::mem::take(&mut current));
+            }
+            // A group display name ends at the first top-level colon
+            ':' if !in_quotes && comment_depth == 0 && !in_angle && !in_literal => {
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+
+    items
+}
+
+fn parse_item(item: &str) -> Result<Mailbox, AddressError> {
+    let (name, address) = match find_angle(item) {
+        Some((open, close)) => {
+            if !strip_comments(&item[close + 1..]).trim().is_empty() {
+                return Err(error(item, "unexpected text after '>'"));
+            }
+            let phrase = strip_comments(&item[..open]);
+            (unquote_phrase(phrase.trim()), item[open + 1..close].trim().to_string())
+        }
+        None => {
+            if item.contains('<') || item.contains('>') {
+                return Err(error(item, "unbalanced angle brackets"));
+            }
+            // Legacy `user@example.com (Name)` form
+            let comment = first_comment(item).map(|c| decode_words(c.trim()));
+            (comment.filter(|c| !c.is_empty()), strip_comments(item).trim().to_string())
+        }
+    };
+
+    let address = validate_addr_spec(&address).map_err(|reason| error(item, &reason))?;
+
+    Ok(Mailbox {
+        name: name.filter(|n| !n.is_empty()),
+        address,
+    })
+}
+
+// Finds the `<...>` outside quotes and comments
+fn find_angle(item: &str) -> Option<(usize, usize)> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut depth = 0usize;
+    let mut open = None;
+
+    for (index, c) in item.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' if depth == 0 => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes && depth > 0 => depth -= 1,
+            '<' if !in_quotes && depth == 0 => open = Some(index),
+            '>' if !in_quotes && depth == 0 => return open.map(|open| (open, index)),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut depth = 0usize;
+
+    for c in text.chars() {
+        if escaped {
+            if depth == 0 {
+                stripped.push(c);
+            }
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                escaped = true;
+                if depth == 0 {
+                    stripped.push(c);
+                }
+            }
+            '"' if depth == 0 => {
+                in_quotes = !in_quotes;
+                stripped.push(c);
+            }
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes && depth > 0 => depth -= 1,
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn first_comment(text: &str) -> Option<&str> {
+    let start = text.find('(')?;
+    let end = text.rfind(')')?;
+    (end > start).then(|| &text[start + 1..end])
+}
+
+// Removes quoting from a display name and decodes any RFC 2047 words outside quotes
+fn unquote_phrase(phrase: &str) -> Option<String> {
+    let mut name = String::with_capacity(phrase.len());
+    let mut plain = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in phrase.chars() {
+        if in_quotes {
+            match c {
+                _ if escaped => {
+                    name.push(c);
+                    escaped = false;
+                }
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => name.push(c),
+            }
+        } else if c == '"' {
+            name.push_str(&decode_words(&std::mem::take(&mut plain)));
+            in_quotes = true;
+        } else {
+            plain.push(c);
+        }
+    }
+    name.push_str(&decode_words(&plain));
+
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+// Validates an addr-spec, allowing UTF-8 in both parts (RFC 6532). Returns the
+// address with the domain lowercased.
+fn validate_addr_spec(address: &str) -> Result<String, String> {
+    if address.is_empty() {
+        return Err("address is empty".to_string());
+    }
+    if address.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("address contains whitespace".to_string());
+    }
+
+    let (local, domain) = address.rsplit_once('@')
+        .ok_or_else(|| "missing '@'".to_string())?;
+
+    validate_local_part(local)?;
+    let domain = validate_domain(domain)?;
+
+    Ok(format!("{}@{}", local, domain))
+}
+
+fn validate_local_part(local: &str) -> Result<(), String> {
+    if local.is_empty() {
+        return Err("missing the part before '@'".to_string());
+    }
+    if local.len() > MAX_LOCAL_PART_BYTES {
+        return Err(format!("the part before '@' is longer than {} bytes", MAX_LOCAL_PART_BYTES));
+    }
+
+    if local.starts_with('"') && local.ends_with('"') && local.len() >= 2 {
+        return Ok(());
+    }
+
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err("misplaced '.' before '@'".to_string());
+    }
+    if let Some(c) = local.chars().find(|&c| !is_atext(c) && c != '.') {
+        return Err(format!("'{}' is not allowed before '@'", c));
+    }
+    Ok(())
+}
+
+fn validate_domain(domain: &str) -> Result<String, String> {
+    if domain.is_empty() {
+        return Err("missing domain".to_string());
+    }
+
+    // Address literals such as [192.0.2.1] are passed through
+    if domain.starts_with('[') && domain.ends_with(']') {
+        return Ok(domain.to_string());
+    }
+
+    let ascii = to_ascii_domain(domain)?;
+    if ascii.len() > MAX_DOMAIN_BYTES {
+        return Err(format!("domain is longer than {} bytes", MAX_DOMAIN_BYTES));
+    }
+    for label in ascii.split('.') {
+        if label.is_empty() {
+            return Err("domain has an empty label".to_string());
+        }
+        if label.len() > MAX_LABEL_BYTES {
+            return Err(format!("domain label '{}' is longer than {} bytes", label, MAX_LABEL_BYTES));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!("domain label '{}' starts or ends with '-'", label));
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("domain label '{}' contains invalid characters", label));
+        }
+    }
+
+    Ok(domain.to_lowercase())
+}
+
+// IDNA (UTS #46) conversion of a possibly internationalized domain to punycode
+pub fn to_ascii_domain(domain: &str) -> Result<String, String> {
+    if domain.is_ascii() {
+        return Ok(domain.to_ascii_lowercase());
+    }
+    idna::domain_to_ascii(domain)
+        .map_err(|_| format!("'{}' is not a valid internationalized domain", domain))
+}
+
+// RFC 5322 atext, extended with any non-ASCII character (RFC 6532)
+fn is_atext(c: char) -> bool {
+    !c.is_ascii() || c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn error(input: &str, reason: &str) -> AddressError {
+    AddressError {
+        input: input.to_string(),
+        reason: reason.to_string(),
+    }
+}
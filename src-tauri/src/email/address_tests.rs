@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::address::*;
+
+    fn mailbox(name: Option<&str>, address: &str) -> Mailbox {
+        Mailbox {
+            name: name.map(|s| s.to_string()),
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_list_with_quoted_commas() {
+        let (mailboxes, errors) = parse_list(r#""Doe, John" <john@example.com>, jane@example.com"#);
+        assert!(errors.is_empty());
+        assert_eq!(mailboxes, vec![
+            mailbox(Some("Doe, John"), "john@example.com"),
+            mailbox(None, "jane@example.com"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_list_flattens_groups() {
+        let (mailboxes, errors) = parse_list("Team: a@example.com, B <b@example.com>;, c@example.com");
+        assert!(errors.is_empty());
+        let addresses: Vec<&str> = mailboxes.iter().map(|m| m.address.as_str()).collect();
+        assert_eq!(addresses, vec!["a@example.com", "b@example.com", "c@example.com"]);
+
+        let (mailboxes, errors) = parse_list("undisclosed-recipients:;");
+        assert!(mailboxes.is_empty() && errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_comment_and_encoded_names() {
+        assert_eq!(parse_mailbox("bob@example.com (Bob Smith)").unwrap(), mailbox(Some("Bob Smith"), "bob@example.com"));
+        assert_eq!(parse_mailbox("=?UTF-8?B?5byg5LiJ?= <zhang@example.com>").unwrap(), mailbox(Some("张三"), "zhang@example.com"));
+    }
+
+    #[test]
+    fn test_each_invalid_address_is_reported() {
+        let result = parse_recipients(&[
+            "ok@example.com, broken@, @nouser.com".to_string(),
+            "two..dots@example.com".to_string(),
+        ]);
+        let errors = result.unwrap_err().errors;
+        let inputs: Vec<&str> = errors.iter().map(|e| e.input.as_str()).collect();
+        assert_eq!(inputs, vec!["broken@", "@nouser.com", "two..dots@example.com"]);
+    }
+
+    #[test]
+    fn test_internationalized_addresses() {
+        let idn = parse_mailbox("user@Bücher.example").unwrap();
+        assert_eq!(idn.address, "user@bücher.example");
+        assert_eq!(idn.ascii_address(), "user@xn--bcher-kva.example");
+        assert!(!idn.requires_smtputf8());
+
+        let utf8 = parse_mailbox("用户@例子.广告").unwrap();
+        assert!(utf8.requires_smtputf8());
+    }
+
+    #[test]
+    fn test_format_list_round_trips() {
+        let mailboxes = vec![
+            mailbox(Some("Doe, John"), "john@example.com"),
+            mailbox(Some("张三"), "zhang@example.com"),
+        ];
+        let (parsed, errors) = parse_list(&format_list(&mailboxes));
+        assert!(errors.is_empty());
+        assert_eq!(parsed, mailboxes);
+    }
+}
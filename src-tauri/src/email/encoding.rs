@@ -0,0 +1,131 @@
+use base64::{Engine as _, engine::general_purpose};
+
+// RFC 2047 limits an encoded-word to 75 characters; "=?UTF-8?B?" + "?=" takes 12
+// of them, leaving 63 for base64, i.e. at most 45 input bytes per word.
+const MAX_ENCODED_WORD_BYTES: usize = 45;
+
+const SPECIALS: &[char] = &['(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '.', '"'];
+
+// Encodes a display name so it can be placed in an address header.
+// Plain ASCII names are left alone, names containing specials are quoted and
+// anything non-ASCII becomes one or more RFC 2047 encoded-words.
+pub fn encode_phrase(phrase: &str) -> String {
+    if phrase.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        if phrase.contains(SPECIALS) {
+            let escaped = phrase.replace('\\', "\\\\").replace('"', "\\\"");
+            return format!("\"{}\"", escaped);
+        }
+        return phrase.to_string();
+    }
+
+    encode_words(phrase)
+}
+
+// Formats a single mailbox, e.g. `=?UTF-8?B?5byg5LiJ?= <zhang@example.com>`
+pub fn format_mailbox(name: Option<&str>, address: &str) -> String {
+    match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => format!("{} <{}>", encode_phrase(name), address),
+        None => address.to_string(),
+    }
+}
+
+fn encode_words(text: &str) -> String {
+    let mut words = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk_len = 0;
+
+    // Split on character boundaries so no UTF-8 sequence straddles two words
+    for (index, c) in text.char_indices() {
+        if chunk_len + c.len_utf8() > MAX_ENCODED_WORD_BYTES {
+            words.push(encode_word(&text[chunk_start..index]));
+            chunk_start = index;
+            chunk_len = 0;
+        }
+        chunk_len += c.len_utf8();
+    }
+    if chunk_len > 0 {
+        words.push(encode_word(&text[chunk_start..]));
+    }
+
+    // Adjacent encoded-words are folded onto continuation lines
+    words.join("\r\n ")
+}
+
+fn encode_word(text: &str) -> String {
+    format!("=?UTF-8?B?{}?=", general_purpose::STANDARD.encode(text.as_bytes()))
+}
+
+// Decodes RFC 2047 encoded-words in a header phrase. Whitespace between two
+// adjacent encoded-words is dropped; anything that fails to decode is kept as is.
+pub fn decode_words(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut previous_was_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_word(candidate) {
+            Some((word, consumed)) => {
+                if !(previous_was_word && before.trim().is_empty()) {
+                    decoded.push_str(before);
+                }
+                decoded.push_str(&word);
+                rest = &candidate[consumed..];
+                previous_was_word = true;
+            }
+            None => {
+                decoded.push_str(before);
+                decoded.push_str("=?");
+                rest = &candidate[2..];
+                previous_was_word = false;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+// Decodes one `=?charset?encoding?text?=` word, returning it with the bytes consumed
+fn decode_word(word: &str) -> Option<(String, usize)> {
+    let inner = word.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let end = inner.find("?=")?;
+    let payload = &inner[..end];
+    if payload.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let bytes = match encoding.to_ascii_uppercase().as_str() {
+        "B" => general_purpose::STANDARD.decode(payload).ok()?,
+        "Q" => decode_q(payload)?,
+        _ => return None,
+    };
+
+    // RFC 2231 allows a language suffix, e.g. UTF-8*en
+    let charset = charset.split('*').next().unwrap_or_default().to_ascii_lowercase();
+    let text = match charset.as_str() {
+        "iso-8859-1" | "latin1" => bytes.iter().map(|&b| b as char).collect(),
+        _ => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+
+    let consumed = word.len() - inner[end + 2..].len();
+    Some((text, consumed))
+}
+
+fn decode_q(payload: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(payload.len());
+    let mut iter = payload.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    Some(bytes)
+}
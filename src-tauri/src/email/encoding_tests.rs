@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::encoding::*;
+
+    #[test]
+    fn test_plain_ascii_name_is_unchanged() {
+        assert_eq!(encode_phrase("John Doe"), "John Doe");
+        assert_eq!(format_mailbox(Some("John Doe"), "john@example.com"), "John Doe <john@example.com>");
+    }
+
+    #[test]
+    fn test_name_with_specials_is_quoted() {
+        assert_eq!(encode_phrase("Doe, John"), "\"Doe, John\"");
+        assert_eq!(encode_phrase("J. \"Jack\" Doe"), "\"J. \\\"Jack\\\" Doe\"");
+    }
+
+    #[test]
+    fn test_non_ascii_name_is_rfc2047_encoded() {
+        assert_eq!(encode_phrase("张三"), "=?UTF-8?B?5byg5LiJ?=");
+        assert_eq!(
+            format_mailbox(Some("张三"), "zhang@example.com"),
+            "=?UTF-8?B?5byg5LiJ?= <zhang@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_long_name_is_split_on_char_boundaries() {
+        let name = "测试".repeat(20);
+        let encoded = encode_phrase(&name);
+
+        let words: Vec<&str> = encoded.split("\r\n ").collect();
+        assert!(words.len() > 1);
+        for word in &words {
+            assert!(word.len() <= 75);
+            assert!(word.starts_with("=?UTF-8?B?") && word.ends_with("?="));
+        }
+    }
+
+    #[test]
+    fn test_empty_name_gives_bare_address() {
+        assert_eq!(format_mailbox(None, "a@example.com"), "a@example.com");
+        assert_eq!(format_mailbox(Some("  "), "a@example.com"), "a@example.com");
+    }
+
+    #[test]
+    fn test_decode_encoded_words() {
+        assert_eq!(decode_words("=?UTF-8?B?5byg5LiJ?="), "张三");
+        assert_eq!(decode_words("=?iso-8859-1?Q?Andr=E9_Pirard?="), "André Pirard");
+        assert_eq!(decode_words("=?UTF-8?Q?a?= =?UTF-8?Q?b?= c"), "ab c");
+        assert_eq!(decode_words("plain =?bogus"), "plain =?bogus");
+    }
+}
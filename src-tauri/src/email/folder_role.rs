@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+// What a folder is for, independent of its (often localized) name. Taken from the
+// SPECIAL-USE attributes of RFC 6154, plus the older Gmail XLIST spellings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderRole {
+    Inbox,
+    Sent,
+    Drafts,
+    Trash,
+    Junk,
+    Archive,
+    All,
+    Flagged,
+}
+
+impl FolderRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FolderRole::Inbox => "inbox",
+            FolderRole::Sent => "sent",
+            FolderRole::Drafts => "drafts",
+            FolderRole::Trash => "trash",
+            FolderRole::Junk => "junk",
+            FolderRole::Archive => "archive",
+            FolderRole::All => "all",
+            FolderRole::Flagged => "flagged",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "inbox" => Some(FolderRole::Inbox),
+            "sent" => Some(FolderRole::Sent),
+            "drafts" => Some(FolderRole::Drafts),
+            "trash" => Some(FolderRole::Trash),
+            "junk" => Some(FolderRole::Junk),
+            "archive" => Some(FolderRole::Archive),
+            "all" => Some(FolderRole::All),
+            "flagged" => Some(FolderRole::Flagged),
+            _ => None,
+        }
+    }
+
+    // The role of a folder from its LIST attributes. INBOX has no attribute of its own
+    // but its name is fixed by RFC 3501 (case-insensitively), so it is the one name we go by.
+    pub fn detect(name: &str, attributes: &[String]) -> Option<Self> {
+        if name.eq_ignore_ascii_case("INBOX") {
+            return Some(FolderRole::Inbox);
+        }
+        attributes.iter().find_map(|attribute| {
+            match attribute.trim_start_matches('\\').to_ascii_lowercase().as_str() {
+                "inbox" => Some(FolderRole::Inbox),
+                "sent" => Some(FolderRole::Sent),
+                "drafts" => Some(FolderRole::Drafts),
+                "trash" => Some(FolderRole::Trash),
+                "junk" | "spam" => Some(FolderRole::Junk),
+                "archive" => Some(FolderRole::Archive),
+                "all" | "allmail" => Some(FolderRole::All),
+                "flagged" | "starred" => Some(FolderRole::Flagged),
+                _ => None,
+            }
+        })
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::folder_role::*;
+
+    fn attributes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_inbox_by_name() {
+        assert_eq!(FolderRole::detect("INBOX", &[]), Some(FolderRole::Inbox));
+        assert_eq!(FolderRole::detect("Inbox", &attributes(&["\\HasNoChildren"])), Some(FolderRole::Inbox));
+    }
+
+    #[test]
+    fn test_special_use_attributes() {
+        assert_eq!(FolderRole::detect("Gesendet", &attributes(&["\\HasNoChildren", "\\Sent"])), Some(FolderRole::Sent));
+        assert_eq!(FolderRole::detect("Brouillons", &attributes(&["\\Drafts"])), Some(FolderRole::Drafts));
+        assert_eq!(FolderRole::detect("Papierkorb", &attributes(&["\\Trash"])), Some(FolderRole::Trash));
+        assert_eq!(FolderRole::detect("Archiv", &attributes(&["\\Archive"])), Some(FolderRole::Archive));
+        assert_eq!(FolderRole::detect("[Gmail]/Spam", &attributes(&["\\Junk"])), Some(FolderRole::Junk));
+    }
+
+    #[test]
+    fn test_xlist_spellings() {
+        assert_eq!(FolderRole::detect("[Gmail]/Spam", &attributes(&["\\Spam"])), Some(FolderRole::Junk));
+        assert_eq!(FolderRole::detect("[Gmail]/All Mail", &attributes(&["\\AllMail"])), Some(FolderRole::All));
+        assert_eq!(FolderRole::detect("[Gmail]/Starred", &attributes(&["\\Starred"])), Some(FolderRole::Flagged));
+    }
+
+    #[test]
+    fn test_names_alone_are_not_roles() {
+        assert_eq!(FolderRole::detect("Sent", &[]), None);
+        assert_eq!(FolderRole::detect("Trash", &attributes(&["\\HasNoChildren"])), None);
+    }
+
+    #[test]
+    fn test_as_str_round_trips() {
+        for role in [
+            FolderRole::Inbox, FolderRole::Sent, FolderRole::Drafts, FolderRole::Trash,
+            FolderRole::Junk, FolderRole::Archive, FolderRole::All, FolderRole::Flagged,
+        ] {
+            assert_eq!(FolderRole::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(FolderRole::parse("outbox"), None);
+    }
+}
@@ -0,0 +1,133 @@
+// Content-type detection for attachments whose declared type is missing or unusable
+
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+// Sniffed types that say less than the extension: containers that Office documents (and
+// many other formats) are built on, and magic bytes short enough to start plain text
+const GENERIC_SNIFFED_TYPES: &[&str] = &[
+    "application/zip",
+    "application/x-ole-storage",
+    "application/xml",
+    "application/gzip",
+    "image/bmp",
+];
+
+// Keeps a well-formed declared type, otherwise sniffs the content. A generic or unreliable
+// sniff gives way to the extension, so report.docx isn't labelled application/zip.
+pub fn resolve_mime_type(declared: &str, filename: &str, content: &[u8]) -> String {
+    let declared = declared.trim().to_ascii_lowercase();
+    if is_valid_mime_type(&declared) && declared != DEFAULT_MIME_TYPE {
+        return declared;
+    }
+
+    match sniff(content) {
+        Some(sniffed) if !GENERIC_SNIFFED_TYPES.contains(&sniffed) => sniffed,
+        sniffed => from_extension(filename).or(sniffed).unwrap_or(DEFAULT_MIME_TYPE),
+    }
+    .to_string()
+}
+
+pub fn is_valid_mime_type(value: &str) -> bool {
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    match essence.split_once('/') {
+        Some((c_type, c_subtype)) => is_token(c_type) && is_token(c_subtype),
+        None => false,
+    }
+}
+
+// Detects common formats from their magic bytes
+pub fn sniff(content: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xFF\xD8\xFF", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"II*\x00", "image/tiff"),
+        (b"MM\x00*", "image/tiff"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1F\x8B", "application/gzip"),
+        (b"7z\xBC\xAF\x27\x1C", "application/x-7z-compressed"),
+        (b"Rar!\x1A\x07", "application/vnd.rar"),
+        (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "application/x-ole-storage"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"BEGIN:VCARD", "text/vcard"),
+        (b"BEGIN:VCALENDAR", "text/calendar"),
+    ];
+
+    for (magic, mime_type) in SIGNATURES {
+        if content.starts_with(magic) {
+            return Some(mime_type);
+        }
+    }
+
+    // RIFF and ISO media containers carry their real type a few bytes in
+    if content.len() >= 12 && &content[0..4] == b"RIFF" {
+        match &content[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    if content.len() >= 12 && &content[4..8] == b"ftyp" {
+        return Some(match &content[8..12] {
+            b"heic" | b"heix" | b"mif1" => "image/heic",
+            b"M4A " => "audio/mp4",
+            b"qt  " => "video/quicktime",
+            _ => "video/mp4",
+        });
+    }
+
+    let head = &content[..content.len().min(512)];
+    if head.starts_with(b"<?xml") || head.starts_with(b"<svg") {
+        if head.windows(4).any(|w| w == b"<svg") {
+            return Some("image/svg+xml");
+        }
+        return Some("application/xml");
+    }
+
+    None
+}
+
+pub fn from_extension(filename: &str) -> Option<&'static str> {
+    let extension = filename.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "htm" | "html" => "text/html",
+        "md" => "text/markdown",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "vcf" => "text/vcard",
+        "ics" => "text/calendar",
+        "eml" => "message/rfc822",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "doc" => "application/msword",
+        "xls" => "application/vnd.ms-excel",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => return None,
+    })
+}
+
+// RFC 2045 token: printable ASCII without spaces or tspecials
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| {
+        b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&b)
+    })
+}
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::mime_sniff::*;
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(sniff(b"\xFF\xD8\xFF\xE0...."), Some("image/jpeg"));
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"\x00\x00\x00\x18ftypmp42"), Some("video/mp4"));
+        assert_eq!(sniff(b"just some text"), None);
+    }
+
+    #[test]
+    fn test_valid_declared_type_is_kept() {
+        assert_eq!(resolve_mime_type("Image/PNG", "a.bin", b"%PDF-"), "image/png");
+        assert_eq!(resolve_mime_type("text/plain; charset=utf-8", "a.txt", b""), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn test_invalid_declared_type_is_sniffed() {
+        assert_eq!(resolve_mime_type("", "scan", b"%PDF-1.4"), "application/pdf");
+        assert_eq!(resolve_mime_type("pdf", "scan", b"%PDF-1.4"), "application/pdf");
+        assert_eq!(resolve_mime_type("application/octet-stream", "logo", b"GIF89a"), "image/gif");
+    }
+
+    #[test]
+    fn test_falls_back_to_extension_then_octet_stream() {
+        assert_eq!(resolve_mime_type("", "notes.md", b"# Notes"), "text/markdown");
+        assert_eq!(resolve_mime_type("", "data.unknown", b"\x00\x01"), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_extension_wins_over_generic_sniff() {
+        // Office documents are ZIP or OLE containers underneath
+        assert_eq!(resolve_mime_type("", "report.docx", b"PK\x03\x04\x14\x00\x06\x00"), "application/vnd.openxmlformats-officedocument.wordprocessingml.document");
+        assert_eq!(resolve_mime_type("application/octet-stream", "budget.xls", b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1\x00"), "application/vnd.ms-excel");
+        // Two bytes of magic are no match for a text file's extension
+        assert_eq!(resolve_mime_type("", "notes.txt", b"BMW service due in May"), "text/plain");
+
+        // Without a telling extension the sniffed type still counts
+        assert_eq!(resolve_mime_type("", "archive", b"PK\x03\x04\x14\x00"), "application/zip");
+        assert_eq!(resolve_mime_type("", "image.dat", b"BM\x36\x00"), "image/bmp");
+        // A specific sniff beats a mismatched extension
+        assert_eq!(resolve_mime_type("", "photo.txt", b"\x89PNG\r\n\x1a\n...."), "image/png");
+    }
+}
@@ -1 +1,25 @@
+pub mod address;
+pub mod encoding;
+pub mod folder_role;
+pub mod mime_sniff;
 pub mod parser;
+pub mod search_query;
+pub mod signature;
+pub mod threading;
+
+#[cfg(test)]
+mod address_tests;
+#[cfg(test)]
+mod encoding_tests;
+#[cfg(test)]
+mod folder_role_tests;
+#[cfg(test)]
+mod mime_sniff_tests;
+#[cfg(test)]
+mod parser_tests;
+#[cfg(test)]
+mod search_query_tests;
+#[cfg(test)]
+mod signature_tests;
+#[cfg(test)]
+mod threading_tests;
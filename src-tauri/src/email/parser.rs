@@ -1,81 +1,452 @@
-use mail_parser::{Message, MimeHeaders};
+use mail_parser::{Message, MessagePart, MimeHeaders, PartType};
 use serde::{Deserialize, Serialize};
+use crate::contacts::vcard::{self, VCard};
+use crate::email::address;
+use crate::email::mime_sniff::resolve_mime_type;
+use crate::error::{MailError, MailResult};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmailAddress {
-    pub name: Option<String>,
-    pub address: String,
+pub use crate::email::address::Mailbox as EmailAddress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Disposition {
+    Inline,
+    Attachment,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// One node of the MIME tree, as declared by its headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MimePart {
+    // IMAP section number (BODY[1.2]) of the part. For a multipart it is the prefix of its
+    // children's numbers, empty at the top level.
+    pub section: String,
+    // Lower-case type/subtype; text/plain when the part doesn't say
+    pub content_type: String,
+    pub charset: Option<String>,
+    pub disposition: Option<Disposition>,
+    // Content-Transfer-Encoding, lower case
+    pub encoding: Option<String>,
+    // From the Content-Disposition filename or Content-Type name, RFC 2047/2231 decoded
+    pub filename: Option<String>,
+    pub content_id: Option<String>,
+    // Decoded size in bytes
+    pub size: usize,
+    pub is_attachment: bool,
+    // The parts of a multipart, or the top part of an attached message/rfc822
+    pub children: Vec<MimePart>,
+    // Decoded content of a leaf part, text converted to UTF-8; not sent to the frontend
+    #[serde(skip)]
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailAttachment {
+    pub section: String,
     pub filename: Option<String>,
     pub mime_type: String,
+    pub disposition: Disposition,
     pub size: usize,
     pub content_id: Option<String>,
-    // We'll handle content separately or save it
+    #[serde(skip)]
+    pub content: Vec<u8>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ParsedEmail {
     pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    // Oldest ancestor first, as in the References header
+    pub references: Vec<String>,
     pub subject: Option<String>,
     pub from: Vec<EmailAddress>,
     pub to: Vec<EmailAddress>,
     pub cc: Vec<EmailAddress>,
     pub bcc: Vec<EmailAddress>,
     pub date: Option<String>,
+    // Every displayable body part in order, not just the first
     pub body_text: Option<String>,
     pub body_html: Option<String>,
+    pub parts: MimePart,
+    // Attachments of this message; those of an attached message stay inside its part
     pub attachments: Vec<EmailAttachment>,
+    pub report: Option<DeliveryReport>,
+    // Contacts from text/vcard attachments, ready for one-click import
+    pub contact_cards: Vec<VCard>,
+}
+
+impl ParsedEmail {
+    pub fn has_attachments(&self) -> bool {
+        !self.attachments.is_empty()
+    }
 }
 
-pub fn parse_email(raw_email: &[u8]) -> Result<ParsedEmail, String> {
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    // SMTP delivery status notification / bounce (RFC 3464)
+    Delivery,
+    // Message disposition notification / read receipt (RFC 8098)
+    Disposition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryReport {
+    pub kind: ReportKind,
+    pub original_message_id: Option<String>,
+    pub original_envelope_id: Option<String>,
+    pub reporting_mta: Option<String>,
+    pub recipients: Vec<RecipientStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipientStatus {
+    pub recipient: String,
+    // DSN action (delivered, failed, delayed, ...) or MDN disposition (displayed, deleted, ...)
+    pub action: Option<String>,
+    // Enhanced status code such as 5.1.1, DSN only
+    pub status: Option<String>,
+    pub diagnostic: Option<String>,
+}
+
+pub fn parse_email(raw_email: &[u8]) -> MailResult<ParsedEmail> {
     let message = Message::parse(raw_email)
-        .ok_or_else(|| "Failed to parse email".to_string())?;
+        .ok_or_else(|| MailError::validation("Failed to parse email"))?;
 
     let from = convert_addresses(message.from());
     let to = convert_addresses(message.to());
     let cc = convert_addresses(message.cc());
     let bcc = convert_addresses(message.bcc());
 
-    let attachments = message
-        .attachments()
-        .map(|att| EmailAttachment {
-            filename: att.attachment_name().map(|s| s.to_string()),
-            mime_type: att.content_type().map(|c| c.c_type.to_string()).unwrap_or_default(),
-            size: att.contents().len(),
-            content_id: att.content_id().map(|s| s.to_string()),
-        })
+    let parts = message_tree(&message, "");
+    let mut attachments = Vec::new();
+    collect_attachments(&parts, &mut attachments);
+
+    let contact_cards = attachments.iter()
+        .filter(|attachment| is_vcard(&attachment.mime_type, attachment.filename.as_deref()))
+        .flat_map(|attachment| vcard::parse(&String::from_utf8_lossy(&attachment.content)).unwrap_or_default())
         .collect();
 
     Ok(ParsedEmail {
         message_id: message.message_id().map(|s| s.to_string()),
+        in_reply_to: header_ids(message.in_reply_to()).pop(),
+        references: header_ids(message.references()),
         subject: message.subject().map(|s| s.to_string()),
         from,
         to,
         cc,
         bcc,
         date: message.date().map(|d| d.to_rfc3339()),
-        body_text: message.body_text(0).map(|s| s.to_string()),
-        body_html: message.body_html(0).map(|s| s.to_string()),
+        body_text: join_bodies((0..message.text_body.len()).filter_map(|i| message.body_text(i)), "\n\n"),
+        body_html: join_bodies((0..message.html_body.len()).filter_map(|i| message.body_html(i)), "\n"),
+        parts,
         attachments,
+        report: extract_report(&message),
+        contact_cards,
+    })
+}
+
+// Inline images between the body parts are listed among them and skipped here
+fn join_bodies<'a>(bodies: impl Iterator<Item = std::borrow::Cow<'a, str>>, separator: &str) -> Option<String> {
+    let bodies: Vec<_> = bodies.collect();
+    if bodies.is_empty() {
+        None
+    } else {
+        Some(bodies.join(separator))
+    }
+}
+
+// The top part of a message. Section numbers follow RFC 3501: the children of a top-level
+// multipart are 1, 2, ...; a message that isn't multipart has its body at <prefix>.1
+fn message_tree(message: &Message, prefix: &str) -> MimePart {
+    let Some(root) = message.parts.first() else {
+        return empty_part(prefix);
+    };
+    let section = match root.body {
+        PartType::Multipart(_) => prefix.to_string(),
+        _ => child_section(prefix, 1),
+    };
+    mime_part(message, 0, section, false)
+}
+
+fn child_section(prefix: &str, position: usize) -> String {
+    if prefix.is_empty() {
+        position.to_string()
+    } else {
+        format!("{}.{}", prefix, position)
+    }
+}
+
+fn empty_part(section: &str) -> MimePart {
+    MimePart {
+        section: section.to_string(),
+        content_type: "text/plain".to_string(),
+        charset: None,
+        disposition: None,
+        encoding: None,
+        filename: None,
+        content_id: None,
+        size: 0,
+        is_attachment: false,
+        children: Vec::new(),
+        content: Vec::new(),
+    }
+}
+
+fn mime_part(message: &Message, id: usize, section: String, in_related: bool) -> MimePart {
+    let part = &message.parts[id];
+    let content_type = declared_type(part);
+    let disposition = part.content_disposition().and_then(|d| match d.ctype().to_ascii_lowercase().as_str() {
+        "attachment" => Some(Disposition::Attachment),
+        "inline" => Some(Disposition::Inline),
+        _ => None,
+    });
+    let filename = part.attachment_name().map(str::to_string);
+    let content_id = part.content_id().map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string());
+
+    let (children, content) = match &part.body {
+        PartType::Multipart(ids) => {
+            let related = content_type == "multipart/related";
+            let children = ids.iter().enumerate()
+                .map(|(i, &child)| mime_part(message, child, child_section(&section, i + 1), related))
+                .collect();
+            (children, Vec::new())
+        }
+        PartType::Message(inner) => (vec![message_tree(inner, &section)], part.contents().to_vec()),
+        _ => (Vec::new(), part.contents().to_vec()),
+    };
+
+    let is_attachment = is_attachment(&content_type, disposition, filename.is_some(), content_id.is_some() && in_related);
+    MimePart {
+        section,
+        charset: part.content_type()
+            .and_then(|c| c.attribute("charset"))
+            .map(|charset| charset.trim().to_ascii_lowercase()),
+        encoding: part.content_transfer_encoding().map(|encoding| encoding.trim().to_ascii_lowercase()),
+        disposition,
+        filename,
+        content_id,
+        size: match part.body {
+            PartType::Multipart(_) => part.offset_end.saturating_sub(part.offset_body),
+            _ => content.len(),
+        },
+        is_attachment,
+        children,
+        content,
+        content_type,
+    }
+}
+
+fn declared_type(part: &MessagePart) -> String {
+    part.content_type()
+        .map(|c| match c.subtype() {
+            Some(subtype) => format!("{}/{}", c.ctype(), subtype),
+            None => c.ctype().to_string(),
+        })
+        .unwrap_or_else(|| "text/plain".to_string())
+        .to_ascii_lowercase()
+}
+
+// Whether a part is something the user would call an attachment: explicit attachments and
+// attached messages, but not message bodies or the images an HTML body shows inline
+fn is_attachment(content_type: &str, disposition: Option<Disposition>, has_filename: bool, inline_resource: bool) -> bool {
+    if content_type.starts_with("multipart/") {
+        return false;
+    }
+    if disposition == Some(Disposition::Attachment) {
+        return true;
+    }
+    if matches!(content_type, "message/rfc822" | "message/global") {
+        return true;
+    }
+    if inline_resource {
+        return false;
+    }
+    match content_type {
+        "text/plain" | "text/html" => has_filename && disposition.is_none(),
+        _ => has_filename || !content_type.starts_with("text/"),
+    }
+}
+
+// Attachments in document order, without descending into attached messages
+fn collect_attachments(part: &MimePart, attachments: &mut Vec<EmailAttachment>) {
+    if part.is_attachment {
+        attachments.push(EmailAttachment {
+            section: part.section.clone(),
+            filename: part.filename.clone(),
+            mime_type: resolve_mime_type(&part.content_type, part.filename.as_deref().unwrap_or_default(), &part.content),
+            disposition: part.disposition.unwrap_or(Disposition::Attachment),
+            size: part.size,
+            content_id: part.content_id.clone(),
+            content: part.content.clone(),
+        });
+        return;
+    }
+    for child in &part.children {
+        collect_attachments(child, attachments);
+    }
+}
+
+// Looks for the machine-readable part of a multipart/report message
+fn extract_report(message: &Message) -> Option<DeliveryReport> {
+    let mut report = None;
+    let mut original_headers = None;
+
+    for part in &message.parts {
+        let Some(content_type) = part.content_type() else {
+            continue;
+        };
+        let c_type = content_type.ctype().to_ascii_lowercase();
+        let c_subtype = content_type.subtype().unwrap_or_default().to_ascii_lowercase();
+
+        match (c_type.as_str(), c_subtype.as_str()) {
+            ("message", "delivery-status") | ("message", "global-delivery-status") => {
+                report = Some((ReportKind::Delivery, part.contents()));
+            }
+            ("message", "disposition-notification") | ("message", "global-disposition-notification") => {
+                report = Some((ReportKind::Disposition, part.contents()));
+            }
+            ("text", "rfc822-headers") | ("message", "rfc822") | ("message", "global") | ("message", "global-headers") => {
+                original_headers = Some(part.contents());
+            }
+            _ => {}
+        }
+    }
+
+    let (kind, fields) = report?;
+    let blocks = parse_field_blocks(&String::from_utf8_lossy(fields));
+    let (per_message, per_recipient) = blocks.split_first()?;
+
+    let mut recipients = Vec::new();
+    let mut original_message_id = field(per_message, "original-message-id").map(strip_angle_brackets);
+
+    match kind {
+        ReportKind::Delivery => {
+            for block in per_recipient {
+                let Some(recipient) = field(block, "final-recipient").or_else(|| field(block, "original-recipient")) else {
+                    continue;
+                };
+                recipients.push(RecipientStatus {
+                    recipient: strip_type(recipient),
+                    action: field(block, "action").map(|a| a.to_ascii_lowercase()),
+                    status: field(block, "status").and_then(|s| s.split_whitespace().next().map(|s| s.to_string())),
+                    diagnostic: field(block, "diagnostic-code").map(strip_type),
+                });
+            }
+        }
+        ReportKind::Disposition => {
+            // An MDN is a single block describing one recipient
+            let recipient = field(per_message, "final-recipient")
+                .or_else(|| field(per_message, "original-recipient"))
+                .map(strip_type)
+                .unwrap_or_default();
+            let disposition = field(per_message, "disposition")
+                .and_then(|d| d.rsplit(';').next().map(|d| d.trim().to_ascii_lowercase()));
+            recipients.push(RecipientStatus {
+                recipient,
+                action: disposition,
+                status: None,
+                diagnostic: field(per_message, "error").map(|e| e.to_string()),
+            });
+        }
+    }
+
+    // Bounces usually carry the original headers rather than an Original-Message-ID field
+    if original_message_id.is_none() {
+        original_message_id = original_headers
+            .map(|headers| parse_field_blocks(&String::from_utf8_lossy(headers)))
+            .and_then(|blocks| blocks.first().and_then(|block| field(block, "message-id")).map(strip_angle_brackets));
+    }
+
+    Some(DeliveryReport {
+        kind,
+        original_message_id,
+        original_envelope_id: field(per_message, "original-envelope-id").map(|id| id.to_string()),
+        reporting_mta: field(per_message, "reporting-mta")
+            .or_else(|| field(per_message, "reporting-ua"))
+            .map(strip_type),
+        recipients,
     })
 }
 
+// Splits "Name: value" fields into blank-line separated groups, unfolding continuation lines
+fn parse_field_blocks(text: &str) -> Vec<Vec<(String, String)>> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = current.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            current.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+
+    blocks
+}
+
+fn field<'a>(block: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    block.iter()
+        .find(|(field_name, _)| field_name == name)
+        .map(|(_, value)| value.as_str())
+}
+
+// "rfc822; user@example.com" -> "user@example.com"
+fn strip_type(value: &str) -> String {
+    match value.split_once(';') {
+        Some((_, rest)) => rest.trim().to_string(),
+        None => value.trim().to_string(),
+    }
+}
+
+fn strip_angle_brackets(value: &str) -> String {
+    value.trim().trim_start_matches('<').trim_end_matches('>').to_string()
+}
+
+fn header_ids(value: &mail_parser::HeaderValue) -> Vec<String> {
+    match value {
+        mail_parser::HeaderValue::Text(id) => vec![id.to_string()],
+        mail_parser::HeaderValue::TextList(ids) => ids.iter().map(|id| id.to_string()).collect(),
+        _ => vec![],
+    }
+}
+
 fn convert_addresses(addresses: &mail_parser::HeaderValue) -> Vec<EmailAddress> {
     match addresses {
-        mail_parser::HeaderValue::Address(addr) => vec![EmailAddress {
-            name: addr.name.clone().map(|s| s.to_string()),
-            address: addr.address.as_ref().map(|s| s.to_string()).unwrap_or_default(),
-        }],
-        mail_parser::HeaderValue::AddressList(list) => list
+        mail_parser::HeaderValue::Address(addr) => to_mailbox(addr).into_iter().collect(),
+        mail_parser::HeaderValue::AddressList(list) => list.iter().filter_map(to_mailbox).collect(),
+        // Groups are flattened into their members
+        mail_parser::HeaderValue::Group(group) => group.addresses.iter().filter_map(to_mailbox).collect(),
+        mail_parser::HeaderValue::GroupList(groups) => groups
             .iter()
-            .map(|addr| EmailAddress {
-                name: addr.name.clone().map(|s| s.to_string()),
-                address: addr.address.as_ref().map(|s| s.to_string()).unwrap_or_default(),
-            })
+            .flat_map(|group| group.addresses.iter().filter_map(to_mailbox))
             .collect(),
         _ => vec![],
     }
 }
+
+fn is_vcard(mime_type: &str, filename: Option<&str>) -> bool {
+    matches!(mime_type, "text/vcard" | "text/x-vcard" | "text/directory")
+        || filename.is_some_and(|name| name.to_ascii_lowercase().ends_with(".vcf"))
+}
+
+fn to_mailbox(addr: &mail_parser::Addr) -> Option<EmailAddress> {
+    let raw = addr.address.as_deref()?.trim();
+    // Normalized through the shared parser; malformed incoming addresses are kept verbatim
+    let address = address::parse_mailbox(raw)
+        .map(|mailbox| mailbox.address)
+        .unwrap_or_else(|_| raw.to_string());
+    Some(EmailAddress {
+        name: addr.name.as_deref().map(str::trim).filter(|n| !n.is_empty()).map(str::to_string),
+        address,
+    })
+}
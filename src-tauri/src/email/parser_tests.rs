@@ -0,0 +1,290 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::parser::*;
+
+    const BOUNCE: &str = "From: MAILER-DAEMON@mx.example.com\r\n\
+To: alice@example.org\r\n\
+Subject: Undelivered Mail Returned to Sender\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/report; report-type=delivery-status; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Your message could not be delivered.\r\n\
+--b1\r\n\
+Content-Type: message/delivery-status\r\n\
+\r\n\
+Reporting-MTA: dns; mx.example.com\r\n\
+Original-Envelope-Id: 3f2a@example.org\r\n\
+\r\n\
+Final-Recipient: rfc822; bob@example.com\r\n\
+Action: failed\r\n\
+Status: 5.1.1\r\n\
+Diagnostic-Code: smtp; 550 5.1.1 User unknown\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/rfc822-headers\r\n\
+\r\n\
+From: alice@example.org\r\n\
+To: bob@example.com\r\n\
+Message-ID: <3f2a@example.org>\r\n\
+Subject: Hello\r\n\
+\r\n\
+--b1--\r\n";
+
+    const READ_RECEIPT: &str = "From: bob@example.com\r\n\
+To: alice@example.org\r\n\
+Subject: Read: Hello\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/report; report-type=disposition-notification; boundary=\"b2\"\r\n\
+\r\n\
+--b2\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Your message was displayed.\r\n\
+--b2\r\n\
+Content-Type: message/disposition-notification\r\n\
+\r\n\
+Reporting-UA: mail.example.com; SimpleMail\r\n\
+Final-Recipient: rfc822; bob@example.com\r\n\
+Original-Message-ID: <3f2a@example.org>\r\n\
+Disposition: manual-action/MDN-sent-manually; displayed\r\n\
+\r\n\
+--b2--\r\n";
+
+    #[test]
+    fn test_parse_bounce() {
+        let report = parse_email(BOUNCE.as_bytes()).unwrap().report.expect("report");
+
+        assert_eq!(report.kind, ReportKind::Delivery);
+        assert_eq!(report.original_message_id.as_deref(), Some("3f2a@example.org"));
+        assert_eq!(report.original_envelope_id.as_deref(), Some("3f2a@example.org"));
+        assert_eq!(report.reporting_mta.as_deref(), Some("mx.example.com"));
+        assert_eq!(report.recipients.len(), 1);
+
+        let recipient = &report.recipients[0];
+        assert_eq!(recipient.recipient, "bob@example.com");
+        assert_eq!(recipient.action.as_deref(), Some("failed"));
+        assert_eq!(recipient.status.as_deref(), Some("5.1.1"));
+        assert_eq!(recipient.diagnostic.as_deref(), Some("550 5.1.1 User unknown"));
+    }
+
+    #[test]
+    fn test_parse_read_receipt() {
+        let report = parse_email(READ_RECEIPT.as_bytes()).unwrap().report.expect("report");
+
+        assert_eq!(report.kind, ReportKind::Disposition);
+        assert_eq!(report.original_message_id.as_deref(), Some("3f2a@example.org"));
+        assert_eq!(report.recipients[0].recipient, "bob@example.com");
+        assert_eq!(report.recipients[0].action.as_deref(), Some("displayed"));
+    }
+
+    #[test]
+    fn test_regular_message_has_no_report() {
+        let raw = b"From: a@example.com\r\nTo: b@example.com\r\nSubject: Hi\r\n\r\nHello\r\n";
+        assert!(parse_email(raw).unwrap().report.is_none());
+    }
+
+    #[test]
+    fn test_vcard_attachment_is_recognized() {
+        let raw = "From: a@example.com\r\n\
+To: b@example.com\r\n\
+Subject: My card\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b3\"\r\n\
+\r\n\
+--b3\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+See attached.\r\n\
+--b3\r\n\
+Content-Type: text/vcard; name=\"alice.vcf\"\r\n\
+Content-Disposition: attachment; filename=\"alice.vcf\"\r\n\
+\r\n\
+BEGIN:VCARD\r\n\
+VERSION:4.0\r\n\
+FN:Alice Example\r\n\
+EMAIL:alice@example.com\r\n\
+END:VCARD\r\n\
+--b3--\r\n";
+
+        let parsed = parse_email(raw.as_bytes()).unwrap();
+        assert_eq!(parsed.contact_cards.len(), 1);
+        assert_eq!(parsed.contact_cards[0].formatted_name.as_deref(), Some("Alice Example"));
+    }
+
+    const NESTED: &str = "From: a@example.com\r\n\
+To: b@example.com\r\n\
+Subject: Fwd: Report\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+--outer\r\n\
+Content-Type: multipart/related; boundary=\"rel\"\r\n\
+\r\n\
+--rel\r\n\
+Content-Type: text/html; charset=ISO-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<p>Caf=E9 <img src=3D\"cid:logo@x\"></p>\r\n\
+--rel\r\n\
+Content-Type: image/png\r\n\
+Content-ID: <logo@x>\r\n\
+Content-Disposition: inline; filename=\"logo.png\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+iVBORw0KGgo=\r\n\
+--rel--\r\n\
+--outer\r\n\
+Content-Type: application/pdf\r\n\
+Content-Disposition: attachment; filename*=UTF-8''%E2%82%AC%20report.pdf\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQK\r\n\
+--outer\r\n\
+Content-Type: message/rfc822\r\n\
+\r\n\
+From: c@example.com\r\n\
+Subject: Original\r\n\
+Content-Type: multipart/mixed; boundary=\"inner\"\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Original text\r\n\
+--inner\r\n\
+Content-Type: text/csv; name=\"data.csv\"\r\n\
+\r\n\
+a,b\r\n\
+--inner--\r\n\
+--outer--\r\n";
+
+    #[test]
+    fn test_part_tree_sections() {
+        let parsed = parse_email(NESTED.as_bytes()).unwrap();
+        let root = &parsed.parts;
+        assert_eq!((root.section.as_str(), root.content_type.as_str()), ("", "multipart/mixed"));
+        assert_eq!(root.children.len(), 3);
+
+        let related = &root.children[0];
+        assert_eq!(related.section, "1");
+        let sections: Vec<&str> = related.children.iter().map(|part| part.section.as_str()).collect();
+        assert_eq!(sections, vec!["1.1", "1.2"]);
+
+        let forwarded = &root.children[2];
+        assert_eq!((forwarded.section.as_str(), forwarded.content_type.as_str()), ("3", "message/rfc822"));
+        let inner = &forwarded.children[0];
+        assert_eq!((inner.section.as_str(), inner.content_type.as_str()), ("3", "multipart/mixed"));
+        let sections: Vec<&str> = inner.children.iter().map(|part| part.section.as_str()).collect();
+        assert_eq!(sections, vec!["3.1", "3.2"]);
+        assert_eq!(inner.children[1].filename.as_deref(), Some("data.csv"));
+    }
+
+    #[test]
+    fn test_part_headers() {
+        let parsed = parse_email(NESTED.as_bytes()).unwrap();
+        let html = &parsed.parts.children[0].children[0];
+        assert_eq!(html.charset.as_deref(), Some("iso-8859-1"));
+        assert_eq!(html.encoding.as_deref(), Some("quoted-printable"));
+        assert!(!html.is_attachment);
+        assert!(String::from_utf8_lossy(&html.content).contains("Café"));
+
+        let logo = &parsed.parts.children[0].children[1];
+        assert_eq!(logo.content_id.as_deref(), Some("logo@x"));
+        assert_eq!(logo.disposition, Some(Disposition::Inline));
+        assert_eq!(logo.size, 8);
+        assert!(!logo.is_attachment);
+    }
+
+    #[test]
+    fn test_attachments_exclude_bodies_and_inline_images() {
+        let parsed = parse_email(NESTED.as_bytes()).unwrap();
+        assert!(parsed.has_attachments());
+
+        let sections: Vec<&str> = parsed.attachments.iter().map(|attachment| attachment.section.as_str()).collect();
+        assert_eq!(sections, vec!["2", "3"]);
+
+        let pdf = &parsed.attachments[0];
+        assert_eq!(pdf.filename.as_deref(), Some("€ report.pdf"));
+        assert_eq!(pdf.mime_type, "application/pdf");
+        assert_eq!(pdf.disposition, Disposition::Attachment);
+        assert_eq!(pdf.content, b"%PDF-1.4\n");
+
+        assert_eq!(parsed.attachments[1].mime_type, "message/rfc822");
+    }
+
+    #[test]
+    fn test_rfc2231_continuations() {
+        let raw = "From: a@example.com\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Hi\r\n\
+--b\r\n\
+Content-Type: application/octet-stream\r\n\
+Content-Disposition: attachment;\r\n\
+\x20filename*0*=UTF-8''Quartals;\r\n\
+\x20filename*1*=%C3%BCbersicht.xlsx\r\n\
+\r\n\
+data\r\n\
+--b--\r\n";
+
+        let parsed = parse_email(raw.as_bytes()).unwrap();
+        assert_eq!(parsed.attachments.len(), 1);
+        assert_eq!(parsed.attachments[0].filename.as_deref(), Some("Quartalsübersicht.xlsx"));
+    }
+
+    #[test]
+    fn test_plain_message_is_section_one() {
+        let raw = b"From: a@example.com\r\nSubject: Hi\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nHello\r\n";
+        let parsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.parts.section, "1");
+        assert_eq!(parsed.parts.charset.as_deref(), Some("utf-8"));
+        assert!(parsed.parts.children.is_empty());
+        assert!(!parsed.has_attachments());
+    }
+
+    #[test]
+    fn test_date_header() {
+        let parsed = parse_email(b"From: a@example.com\r\nDate: Sun, 1 Mar 2026 10:00:00 +0100\r\n\r\nHello\r\n").unwrap();
+        assert_eq!(parsed.date.as_deref(), Some("2026-03-01T10:00:00+01:00"));
+
+        // Only readable dates are returned, so they can be stored and sorted as RFC 3339
+        let parsed = parse_email(b"From: a@example.com\r\nDate: sometime last week\r\n\r\nHello\r\n").unwrap();
+        assert_eq!(parsed.date, None);
+
+        let parsed = parse_email(b"From: a@example.com\r\n\r\nHello\r\n").unwrap();
+        assert_eq!(parsed.date, None);
+    }
+
+    #[test]
+    fn test_every_text_body_is_kept() {
+        let raw = "From: a@example.com\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+First\r\n\
+--b\r\n\
+Content-Type: image/jpeg\r\n\
+Content-Disposition: inline\r\n\
+\r\n\
+x\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Second\r\n\
+--b--\r\n";
+
+        let parsed = parse_email(raw.as_bytes()).unwrap();
+        let text = parsed.body_text.unwrap();
+        assert!(text.contains("First") && text.contains("Second"));
+        assert_eq!(parsed.attachments.len(), 1);
+    }
+}
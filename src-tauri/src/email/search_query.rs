@@ -0,0 +1,314 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+// Gmail-style search syntax:
+//
+//   from:alice to:bob subject:"q3 plan" has:attachment is:unread before:2026-01-01
+//   in:Archive larger:5M filename:pdf -word (budget OR forecast)
+//
+// Terms next to each other must all match, OR (upper case) binds tighter than that, and
+// a leading '-' excludes a term or a parenthesized group.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Flag {
+    Read,
+    Unread,
+    Starred,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Term {
+    // Free text, matched against every indexed field
+    Text(String),
+    From(String),
+    // To, Cc or Bcc
+    To(String),
+    Subject(String),
+    Body(String),
+    Filename(String),
+    HasAttachment,
+    Is(Flag),
+    Before(NaiveDate),
+    After(NaiveDate),
+    // Folder name or id
+    In(String),
+    // Sizes in bytes
+    Larger(u64),
+    Smaller(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Query {
+    Term(Term),
+    Not(Box<Query>),
+    // An empty And matches everything
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+// Positions count characters from the start of the query, so the search box can
+// underline the offending part
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[error("{message} at position {position}")]
+pub struct QueryError {
+    pub position: usize,
+    pub length: usize,
+    pub message: String,
+}
+
+const OPERATORS: &[&str] = &[
+    "from", "to", "subject", "body", "filename", "has", "is", "before", "after", "in", "larger", "smaller",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Text(String),
+    Operator { name: String, value: String, value_start: usize },
+    Not,
+    Or,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    length: usize,
+}
+
+pub fn parse(input: &str) -> Result<Query, QueryError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, position: 0, input_length: input.chars().count() };
+    let query = parser.parse_group()?;
+    if let Some(token) = parser.peek() {
+        return Err(error(token.start, token.length, "Unexpected ')'"));
+    }
+    Ok(query)
+}
+
+fn error(position: usize, length: usize, message: impl Into<String>) -> QueryError {
+    QueryError { position, length, message: message.into() }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '(' => {
+                tokens.push(Token { kind: TokenKind::Open, start, length: 1 });
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token { kind: TokenKind::Close, start, length: 1 });
+                i += 1;
+            }
+            '-' if chars.get(i + 1).is_some_and(|next| !next.is_whitespace()) => {
+                tokens.push(Token { kind: TokenKind::Not, start, length: 1 });
+                i += 1;
+            }
+            '"' => {
+                let (phrase, end) = read_quoted(&chars, i)?;
+                tokens.push(Token { kind: TokenKind::Text(phrase), start, length: end - start });
+                i = end;
+            }
+            _ => {
+                let mut word = String::new();
+                while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+                    // subject:"q3 plan"
+                    if chars[i] == '"' && word.ends_with(':') {
+                        break;
+                    }
+                    word.push(chars[i]);
+                    i += 1;
+                }
+
+                let kind = match word.split_once(':') {
+                    Some((name, value)) if OPERATORS.contains(&name.to_ascii_lowercase().as_str()) => {
+                        let value_start = start + name.chars().count() + 1;
+                        let value = if value.is_empty() && chars.get(i) == Some(&'"') {
+                            let (phrase, end) = read_quoted(&chars, i)?;
+                            i = end;
+                            phrase
+                        } else {
+                            value.to_string()
+                        };
+                        TokenKind::Operator { name: name.to_ascii_lowercase(), value, value_start }
+                    }
+                    _ if word == "OR" => TokenKind::Or,
+                    _ => TokenKind::Text(word),
+                };
+                // "AND" is what adjacent terms mean anyway
+                if kind != TokenKind::Text("AND".to_string()) {
+                    tokens.push(Token { kind, start, length: i - start });
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// The phrase starting at the quote at `start`, and the position just past its closing quote
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), QueryError> {
+    let mut phrase = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == '"' {
+            return Ok((phrase, i + 1));
+        }
+        phrase.push(chars[i]);
+        i += 1;
+    }
+    Err(error(start, chars.len() - start, "Missing closing quote"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    input_length: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_group(&mut self) -> Result<Query, QueryError> {
+        let query = self.parse_and()?;
+        // parse_and takes care of every OR that has a term before it
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Or => Err(error(token.start, token.length, "Nothing before OR")),
+            _ => Ok(query),
+        }
+    }
+
+    // Terms up to a closing parenthesis or the end. As in Gmail `a OR b c` is `(a OR b) c`:
+    // an OR only takes the single term on either side
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Close => break,
+                TokenKind::Or => {
+                    if items.is_empty() {
+                        break;
+                    }
+                    let left = items.pop().unwrap();
+                    let mut alternatives = vec![left];
+                    while self.peek().is_some_and(|t| t.kind == TokenKind::Or) {
+                        let or = self.next().unwrap();
+                        match self.peek() {
+                            Some(next) if !matches!(next.kind, TokenKind::Or | TokenKind::Close) => {
+                                alternatives.push(self.parse_unary()?);
+                            }
+                            _ => return Err(error(or.start, or.length, "Nothing after OR")),
+                        }
+                    }
+                    items.push(Query::Or(alternatives));
+                }
+                _ => items.push(self.parse_unary()?),
+            }
+        }
+
+        if items.len() == 1 {
+            Ok(items.pop().unwrap())
+        } else {
+            Ok(Query::And(items))
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        let token = self.next().unwrap();
+        match token.kind {
+            TokenKind::Not => match self.peek() {
+                Some(next) if !matches!(next.kind, TokenKind::Or | TokenKind::Close) => {
+                    Ok(Query::Not(Box::new(self.parse_unary()?)))
+                }
+                _ => Err(error(token.start, token.length, "Nothing to exclude after '-'")),
+            },
+            TokenKind::Open => {
+                let inner = self.parse_group()?;
+                match self.next() {
+                    Some(Token { kind: TokenKind::Close, .. }) => Ok(inner),
+                    _ => Err(error(token.start, self.input_length - token.start, "Missing closing ')'")),
+                }
+            }
+            TokenKind::Text(text) => Ok(Query::Term(Term::Text(text))),
+            TokenKind::Operator { name, value, value_start } => {
+                operator_term(&name, value, value_start, token.start + token.length).map(Query::Term)
+            }
+            TokenKind::Or | TokenKind::Close => unreachable!("handled by the callers"),
+        }
+    }
+}
+
+fn operator_term(name: &str, value: String, value_start: usize, end: usize) -> Result<Term, QueryError> {
+    let value_error = |message: String| error(value_start, end.saturating_sub(value_start).max(1), message);
+    if value.trim().is_empty() {
+        return Err(value_error(format!("Missing value after '{}:'", name)));
+    }
+
+    let lower = value.to_lowercase();
+    match name {
+        "from" => Ok(Term::From(value)),
+        "to" => Ok(Term::To(value)),
+        "subject" => Ok(Term::Subject(value)),
+        "body" => Ok(Term::Body(value)),
+        "filename" => Ok(Term::Filename(value)),
+        "in" => Ok(Term::In(value)),
+        "has" => match lower.as_str() {
+            "attachment" | "attachments" => Ok(Term::HasAttachment),
+            _ => Err(value_error(format!("Unknown value '{}' for has:, expected attachment", value))),
+        },
+        "is" => match lower.as_str() {
+            "read" => Ok(Term::Is(Flag::Read)),
+            "unread" => Ok(Term::Is(Flag::Unread)),
+            "starred" => Ok(Term::Is(Flag::Starred)),
+            _ => Err(value_error(format!("Unknown value '{}' for is:, expected read, unread or starred", value))),
+        },
+        "before" | "after" => {
+            let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(&value, "%Y/%m/%d"))
+                .map_err(|_| value_error(format!("Invalid date '{}', expected YYYY-MM-DD", value)))?;
+            Ok(if name == "before" { Term::Before(date) } else { Term::After(date) })
+        }
+        "larger" | "smaller" => {
+            let bytes = parse_size(&value)
+                .ok_or_else(|| value_error(format!("Invalid size '{}', expected a number such as 500K or 5M", value)))?;
+            Ok(if name == "larger" { Term::Larger(bytes) } else { Term::Smaller(bytes) })
+        }
+        _ => unreachable!("only known operators are tokenized as such"),
+    }
+}
+
+// "5M", "500k", "2mb", "1024" (bytes)
+pub fn parse_size(value: &str) -> Option<u64> {
+    let lower = value.trim().to_ascii_lowercase();
+    let lower = lower.strip_suffix('b').filter(|rest| !rest.is_empty()).unwrap_or(&lower);
+    let (digits, multiplier) = match lower.chars().last()? {
+        'k' => (&lower[..lower.len() - 1], 1024),
+        'm' => (&lower[..lower.len() - 1], 1024 * 1024),
+        'g' => (&lower[..lower.len() - 1], 1024 * 1024 * 1024),
+        _ => (lower, 1),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
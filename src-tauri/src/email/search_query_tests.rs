@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::search_query::*;
+    use chrono::NaiveDate;
+
+    fn term(term: Term) -> Query {
+        Query::Term(term)
+    }
+
+    fn text(value: &str) -> Query {
+        term(Term::Text(value.to_string()))
+    }
+
+    #[test]
+    fn test_operators() {
+        let query = parse(r#"from:alice to:bob subject:"q3 plan" has:attachment is:unread before:2026-01-01 after:2025/12/01 in:Archive larger:5M filename:pdf"#).unwrap();
+        assert_eq!(query, Query::And(vec![
+            term(Term::From("alice".to_string())),
+            term(Term::To("bob".to_string())),
+            term(Term::Subject("q3 plan".to_string())),
+            term(Term::HasAttachment),
+            term(Term::Is(Flag::Unread)),
+            term(Term::Before(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap())),
+            term(Term::After(NaiveDate::from_ymd_opt(2025, 12, 1).unwrap())),
+            term(Term::In("Archive".to_string())),
+            term(Term::Larger(5 * 1024 * 1024)),
+            term(Term::Filename("pdf".to_string())),
+        ]));
+    }
+
+    #[test]
+    fn test_boolean_structure() {
+        // OR only takes its neighbours, as in Gmail
+        assert_eq!(parse("a OR b c").unwrap(), Query::And(vec![
+            Query::Or(vec![text("a"), text("b")]),
+            text("c"),
+        ]));
+        assert_eq!(parse("-(draft OR spam) \"exact phrase\" AND x").unwrap(), Query::And(vec![
+            Query::Not(Box::new(Query::Or(vec![text("draft"), text("spam")]))),
+            text("exact phrase"),
+            text("x"),
+        ]));
+        // Lower case "or" and unknown prefixes are plain words
+        assert_eq!(parse("this or https://example.com").unwrap(), Query::And(vec![
+            text("this"),
+            text("or"),
+            text("https://example.com"),
+        ]));
+        assert_eq!(parse("  ").unwrap(), Query::And(vec![]));
+    }
+
+    #[test]
+    fn test_errors_point_at_the_problem() {
+        let cases = [
+            ("subject:\"open", 8, "Missing closing quote"),
+            ("budget OR", 7, "Nothing after OR"),
+            ("OR budget", 0, "Nothing before OR"),
+            ("(a b", 0, "Missing closing ')'"),
+            ("a b)", 3, "Unexpected ')'"),
+            ("is:important", 3, "Unknown value 'important' for is:, expected read, unread or starred"),
+            ("会议 before:yesterday", 10, "Invalid date 'yesterday', expected YYYY-MM-DD"),
+            ("larger:lots", 7, "Invalid size 'lots', expected a number such as 500K or 5M"),
+            ("from:", 5, "Missing value after 'from:'"),
+        ];
+        for (input, position, message) in cases {
+            let error = parse(input).unwrap_err();
+            assert_eq!((error.position, error.message.as_str()), (position, message), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("500k"), Some(500 * 1024));
+        assert_eq!(parse_size("2MB"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("-5"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::models::SignatureImage;
+use crate::smtp_client::{ComposeMode, EmailAttachment, EmailMessage};
+use serde::{Deserialize, Serialize};
+
+// Where the signature goes relative to quoted text in replies and forwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignaturePlacement {
+    AboveQuote,
+    BelowQuote,
+    Omit,
+}
+
+impl SignaturePlacement {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "below_quote" => SignaturePlacement::BelowQuote,
+            "omit" => SignaturePlacement::Omit,
+            _ => SignaturePlacement::AboveQuote,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignaturePlacement::AboveQuote => "above_quote",
+            SignaturePlacement::BelowQuote => "below_quote",
+            SignaturePlacement::Omit => "omit",
+        }
+    }
+}
+
+// A stored signature with its inline images, ready to be merged into a message
+#[derive(Debug, Clone)]
+pub struct RichSignature {
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+    pub images: Vec<SignatureImage>,
+}
+
+// Merges the composed body, the signature and the quoted original into the final
+// text/HTML bodies. Signature images are added as inline parts referenced by CID.
+pub fn apply_signature(message: &EmailMessage, signature: Option<&RichSignature>, placement: SignaturePlacement) -> EmailMessage {
+    let mut message = message.clone();
+
+    let placement = match message.compose_mode {
+        ComposeMode::New => SignaturePlacement::AboveQuote,
+        _ => placement,
+    };
+    let signature = signature.filter(|_| placement != SignaturePlacement::Omit);
+
+    let quoted_text = message.quoted_text.take();
+    let quoted_html = message.quoted_html.take();
+
+    // Plain text: the "-- " delimiter lets clients strip the signature when quoting
+    let text_signature = signature.and_then(|s| {
+        s.body_text.clone().or_else(|| s.body_html.as_deref().map(html_to_text))
+    });
+    let mut text_sections = vec![message.body_text.clone()];
+    let text_quote = quoted_text.clone().or_else(|| quoted_html.as_deref().map(html_to_text));
+    push_ordered(
+        &mut text_sections,
+        text_signature.map(|s| format!("-- \r\n{}", s)),
+        text_quote,
+        placement,
+    );
+    message.body_text = text_sections.join("\r\n\r\n");
+
+    if let Some(html) = message.body_html.take() {
+        let html_signature = signature.and_then(|s| {
+            s.body_html.clone().or_else(|| s.body_text.as_deref().map(text_to_html))
+        });
+        let html_quote = quoted_html.or_else(|| quoted_text.as_deref().map(|q| {
+            format!("<blockquote type=\"cite\">{}</blockquote>", text_to_html(q))
+        }));
+
+        let mut html_sections = vec![html];
+        push_ordered(
+            &mut html_sections,
+            html_signature.map(|s| format!("<div class=\"signature\">-- <br>{}</div>", s)),
+            html_quote,
+            placement,
+        );
+        message.body_html = Some(html_sections.join("<br>"));
+
+        if let Some(signature) = signature {
+            for image in &signature.images {
+                message.attachments.push(EmailAttachment {
+                    filename: image.filename.clone().unwrap_or_else(|| image.content_id.clone()),
+                    content: image.content.clone(),
+                    // Resolved from the content when the message is built
+                    mime_type: image.mime_type.clone().unwrap_or_default(),
+                    content_id: Some(image.content_id.clone()),
+                });
+            }
+        }
+    }
+
+    message
+}
+
+fn push_ordered(sections: &mut Vec<String>, signature: Option<String>, quote: Option<String>, placement: SignaturePlacement) {
+    match placement {
+        SignaturePlacement::BelowQuote => {
+            sections.extend(quote);
+            sections.extend(signature);
+        }
+        _ => {
+            sections.extend(signature);
+            sections.extend(quote);
+        }
+    }
+}
+
+// Content-IDs the HTML points at with cid: URLs (RFC 2392), e.g. from <img src="cid:logo">
+pub fn cid_references(html: &str) -> Vec<&str> {
+    let mut references = Vec::new();
+    let lower = html.to_ascii_lowercase();
+    let mut rest = 0;
+    while let Some(found) = lower[rest..].find("cid:") {
+        let start = rest + found + "cid:".len();
+        let end = html[start..]
+            .find(|c: char| c == '"' || c == '\'' || c == ')' || c == '>' || c.is_whitespace())
+            .map_or(html.len(), |length| start + length);
+        if end > start && !references.contains(&&html[start..end]) {
+            references.push(&html[start..end]);
+        }
+        rest = end;
+    }
+    references
+}
+
+fn text_to_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+// Rough conversion used only when a signature has no plain-text variant
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    let mut tag = String::new();
+
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                tag.clear();
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag.trim_start_matches('/').split_whitespace().next().unwrap_or("").to_lowercase();
+                if matches!(name.as_str(), "br" | "br/" | "p" | "div" | "tr" | "li") {
+                    text.push('\n');
+                }
+            }
+            _ if in_tag => tag.push(c),
+            _ => text.push(c),
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
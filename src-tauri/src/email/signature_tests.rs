@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::signature::*;
+    use crate::models::SignatureImage;
+    use crate::smtp_client::{ComposeMode, EmailMessage};
+
+    fn reply(body_html: Option<&str>) -> EmailMessage {
+        EmailMessage {
+            to: vec!["bob@example.com".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: "Re: Plan".to_string(),
+            body_text: "Sounds good.".to_string(),
+            body_html: body_html.map(|s| s.to_string()),
+            attachments: vec![],
+            identity_id: None,
+            compose_mode: ComposeMode::Reply,
+            in_reply_to: None,
+            references: vec![],
+            quoted_text: Some("> original".to_string()),
+            quoted_html: None,
+            send_at: None,
+            request_dsn: false,
+            request_read_receipt: false,
+        }
+    }
+
+    fn signature() -> RichSignature {
+        RichSignature {
+            body_text: Some("Alice".to_string()),
+            body_html: Some("<b>Alice</b><img src=\"cid:logo\">".to_string()),
+            images: vec![SignatureImage {
+                id: "img-1".to_string(),
+                signature_id: "sig-1".to_string(),
+                content_id: "logo".to_string(),
+                filename: Some("logo.png".to_string()),
+                mime_type: Some("image/png".to_string()),
+                content: vec![0x89, 0x50, 0x4E, 0x47],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_signature_above_quote() {
+        let message = apply_signature(&reply(None), Some(&signature()), SignaturePlacement::AboveQuote);
+        assert_eq!(message.body_text, "Sounds good.\r\n\r\n-- \r\nAlice\r\n\r\n> original");
+        assert!(message.quoted_text.is_none());
+    }
+
+    #[test]
+    fn test_signature_below_quote() {
+        let message = apply_signature(&reply(None), Some(&signature()), SignaturePlacement::BelowQuote);
+        assert_eq!(message.body_text, "Sounds good.\r\n\r\n> original\r\n\r\n-- \r\nAlice");
+    }
+
+    #[test]
+    fn test_signature_omitted() {
+        let message = apply_signature(&reply(None), Some(&signature()), SignaturePlacement::Omit);
+        assert_eq!(message.body_text, "Sounds good.\r\n\r\n> original");
+    }
+
+    #[test]
+    fn test_html_signature_adds_inline_images() {
+        let message = apply_signature(&reply(Some("<p>Sounds good.</p>")), Some(&signature()), SignaturePlacement::AboveQuote);
+
+        let html = message.body_html.unwrap();
+        assert!(html.contains("<b>Alice</b>"));
+        assert!(html.find("Alice").unwrap() < html.find("original").unwrap());
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].content_id.as_deref(), Some("logo"));
+    }
+
+    #[test]
+    fn test_plain_message_gets_no_signature_images() {
+        let message = apply_signature(&reply(None), Some(&signature()), SignaturePlacement::AboveQuote);
+        assert!(message.attachments.is_empty());
+    }
+
+    #[test]
+    fn test_cid_references() {
+        let html = r#"<img src="cid:logo"><img src='CID:banner@example.com'><div style="background: url(cid:bg)"></div><img src="cid:logo">"#;
+        assert_eq!(cid_references(html), vec!["logo", "banner@example.com", "bg"]);
+        assert!(cid_references("<p>No images, just a cid: mention</p>").is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+
+// Reply and forward markers in the languages mail clients commonly write them in
+const REPLY_PREFIXES: &[&str] = &["re", "fw", "fwd", "aw", "wg", "sv", "vs", "antw", "doorst", "tr", "rif", "odp", "ynt"];
+
+// Threading headers of one message, keyed by its position in the input slice
+#[derive(Debug, Clone, Default)]
+pub struct ThreadInput {
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    pub subject: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Container {
+    // Several copies of one message (say in Inbox and Archive) share a container
+    messages: Vec<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+// The subject with reply/forward prefixes, list tags and case removed, so that
+// "Re: [dev] Fwd: Release plan" and "release plan" compare equal
+pub fn base_subject(subject: &str) -> String {
+    strip_subject(subject).0
+}
+
+// Whether the subject carries a reply or forward prefix
+pub fn is_reply_subject(subject: &str) -> bool {
+    strip_subject(subject).1
+}
+
+fn strip_subject(subject: &str) -> (String, bool) {
+    let mut rest = subject.trim();
+    let mut reply = false;
+
+    loop {
+        let before = rest;
+
+        // Mailing list tag such as "[dev]"
+        if rest.starts_with('[') {
+            if let Some(end) = rest.find(']') {
+                rest = rest[end + 1..].trim_start();
+            }
+        }
+
+        // "Re:", "RE[2]:", "Fwd :" ...
+        if let Some(colon) = rest.find(':') {
+            let head = rest[..colon].trim_end();
+            let word = head.split('[').next().unwrap_or(head);
+            let counter_ok = head.len() == word.len()
+                || (head.ends_with(']') && head[word.len() + 1..head.len() - 1].chars().all(|c| c.is_ascii_digit()));
+            if counter_ok && REPLY_PREFIXES.iter().any(|prefix| word.eq_ignore_ascii_case(prefix)) {
+                rest = rest[colon + 1..].trim_start();
+                reply = true;
+            }
+        }
+
+        if rest == before {
+            break;
+        }
+    }
+
+    let rest = rest.strip_suffix("(fwd)").unwrap_or(rest);
+    let base = rest.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    (base, reply)
+}
+
+// Groups messages into conversations with Jamie Zawinski's threading algorithm
+// (https://www.jwz.org/doc/threading.html). Returns one list of input positions per
+// thread, parents before their replies.
+//
+// Unlike the original, roots are only merged on subject when at least one side is a
+// reply: two unrelated messages that are both called "Hello" stay apart.
+pub fn thread(messages: &[ThreadInput]) -> Vec<Vec<usize>> {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut by_id: HashMap<&str, usize> = HashMap::new();
+
+    // 1. Build the reference tree
+    for (index, message) in messages.iter().enumerate() {
+        let own_id = message.message_id.as_deref().filter(|id| !id.is_empty());
+        let this = match own_id {
+            Some(id) => container_for(&mut containers, &mut by_id, id),
+            None => new_container(&mut containers),
+        };
+        containers[this].messages.push(index);
+
+        let mut references: Vec<&str> = message.references.iter().map(String::as_str).collect();
+        if let Some(in_reply_to) = message.in_reply_to.as_deref() {
+            if references.last() != Some(&in_reply_to) {
+                references.push(in_reply_to);
+            }
+        }
+        references.retain(|reference| Some(*reference) != own_id && !reference.is_empty());
+
+        // Link the chain of references, keeping links made earlier and never creating loops
+        let mut previous: Option<usize> = None;
+        for reference in references {
+            let current = container_for(&mut containers, &mut by_id, reference);
+            if let Some(parent) = previous {
+                if containers[current].parent.is_none() && parent != current && !is_ancestor(&containers, current, parent) {
+                    link(&mut containers, parent, current);
+                }
+            }
+            previous = Some(current);
+        }
+
+        // The message itself knows best who its parent is
+        unlink(&mut containers, this);
+        if let Some(parent) = previous {
+            if parent != this && !is_ancestor(&containers, this, parent) {
+                link(&mut containers, parent, this);
+            }
+        }
+    }
+
+    // 2. Find the roots and 3. drop containers for messages we never saw
+    let roots: Vec<usize> = (0..containers.len()).filter(|&c| containers[c].parent.is_none()).collect();
+    let mut roots = prune(&mut containers, roots, true);
+
+    // 4. Gather roots that share a subject
+    let subject_of = |containers: &[Container], root: usize| -> Option<String> {
+        first_message(containers, root).and_then(|index| messages[index].subject.clone())
+    };
+
+    let mut by_subject: HashMap<String, usize> = HashMap::new();
+    for &root in &roots {
+        let Some(subject) = subject_of(&containers, root) else { continue };
+        let key = base_subject(&subject);
+        if key.is_empty() {
+            continue;
+        }
+        match by_subject.get(&key) {
+            Some(&existing) => {
+                // Prefer a placeholder, then an original over a reply
+                let existing_subject = subject_of(&containers, existing).unwrap_or_default();
+                let better = (containers[root].messages.is_empty() && !containers[existing].messages.is_empty())
+                    || (is_reply_subject(&existing_subject) && !is_reply_subject(&subject));
+                if better {
+                    by_subject.insert(key, root);
+                }
+            }
+            None => {
+                by_subject.insert(key, root);
+            }
+        }
+    }
+
+    let mut merged = HashSet::new();
+    for index in 0..roots.len() {
+        let root = roots[index];
+        let Some(subject) = subject_of(&containers, root) else { continue };
+        let key = base_subject(&subject);
+        let Some(&target) = by_subject.get(&key) else { continue };
+        if target == root || merged.contains(&target) {
+            continue;
+        }
+
+        let target_subject = subject_of(&containers, target).unwrap_or_default();
+        let root_empty = containers[root].messages.is_empty();
+        let target_empty = containers[target].messages.is_empty();
+        let root_reply = is_reply_subject(&subject);
+        let target_reply = is_reply_subject(&target_subject);
+
+        if root_empty && target_empty {
+            for child in std::mem::take(&mut containers[root].children) {
+                containers[child].parent = None;
+                link(&mut containers, target, child);
+            }
+        } else if target_empty || (root_reply && !target_reply) {
+            link(&mut containers, target, root);
+        } else if root_reply && target_reply {
+            // Two replies to an original we do not have: hang both under a placeholder
+            let placeholder = new_container(&mut containers);
+            link(&mut containers, placeholder, target);
+            link(&mut containers, placeholder, root);
+            by_subject.insert(key, placeholder);
+            roots.push(placeholder);
+            merged.insert(target);
+        } else {
+            continue;
+        }
+        merged.insert(root);
+    }
+
+    // 5. Flatten each remaining tree
+    roots.iter()
+        .filter(|root| !merged.contains(root) && containers[**root].parent.is_none())
+        .map(|&root| {
+            let mut indices = Vec::new();
+            collect(&containers, root, &mut indices);
+            indices
+        })
+        .filter(|indices| !indices.is_empty())
+        .collect()
+}
+
+fn new_container(containers: &mut Vec<Container>) -> usize {
+    containers.push(Container::default());
+    containers.len() - 1
+}
+
+fn container_for<'a>(containers: &mut Vec<Container>, by_id: &mut HashMap<&'a str, usize>, id: &'a str) -> usize {
+    if let Some(&existing) = by_id.get(id) {
+        return existing;
+    }
+    let container = new_container(containers);
+    by_id.insert(id, container);
+    container
+}
+
+fn link(containers: &mut [Container], parent: usize, child: usize) {
+    containers[child].parent = Some(parent);
+    containers[parent].children.push(child);
+}
+
+fn unlink(containers: &mut [Container], child: usize) {
+    if let Some(parent) = containers[child].parent.take() {
+        containers[parent].children.retain(|&c| c != child);
+    }
+}
+
+// Whether `ancestor` is `node` or one of its parents
+fn is_ancestor(containers: &[Container], ancestor: usize, node: usize) -> bool {
+    let mut current = Some(node);
+    while let Some(c) = current {
+        if c == ancestor {
+            return true;
+        }
+        current = containers[c].parent;
+    }
+    false
+}
+
+// Removes empty containers, moving their children up a level. At the root level an
+// empty container is only kept when it holds several children together.
+fn prune(containers: &mut [Container], nodes: Vec<usize>, at_root: bool) -> Vec<usize> {
+    let mut kept = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let children = std::mem::take(&mut containers[node].children);
+        let children = prune(containers, children, false);
+        for &child in &children {
+            containers[child].parent = Some(node);
+        }
+
+        if !containers[node].messages.is_empty() || (at_root && children.len() > 1) {
+            containers[node].children = children;
+            kept.push(node);
+        } else {
+            let parent = containers[node].parent;
+            for &child in &children {
+                containers[child].parent = parent;
+            }
+            kept.extend(children);
+        }
+    }
+    kept
+}
+
+fn first_message(containers: &[Container], node: usize) -> Option<usize> {
+    if let Some(&index) = containers[node].messages.first() {
+        return Some(index);
+    }
+    containers[node].children.iter().find_map(|&child| first_message(containers, child))
+}
+
+fn collect(containers: &[Container], node: usize, indices: &mut Vec<usize>) {
+    indices.extend(&containers[node].messages);
+    for &child in &containers[node].children {
+        collect(containers, child, indices);
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::threading::*;
+
+    fn message(id: &str, in_reply_to: Option<&str>, references: &[&str], subject: &str) -> ThreadInput {
+        ThreadInput {
+            message_id: Some(id.to_string()),
+            in_reply_to: in_reply_to.map(str::to_string),
+            references: references.iter().map(|r| r.to_string()).collect(),
+            subject: Some(subject.to_string()),
+        }
+    }
+
+    fn sorted(mut threads: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        for thread in &mut threads {
+            thread.sort();
+        }
+        threads.sort();
+        threads
+    }
+
+    #[test]
+    fn test_base_subject() {
+        assert_eq!(base_subject("Re: [dev] Fwd: Release  plan"), "release plan");
+        assert_eq!(base_subject("RE[2]: AW: Budget"), "budget");
+        assert_eq!(base_subject("Meeting: agenda"), "meeting: agenda");
+        assert!(is_reply_subject("Re: hello"));
+        assert!(!is_reply_subject("[dev] hello"));
+    }
+
+    #[test]
+    fn test_references_build_one_thread() {
+        let messages = vec![
+            message("c@x", Some("b@x"), &["a@x", "b@x"], "Re: Plan"),
+            message("a@x", None, &[], "Plan"),
+            message("d@x", Some("a@x"), &["a@x"], "Re: Plan"),
+            message("other@x", None, &[], "Something else"),
+        ];
+        let threads = thread(&messages);
+
+        assert_eq!(sorted(threads.clone()), vec![vec![0, 1, 2], vec![3]]);
+        // Parents come before their replies
+        let plan = threads.iter().find(|t| t.contains(&1)).unwrap();
+        assert_eq!(plan[0], 1);
+    }
+
+    #[test]
+    fn test_missing_parent_keeps_siblings_together() {
+        // Both reply to a message we never received
+        let messages = vec![
+            message("b@x", Some("a@x"), &["a@x"], "Re: Lost"),
+            message("c@x", Some("a@x"), &["a@x"], "Re: Lost"),
+        ];
+        assert_eq!(sorted(thread(&messages)), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_subject_fallback_only_joins_replies() {
+        let messages = vec![
+            message("a@x", None, &[], "Lunch?"),
+            // A client that drops In-Reply-To
+            message("b@x", None, &[], "Re: Lunch?"),
+            message("c@x", None, &[], "Hello"),
+            message("d@x", None, &[], "Hello"),
+        ];
+        assert_eq!(sorted(thread(&messages)), vec![vec![0, 1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn test_copies_and_reference_loops() {
+        let messages = vec![
+            message("a@x", Some("b@x"), &["b@x"], "Loop"),
+            message("b@x", Some("a@x"), &["a@x"], "Loop"),
+            // The same message filed in a second folder
+            message("a@x", Some("b@x"), &["b@x"], "Loop"),
+            ThreadInput { message_id: None, subject: Some("No id".to_string()), ..ThreadInput::default() },
+        ];
+        assert_eq!(sorted(thread(&messages)), vec![vec![0, 1, 2], vec![3]]);
+    }
+}
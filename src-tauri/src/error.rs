@@ -0,0 +1,282 @@
+use crate::contacts::carddav::CardDavError;
+use crate::email::address::{AddressError, InvalidAddresses};
+use crate::email::search_query::QueryError;
+use crate::smtp_client::MessageTooLarge;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+use std::io::ErrorKind;
+
+pub type MailResult<T> = Result<T, MailError>;
+
+// Every error a command can return. The frontend gets it as
+// `{kind, message, retryable, details}`, so it can tell a wrong password from a dropped
+// connection and decide whether offering "Try again" makes sense.
+#[derive(Debug, thiserror::Error)]
+pub enum MailError {
+    // The server rejected the credentials
+    #[error("{0}")]
+    Auth(String),
+    // Connecting failed, timed out or the connection dropped
+    #[error("{0}")]
+    Network(String),
+    #[error("{0}")]
+    Tls(String),
+    // The server refused a command or answered something we could not use
+    #[error("{message}")]
+    Protocol { message: String, server_response: Option<String> },
+    #[error("{0}")]
+    NotFound(String),
+    // A size or storage limit was hit; `details` says which
+    #[error("{message}")]
+    Quota { message: String, details: Option<Value> },
+    // Input the user can fix, e.g. a bad address; `details` points at what is wrong
+    #[error("{message}")]
+    Validation { message: String, details: Option<Value> },
+    // `busy` is set when SQLite was locked by another connection
+    #[error("{message}")]
+    Db { message: String, busy: bool },
+    #[error("{0}")]
+    Io(String),
+    // Errors from code that still reports plain strings
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl MailError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        MailError::Validation { message: message.into(), details: None }
+    }
+
+    pub fn protocol(message: impl Into<String>, server_response: Option<String>) -> Self {
+        MailError::Protocol { message: message.into(), server_response }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MailError::Auth(_) => "auth",
+            MailError::Network(_) => "network",
+            MailError::Tls(_) => "tls",
+            MailError::Protocol { .. } => "protocol",
+            MailError::NotFound(_) => "not_found",
+            MailError::Quota { .. } => "quota",
+            MailError::Validation { .. } => "validation",
+            MailError::Db { .. } => "db",
+            MailError::Io(_) => "io",
+            MailError::Internal(_) => "internal",
+        }
+    }
+
+    // Whether the same request may succeed if simply tried again
+    pub fn retryable(&self) -> bool {
+        match self {
+            MailError::Network(_) => true,
+            MailError::Db { busy, .. } => *busy,
+            // SMTP 4xx replies are transient (RFC 5321 4.2.1); IMAP marks them with a response code
+            MailError::Protocol { server_response: Some(response), .. } => {
+                response.starts_with('4') || response.contains("[UNAVAILABLE]") || response.contains("[INUSE]")
+            }
+            _ => false,
+        }
+    }
+
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            MailError::Protocol { server_response: Some(response), .. } => Some(json!({ "server_response": response })),
+            MailError::Quota { details, .. } | MailError::Validation { details, .. } => details.clone(),
+            _ => None,
+        }
+    }
+
+    // Prefixes the message with what was being done, keeping the kind
+    pub fn context(mut self, context: &str) -> Self {
+        let message = match &mut self {
+            MailError::Auth(message)
+            | MailError::Network(message)
+            | MailError::Tls(message)
+            | MailError::NotFound(message)
+            | MailError::Io(message)
+            | MailError::Internal(message) => message,
+            MailError::Protocol { message, .. }
+            | MailError::Quota { message, .. }
+            | MailError::Validation { message, .. }
+            | MailError::Db { message, .. } => message,
+        };
+        *message = format!("{}: {}", context, message);
+        self
+    }
+
+    fn from_io(kind: ErrorKind, message: String) -> Self {
+        match kind {
+            ErrorKind::NotFound => MailError::NotFound(message),
+            ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::AddrNotAvailable
+            | ErrorKind::UnexpectedEof => MailError::Network(message),
+            _ => MailError::Io(message),
+        }
+    }
+}
+
+impl Serialize for MailError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("MailError", 4)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("retryable", &self.retryable())?;
+        error.serialize_field("details", &self.details())?;
+        error.end()
+    }
+}
+
+// `.context("Failed to ...")` on any result whose error converts into a MailError
+pub trait Context<T> {
+    fn context(self, context: &str) -> MailResult<T>;
+}
+
+impl<T, E: Into<MailError>> Context<T> for Result<T, E> {
+    fn context(self, context: &str) -> MailResult<T> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+impl From<String> for MailError {
+    fn from(message: String) -> Self {
+        MailError::Internal(message)
+    }
+}
+
+impl From<&str> for MailError {
+    fn from(message: &str) -> Self {
+        MailError::Internal(message.to_string())
+    }
+}
+
+impl From<std::io::Error> for MailError {
+    fn from(error: std::io::Error) -> Self {
+        MailError::from_io(error.kind(), error.to_string())
+    }
+}
+
+impl From<sqlx::Error> for MailError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => MailError::NotFound("Record not found".to_string()),
+            sqlx::Error::PoolTimedOut => MailError::Db { message: error.to_string(), busy: true },
+            sqlx::Error::Database(db_error) => {
+                // SQLITE_BUSY and SQLITE_LOCKED, including their extended codes
+                let busy = db_error.code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6));
+                MailError::Db { message: error.to_string(), busy }
+            }
+            _ => MailError::Db { message: error.to_string(), busy: false },
+        }
+    }
+}
+
+// Stored JSON that no longer parses, or a value that can't be written as JSON
+impl From<serde_json::Error> for MailError {
+    fn from(error: serde_json::Error) -> Self {
+        MailError::Internal(error.to_string())
+    }
+}
+
+impl From<native_tls::Error> for MailError {
+    fn from(error: native_tls::Error) -> Self {
+        MailError::Tls(error.to_string())
+    }
+}
+
+impl From<imap::Error> for MailError {
+    fn from(error: imap::Error) -> Self {
+        let message = error.to_string();
+        match &error {
+            imap::Error::Io(e) => MailError::from_io(e.kind(), message),
+            imap::Error::Tls(_) | imap::Error::TlsHandshake(_) => MailError::Tls(message),
+            imap::Error::ConnectionLost | imap::Error::Bye(_) => MailError::Network(message),
+            imap::Error::No(_) | imap::Error::Bad(_) => MailError::protocol(message.clone(), Some(message)),
+            imap::Error::Validate(_) => MailError::validation(message),
+            _ => MailError::protocol(message, None),
+        }
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for MailError {
+    fn from(error: lettre::transport::smtp::Error) -> Self {
+        let message = error.to_string();
+        if error.is_tls() {
+            MailError::Tls(message)
+        } else if error.is_timeout() {
+            MailError::Network(message)
+        } else if let Some(code) = error.status() {
+            // 552 is "exceeded storage allocation" (RFC 5321 4.2.2)
+            let code = code.to_string();
+            if code == "552" {
+                MailError::Quota { message, details: Some(json!({ "server_response": code })) }
+            } else {
+                MailError::protocol(message, Some(code))
+            }
+        } else if error.is_client() || error.is_response() {
+            MailError::protocol(message, None)
+        } else {
+            MailError::Network(message)
+        }
+    }
+}
+
+impl From<lettre::address::AddressError> for MailError {
+    fn from(error: lettre::address::AddressError) -> Self {
+        MailError::validation(error.to_string())
+    }
+}
+
+impl From<lettre::error::Error> for MailError {
+    fn from(error: lettre::error::Error) -> Self {
+        MailError::validation(error.to_string())
+    }
+}
+
+impl From<AddressError> for MailError {
+    fn from(error: AddressError) -> Self {
+        MailError::Validation { message: error.to_string(), details: serde_json::to_value(&error).ok() }
+    }
+}
+
+// The composer marks each bad recipient from `details.errors`
+impl From<InvalidAddresses> for MailError {
+    fn from(error: InvalidAddresses) -> Self {
+        MailError::Validation { message: error.to_string(), details: serde_json::to_value(&error).ok() }
+    }
+}
+
+// `details` holds the position and length of the part to underline
+impl From<QueryError> for MailError {
+    fn from(error: QueryError) -> Self {
+        MailError::Validation { message: error.to_string(), details: serde_json::to_value(&error).ok() }
+    }
+}
+
+// `details.attachments` lists the attachments to drop to get under the server's limit
+impl From<MessageTooLarge> for MailError {
+    fn from(error: MessageTooLarge) -> Self {
+        MailError::Quota { message: error.to_string(), details: serde_json::to_value(&error).ok() }
+    }
+}
+
+impl From<CardDavError> for MailError {
+    fn from(error: CardDavError) -> Self {
+        let message = error.to_string();
+        match &error {
+            CardDavError::Http(e) if e.is_timeout() || e.is_connect() => MailError::Network(message),
+            CardDavError::Status { status: 401 | 403, .. } => MailError::Auth(message),
+            CardDavError::Status { status, .. } => MailError::protocol(message, Some(status.to_string())),
+            CardDavError::Url(_) => MailError::validation(message),
+            CardDavError::NoAddressBook(_) => MailError::NotFound(message),
+            _ => MailError::protocol(message, None),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use crate::email::address::parse_recipients;
+    use crate::email::search_query;
+    use crate::error::*;
+    use crate::smtp_client::{AttachmentSize, MessageTooLarge};
+    use crate::test_utils::memory_pool;
+    use serde_json::json;
+    use std::io;
+
+    #[test]
+    fn test_serialized_shape() {
+        let error = MailError::Auth("Login failed: [AUTHENTICATIONFAILED] Invalid credentials".to_string());
+        assert_eq!(serde_json::to_value(&error).unwrap(), json!({
+            "kind": "auth",
+            "message": "Login failed: [AUTHENTICATIONFAILED] Invalid credentials",
+            "retryable": false,
+            "details": null,
+        }));
+
+        let error = MailError::protocol("Mailbox unavailable", Some("450".to_string()));
+        assert_eq!(serde_json::to_value(&error).unwrap(), json!({
+            "kind": "protocol",
+            "message": "Mailbox unavailable",
+            "retryable": true,
+            "details": { "server_response": "450" },
+        }));
+    }
+
+    #[test]
+    fn test_retryable() {
+        assert!(MailError::Network("Connection reset".to_string()).retryable());
+        assert!(MailError::Db { message: "database is locked".to_string(), busy: true }.retryable());
+        assert!(MailError::protocol("NO [UNAVAILABLE] Try later", Some("NO [UNAVAILABLE] Try later".to_string())).retryable());
+
+        assert!(!MailError::protocol("Relay denied", Some("550".to_string())).retryable());
+        assert!(!MailError::Tls("Certificate expired".to_string()).retryable());
+        assert!(!MailError::validation("Message has no recipients").retryable());
+        assert!(!MailError::NotFound("Account a not found".to_string()).retryable());
+    }
+
+    #[test]
+    fn test_context_keeps_kind() {
+        let result: Result<(), io::Error> = Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        let error = result.context("Failed to connect to IMAP").unwrap_err();
+        assert_eq!(error.kind(), "network");
+        assert_eq!(error.to_string(), "Failed to connect to IMAP: refused");
+
+        let error = MailError::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(error.kind(), "not_found");
+        let error = MailError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!(error.kind(), "io");
+    }
+
+    #[test]
+    fn test_bad_input_carries_details() {
+        let inputs = vec!["ok@example.com".to_string(), "broken@".to_string()];
+        let error = MailError::from(parse_recipients(&inputs).unwrap_err());
+        assert_eq!(error.kind(), "validation");
+        assert_eq!(error.details().unwrap()["errors"][0]["input"], "broken@");
+
+        let error = MailError::from(search_query::parse("from:").unwrap_err());
+        assert_eq!(error.kind(), "validation");
+        assert_eq!(error.details().unwrap()["position"], 5);
+
+        let attachments = vec![
+            AttachmentSize { filename: "small.txt".to_string(), size: 10, encoded_size: 16 },
+            AttachmentSize { filename: "video.mp4".to_string(), size: 900, encoded_size: 1200 },
+        ];
+        let error = MailError::from(MessageTooLarge::new(1000, 1300, &attachments));
+        assert_eq!(error.kind(), "quota");
+        assert!(!error.retryable());
+        assert_eq!(error.details().unwrap()["attachments"][0]["filename"], "video.mp4");
+    }
+
+    #[tokio::test]
+    async fn test_database_errors() {
+        let pool = memory_pool().await;
+
+        let error = MailError::from(sqlx::query("SELECT 1 WHERE 0").fetch_one(&pool).await.map(|_| ()).unwrap_err());
+        assert_eq!(error.kind(), "not_found");
+
+        sqlx::query("CREATE TABLE t (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO t VALUES ('a')").execute(&pool).await.unwrap();
+        let error = MailError::from(sqlx::query("INSERT INTO t VALUES ('a')").execute(&pool).await.unwrap_err());
+        assert_eq!(error.kind(), "db");
+        assert!(!error.retryable());
+    }
+}
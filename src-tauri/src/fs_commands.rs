@@ -1,22 +1,23 @@
+use crate::error::MailResult;
 use tauri::command;
 use std::fs;
 use std::path::PathBuf;
 
 #[command]
-pub fn read_text_file(path: String) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| e.to_string())
+pub fn read_text_file(path: String) -> MailResult<String> {
+    Ok(fs::read_to_string(path)?)
 }
 
 #[command]
-pub fn write_text_file(path: String, content: String) -> Result<(), String> {
-    fs::write(path, content).map_err(|e| e.to_string())
+pub fn write_text_file(path: String, content: String) -> MailResult<()> {
+    Ok(fs::write(path, content)?)
 }
 
 #[command]
-pub fn save_attachment(filename: String, content: Vec<u8>) -> Result<String, String> {
+pub fn save_attachment(filename: String, content: Vec<u8>) -> MailResult<String> {
     let downloads_dir = dirs::download_dir().unwrap_or_else(|| PathBuf::from("."));
     let file_path = downloads_dir.join(filename);
     
-    fs::write(&file_path, content).map_err(|e| e.to_string())?;
+    fs::write(&file_path, content)?;
     Ok(file_path.to_string_lossy().to_string())
 }
@@ -1,7 +1,11 @@
 use imap::{Client, Session};
-use mailparse::MailHeaderMap;
+use imap_proto::NameAttribute;
 use std::net::TcpStream;
 use serde::{Deserialize, Serialize};
+use crate::email::address::Mailbox;
+use crate::email::parser::{self as email_parser, DeliveryReport, EmailAttachment};
+use crate::error::{Context, MailError, MailResult};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImapConfig {
@@ -16,15 +20,25 @@ pub struct ImapConfig {
 pub struct ImapEmail {
     pub id: String,
     pub uid: u32,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
     pub from: String,
-    pub to: Vec<String>,
+    pub to: Vec<Mailbox>,
+    pub cc: Vec<Mailbox>,
     pub subject: String,
     pub body: String,
-    pub date: String,
+    // RFC 3339 in UTC
+    pub date: Option<String>,
+    pub size: u32,
     pub read: bool,
     pub starred: bool,
     pub has_attachments: bool,
+    // Every attachment part, with its content
+    pub attachments: Vec<EmailAttachment>,
     pub folder: String,
+    // Set when the message is a bounce or read receipt
+    pub report: Option<DeliveryReport>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,47 +62,47 @@ impl ImapClient {
         }
     }
 
-    pub fn connect(&mut self) -> Result<(), String> {
+    pub fn connect(&mut self) -> MailResult<()> {
         let imap_addr = format!("{}:{}", self.config.host, self.config.port);
         
         // Create TCP connection
         let stream = TcpStream::connect(&imap_addr)
-            .map_err(|e| format!("Failed to connect to {}: {}", imap_addr, e))?;
+            .context(&format!("Failed to connect to {}", imap_addr))?;
         
         // Create TLS connection
         let tls_stream = native_tls::TlsConnector::builder()
             .build()
-            .map_err(|e| format!("Failed to create TLS connector: {}", e))?
+            .context("Failed to create TLS connector")?
             .connect(&self.config.host, stream)
-            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+            .map_err(|e| MailError::Tls(format!("TLS handshake failed: {}", e)))?;
         
         // Create IMAP client
         let client = Client::new(tls_stream);
         let mut session = client.login(&self.config.username, &self.config.password)
-            .map_err(|e| format!("Login failed: {:?}", e))?;
+            .map_err(|(e, _)| login_error(e))?;
 
         // Test connection
         session.capabilities()
-            .map_err(|e| format!("Failed to get capabilities: {}", e))?;
+            .context("Failed to get capabilities")?;
 
         self.session = Some(session);
         Ok(())
     }
 
-    pub fn disconnect(&mut self) -> Result<(), String> {
+    pub fn disconnect(&mut self) -> MailResult<()> {
         if let Some(mut session) = self.session.take() {
             session.logout()
-                .map_err(|e| format!("Failed to logout: {}", e))?;
+                .context("Failed to logout")?;
         }
         Ok(())
     }
 
-    pub fn list_folders(&mut self) -> Result<Vec<ImapFolder>, String> {
+    pub fn list_folders(&mut self) -> MailResult<Vec<ImapFolder>> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         let folders = session.list(None, Some("*"))
-            .map_err(|e| format!("Failed to list folders: {}", e))?;
+            .context("Failed to list folders")?;
 
         let mut result = Vec::new();
         for folder in folders.iter() {
@@ -96,7 +110,7 @@ impl ImapClient {
                 result.push(ImapFolder {
                     name: folder_name.to_string(),
                     delimiter: folder.delimiter().unwrap_or("/").to_string(),
-                    flags: vec![], // TODO: Parse folder flags
+                    flags: folder.attributes().iter().map(attribute_name).collect(),
                     message_count: None,
                 });
         }
@@ -104,19 +118,43 @@ impl ImapClient {
         Ok(result)
     }
 
-    pub fn select_folder(&mut self, folder: &str) -> Result<u32, String> {
+    pub fn select_folder(&mut self, folder: &str) -> MailResult<u32> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         let mailbox = session.select(folder)
-            .map_err(|e| format!("Failed to select folder '{}': {}", folder, e))?;
+            .context(&format!("Failed to select folder '{}'", folder))?;
         Ok(mailbox.exists)
     }
 
-    pub fn fetch_emails(&mut self, folder: &str, limit: u32) -> Result<Vec<ImapEmail>, String> {
+    pub fn create_folder(&mut self, folder: &str) -> MailResult<()> {
+        let session = self.session.as_mut()
+            .ok_or_else(not_connected)?;
+
+        session.create(folder)
+            .context(&format!("Failed to create folder '{}'", folder))
+    }
+
+    pub fn rename_folder(&mut self, folder: &str, new_name: &str) -> MailResult<()> {
+        let session = self.session.as_mut()
+            .ok_or_else(not_connected)?;
+
+        session.rename(folder, new_name)
+            .context(&format!("Failed to rename folder '{}'", folder))
+    }
+
+    pub fn delete_folder(&mut self, folder: &str) -> MailResult<()> {
+        let session = self.session.as_mut()
+            .ok_or_else(not_connected)?;
+
+        session.delete(folder)
+            .context(&format!("Failed to delete folder '{}'", folder))
+    }
+
+    pub fn fetch_emails(&mut self, folder: &str, limit: u32) -> MailResult<Vec<ImapEmail>> {
         let message_count = self.select_folder(folder)?;
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         // Fetch latest emails
         let start_seq = if message_count > limit {
@@ -125,14 +163,14 @@ impl ImapClient {
             1
         };
 
-        let messages = session.fetch(format!("{}:{}", start_seq, message_count), "(UID RFC822 FLAGS)")
-            .map_err(|e| format!("Failed to fetch emails: {}", e))?;
+        let messages = session.fetch(format!("{}:{}", start_seq, message_count), "(UID RFC822 FLAGS INTERNALDATE)")
+            .context("Failed to fetch emails")?;
 
         let mut emails = Vec::new();
         for msg in messages.iter().rev() {
             if let Some(uid) = msg.uid {
                 if let Some(body) = msg.body() {
-                    let email = self.parse_email(uid, body, folder)?;
+                    let email = self.parse_email(uid, body, msg.internal_date(), folder)?;
                     emails.push(email);
                 }
             }
@@ -141,124 +179,170 @@ impl ImapClient {
         Ok(emails)
     }
 
-    fn parse_email(&self, uid: u32, raw_body: &[u8], folder: &str) -> Result<ImapEmail, String> {
-        let parsed = mailparse::parse_mail(raw_body)
-            .map_err(|e| format!("Failed to parse email: {}", e))?;
+    fn parse_email(&self, uid: u32, raw_body: &[u8], internal_date: Option<DateTime<FixedOffset>>, folder: &str) -> MailResult<ImapEmail> {
+        // A message the server handed us that can't be read is the server's doing
+        let parsed = email_parser::parse_email(raw_body)
+            .map_err(|e| MailError::protocol(e.to_string(), None))?;
 
-        let from = parsed.headers.get_first_value("From")
+        let from = parsed.from.first()
+            .map(display_mailbox)
             .unwrap_or_else(|| "Unknown".to_string());
 
-        let to = parsed.headers.get_all_values("To");
-        let to = if to.is_empty() {
-            vec!["Unknown".to_string()]
-        } else {
-            to
-        };
-
-        let subject = parsed.headers.get_first_value("Subject")
-            .unwrap_or_else(|| "No Subject".to_string());
-
-        let date = parsed.headers.get_first_value("Date")
-            .unwrap_or_else(|| "".to_string());
+        // Stored as RFC 3339 in UTC so dates sort as text. Without a readable Date header the
+        // message is dated by when the server received it.
+        let date = parsed.date.as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .or(internal_date)
+            .map(|date| date.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true));
 
-        let body = parsed.get_body()
-            .unwrap_or_else(|_| "Failed to parse body".to_string());
+        let body = parsed.body_text.clone()
+            .or_else(|| parsed.body_html.clone())
+            .unwrap_or_default();
 
         let read = true; // Default to read since we can't access flags
         let starred = false;
-        let has_attachments = parsed.subparts.len() > 1; // Simple attachment detection
 
         Ok(ImapEmail {
             id: format!("{}-{}", folder, uid),
             uid,
+            has_attachments: parsed.has_attachments(),
+            message_id: parsed.message_id,
+            in_reply_to: parsed.in_reply_to,
+            references: parsed.references,
             from,
-            to,
-            subject,
+            to: parsed.to,
+            cc: parsed.cc,
+            subject: parsed.subject.unwrap_or_else(|| "No Subject".to_string()),
             body,
             date,
+            size: raw_body.len() as u32,
             read,
             starred,
-            has_attachments,
+            attachments: parsed.attachments,
             folder: folder.to_string(),
+            report: parsed.report,
         })
     }
 
-    pub fn mark_as_read(&mut self, folder: &str, uid: u32) -> Result<(), String> {
+    pub fn mark_as_read(&mut self, folder: &str, uid: u32) -> MailResult<()> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         session.select(folder)
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .context("Failed to select folder")?;
 
         session.store(format!("{}", uid), "+FLAGS (\\Seen)")
-            .map_err(|e| format!("Failed to mark as read: {}", e))?;
+            .context("Failed to mark as read")?;
 
         Ok(())
     }
 
-    pub fn mark_as_starred(&mut self, folder: &str, uid: u32) -> Result<(), String> {
+    pub fn mark_as_starred(&mut self, folder: &str, uid: u32) -> MailResult<()> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         session.select(folder)
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .context("Failed to select folder")?;
 
         session.store(format!("{}", uid), "+FLAGS (\\Flagged)")
-            .map_err(|e| format!("Failed to mark as starred: {}", e))?;
+            .context("Failed to mark as starred")?;
 
         Ok(())
     }
 
-    pub fn delete_email(&mut self, folder: &str, uid: u32) -> Result<(), String> {
+    pub fn delete_email(&mut self, folder: &str, uid: u32) -> MailResult<()> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         session.select(folder)
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .context("Failed to select folder")?;
 
         // Mark for deletion
         session.store(format!("{}", uid), "+FLAGS (\\Deleted)")
-            .map_err(|e| format!("Failed to mark for deletion: {}", e))?;
+            .context("Failed to mark for deletion")?;
 
         // Expunge to actually delete
         session.expunge()
-            .map_err(|e| format!("Failed to expunge deleted emails: {}", e))?;
+            .context("Failed to expunge deleted emails")?;
 
         Ok(())
     }
 
-    pub fn mark_as_unread(&mut self, folder: &str, uid: u32) -> Result<(), String> {
+    pub fn mark_as_unread(&mut self, folder: &str, uid: u32) -> MailResult<()> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         session.select(folder)
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .context("Failed to select folder")?;
 
         session.store(format!("{}", uid), "-FLAGS (\\Seen)")
-            .map_err(|e| format!("Failed to mark as unread: {}", e))?;
+            .context("Failed to mark as unread")?;
 
         Ok(())
     }
 
-    pub fn move_email(&mut self, folder: &str, uid: u32, dest_folder: &str) -> Result<(), String> {
+    pub fn move_email(&mut self, folder: &str, uid: u32, dest_folder: &str) -> MailResult<()> {
         let session = self.session.as_mut()
-            .ok_or("Not connected to IMAP server")?;
+            .ok_or_else(not_connected)?;
 
         session.select(folder)
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .context("Failed to select folder")?;
 
         // Copy email to destination folder
         session.copy(format!("{}", uid), dest_folder)
-            .map_err(|e| format!("Failed to copy email: {}", e))?;
+            .context("Failed to copy email")?;
 
         // Mark original for deletion
         session.store(format!("{}", uid), "+FLAGS (\\Deleted)")
-            .map_err(|e| format!("Failed to mark for deletion: {}", e))?;
+            .context("Failed to mark for deletion")?;
 
         // Expunge to actually delete
         session.expunge()
-            .map_err(|e| format!("Failed to expunge deleted emails: {}", e))?;
+            .context("Failed to expunge deleted emails")?;
 
         Ok(())
     }
 }
+
+fn not_connected() -> MailError {
+    MailError::Network("Not connected to IMAP server".to_string())
+}
+
+// A NO or BAD answer to LOGIN means the credentials were refused
+fn login_error(error: imap::Error) -> MailError {
+    match error {
+        imap::Error::No(_) | imap::Error::Bad(_) => MailError::Auth(format!("Login failed: {}", error)),
+        error => MailError::from(error).context("Login failed"),
+    }
+}
+
+// "Name <address>" as shown in the message list, with the name already decoded
+fn display_mailbox(mailbox: &Mailbox) -> String {
+    match mailbox.name.as_deref() {
+        Some(name) if name.contains([',', ';', '<', '>', '"', '@']) => {
+            format!("\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), mailbox.address)
+        }
+        Some(name) => format!("{} <{}>", name, mailbox.address),
+        None => mailbox.address.clone(),
+    }
+}
+
+// LIST attribute as the server spelled it, e.g. \Sent
+fn attribute_name(attribute: &NameAttribute) -> String {
+    match attribute {
+        NameAttribute::NoInferiors => "\\Noinferiors".to_string(),
+        NameAttribute::NoSelect => "\\Noselect".to_string(),
+        NameAttribute::Marked => "\\Marked".to_string(),
+        NameAttribute::Unmarked => "\\Unmarked".to_string(),
+        NameAttribute::All => "\\All".to_string(),
+        NameAttribute::Archive => "\\Archive".to_string(),
+        NameAttribute::Drafts => "\\Drafts".to_string(),
+        NameAttribute::Flagged => "\\Flagged".to_string(),
+        NameAttribute::Junk => "\\Junk".to_string(),
+        NameAttribute::Sent => "\\Sent".to_string(),
+        NameAttribute::Trash => "\\Trash".to_string(),
+        NameAttribute::Extension(name) => name.to_string(),
+        // Attributes added to imap-proto later, which no folder role depends on
+        other => format!("{:?}", other),
+    }
+}
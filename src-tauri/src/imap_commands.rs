@@ -1,3 +1,4 @@
+use crate::error::{Context, MailError, MailResult};
 use crate::imap_client::{ImapClient, ImapConfig, ImapEmail, ImapFolder};
 use serde::{Deserialize, Serialize};
 use std::sync::{Mutex, LazyLock};
@@ -29,64 +30,64 @@ pub struct MarkEmailRequest {
 }
 
 #[tauri::command]
-pub async fn imap_connect(request: ConnectRequest) -> Result<(), String> {
+pub async fn imap_connect(request: ConnectRequest) -> MailResult<()> {
     let mut clients = IMAP_CLIENTS.lock().unwrap();
     let mut client = ImapClient::new(request.imap_config);
     client.connect()
-        .map_err(|e| format!("Failed to connect: {}", e))?;
+        .context("Failed to connect")?;
     clients.insert(request.account_id, client);
     Ok(())
 }
 
 #[tauri::command]
-pub fn imap_disconnect(account_id: String) -> Result<String, String> {
+pub fn imap_disconnect(account_id: String) -> MailResult<String> {
     let mut connections = IMAP_CLIENTS.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| MailError::Internal(format!("Failed to acquire lock: {}", e)))?;
     
     if let Some(mut client) = connections.remove(&account_id) {
         client.disconnect()
-            .map_err(|e| format!("Failed to disconnect: {}", e))?;
+            .context("Failed to disconnect")?;
     }
     
     Ok("Disconnected successfully".to_string())
 }
 
 #[tauri::command]
-pub async fn imap_list_folders(account_id: String) -> Result<Vec<ImapFolder>, String> {
+pub async fn imap_list_folders(account_id: String) -> MailResult<Vec<ImapFolder>> {
     let mut clients = IMAP_CLIENTS.lock().unwrap();
     
     let client = clients.get_mut(&account_id)
-        .ok_or("No connection found for account")?;
+        .ok_or_else(|| MailError::NotFound("No connection found for account".to_string()))?;
     
     client.list_folders()
 }
 
 #[tauri::command]
-pub fn imap_fetch_emails(request: FetchEmailsRequest) -> Result<Vec<ImapEmail>, String> {
+pub fn imap_fetch_emails(request: FetchEmailsRequest) -> MailResult<Vec<ImapEmail>> {
     let mut connections = IMAP_CLIENTS.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| MailError::Internal(format!("Failed to acquire lock: {}", e)))?;
     
     let client = connections.get_mut(&request.account_id)
-        .ok_or("No connection found for account")?;
+        .ok_or_else(|| MailError::NotFound("No connection found for account".to_string()))?;
     
     let limit = request.limit.unwrap_or(50);
     client.fetch_emails(&request.folder, limit)
 }
 
 #[tauri::command]
-pub fn imap_mark_email(request: MarkEmailRequest) -> Result<(), String> {
+pub fn imap_mark_email(request: MarkEmailRequest) -> MailResult<()> {
     let mut connections = IMAP_CLIENTS.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| MailError::Internal(format!("Failed to acquire lock: {}", e)))?;
     
     let client = connections.get_mut(&request.account_id)
-        .ok_or("No connection found for account")?;
+        .ok_or_else(|| MailError::NotFound("No connection found for account".to_string()))?;
     
     match request.action.as_str() {
         "read" => client.mark_as_read(&request.folder, request.uid),
         "unread" => client.mark_as_unread(&request.folder, request.uid),
         "starred" => client.mark_as_starred(&request.folder, request.uid),
         "delete" => client.delete_email(&request.folder, request.uid),
-        _ => Err(format!("Unknown action: {}", request.action)),
+        _ => Err(MailError::validation(format!("Unknown action: {}", request.action))),
     }
 }
 
@@ -99,25 +100,25 @@ pub struct MoveEmailRequest {
 }
 
 #[tauri::command]
-pub fn imap_move_email(request: MoveEmailRequest) -> Result<(), String> {
+pub fn imap_move_email(request: MoveEmailRequest) -> MailResult<()> {
     let mut connections = IMAP_CLIENTS.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| MailError::Internal(format!("Failed to acquire lock: {}", e)))?;
     
     let client = connections.get_mut(&request.account_id)
-        .ok_or("No connection found for account")?;
+        .ok_or_else(|| MailError::NotFound("No connection found for account".to_string()))?;
     
     client.move_email(&request.folder, request.uid, &request.dest_folder)
 }
 
 #[tauri::command]
-pub fn imap_test_connection(imap_config: ImapConfig) -> Result<String, String> {
+pub fn imap_test_connection(imap_config: ImapConfig) -> MailResult<String> {
     let mut client = ImapClient::new(imap_config);
     
     client.connect()
-        .map_err(|e| format!("Connection test failed: {}", e))?;
+        .context("Connection test failed")?;
     
     client.disconnect()
-        .map_err(|e| format!("Failed to disconnect after test: {}", e))?;
+        .context("Failed to disconnect after test")?;
     
     Ok("Connection test successful".to_string())
 }
@@ -15,13 +15,29 @@ mod commands;
 mod db;
 mod models;
 mod credentials;
+mod error;
+mod contacts;
+mod outbox;
+#[cfg(test)]
 mod test_utils;
 
+#[cfg(test)]
+mod credentials_tests;
+#[cfg(test)]
+mod error_tests;
+#[cfg(test)]
+mod smtp_client_tests;
+#[cfg(test)]
+mod outbox_tests;
+
 use tauri::Manager;
 
 type SmtpClients = Mutex<HashMap<String, smtp_client::SmtpClient>>;
 
 fn main() {
+    // Log to stderr
+    tracing_subscriber::fmt::init();
+
     // Initialize SMTP clients map
     let smtp_clients: SmtpClients = std::sync::Mutex::new(std::collections::HashMap::new());
     
@@ -31,6 +47,7 @@ fn main() {
                 let db = db::Database::init(app.handle()).await.expect("Failed to initialize database");
                 app.manage(db);
             });
+            outbox::spawn_scheduler(app.handle().clone());
             Ok(())
         })
         .manage(smtp_clients)
@@ -50,20 +67,15 @@ fn main() {
             smtp_commands::smtp_connect,
             smtp_commands::smtp_disconnect,
             smtp_commands::smtp_send_email,
+            smtp_commands::smtp_validate_addresses,
             // Email commands
             commands::email::parse_email_content,
-            commands::email_ops::save_account,
-            commands::email_ops::get_accounts,
-            commands::email_ops::delete_account,
-            commands::email_ops::sync_folders,
-            commands::email_ops::fetch_emails,
-            commands::email_ops::send_email,
-            commands::email_ops::test_smtp_connection,
             // Secure email commands
             commands::email_secure::save_account_secure,
             commands::email_secure::get_accounts_secure,
             commands::email_secure::delete_account_secure,
             commands::email_secure::get_account_with_credentials,
+            commands::email_secure::update_account_credential,
             commands::email_secure::sync_folders_secure,
             commands::email_secure::fetch_emails_secure,
             commands::email_secure::send_email_secure,
@@ -76,6 +88,7 @@ fn main() {
             commands::folder_ops::move_emails_to_folder,
             commands::folder_ops::empty_folder,
             commands::folder_ops::get_folder_stats,
+            commands::folder_ops::list_folders,
             // Email actions
             commands::email_actions::mark_emails_as_read,
             commands::email_actions::mark_emails_as_unread,
@@ -91,11 +104,13 @@ fn main() {
             commands::attachments::upload_multiple_attachments,
             commands::attachments::get_email_attachments,
             commands::attachments::download_attachment,
+            commands::attachments::get_attachment_path,
             commands::attachments::delete_attachment,
             commands::attachments::get_attachment_preview,
             commands::attachments::get_text_attachment_content,
             commands::attachments::save_attachment_to_file,
             commands::attachments::get_attachment_stats,
+            commands::attachments::collect_attachment_garbage,
             // Search
             commands::search::search_emails,
             commands::search::quick_search,
@@ -106,6 +121,56 @@ fn main() {
             commands::search::search_starred_emails,
             commands::search::search_by_date_range,
             commands::search::get_search_suggestions,
+            commands::search::parse_search_query,
+            // Message lists
+            commands::messages::list_messages,
+            // Unified inbox
+            commands::unified::list_unified_messages,
+            commands::unified::get_unified_counts,
+            // Saved searches
+            commands::saved_searches::list_saved_searches,
+            commands::saved_searches::save_saved_search,
+            commands::saved_searches::delete_saved_search,
+            commands::saved_searches::list_saved_search_messages,
+            commands::saved_searches::list_folder_entries,
+            // Identities
+            commands::identities::list_identities,
+            commands::identities::save_identity,
+            commands::identities::delete_identity,
+            commands::identities::get_reply_identity,
+            // Signatures
+            commands::signatures::list_signatures,
+            commands::signatures::get_signature_images,
+            commands::signatures::save_signature,
+            commands::signatures::delete_signature,
+            // Scheduled send
+            commands::scheduled::undo_send,
+            commands::scheduled::list_scheduled_emails,
+            commands::scheduled::reschedule_email,
+            commands::scheduled::get_undo_send_delay,
+            commands::scheduled::set_undo_send_delay,
+            // Delivery status and read receipts
+            commands::delivery_status::get_delivery_status,
+            commands::delivery_status::list_sent_messages,
+            // Contacts
+            commands::contacts::list_contacts,
+            commands::contacts::get_contact,
+            commands::contacts::autocomplete_contacts,
+            commands::contacts::save_contact,
+            commands::contacts::merge_contacts,
+            commands::contacts::delete_contact,
+            commands::contacts::import_vcard_file,
+            commands::contacts::import_contact_cards,
+            commands::contacts::export_contacts_vcard,
+            commands::contacts::export_contacts_vcard_file,
+            commands::contacts::discover_carddav_addressbooks,
+            commands::contacts::add_carddav_account,
+            commands::contacts::list_carddav_accounts,
+            commands::contacts::remove_carddav_account,
+            commands::contacts::sync_carddav_account,
+            // Threads
+            commands::threads::list_threads,
+            commands::threads::get_thread_messages,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
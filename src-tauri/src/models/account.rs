@@ -12,3 +12,21 @@ pub struct Account {
     pub smtp_host: Option<String>,
     pub smtp_port: Option<i64>,
 }
+
+// An account with everything needed to connect to its servers except the passwords,
+// which are kept in the credential store
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MailAccount {
+    pub id: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub provider: Option<String>,
+    pub imap_host: Option<String>,
+    pub imap_port: Option<i64>,
+    pub imap_username: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<i64>,
+    pub smtp_username: Option<String>,
+    #[sqlx(default)]
+    pub created_at: Option<String>,
+}
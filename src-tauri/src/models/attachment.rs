@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// An attachment's metadata; the content is in the blob store under `content_hash`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MailAttachment {
+    pub id: String,
+    pub email_id: String,
+    // IMAP section within the message; None for uploaded files
+    pub section: Option<String>,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub size: Option<i64>,
+    pub content_id: Option<String>,
+    pub disposition: Option<String>,
+    pub content_hash: Option<String>,
+}
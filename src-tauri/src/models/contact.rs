@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Contact {
+    pub id: String,
+    pub uid: Option<String>,
+    pub display_name: Option<String>,
+    pub organization: Option<String>,
+    pub notes: Option<String>,
+    pub photo_mime_type: Option<String>,
+    pub is_manual: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ContactEmail {
+    pub email: String,
+    pub contact_id: String,
+    pub is_primary: bool,
+    pub times_contacted: i64,
+    pub times_seen: i64,
+    pub last_used: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ContactPhone {
+    pub id: String,
+    pub contact_id: String,
+    pub number: String,
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CardDavAccount {
+    pub id: String,
+    pub server_url: String,
+    pub username: String,
+    pub addressbook_url: String,
+    pub display_name: Option<String>,
+    pub sync_token: Option<String>,
+    pub last_synced_at: Option<String>,
+}
@@ -16,12 +16,7 @@ pub struct Email {
     pub is_starred: bool,
     pub has_attachments: bool,
     pub preview: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct EmailDetail {
-    #[sqlx(flatten)]
-    pub header: Email,
-    pub body_text: Option<String>,
-    pub body_html: Option<String>,
+    // Bytes; not every query selects it
+    #[sqlx(default)]
+    pub size: Option<i64>,
 }
@@ -7,4 +7,16 @@ pub struct Folder {
     pub account_id: String,
     pub name: String,
     pub delimiter: Option<String>,
+    #[sqlx(default)]
+    pub role: Option<String>,
+}
+
+// A folder with the number of messages stored locally
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MailFolder {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub folder: Folder,
+    pub total_count: i64,
+    pub unread_count: i64,
 }
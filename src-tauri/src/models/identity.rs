@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Identity {
+    pub id: String,
+    pub account_id: String,
+    pub display_name: Option<String>,
+    pub email: String,
+    pub reply_to: Option<String>,
+    pub signature_id: Option<String>,
+    pub reply_placement: String,
+    pub forward_placement: String,
+    pub default_bcc: Option<String>,
+    pub is_default: bool,
+}
@@ -1,3 +1,17 @@
 pub mod account;
+pub mod attachment;
+pub mod contact;
 pub mod email;
 pub mod folder;
+pub mod identity;
+pub mod saved_search;
+pub mod signature;
+
+pub use account::{Account, MailAccount};
+pub use attachment::MailAttachment;
+pub use contact::{CardDavAccount, Contact, ContactEmail, ContactPhone};
+pub use email::Email;
+pub use folder::{Folder, MailFolder};
+pub use identity::Identity;
+pub use saved_search::SavedSearch;
+pub use signature::{Signature, SignatureImage};
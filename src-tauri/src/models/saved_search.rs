@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    // Empty means every account or folder
+    pub account_ids: Vec<String>,
+    pub folder_ids: Vec<String>,
+    pub position: i64,
+}
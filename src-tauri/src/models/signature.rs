@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Signature {
+    pub id: String,
+    pub account_id: String,
+    pub name: String,
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SignatureImage {
+    pub id: String,
+    pub signature_id: String,
+    pub content_id: String,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub content: Vec<u8>,
+}
@@ -0,0 +1,246 @@
+use crate::db::{self, Database};
+use crate::error::{Context, MailError, MailResult};
+use crate::smtp_client::EmailMessage;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::future::Future;
+use tauri::{AppHandle, Manager};
+
+const UNDO_SEND_DELAY_KEY: &str = "undo_send_seconds";
+const DEFAULT_UNDO_SEND_SECONDS: u32 = 10;
+pub const MAX_UNDO_SEND_SECONDS: u32 = 120;
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+const MAX_ATTEMPTS: i64 = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduledEmail {
+    pub id: String,
+    pub account_id: String,
+    pub message: EmailMessage,
+    pub send_at: String,
+    pub status: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+}
+
+// Timestamps are stored as second-precision UTC RFC 3339 so they compare as strings
+pub fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn parse_timestamp(value: &str) -> MailResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| MailError::validation(format!("Invalid timestamp '{}': {}", value, e)))
+}
+
+pub async fn undo_send_delay(pool: &Pool<Sqlite>) -> MailResult<u32> {
+    Ok(db::get_setting(pool, UNDO_SEND_DELAY_KEY).await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_UNDO_SEND_SECONDS))
+}
+
+pub async fn set_undo_send_delay(pool: &Pool<Sqlite>, seconds: u32) -> MailResult<()> {
+    if seconds > MAX_UNDO_SEND_SECONDS {
+        return Err(MailError::validation(format!("Undo send delay cannot exceed {} seconds", MAX_UNDO_SEND_SECONDS)));
+    }
+    db::set_setting(pool, UNDO_SEND_DELAY_KEY, &seconds.to_string()).await
+}
+
+// When a message should actually leave: its scheduled time, but never before the
+// undo window has passed. None means it can be sent right away.
+pub fn due_time(send_at: Option<&str>, undo_seconds: u32) -> MailResult<Option<DateTime<Utc>>> {
+    let now = Utc::now();
+    let earliest = now + Duration::seconds(undo_seconds as i64);
+
+    let requested = send_at.map(parse_timestamp).transpose()?;
+    let due = match requested {
+        Some(time) if time > earliest => time,
+        _ => earliest,
+    };
+
+    Ok(if due > now { Some(due) } else { None })
+}
+
+pub async fn enqueue(pool: &Pool<Sqlite>, account_id: &str, message: &EmailMessage, send_at: DateTime<Utc>) -> MailResult<String> {
+    let outbox_id = uuid::Uuid::new_v4().to_string();
+    let payload = serde_json::to_string(message)
+        .context("Failed to serialize message")?;
+
+    sqlx::query("INSERT INTO outbox (id, account_id, message, send_at) VALUES (?, ?, ?, ?)")
+        .bind(&outbox_id)
+        .bind(account_id)
+        .bind(&payload)
+        .bind(format_timestamp(send_at))
+        .execute(pool)
+        .await
+        .context("Failed to queue message")?;
+
+    Ok(outbox_id)
+}
+
+// Removes a message that has not gone out yet and hands it back to the composer
+pub async fn withdraw(pool: &Pool<Sqlite>, outbox_id: &str) -> MailResult<EmailMessage> {
+    let payload = sqlx::query_scalar::<_, String>(
+        "DELETE FROM outbox WHERE id = ? AND status IN ('pending', 'failed') RETURNING message"
+    )
+    .bind(outbox_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to withdraw message")?
+    .ok_or_else(|| MailError::NotFound("Message is already being sent and can no longer be undone".to_string()))?;
+
+    let mut message: EmailMessage = serde_json::from_str(&payload)
+        .context("Failed to parse queued message")?;
+    message.send_at = None;
+    Ok(message)
+}
+
+pub async fn reschedule(pool: &Pool<Sqlite>, outbox_id: &str, send_at: DateTime<Utc>) -> MailResult<()> {
+    let result = sqlx::query(
+        "UPDATE outbox SET send_at = ?, status = 'pending', attempts = 0, last_error = NULL WHERE id = ? AND status IN ('pending', 'failed')"
+    )
+    .bind(format_timestamp(send_at))
+    .bind(outbox_id)
+    .execute(pool)
+    .await
+    .context("Failed to reschedule message")?;
+
+    if result.rows_affected() == 0 {
+        return Err(MailError::NotFound("Message is already being sent and can no longer be rescheduled".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn list(pool: &Pool<Sqlite>, account_id: Option<&str>) -> MailResult<Vec<ScheduledEmail>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, account_id, message, send_at, status, attempts, last_error
+        FROM outbox
+        WHERE (?1 IS NULL OR account_id = ?1)
+        ORDER BY send_at
+        "#
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list scheduled messages")?;
+
+    let mut scheduled = Vec::with_capacity(rows.len());
+    for row in rows {
+        let payload: String = row.get("message");
+        scheduled.push(ScheduledEmail {
+            id: row.get("id"),
+            account_id: row.get("account_id"),
+            message: serde_json::from_str(&payload)
+                .context("Failed to parse queued message")?,
+            send_at: row.get("send_at"),
+            status: row.get("status"),
+            attempts: row.get("attempts"),
+            last_error: row.get("last_error"),
+        });
+    }
+
+    Ok(scheduled)
+}
+
+// Polls the outbox for due messages. The queue lives in SQLite, so anything
+// scheduled before the app was closed is picked up again on the next start.
+pub fn spawn_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let pool = app_handle.state::<Database>().pool.clone();
+
+        // A crash mid-send leaves rows in 'sending'; retry them rather than lose them
+        if let Err(e) = sqlx::query("UPDATE outbox SET status = 'pending' WHERE status = 'sending'")
+            .execute(&pool)
+            .await
+        {
+            tracing::error!("Failed to recover interrupted outbox messages: {}", e);
+        }
+
+        let app = &app_handle;
+        loop {
+            let delivered = deliver_due(&pool, move |account_id, message| {
+                crate::commands::email_secure::deliver_message(app.state::<Database>(), app.clone(), account_id, message)
+            })
+            .await;
+            if let Err(e) = delivered {
+                tracing::error!("Outbox delivery failed: {}", e);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+// Sends every pending message that is due through `send`, which is handed the account
+// and the message
+pub async fn deliver_due<F, Fut>(pool: &Pool<Sqlite>, mut send: F) -> MailResult<()>
+where
+    F: FnMut(String, EmailMessage) -> Fut,
+    Fut: Future<Output = MailResult<()>>,
+{
+    let due = sqlx::query(
+        "SELECT id, account_id, message, attempts FROM outbox WHERE status = 'pending' AND send_at <= ? ORDER BY send_at"
+    )
+    .bind(format_timestamp(Utc::now()))
+    .fetch_all(pool)
+    .await
+    .context("Failed to read outbox")?;
+
+    for row in due {
+        let outbox_id: String = row.get("id");
+        let account_id: String = row.get("account_id");
+        let payload: String = row.get("message");
+        let attempts: i64 = row.get::<i64, _>("attempts") + 1;
+
+        // Claim the row; losing the race means it was undone in the meantime
+        let claimed = sqlx::query("UPDATE outbox SET status = 'sending', attempts = ? WHERE id = ? AND status = 'pending'")
+            .bind(attempts)
+            .bind(&outbox_id)
+            .execute(pool)
+            .await
+            .context("Failed to claim outbox message")?;
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+
+        let result = match serde_json::from_str::<EmailMessage>(&payload) {
+            Ok(message) => send(account_id, message).await,
+            Err(e) => Err(MailError::from(e).context("Failed to parse queued message")),
+        };
+
+        match result {
+            Ok(()) => {
+                sqlx::query("DELETE FROM outbox WHERE id = ?")
+                    .bind(&outbox_id)
+                    .execute(pool)
+                    .await
+                    .context("Failed to remove sent message from outbox")?;
+            }
+            // A refused password or bad address fails the same way every time, so only
+            // transient errors are retried
+            Err(error) if error.retryable() && attempts < MAX_ATTEMPTS => {
+                let retry_at = Utc::now() + Duration::minutes(attempts);
+                sqlx::query("UPDATE outbox SET status = 'pending', send_at = ?, last_error = ? WHERE id = ?")
+                    .bind(format_timestamp(retry_at))
+                    .bind(error.to_string())
+                    .bind(&outbox_id)
+                    .execute(pool)
+                    .await
+                    .context("Failed to reschedule outbox message")?;
+            }
+            Err(error) => {
+                sqlx::query("UPDATE outbox SET status = 'failed', last_error = ? WHERE id = ?")
+                    .bind(error.to_string())
+                    .bind(&outbox_id)
+                    .execute(pool)
+                    .await
+                    .context("Failed to mark outbox message as failed")?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+#[cfg(test)]
+mod tests {
+    use crate::error::MailError;
+    use crate::outbox::*;
+    use crate::smtp_client::{ComposeMode, EmailMessage};
+    use crate::test_utils::seeded_pool;
+    use chrono::{Duration, Utc};
+    use sqlx::{Pool, Sqlite};
+    use std::sync::{Arc, Mutex};
+
+    async fn setup_pool() -> Pool<Sqlite> {
+        seeded_pool("INSERT INTO accounts (id, email) VALUES ('acc', 'me@example.com')").await
+    }
+
+    fn message(subject: &str) -> EmailMessage {
+        EmailMessage {
+            to: vec!["bob@example.com".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: subject.to_string(),
+            body_text: "Hello".to_string(),
+            body_html: None,
+            attachments: vec![],
+            identity_id: None,
+            compose_mode: ComposeMode::New,
+            in_reply_to: None,
+            references: vec![],
+            quoted_text: None,
+            quoted_html: None,
+            send_at: Some("2030-01-01T09:00:00Z".to_string()),
+            request_dsn: false,
+            request_read_receipt: false,
+        }
+    }
+
+    async fn row(pool: &Pool<Sqlite>, id: &str) -> Option<(String, i64, String, Option<String>)> {
+        sqlx::query_as("SELECT status, attempts, send_at, last_error FROM outbox WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn set_status(pool: &Pool<Sqlite>, id: &str, status: &str, attempts: i64) {
+        sqlx::query("UPDATE outbox SET status = ?, attempts = ? WHERE id = ?")
+            .bind(status)
+            .bind(attempts)
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_due_time() {
+        let now = Utc::now();
+
+        // No schedule and no undo window: straight out
+        assert_eq!(due_time(None, 0).unwrap(), None);
+        assert_eq!(due_time(Some("2020-01-01T00:00:00Z"), 0).unwrap(), None);
+
+        // The undo window holds a message back
+        let due = due_time(None, 10).unwrap().unwrap();
+        assert!(due >= now + Duration::seconds(10) && due <= Utc::now() + Duration::seconds(10));
+
+        // A later schedule wins over the undo window, an earlier one doesn't
+        let due = due_time(Some("2030-01-01T09:00:00Z"), 10).unwrap().unwrap();
+        assert_eq!(format_timestamp(due), "2030-01-01T09:00:00Z");
+        let due = due_time(Some("2020-01-01T00:00:00Z"), 10).unwrap().unwrap();
+        assert!(due >= now + Duration::seconds(10));
+
+        assert!(matches!(due_time(Some("tomorrow"), 10).unwrap_err(), MailError::Validation { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_and_withdraw() {
+        let pool = setup_pool().await;
+        let send_at = parse_timestamp("2030-01-01T09:00:00Z").unwrap();
+        let id = enqueue(&pool, "acc", &message("Plan"), send_at).await.unwrap();
+
+        let scheduled = list(&pool, Some("acc")).await.unwrap();
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].id, id);
+        assert_eq!(scheduled[0].status, "pending");
+        assert_eq!(scheduled[0].send_at, "2030-01-01T09:00:00Z");
+        assert_eq!(scheduled[0].message.subject, "Plan");
+        assert!(list(&pool, Some("other")).await.unwrap().is_empty());
+
+        // Back in the composer, without its schedule
+        let withdrawn = withdraw(&pool, &id).await.unwrap();
+        assert_eq!(withdrawn.subject, "Plan");
+        assert_eq!(withdrawn.send_at, None);
+        assert!(row(&pool, &id).await.is_none());
+        assert!(matches!(withdraw(&pool, &id).await.unwrap_err(), MailError::NotFound(_)));
+
+        // A message on its way out can't be undone
+        let id = enqueue(&pool, "acc", &message("Plan"), send_at).await.unwrap();
+        set_status(&pool, &id, "sending", 1).await;
+        assert!(matches!(withdraw(&pool, &id).await.unwrap_err(), MailError::NotFound(_)));
+        assert!(row(&pool, &id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reschedule() {
+        let pool = setup_pool().await;
+        let id = enqueue(&pool, "acc", &message("Plan"), Utc::now()).await.unwrap();
+        sqlx::query("UPDATE outbox SET status = 'failed', attempts = 3, last_error = 'Connection reset' WHERE id = ?")
+            .bind(&id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Rescheduling a failed message gives it a fresh start
+        reschedule(&pool, &id, parse_timestamp("2030-01-02T08:00:00Z").unwrap()).await.unwrap();
+        assert_eq!(row(&pool, &id).await.unwrap(), ("pending".to_string(), 0, "2030-01-02T08:00:00Z".to_string(), None));
+
+        set_status(&pool, &id, "sending", 1).await;
+        let error = reschedule(&pool, &id, Utc::now()).await.unwrap_err();
+        assert!(matches!(error, MailError::NotFound(_)));
+        assert!(matches!(reschedule(&pool, "missing", Utc::now()).await.unwrap_err(), MailError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_deliver_due() {
+        let pool = setup_pool().await;
+        let past = Utc::now() - Duration::minutes(1);
+        let sent = enqueue(&pool, "acc", &message("Sent"), past).await.unwrap();
+        let flaky = enqueue(&pool, "acc", &message("Flaky"), past).await.unwrap();
+        let refused = enqueue(&pool, "acc", &message("Refused"), past).await.unwrap();
+        let later = enqueue(&pool, "acc", &message("Later"), Utc::now() + Duration::hours(1)).await.unwrap();
+        let undone = enqueue(&pool, "acc", &message("Undone"), past).await.unwrap();
+        set_status(&pool, &undone, "failed", 3).await;
+
+        let attempted = Arc::new(Mutex::new(Vec::new()));
+        let send = |attempted: Arc<Mutex<Vec<String>>>, pool: Pool<Sqlite>| {
+            move |account_id: String, message: EmailMessage| {
+                let attempted = attempted.clone();
+                let pool = pool.clone();
+                async move {
+                    assert_eq!(account_id, "acc");
+                    // The row is claimed while it's being sent, so it can't be undone
+                    let status: String = sqlx::query_scalar("SELECT status FROM outbox WHERE message LIKE ?")
+                        .bind(format!("%\"subject\":\"{}\"%", message.subject))
+                        .fetch_one(&pool)
+                        .await
+                        .unwrap();
+                    assert_eq!(status, "sending");
+                    attempted.lock().unwrap().push(message.subject.clone());
+                    match message.subject.as_str() {
+                        "Flaky" => Err(MailError::Network("Connection reset".to_string())),
+                        "Refused" => Err(MailError::Auth("Authentication failed".to_string())),
+                        _ => Ok(()),
+                    }
+                }
+            }
+        };
+
+        deliver_due(&pool, send(attempted.clone(), pool.clone())).await.unwrap();
+        let mut subjects = attempted.lock().unwrap().clone();
+        subjects.sort();
+        assert_eq!(subjects, vec!["Flaky", "Refused", "Sent"]);
+
+        assert!(row(&pool, &sent).await.is_none());
+        // A transient failure goes back in the queue a little later
+        let (status, attempts, send_at, last_error) = row(&pool, &flaky).await.unwrap();
+        assert_eq!((status.as_str(), attempts), ("pending", 1));
+        assert!(parse_timestamp(&send_at).unwrap() > Utc::now());
+        assert!(last_error.unwrap().contains("Connection reset"));
+        // A refusal fails the same way every time, so it isn't retried
+        let (status, attempts, _, last_error) = row(&pool, &refused).await.unwrap();
+        assert_eq!((status.as_str(), attempts), ("failed", 1));
+        assert!(last_error.unwrap().contains("Authentication failed"));
+        assert_eq!(row(&pool, &later).await.unwrap().0, "pending");
+        assert_eq!(row(&pool, &undone).await.unwrap().0, "failed");
+
+        // Once the retries run out the message fails for good
+        sqlx::query("UPDATE outbox SET send_at = ?, attempts = 2 WHERE id = ?")
+            .bind(format_timestamp(past))
+            .bind(&flaky)
+            .execute(&pool)
+            .await
+            .unwrap();
+        attempted.lock().unwrap().clear();
+        deliver_due(&pool, send(attempted.clone(), pool.clone())).await.unwrap();
+        assert_eq!(*attempted.lock().unwrap(), vec!["Flaky"]);
+        let (status, attempts, _, _) = row(&pool, &flaky).await.unwrap();
+        assert_eq!((status.as_str(), attempts), ("failed", 3));
+    }
+}
@@ -1,7 +1,15 @@
-use lettre::{Message, SmtpTransport, Transport, transport::smtp::authentication::Credentials};
+use lettre::address::{Address as EnvelopeAddress, Envelope};
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::client::{SmtpConnection, TlsParameters};
+use lettre::transport::smtp::commands::{Data, Ehlo, Mail, Rcpt};
+use lettre::transport::smtp::extension::{ClientId, MailParameter, RcptParameter};
+use mail_builder::{MessageBuilder, headers::raw::Raw, mime::MimePart};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
-use std::str::FromStr;
+use crate::email::address::{self, InvalidAddresses, Mailbox};
+use crate::email::mime_sniff::resolve_mime_type;
+use crate::email::signature::{apply_signature, RichSignature, SignaturePlacement};
+use crate::error::{Context, MailError, MailResult};
+use crate::models::Identity;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SmtpConfig {
@@ -21,6 +29,41 @@ pub struct EmailMessage {
     pub body_text: String,
     pub body_html: Option<String>,
     pub attachments: Vec<EmailAttachment>,
+    // Identity to send as; the account's default identity is used when unset
+    #[serde(default)]
+    pub identity_id: Option<String>,
+    #[serde(default)]
+    pub compose_mode: ComposeMode,
+    // Message-ID of the message being replied to, and the References of its thread,
+    // so the reply is threaded by the recipient's client (RFC 5322 3.6.4)
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    // Original message being replied to or forwarded, kept apart from the new text
+    // so the signature can be placed above or below it
+    #[serde(default)]
+    pub quoted_text: Option<String>,
+    #[serde(default)]
+    pub quoted_html: Option<String>,
+    // RFC 3339 time to send at; None sends as soon as the undo window has passed
+    #[serde(default)]
+    pub send_at: Option<String>,
+    // Ask the receiving servers for SMTP delivery status notifications (RFC 3461)
+    #[serde(default)]
+    pub request_dsn: bool,
+    // Ask the recipient's client for a read receipt (RFC 8098)
+    #[serde(default)]
+    pub request_read_receipt: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ComposeMode {
+    #[default]
+    New,
+    Reply,
+    Forward,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +71,103 @@ pub struct EmailAttachment {
     pub filename: String,
     pub content: Vec<u8>,
     pub mime_type: String,
+    // Set for images referenced from the HTML body as cid:<content_id>
+    #[serde(default)]
+    pub content_id: Option<String>,
+}
+
+// The From/Reply-To side of a message, usually derived from an identity
+#[derive(Debug, Clone)]
+pub struct Sender {
+    pub name: Option<String>,
+    pub address: String,
+    pub reply_to: Option<String>,
+    pub default_bcc: Vec<String>,
+    pub signature: Option<RichSignature>,
+    pub reply_placement: SignaturePlacement,
+    pub forward_placement: SignaturePlacement,
+}
+
+impl Sender {
+    pub fn from_address(address: &str) -> Self {
+        Self {
+            name: None,
+            address: address.to_string(),
+            reply_to: None,
+            default_bcc: vec![],
+            signature: None,
+            reply_placement: SignaturePlacement::AboveQuote,
+            forward_placement: SignaturePlacement::AboveQuote,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Option<RichSignature>) -> Self {
+        self.signature = signature;
+        self
+    }
+
+    fn placement_for(&self, mode: ComposeMode) -> SignaturePlacement {
+        match mode {
+            ComposeMode::New => SignaturePlacement::AboveQuote,
+            ComposeMode::Reply => self.reply_placement,
+            ComposeMode::Forward => self.forward_placement,
+        }
+    }
+}
+
+impl From<&Identity> for Sender {
+    fn from(identity: &Identity) -> Self {
+        Self {
+            name: identity.display_name.clone(),
+            address: identity.email.clone(),
+            reply_to: identity.reply_to.clone(),
+            default_bcc: identity.default_bcc.as_deref()
+                .map(|bcc| bcc.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default(),
+            // Loaded separately, see commands::signatures::load_rich_signature
+            signature: None,
+            reply_placement: SignaturePlacement::parse(&identity.reply_placement),
+            forward_placement: SignaturePlacement::parse(&identity.forward_placement),
+        }
+    }
+}
+
+// A fully rendered RFC 5322 message together with its SMTP envelope
+pub struct OutgoingMessage {
+    pub message_id: String,
+    pub envelope: Envelope,
+    pub raw: Vec<u8>,
+    pub attachments: Vec<AttachmentSize>,
+    // Set when an address has a non-ASCII local part (RFC 6531)
+    pub smtputf8: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentSize {
+    pub filename: String,
+    pub size: usize,
+    // Size once base64 encoded and wrapped, which is what counts against the limit
+    pub encoded_size: usize,
+}
+
+// Returned when the rendered message exceeds the server's SIZE limit (RFC 1870).
+// Commands pass it to the frontend as JSON so it can point at the attachments to drop.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("Message is {size} bytes but the server accepts at most {limit} bytes")]
+pub struct MessageTooLarge {
+    pub limit: usize,
+    pub size: usize,
+    pub attachments: Vec<AttachmentSize>,
+}
+
+impl MessageTooLarge {
+    pub fn new(limit: usize, size: usize, attachments: &[AttachmentSize]) -> Self {
+        Self {
+            limit,
+            size,
+            attachments: oversized_attachments(attachments, size, limit),
+        }
+    }
 }
 
 pub struct SmtpClient {
@@ -39,82 +179,345 @@ impl SmtpClient {
         Self { config }
     }
 
-    pub fn send_email(&self, message: EmailMessage) -> Result<(), Box<dyn Error>> {
-        let mut email_builder = Message::builder()
-            .from(self.config.from.parse()?);
+    pub fn send_email(&self, message: EmailMessage) -> MailResult<()> {
+        let sender = Sender::from_address(&self.config.from);
+        self.send_email_as(message, &sender).map(|_| ())
+    }
+
+    // Sends the message and returns the Message-ID it went out with
+    pub fn send_email_as(&self, message: EmailMessage, sender: &Sender) -> MailResult<String> {
+        let outgoing = self.build_message(&message, sender)?;
+
+        let mut connection = self.connect()?;
+        let result = self.transmit(&mut connection, &outgoing, message.request_dsn);
+        let _ = connection.quit();
+        result?;
+
+        Ok(outgoing.message_id)
+    }
 
-        // Add recipients
-        for to_addr in &message.to {
-            email_builder = email_builder.to(to_addr.parse()?);
+    fn connect(&self) -> MailResult<SmtpConnection> {
+        let hello = ClientId::default();
+        let tls = TlsParameters::new(self.config.host.clone())?;
+        let address = (self.config.host.as_str(), self.config.port);
+        let timeout = Some(std::time::Duration::from_secs(60));
+
+        // Port 465 is implicit TLS; everything else must upgrade with STARTTLS
+        let mut connection = if self.config.port == 465 {
+            SmtpConnection::connect(address, timeout, &hello, Some(&tls), None)?
+        } else {
+            let mut connection = SmtpConnection::connect(address, timeout, &hello, None, None)?;
+            if !connection.can_starttls() {
+                return Err(MailError::Tls(format!("{} does not support STARTTLS", self.config.host)));
+            }
+            connection.starttls(&tls, &hello)?;
+            connection
+        };
+
+        let credentials = Credentials::new(self.config.username.clone(), self.config.password.clone());
+        connection.auth(&[Mechanism::Plain, Mechanism::Login], &credentials)
+            .map_err(auth_error)?;
+
+        Ok(connection)
+    }
+
+    fn transmit(&self, connection: &mut SmtpConnection, outgoing: &OutgoingMessage, request_dsn: bool) -> MailResult<()> {
+        // lettre only tracks the extensions it uses itself, so read the EHLO keywords directly
+        let ehlo = connection.command(Ehlo::new(ClientId::default()))?;
+        let extensions: Vec<String> = ehlo.message()
+            .map(|line| line.to_ascii_uppercase())
+            .collect();
+        let supports_dsn = extensions.iter().any(|line| line.split_whitespace().next() == Some("DSN"));
+        let size_limit = parse_size_limit(&extensions);
+
+        // Fail before DATA instead of letting the server reject the upload halfway
+        let size = outgoing.raw.len();
+        if let Some(limit) = size_limit {
+            if size > limit {
+                return Err(MessageTooLarge::new(limit, size, &outgoing.attachments).into());
+            }
+        }
+
+        if outgoing.smtputf8 && !extensions.iter().any(|line| line == "SMTPUTF8") {
+            return Err(MailError::protocol("The server does not support internationalized (SMTPUTF8) addresses", None));
+        }
+
+        let dsn = request_dsn && supports_dsn;
+
+        let mut mail_parameters = Vec::new();
+        if outgoing.smtputf8 {
+            mail_parameters.push(MailParameter::SmtpUtfEight);
         }
-        for cc_addr in &message.cc {
-            email_builder = email_builder.cc(cc_addr.parse()?);
+        if size_limit.is_some() || extensions.iter().any(|line| line == "SIZE") {
+            mail_parameters.push(MailParameter::Size(size));
         }
-        for bcc_addr in &message.bcc {
-            email_builder = email_builder.bcc(bcc_addr.parse()?);
+        if dsn {
+            mail_parameters.push(MailParameter::Other { keyword: "RET".to_string(), value: Some("HDRS".to_string()) });
+            // ENVID comes back as Original-Envelope-Id, linking the report to this message
+            mail_parameters.push(MailParameter::Other { keyword: "ENVID".to_string(), value: Some(xtext(&outgoing.message_id)) });
         }
+        connection.command(Mail::new(outgoing.envelope.from().cloned(), mail_parameters))?;
 
-        // Build multipart email if needed
-        let email_body = if message.body_html.is_some() || !message.attachments.is_empty() {
-            // Use mail-builder for complex emails
-            self.build_multipart_email(&message)?
-        } else {
-            // Simple text email
-            message.body_text
+        for recipient in outgoing.envelope.to() {
+            let mut rcpt_parameters = Vec::new();
+            if dsn {
+                rcpt_parameters.push(RcptParameter::Other { keyword: "NOTIFY".to_string(), value: Some("SUCCESS,FAILURE".to_string()) });
+            }
+            connection.command(Rcpt::new(recipient.clone(), rcpt_parameters))?;
+        }
+
+        connection.command(Data)?;
+        connection.message(&outgoing.raw)?;
+        Ok(())
+    }
+
+    pub fn build_message(&self, message: &EmailMessage, sender: &Sender) -> MailResult<OutgoingMessage> {
+        let from = address::parse_mailbox(&sender.address)
+            .context("Invalid sender address")?;
+        let from = Mailbox { name: sender.name.clone().or(from.name), address: from.address };
+
+        // Collect every bad address across all fields so they can be reported together
+        let mut invalid = Vec::new();
+        let mut parse = |addresses: &[String]| match address::parse_recipients(addresses) {
+            Ok(mailboxes) => mailboxes,
+            Err(e) => {
+                invalid.extend(e.errors);
+                vec![]
+            }
         };
+        let to = parse(&message.to);
+        let cc = parse(&message.cc);
+        let mut bcc = parse(&message.bcc);
+        for mailbox in parse(&sender.default_bcc) {
+            if !bcc.iter().any(|existing| existing.same_address(&mailbox)) {
+                bcc.push(mailbox);
+            }
+        }
+        if !invalid.is_empty() {
+            return Err(InvalidAddresses { errors: invalid }.into());
+        }
 
-        let email = email_builder
-            .subject(&message.subject)
-            .body(email_body)?;
+        if to.is_empty() && cc.is_empty() && bcc.is_empty() {
+            return Err(MailError::validation("Message has no recipients"));
+        }
 
-        let transport = SmtpTransport::relay(&self.config.host)?
-            .port(self.config.port)
-            .credentials(Credentials::new(
-                self.config.username.clone(),
-                self.config.password.clone(),
-            ))
-            .build();
+        // Bcc recipients only appear in the envelope, never in the headers
+        let all: Vec<&Mailbox> = to.iter().chain(cc.iter()).chain(bcc.iter()).collect();
+        let smtputf8 = from.requires_smtputf8() || all.iter().any(|mailbox| mailbox.requires_smtputf8());
+        let mut recipients = Vec::with_capacity(all.len());
+        for mailbox in all {
+            recipients.push(envelope_address(mailbox)?);
+        }
+        let envelope = Envelope::new(Some(envelope_address(&from)?), recipients)?;
 
-        transport.send(&email)?;
-        Ok(())
+        let message_id = format!("{}@{}", uuid::Uuid::new_v4().simple(), address::to_ascii_domain(from.domain()).map_err(MailError::validation)?);
+
+        // Address headers are written raw so display names get our RFC 2047 encoding
+        let mut builder = MessageBuilder::new()
+            .header("From", Raw::new(from.to_header()))
+            .subject(message.subject.as_str())
+            .message_id(message_id.as_str())
+            .date(chrono::Utc::now().timestamp());
+
+        if !to.is_empty() {
+            builder = builder.header("To", Raw::new(format_mailbox_list(&to)));
+        }
+        if !cc.is_empty() {
+            builder = builder.header("Cc", Raw::new(format_mailbox_list(&cc)));
+        }
+        if let Some(in_reply_to) = message.in_reply_to.as_deref().map(bare_message_id).filter(|id| !id.is_empty()) {
+            builder = builder.header("In-Reply-To", Raw::new(format!("<{}>", in_reply_to)));
+        }
+        let references = reference_ids(&message.references, message.in_reply_to.as_deref());
+        if !references.is_empty() {
+            let references: Vec<String> = references.iter().map(|id| format!("<{}>", id)).collect();
+            builder = builder.header("References", Raw::new(references.join("\r\n ")));
+        }
+        if message.request_read_receipt {
+            builder = builder.header("Disposition-Notification-To", Raw::new(from.to_header()));
+        }
+        if let Some(reply_to) = sender.reply_to.as_deref().filter(|r| !r.trim().is_empty()) {
+            let reply_to = address::parse_mailbox(reply_to)
+                .context("Invalid reply-to address")?;
+            builder = builder.header("Reply-To", Raw::new(reply_to.to_header()));
+        }
+
+        let placement = sender.placement_for(message.compose_mode);
+        let mut message = apply_signature(message, sender.signature.as_ref(), placement);
+        validate_attachments(&mut message.attachments)?;
+        let attachments = message.attachments.iter()
+            .map(|attachment| AttachmentSize {
+                filename: attachment.filename.clone(),
+                size: attachment.content.len(),
+                encoded_size: encoded_size(attachment.content.len()),
+            })
+            .collect();
+
+        // mail-builder adds MIME-Version itself when writing the headers
+        let raw = builder
+            .body(build_body(&message))
+            .write_to_vec()?;
+
+        Ok(OutgoingMessage {
+            message_id,
+            envelope,
+            raw,
+            attachments,
+            smtputf8,
+        })
     }
+}
 
-    fn build_multipart_email(&self, message: &EmailMessage) -> Result<String, Box<dyn Error>> {
-        use mail_builder::{MessageBuilder, headers::address::Address, mime::Mime};
-        
-        let mut builder = MessageBuilder::new();
-        builder = builder.from(Address::new_address(&self.config.from));
-        
-        for to_addr in &message.to {
-            builder = builder.to(Address::new_address(to_addr));
+// Lays out the body as:
+//   multipart/mixed
+//     multipart/alternative
+//       text/plain
+//       multipart/related
+//         text/html
+//         inline images (Content-ID)
+//     attachments
+// collapsing any level that would only hold a single part.
+fn build_body(message: &EmailMessage) -> MimePart<'_> {
+    let (inline, attached): (Vec<&EmailAttachment>, Vec<&EmailAttachment>) = message
+        .attachments
+        .iter()
+        .partition(|attachment| attachment.content_id.is_some() && message.body_html.is_some());
+
+    let text_part = MimePart::new("text/plain", message.body_text.as_str());
+
+    let content = match &message.body_html {
+        Some(html) => {
+            let html_part = MimePart::new("text/html", html.as_str());
+
+            let html_part = if inline.is_empty() {
+                html_part
+            } else {
+                let mut related = vec![html_part];
+                for attachment in &inline {
+                    related.push(
+                        MimePart::new(attachment.mime_type.as_str(), attachment.content.as_slice())
+                            .inline()
+                            .cid(attachment.content_id.as_deref().unwrap_or_default()),
+                    );
+                }
+                MimePart::new("multipart/related", related)
+            };
+
+            MimePart::new("multipart/alternative", vec![text_part, html_part])
         }
-        for cc_addr in &message.cc {
-            builder = builder.cc(Address::new_address(cc_addr));
+        None => text_part,
+    };
+
+    if attached.is_empty() {
+        return content;
+    }
+
+    let mut mixed = vec![content];
+    for attachment in attached {
+        mixed.push(
+            MimePart::new(attachment.mime_type.as_str(), attachment.content.as_slice())
+                .attachment(attachment.filename.as_str()),
+        );
+    }
+    MimePart::new("multipart/mixed", mixed)
+}
+
+// Rejects unusable attachments and fills in a content type where the declared one
+// is missing or invalid, so nothing goes out mislabelled
+fn validate_attachments(attachments: &mut [EmailAttachment]) -> MailResult<()> {
+    for attachment in attachments.iter_mut() {
+        if attachment.filename.trim().is_empty() && attachment.content_id.is_none() {
+            return Err(MailError::validation("Attachment is missing a file name"));
         }
-        for bcc_addr in &message.bcc {
-            builder = builder.bcc(Address::new_address(bcc_addr));
+        if attachment.content.is_empty() {
+            return Err(MailError::validation(format!("Attachment '{}' is empty", attachment.filename)));
         }
-        
-        builder = builder.subject(&message.subject);
+        attachment.mime_type = resolve_mime_type(&attachment.mime_type, &attachment.filename, &attachment.content);
+    }
+    Ok(())
+}
+
+// "SIZE 35882577" advertises a limit; a bare "SIZE" or "SIZE 0" means none
+pub fn parse_size_limit(extensions: &[String]) -> Option<usize> {
+    extensions.iter()
+        .find_map(|line| line.strip_prefix("SIZE "))
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+}
+
+// A permanent failure to AUTH means the server refused the credentials
+fn auth_error(error: lettre::transport::smtp::Error) -> MailError {
+    if error.is_permanent() {
+        MailError::Auth(format!("Authentication failed: {}", error))
+    } else {
+        error.into()
+    }
+}
+
+// Base64 grows the content by 4/3 and mail-builder wraps it at 76 columns with CRLF
+pub fn encoded_size(size: usize) -> usize {
+    let base64 = size.div_ceil(3) * 4;
+    base64 + base64.div_ceil(76) * 2
+}
 
-        // Add text part
-        builder = builder.text_body(&message.body_text);
+// Largest attachments first, until dropping them would bring the message under the limit
+pub fn oversized_attachments(attachments: &[AttachmentSize], size: usize, limit: usize) -> Vec<AttachmentSize> {
+    let mut sorted = attachments.to_vec();
+    sorted.sort_by_key(|attachment| std::cmp::Reverse(attachment.encoded_size));
 
-        // Add HTML part if present
-        if let Some(html_body) = &message.body_html {
-            builder = builder.html_body(html_body);
+    let mut remaining = size;
+    let mut offending = Vec::new();
+    for attachment in sorted {
+        if remaining <= limit {
+            break;
         }
+        remaining = remaining.saturating_sub(attachment.encoded_size);
+        offending.push(attachment);
+    }
+    offending
+}
+
+// SMTP envelope address with the domain in punycode
+fn envelope_address(mailbox: &Mailbox) -> MailResult<EnvelopeAddress> {
+    mailbox.ascii_address().parse::<EnvelopeAddress>()
+        .map_err(|e| MailError::validation(format!("Invalid address '{}': {}", mailbox.address, e)))
+}
 
-        // Add attachments
-        for attachment in &message.attachments {
-            builder = builder.attachment(
-                &attachment.filename,
-                &attachment.content,
-                &Mime::from_str(&attachment.mime_type).unwrap_or(Mime::TEXT_PLAIN),
-            );
+// RFC 3461 xtext: '+', '=' and anything outside printable ASCII are hex-escaped
+fn xtext(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if (33..=126).contains(&byte) && byte != b'+' && byte != b'=' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("+{:02X}", byte));
         }
+    }
+    encoded
+}
+
+// Message-IDs are stored with or without their angle brackets
+fn bare_message_id(id: &str) -> &str {
+    id.trim().trim_start_matches('<').trim_end_matches('>')
+}
 
-        let email = builder.write_to_vec()?;
-        Ok(String::from_utf8(email)?)
+// The thread's references followed by the message being replied to, as RFC 5322 asks
+pub fn reference_ids(references: &[String], in_reply_to: Option<&str>) -> Vec<String> {
+    let mut ids: Vec<String> = references.iter()
+        .map(|id| bare_message_id(id).to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    if let Some(parent) = in_reply_to.map(bare_message_id).filter(|id| !id.is_empty()) {
+        if ids.last().map(String::as_str) != Some(parent) {
+            ids.push(parent.to_string());
+        }
     }
+    ids
+}
+
+fn format_mailbox_list(mailboxes: &[Mailbox]) -> String {
+    mailboxes.iter()
+        .map(Mailbox::to_header)
+        .collect::<Vec<_>>()
+        .join(",\r\n ")
 }
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use crate::smtp_client::*;
+
+    fn client() -> SmtpClient {
+        SmtpClient::new(SmtpConfig {
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            username: "me@example.com".to_string(),
+            password: "secret".to_string(),
+            from: "me@example.com".to_string(),
+        })
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            to: vec!["Bob <bob@example.com>".to_string()],
+            cc: vec![],
+            bcc: vec![],
+            subject: "Plan".to_string(),
+            body_text: "See you.".to_string(),
+            body_html: None,
+            attachments: vec![],
+            identity_id: None,
+            compose_mode: ComposeMode::New,
+            in_reply_to: None,
+            references: vec![],
+            quoted_text: None,
+            quoted_html: None,
+            send_at: None,
+            request_dsn: false,
+            request_read_receipt: false,
+        }
+    }
+
+    fn attachment(filename: &str, content_id: Option<&str>) -> EmailAttachment {
+        EmailAttachment {
+            filename: filename.to_string(),
+            content: vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A],
+            mime_type: "image/png".to_string(),
+            content_id: content_id.map(|id| id.to_string()),
+        }
+    }
+
+    fn build(message: &EmailMessage) -> (OutgoingMessage, String) {
+        let outgoing = client().build_message(message, &Sender::from_address("me@example.com")).unwrap();
+        let raw = String::from_utf8(outgoing.raw.clone()).unwrap();
+        (outgoing, raw)
+    }
+
+    // The headers of the message, before the first blank line
+    fn headers(raw: &str) -> &str {
+        raw.split("\r\n\r\n").next().unwrap()
+    }
+
+    #[test]
+    fn test_plain_message_has_single_part() {
+        let (outgoing, raw) = build(&message());
+        let headers = headers(&raw);
+
+        assert!(headers.contains("MIME-Version: 1.0"));
+        assert!(headers.contains("Date: "));
+        assert!(headers.contains(&format!("Message-ID: <{}>", outgoing.message_id)));
+        assert!(headers.contains("Content-Type: text/plain"));
+        assert!(!raw.contains("multipart/"));
+        // One set of headers, not a message nested inside the body
+        assert_eq!(raw.matches("Subject: ").count(), 1);
+    }
+
+    #[test]
+    fn test_multipart_structure() {
+        let mut message = message();
+        message.body_html = Some("<p>See you.</p><img src=\"cid:logo\">".to_string());
+        message.attachments = vec![attachment("logo.png", Some("logo")), attachment("photo.png", None)];
+        let (_, raw) = build(&message);
+
+        assert!(headers(&raw).contains("Content-Type: multipart/mixed"));
+        let mixed = raw.find("multipart/mixed").unwrap();
+        let alternative = raw.find("multipart/alternative").unwrap();
+        let text = raw.find("Content-Type: text/plain").unwrap();
+        let related = raw.find("multipart/related").unwrap();
+        let html = raw.find("Content-Type: text/html").unwrap();
+        let inline = raw.find("Content-ID: <logo>").unwrap();
+        let attached = raw.find("filename=\"photo.png\"").unwrap();
+        assert!(mixed < alternative && alternative < text && text < related && related < html && html < inline && inline < attached);
+
+        assert!(raw.contains("Content-Disposition: inline"));
+        assert!(raw.contains("Content-Disposition: attachment"));
+    }
+
+    #[test]
+    fn test_html_without_files_is_alternative_only() {
+        let mut message = message();
+        message.body_html = Some("<p>See you.</p>".to_string());
+        let (_, raw) = build(&message);
+
+        assert!(headers(&raw).contains("Content-Type: multipart/alternative"));
+        assert!(!raw.contains("multipart/mixed"));
+        assert!(!raw.contains("multipart/related"));
+    }
+
+    #[test]
+    fn test_reply_headers() {
+        let mut message = message();
+        message.compose_mode = ComposeMode::Reply;
+        message.in_reply_to = Some("<parent@example.com>".to_string());
+        message.references = vec!["<root@example.com>".to_string(), "parent@example.com".to_string()];
+        let (_, raw) = build(&message);
+        let headers = headers(&raw);
+
+        assert!(headers.contains("In-Reply-To: <parent@example.com>\r\n"));
+        assert!(headers.contains("References: <root@example.com>\r\n <parent@example.com>\r\n"));
+    }
+
+    #[test]
+    fn test_reference_ids() {
+        assert_eq!(reference_ids(&[], Some("<parent@example.com>")), vec!["parent@example.com"]);
+        assert_eq!(
+            reference_ids(&["<root@example.com>".to_string()], Some("parent@example.com")),
+            vec!["root@example.com", "parent@example.com"]
+        );
+        assert!(reference_ids(&[], None).is_empty());
+    }
+
+    #[test]
+    fn test_bcc_only_in_envelope() {
+        let mut message = message();
+        message.cc = vec!["carol@example.com".to_string()];
+        message.bcc = vec!["hidden@example.com".to_string()];
+        let (outgoing, raw) = build(&message);
+
+        assert!(headers(&raw).contains("Cc: carol@example.com"));
+        assert!(!raw.contains("Bcc:"));
+        assert!(!raw.contains("hidden@example.com"));
+
+        let recipients: Vec<String> = outgoing.envelope.to().iter().map(|address| address.to_string()).collect();
+        assert_eq!(recipients, vec!["bob@example.com", "carol@example.com", "hidden@example.com"]);
+    }
+
+    #[test]
+    fn test_encoded_size() {
+        assert_eq!(encoded_size(0), 0);
+        assert_eq!(encoded_size(1), 6);
+        assert_eq!(encoded_size(57), 78);
+        assert_eq!(encoded_size(58), 84);
+
+        // Matches what the attachment actually adds to the built message, give or take its headers
+        let plain = build(&message()).1.len();
+        let mut with_file = message();
+        with_file.attachments = vec![EmailAttachment {
+            filename: "data.bin".to_string(),
+            content: vec![0xA5; 10_000],
+            mime_type: "application/octet-stream".to_string(),
+            content_id: None,
+        }];
+        let grown = build(&with_file).1.len() - plain;
+        assert!(grown >= encoded_size(10_000) && grown < encoded_size(10_000) + 600, "grew by {}", grown);
+    }
+
+    #[test]
+    fn test_parse_size_limit() {
+        let extensions = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(parse_size_limit(&extensions(&["SMTP.EXAMPLE.COM", "PIPELINING", "SIZE 35882577", "8BITMIME"])), Some(35882577));
+        assert_eq!(parse_size_limit(&extensions(&["SIZE", "DSN"])), None);
+        assert_eq!(parse_size_limit(&extensions(&["SIZE 0"])), None);
+        assert_eq!(parse_size_limit(&extensions(&["PIPELINING"])), None);
+    }
+
+    #[test]
+    fn test_oversized_attachments() {
+        let attachment = |filename: &str, encoded_size: usize| AttachmentSize {
+            filename: filename.to_string(),
+            size: encoded_size * 3 / 4,
+            encoded_size,
+        };
+        let attachments = vec![attachment("notes.txt", 100), attachment("video.mp4", 5000), attachment("photo.jpg", 2000)];
+
+        // Largest first, only as many as it takes to fit
+        let names = |found: Vec<AttachmentSize>| found.into_iter().map(|a| a.filename).collect::<Vec<_>>();
+        assert_eq!(names(oversized_attachments(&attachments, 7500, 4000)), vec!["video.mp4"]);
+        assert_eq!(names(oversized_attachments(&attachments, 7500, 1000)), vec!["video.mp4", "photo.jpg"]);
+        assert!(oversized_attachments(&attachments, 7500, 8000).is_empty());
+    }
+}
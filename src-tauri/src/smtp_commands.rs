@@ -1,4 +1,6 @@
-use crate::smtp_client::{SmtpClient, SmtpConfig, EmailMessage};
+use crate::email::address::{self, AddressError, Mailbox};
+use crate::error::{Context, MailError, MailResult};
+use crate::smtp_client::{SmtpClient, SmtpConfig, EmailMessage, ComposeMode};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 use std::collections::HashMap;
@@ -23,7 +25,7 @@ pub struct SendEmailParams {
 }
 
 #[tauri::command]
-pub async fn smtp_connect(request: ConnectRequest) -> Result<(), String> {
+pub async fn smtp_connect(request: ConnectRequest) -> MailResult<()> {
     let mut clients = SMTP_CLIENTS.lock().unwrap();
     let client = SmtpClient::new(request.smtp_config);
     clients.insert(request.account_id, client);
@@ -31,9 +33,9 @@ pub async fn smtp_connect(request: ConnectRequest) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn smtp_disconnect(account_id: String) -> Result<bool, String> {
+pub fn smtp_disconnect(account_id: String) -> MailResult<bool> {
     let mut clients = SMTP_CLIENTS.lock()
-        .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        .map_err(|e| MailError::Internal(format!("Failed to acquire lock: {}", e)))?;
     
     let removed = clients.remove(&account_id).is_some();
     Ok(removed)
@@ -42,18 +44,44 @@ pub fn smtp_disconnect(account_id: String) -> Result<bool, String> {
 #[tauri::command]
 pub async fn smtp_send_email(
     params: SendEmailParams,
-) -> Result<(), String> {
+) -> MailResult<()> {
     let message = EmailMessage {
-        to: params.to,
+        to: vec![params.to],
+        cc: vec![],
+        bcc: vec![],
         subject: params.subject,
-        body: params.body,
+        body_text: params.body,
+        body_html: None,
+        attachments: vec![],
+        identity_id: None,
+        compose_mode: ComposeMode::New,
+        in_reply_to: None,
+        references: vec![],
+        quoted_text: None,
+        quoted_html: None,
+        send_at: None,
+        request_dsn: false,
+        request_read_receipt: false,
     };
 
     // Get the client and send
     let clients = SMTP_CLIENTS.lock().unwrap();
     let client = clients
         .get(&params.account_id)
-        .ok_or_else(|| "SMTP client not found".to_string())?;
+        .ok_or_else(|| MailError::NotFound("SMTP client not found".to_string()))?;
 
-    client.send_email(message).map_err(|e| e.to_string())
+    client.send_email(message).context("Failed to send email")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddressValidation {
+    pub mailboxes: Vec<Mailbox>,
+    pub errors: Vec<AddressError>,
+}
+
+// Lets the composer check a recipient field as it is typed and mark each bad entry
+#[tauri::command]
+pub fn smtp_validate_addresses(input: String) -> AddressValidation {
+    let (mailboxes, errors) = address::parse_list(&input);
+    AddressValidation { mailboxes, errors }
 }